@@ -0,0 +1,14 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // `tktts self-update` picks the release asset for this target
+    println!("cargo:rustc-env=TKTTS_TARGET={}", std::env::var("TARGET")?);
+    println!("cargo:rerun-if-env-changed=TKTTS_RELEASE_PUBLIC_KEY");
+    #[cfg(feature = "cli")]
+    {
+        println!("cargo:rerun-if-changed=proto/tktts.proto");
+        let descriptors = protox::compile(["proto/tktts.proto"], ["proto"])?;
+        tonic_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)?;
+    }
+    Ok(())
+}
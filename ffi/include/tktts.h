@@ -0,0 +1,50 @@
+/* C bindings for tktts: TikTok text-to-speech synthesis.
+ *
+ * Link against libtktts_ffi (cdylib or staticlib built from the ffi/ crate).
+ * Calls block until the audio is ready; a client may be shared between threads.
+ */
+#ifndef TKTTS_H
+#define TKTTS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TKTTS_OK 0
+#define TKTTS_ERR_INVALID_ARGUMENT -1
+#define TKTTS_ERR_SYNTHESIS -2
+
+typedef struct tktts_client tktts_client;
+
+/* Per-call settings; zero fields keep the defaults (300 bytes, 8 requests). */
+typedef struct tktts_options {
+    size_t byte_limit;
+    size_t concurrency;
+} tktts_options;
+
+/* MP3 audio owned by the library; release with tktts_buffer_free. */
+typedef struct tktts_buffer {
+    uint8_t *data;
+    size_t len;
+} tktts_buffer;
+
+/* Returns NULL on failure; see tktts_last_error. */
+tktts_client *tktts_client_new(const char *session_id, const char *api_base_url);
+void tktts_client_free(tktts_client *client);
+
+/* Returns TKTTS_OK or a negative status. options may be NULL. */
+int tktts_synthesize(const tktts_client *client, const char *text, const char *voice,
+                     const tktts_options *options, tktts_buffer *out_buf);
+void tktts_buffer_free(tktts_buffer *buffer);
+
+/* Message of the last failure on the calling thread, or NULL. */
+const char *tktts_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TKTTS_H */
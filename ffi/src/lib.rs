@@ -0,0 +1,200 @@
+//! C bindings for tktts.
+//!
+//! A `tktts_client` handle owns a [`tktts::client::Client`] and the runtime that drives it;
+//! each call blocks until the audio is ready. Failing calls return a negative status and
+//! leave a message for `tktts_last_error` on the calling thread. See `include/tktts.h`.
+
+#![allow(non_camel_case_types)]
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+pub const TKTTS_OK: i32 = 0;
+pub const TKTTS_ERR_INVALID_ARGUMENT: i32 = -1;
+pub const TKTTS_ERR_SYNTHESIS: i32 = -2;
+
+pub struct tktts_client {
+    client: tktts::client::Client,
+    runtime: tokio::runtime::Runtime,
+}
+
+/// Per-call settings; zero fields keep the client's defaults.
+#[repr(C)]
+pub struct tktts_options {
+    pub byte_limit: usize,
+    pub concurrency: usize,
+}
+
+/// Audio returned by `tktts_synthesize`, released with `tktts_buffer_free`.
+#[repr(C)]
+pub struct tktts_buffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).ok();
+    LAST_ERROR.with(|error| *error.borrow_mut() = message);
+}
+
+/// Reads a required UTF-8 argument.
+///
+/// # Safety
+///
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, i32> {
+    if value.is_null() {
+        set_error(format!("{} must not be NULL", name));
+        return Err(TKTTS_ERR_INVALID_ARGUMENT);
+    }
+    unsafe { CStr::from_ptr(value) }.to_str().map_err(|_| {
+        set_error(format!("{} is not valid UTF-8", name));
+        TKTTS_ERR_INVALID_ARGUMENT
+    })
+}
+
+/// Creates a client for the API host `api_base_url`. Returns NULL on failure.
+///
+/// # Safety
+///
+/// Both arguments must be NUL-terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tktts_client_new(
+    session_id: *const c_char,
+    api_base_url: *const c_char,
+) -> *mut tktts_client {
+    let (Ok(session_id), Ok(api_base_url)) =
+        (unsafe { str_arg(session_id, "session_id") }, unsafe {
+            str_arg(api_base_url, "api_base_url")
+        })
+    else {
+        return ptr::null_mut();
+    };
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            set_error(format!("Failed to start runtime: {}", e));
+            return ptr::null_mut();
+        }
+    };
+    Box::into_raw(Box::new(tktts_client {
+        client: tktts::client::Client::new(session_id, api_base_url),
+        runtime,
+    }))
+}
+
+/// Releases a client. NULL is ignored.
+///
+/// # Safety
+///
+/// `client` must come from `tktts_client_new` and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tktts_client_free(client: *mut tktts_client) {
+    if !client.is_null() {
+        drop(unsafe { Box::from_raw(client) });
+    }
+}
+
+/// Synthesizes `text` with `voice` into `out_buf`. `options` may be NULL.
+///
+/// Returns `TKTTS_OK`, or a negative status with `out_buf` left empty.
+///
+/// # Safety
+///
+/// `client` must be a live handle, `text` and `voice` NUL-terminated strings, `options`
+/// null or valid, and `out_buf` writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tktts_synthesize(
+    client: *const tktts_client,
+    text: *const c_char,
+    voice: *const c_char,
+    options: *const tktts_options,
+    out_buf: *mut tktts_buffer,
+) -> i32 {
+    if client.is_null() || out_buf.is_null() {
+        set_error("client and out_buf must not be NULL");
+        return TKTTS_ERR_INVALID_ARGUMENT;
+    }
+    let out_buf = unsafe { &mut *out_buf };
+    *out_buf = tktts_buffer {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+
+    let handle = unsafe { &*client };
+    let text = match unsafe { str_arg(text, "text") } {
+        Ok(text) => text,
+        Err(status) => return status,
+    };
+    let voice = match unsafe { str_arg(voice, "voice") } {
+        Ok(voice) => voice,
+        Err(status) => return status,
+    };
+
+    let result = match unsafe { options.as_ref() } {
+        Some(options) => {
+            let mut client = handle.client.clone();
+            if options.byte_limit != 0 {
+                client = client.byte_limit(options.byte_limit);
+            }
+            if options.concurrency != 0 {
+                client = client.concurrency(options.concurrency);
+            }
+            handle.runtime.block_on(client.synthesize(text, voice))
+        }
+        None => handle
+            .runtime
+            .block_on(handle.client.synthesize(text, voice)),
+    };
+
+    match result {
+        Ok(audio) => {
+            let audio = audio.into_boxed_slice();
+            out_buf.len = audio.len();
+            out_buf.data = Box::into_raw(audio) as *mut u8;
+            TKTTS_OK
+        }
+        Err(e) => {
+            set_error(e.to_string());
+            TKTTS_ERR_SYNTHESIS
+        }
+    }
+}
+
+/// Releases the audio of a `tktts_buffer` and empties it.
+///
+/// # Safety
+///
+/// `buffer` must be null or filled by `tktts_synthesize` and not freed before.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tktts_buffer_free(buffer: *mut tktts_buffer) {
+    let Some(buffer) = (unsafe { buffer.as_mut() }) else {
+        return;
+    };
+    if !buffer.data.is_null() {
+        let audio = ptr::slice_from_raw_parts_mut(buffer.data, buffer.len);
+        drop(unsafe { Box::from_raw(audio) });
+    }
+    buffer.data = ptr::null_mut();
+    buffer.len = 0;
+}
+
+/// The message of the last failed call on this thread, or NULL. Valid until the next call.
+#[unsafe(no_mangle)]
+pub extern "C" fn tktts_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
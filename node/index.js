@@ -0,0 +1,15 @@
+'use strict';
+
+const native = require('./tktts.node');
+
+class Client extends native.Client {
+  /** Yields each chunk's MP3 audio as a Buffer, in order. */
+  async *synthesizeChunks(text, voice) {
+    const stream = this.stream(text, voice);
+    for (let chunk = await stream.next(); chunk !== null; chunk = await stream.next()) {
+      yield chunk;
+    }
+  }
+}
+
+module.exports = { Client, voices: native.voices, splitText: native.splitText };
@@ -0,0 +1,110 @@
+//! Node.js bindings for tktts, built with napi-rs into `tktts.node`.
+//!
+//! `index.js` wraps [`ChunkStream`] in an async iterator; everything else is exported as is.
+
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+fn to_napi_err(e: tktts::TktsError) -> napi::Error {
+    napi::Error::from_reason(e.to_string())
+}
+
+#[napi(object)]
+pub struct ClientOptions {
+    pub byte_limit: Option<u32>,
+    pub concurrency: Option<u32>,
+}
+
+#[napi(object)]
+pub struct Voice {
+    pub code: String,
+    pub name: String,
+    pub language: String,
+    pub tags: Vec<String>,
+}
+
+/// A TikTok TTS client authenticated with a session ID.
+#[napi]
+pub struct Client {
+    inner: tktts::client::Client,
+}
+
+#[napi]
+impl Client {
+    #[napi(constructor)]
+    pub fn new(session_id: String, api_base_url: String, options: Option<ClientOptions>) -> Self {
+        let mut inner = tktts::client::Client::new(&session_id, &api_base_url);
+        if let Some(options) = options {
+            if let Some(byte_limit) = options.byte_limit {
+                inner = inner.byte_limit(byte_limit as usize);
+            }
+            if let Some(concurrency) = options.concurrency {
+                inner = inner.concurrency(concurrency as usize);
+            }
+        }
+        Client { inner }
+    }
+
+    /// Resolves to the joined MP3 audio of `text`.
+    #[napi]
+    pub async fn synthesize(&self, text: String, voice: String) -> napi::Result<Buffer> {
+        let audio = self
+            .inner
+            .synthesize(&text, &voice)
+            .await
+            .map_err(to_napi_err)?;
+        Ok(audio.into())
+    }
+
+    /// Starts synthesizing `text`; the chunks' audio is read in order from the stream.
+    #[napi]
+    pub fn stream(&self, text: String, voice: String) -> ChunkStream {
+        let receiver = napi::bindgen_prelude::within_runtime_if_available(|| {
+            self.inner.synthesize_stream(&text, &voice)
+        });
+        ChunkStream {
+            receiver: Arc::new(Mutex::new(receiver)),
+        }
+    }
+}
+
+#[napi]
+pub struct ChunkStream {
+    receiver: Arc<Mutex<tktts::client::ChunkStream>>,
+}
+
+#[napi]
+impl ChunkStream {
+    /// Resolves to the next chunk's audio, or `null` once the text is done.
+    #[napi]
+    pub async fn next(&self) -> napi::Result<Option<Buffer>> {
+        let receiver = self.receiver.clone();
+        let next = receiver.lock().await.recv().await;
+        next.transpose()
+            .map(|chunk| chunk.map(|chunk| Buffer::from(chunk.bytes)))
+            .map_err(to_napi_err)
+    }
+}
+
+/// The known voices.
+#[napi]
+pub fn voices() -> Vec<Voice> {
+    tktts::voices::VOICES
+        .iter()
+        .map(|voice| Voice {
+            code: voice.code.to_string(),
+            name: voice.name.to_string(),
+            language: voice.language.to_string(),
+            tags: voice.tags.iter().map(|tag| tag.to_string()).collect(),
+        })
+        .collect()
+}
+
+/// Splits `text` into chunks of at most `byteLimit` bytes, as the CLI does.
+#[napi]
+pub fn split_text(text: String, byte_limit: Option<u32>) -> Vec<String> {
+    let byte_limit = byte_limit.map_or(tktts::client::DEFAULT_BYTE_LIMIT, |limit| limit as usize);
+    tktts::split_text(&text, byte_limit.max(tktts::MIN_BYTE_LIMIT))
+}
@@ -0,0 +1,99 @@
+//! Python bindings for tktts, built with `maturin` into the `tktts` module.
+//!
+//! `Client.synthesize` blocks (releasing the GIL) while `Client.synthesize_async` returns
+//! an awaitable driven by a shared tokio runtime.
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+fn to_py_err(e: tktts::TktsError) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+/// A TikTok TTS client authenticated with a session ID.
+#[pyclass(module = "tktts", frozen)]
+struct Client {
+    inner: tktts::client::Client,
+}
+
+#[pymethods]
+impl Client {
+    #[new]
+    #[pyo3(signature = (session_id, api_base_url, *, byte_limit=None, concurrency=None))]
+    fn new(
+        session_id: &str,
+        api_base_url: &str,
+        byte_limit: Option<usize>,
+        concurrency: Option<usize>,
+    ) -> Self {
+        let mut inner = tktts::client::Client::new(session_id, api_base_url);
+        if let Some(byte_limit) = byte_limit {
+            inner = inner.byte_limit(byte_limit);
+        }
+        if let Some(concurrency) = concurrency {
+            inner = inner.concurrency(concurrency);
+        }
+        Client { inner }
+    }
+
+    /// Synthesizes `text` with `voice` and returns the MP3 audio.
+    fn synthesize<'py>(
+        &self,
+        py: Python<'py>,
+        text: &str,
+        voice: &str,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let runtime = pyo3_async_runtimes::tokio::get_runtime();
+        let audio = py
+            .allow_threads(|| runtime.block_on(self.inner.synthesize(text, voice)))
+            .map_err(to_py_err)?;
+        Ok(PyBytes::new(py, &audio))
+    }
+
+    /// Like `synthesize`, but returns an awaitable for use with asyncio.
+    fn synthesize_async<'py>(
+        &self,
+        py: Python<'py>,
+        text: String,
+        voice: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let inner = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let audio = inner.synthesize(&text, &voice).await.map_err(to_py_err)?;
+            Ok(Python::with_gil(|py| PyBytes::new(py, &audio).unbind()))
+        })
+    }
+}
+
+/// Lists the known voices as dicts with `code`, `name`, `language` and `tags`.
+#[pyfunction]
+fn voices(py: Python<'_>) -> PyResult<Vec<Bound<'_, PyDict>>> {
+    tktts::voices::VOICES
+        .iter()
+        .map(|voice| {
+            let dict = PyDict::new(py);
+            dict.set_item("code", voice.code)?;
+            dict.set_item("name", voice.name)?;
+            dict.set_item("language", voice.language)?;
+            dict.set_item("tags", voice.tags.to_vec())?;
+            Ok(dict)
+        })
+        .collect()
+}
+
+/// Splits `text` into chunks of at most `byte_limit` bytes, as the CLI does.
+#[pyfunction]
+#[pyo3(signature = (text, byte_limit=tktts::client::DEFAULT_BYTE_LIMIT))]
+fn split_text(text: &str, byte_limit: usize) -> Vec<String> {
+    tktts::split_text(text, byte_limit.max(tktts::MIN_BYTE_LIMIT))
+}
+
+#[pymodule]
+#[pyo3(name = "tktts")]
+fn tktts_python(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Client>()?;
+    module.add_function(wrap_pyfunction!(voices, module)?)?;
+    module.add_function(wrap_pyfunction!(split_text, module)?)?;
+    Ok(())
+}
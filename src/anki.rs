@@ -0,0 +1,158 @@
+//! `tktts anki`: audio for the cards of a CSV file, written into an Anki media folder.
+//!
+//! Each row's text column is synthesized into `<id>.mp3` in `--media-dir`, and the CSV is
+//! written out again with an audio column holding `--field-template`, e.g.
+//! `[sound:<id>.mp3]`, ready for Anki's import. Without `--id-column` the ID is a hash of
+//! voice and text, so re-running after adding cards only synthesizes the new ones.
+
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct AnkiArgs {
+    /// Card CSV with a header row
+    #[arg(long, value_name = "FILE")]
+    csv: PathBuf,
+
+    /// Anki's collection.media folder, or any folder to import the files from
+    #[arg(long, value_name = "DIR")]
+    media_dir: PathBuf,
+
+    /// Column with the text to speak
+    #[arg(long, value_name = "NAME", default_value = "text")]
+    text_column: String,
+
+    /// Column naming each media file, instead of a hash of voice and text
+    #[arg(long, value_name = "NAME")]
+    id_column: Option<String>,
+
+    /// Column written with the sound reference; added when the CSV has none
+    #[arg(long, value_name = "NAME", default_value = "audio")]
+    audio_column: String,
+
+    /// Value of the audio column; {id} is the media file name without .mp3
+    #[arg(long, value_name = "TEMPLATE", default_value = "[sound:{id}.mp3]")]
+    field_template: String,
+
+    /// Write the updated CSV here instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Voice used for the cards
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+}
+
+/// A media file name part without path separators or characters Anki rejects.
+fn file_id(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '[' | ']' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+fn hash_id(speaker: &str, text: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(speaker.as_bytes())
+        .chain_update([0])
+        .chain_update(text.as_bytes())
+        .finalize();
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("tktts-{}", hex)
+}
+
+pub async fn run(args: AnkiArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    if !args.field_template.contains("{id}") {
+        return Err("--field-template needs an {id} placeholder".into());
+    }
+
+    let mut reader = csv::Reader::from_path(&args.csv)
+        .map_err(|e| format!("Failed to read {}: {}", args.csv.display(), e))?;
+    let mut headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let text_column = column(&args.text_column).ok_or_else(|| {
+        format!(
+            "{} has no '{}' column",
+            args.csv.display(),
+            args.text_column
+        )
+    })?;
+    let id_column = match &args.id_column {
+        Some(name) => Some(
+            column(name)
+                .ok_or_else(|| format!("{} has no '{}' column", args.csv.display(), name))?,
+        ),
+        None => None,
+    };
+    let audio_column = match column(&args.audio_column) {
+        Some(index) => index,
+        None => {
+            headers.push_field(&args.audio_column);
+            headers.len() - 1
+        }
+    };
+    let rows = reader.records().collect::<Result<Vec<_>, _>>()?;
+    fs::create_dir_all(&args.media_dir)?;
+
+    let mut cards = Vec::with_capacity(rows.len());
+    let (mut synthesized, mut reused) = (0, 0);
+    for (index, row) in rows.iter().enumerate() {
+        let text = row.get(text_column).unwrap_or("").trim();
+        let id = id_column
+            .and_then(|column| row.get(column))
+            .map(file_id)
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| hash_id(&args.speaker, text));
+        let mut fields: Vec<String> = row.iter().map(str::to_string).collect();
+        fields.resize(headers.len(), String::new());
+
+        fields[audio_column] = if text.is_empty() {
+            String::new()
+        } else {
+            let path = args.media_dir.join(format!("{}.mp3", id));
+            if path.exists() {
+                reused += 1;
+            } else {
+                let audio = crate::synthesize(text, &args.speaker, &session_id, &api_root_url)
+                    .await
+                    .map_err(|e| format!("Card {}: {}", index + 1, e))?;
+                fs::write(&path, audio)?;
+                synthesized += 1;
+            }
+            args.field_template.replace("{id}", &id)
+        };
+        cards.push(fields);
+    }
+
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(fs::File::create(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(&headers)?;
+    for fields in cards {
+        writer.write_record(fields)?;
+    }
+    writer.flush()?;
+
+    crate::term::status(
+        crate::term::Style::Green,
+        "Finished",
+        format!(
+            "{} cards, {} new media files, {} already in {}",
+            rows.len(),
+            synthesized,
+            reused,
+            args.media_dir.display()
+        ),
+    );
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! `--append FILE`: adds the new audio to the end of an existing MP3 or WAV file.
+//!
+//! MP3 frames are simply joined when the file has the API's sample rate and channels;
+//! otherwise the new audio is resampled and encoded to match the file, so its existing
+//! frames are never re-encoded. A Xing or Info header frame is dropped on the way, as
+//! its frame count would no longer hold, and a trailing ID3v1 tag is dropped as well.
+//! WAV files are decoded and rewritten as mono. The result replaces the file in one
+//! rename, so an interrupted run leaves the old file intact.
+
+use crate::pcm;
+use std::fs;
+use std::path::Path;
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+}
+
+/// `existing` MP3 audio followed by the `new` API audio.
+fn join_mp3(
+    existing: &[u8],
+    new: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let info = crate::mp3::info(existing);
+    if info.frames == 0 {
+        return Err("not an MP3 file".into());
+    }
+    if info.channels != 1 {
+        return Err("only mono MP3 files can be appended to".into());
+    }
+    let new_info = crate::mp3::info(new);
+    let new = match new_info.sample_rate == info.sample_rate && new_info.channels == 1 {
+        true => new.to_vec(),
+        false => pcm::mp3(&pcm::resample(pcm::decode(new)?, info.sample_rate))?,
+    };
+
+    let span = crate::mp3::frame_span(existing);
+    let new_span = crate::mp3::frame_span(&new);
+    let mut joined = Vec::with_capacity(existing.len() + new.len());
+    joined.extend_from_slice(&existing[..crate::mp3::id3v2_len(existing).min(span.start)]);
+    joined.extend_from_slice(&existing[span]);
+    joined.extend_from_slice(&new[new_span]);
+    Ok(joined)
+}
+
+/// `existing` WAV audio followed by the `new` API audio, as a mono WAV file.
+fn join_wav(
+    existing: &[u8],
+    new: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut joined = pcm::decode_as(existing, "wav")?;
+    let new = pcm::resample(pcm::decode(new)?, joined.sample_rate);
+    joined.samples.extend(new.samples);
+    Ok(pcm::wav(&joined))
+}
+
+/// Appends `audio` to the file at `path`, or creates it with `audio` (as WAV for a
+/// `.wav` path). Returns the file's new contents.
+pub fn write(
+    path: &Path,
+    audio: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let joined = match fs::read(path) {
+        Ok(existing) => match is_wav(path) {
+            true => join_wav(&existing, audio),
+            false => join_mp3(&existing, audio),
+        }
+        .map_err(|e| format!("Cannot append to {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => match is_wav(path) {
+            true => pcm::wav(&pcm::decode(audio)?),
+            false => audio.to_vec(),
+        },
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e).into()),
+    };
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    fs::write(&temp, &joined)
+        .and_then(|_| fs::rename(&temp, path))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(joined)
+}
@@ -0,0 +1,268 @@
+//! `tktts auth`: session checks and device registration for endpoints that expect
+//! `device_id`/`iid`.
+//!
+//! `check` sends one short request with each session ID and says which the API accepts.
+//! `register-device` performs the app's device-registration handshake once and stores
+//! the identifiers in the `[device]` section of the config file. From then on they are
+//! appended to every TTS request together with the matching install parameters.
+
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::UrlQuery;
+use url::form_urlencoded::Serializer;
+
+const REGISTER_URL: &str = "https://log-va.tiktokv.com/service/2/device_register/";
+
+/// Install parameters matching the app version in `USER_AGENT`; `app_name` comes from
+/// the active preset.
+const INSTALL_PARAMS: [(&str, &str); 5] = [
+    ("device_platform", "android"),
+    ("version_code", "260003"),
+    ("manifest_version_code", "2022600030"),
+    ("channel", "googleplay"),
+    ("os_version", "7.1.2"),
+];
+
+/// The query parameters of [`append_params`] that identify the device.
+pub const DEVICE_PARAMS: [&str; 3] = ["device_id", "iid", "openudid"];
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    pub device_id: Option<String>,
+    pub iid: Option<String>,
+    pub openudid: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum AuthCommand {
+    /// Check with one short request per session ID that the API accepts each of them
+    Check,
+
+    /// Register a device once and save its device_id/iid in the config file
+    RegisterDevice {
+        /// Register again even if the config already has a device
+        #[arg(long)]
+        force: bool,
+
+        /// Registration endpoint
+        #[arg(long, value_name = "URL", default_value = REGISTER_URL)]
+        endpoint: String,
+    },
+}
+
+/// Appends the registered device identifiers and install parameters to a request query.
+pub fn append_params(query: &mut Serializer<UrlQuery>) {
+    let device = &crate::config::get().device;
+    let (Some(device_id), Some(iid)) = (&device.device_id, &device.iid) else {
+        return;
+    };
+    query.append_pair("device_id", device_id);
+    query.append_pair("iid", iid);
+    if let Some(openudid) = &device.openudid {
+        query.append_pair("openudid", openudid);
+    }
+    query.append_pair("app_name", &crate::preset::active().app_name);
+    for (name, value) in INSTALL_PARAMS {
+        query.append_pair(name, value);
+    }
+}
+
+/// Hex digits derived from the clock and process ID, for per-install identifiers.
+fn random_hex(len: usize) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let digest = Sha256::new()
+        .chain_update(nanos.to_le_bytes())
+        .chain_update(std::process::id().to_le_bytes())
+        .finalize();
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    hex[..len].to_string()
+}
+
+#[derive(Deserialize)]
+struct RegisterResponse {
+    #[serde(default)]
+    device_id_str: Option<String>,
+    #[serde(default)]
+    install_id_str: Option<String>,
+    #[serde(default)]
+    device_id: Option<u64>,
+    #[serde(default)]
+    install_id: Option<u64>,
+}
+
+async fn register(
+    endpoint: &str,
+    openudid: &str,
+) -> Result<(String, String), Box<dyn std::error::Error + Send + Sync>> {
+    let clientudid = random_hex(32);
+    let clientudid = format!(
+        "{}-{}-{}-{}-{}",
+        &clientudid[..8],
+        &clientudid[8..12],
+        &clientudid[12..16],
+        &clientudid[16..20],
+        &clientudid[20..]
+    );
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let preset = crate::preset::active();
+    let mut url = url::Url::parse(endpoint)?;
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("aid", &preset.aid);
+        query.append_pair("app_name", &preset.app_name);
+        for (name, value) in INSTALL_PARAMS {
+            query.append_pair(name, value);
+        }
+        query.append_pair("openudid", openudid);
+        query.append_pair("_rticket", &now.as_millis().to_string());
+    }
+
+    let body = serde_json::json!({
+        "magic_tag": "ss_app_log",
+        "header": {
+            "display_name": "TikTok",
+            "aid": preset.aid.parse::<u64>().unwrap_or(1233),
+            "app_version": "26.0.3",
+            "version_code": 260003,
+            "manifest_version_code": 2022600030,
+            "update_version_code": 2022600030,
+            "package": "com.zhiliaoapp.musically",
+            "channel": "googleplay",
+            "os": "Android",
+            "os_version": "7.1.2",
+            "os_api": 25,
+            "device_model": "SM-G988N",
+            "device_brand": "samsung",
+            "cpu_abi": "arm64-v8a",
+            "resolution": "1600x900",
+            "density_dpi": 320,
+            "language": "en",
+            "region": "US",
+            "timezone": 0,
+            "tz_name": "UTC",
+            "access": "wifi",
+            "openudid": openudid,
+            "clientudid": clientudid,
+        },
+        "_gen_time": now.as_millis() as u64,
+    });
+
+    let response = crate::http::client()
+        .post(url)
+        .header("User-Agent", &preset.user_agent)
+        .json(&body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(format!("Device registration failed ({})", response.status()).into());
+    }
+    let response: RegisterResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected device registration response: {}", e))?;
+
+    let device_id = response
+        .device_id_str
+        .or(response.device_id.map(|id| id.to_string()))
+        .filter(|id| id != "0");
+    let iid = response
+        .install_id_str
+        .or(response.install_id.map(|id| id.to_string()))
+        .filter(|id| id != "0");
+    match (device_id, iid) {
+        (Some(device_id), Some(iid)) => Ok((device_id, iid)),
+        _ => Err("The registration endpoint did not return a device_id and install_id".into()),
+    }
+}
+
+/// Writes the `[device]` section, keeping the rest of the config file (and its comments).
+fn save_device(
+    device_id: &str,
+    iid: &str,
+    openudid: &str,
+) -> Result<std::path::PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let path = crate::config::path().ok_or("Could not determine the config directory")?;
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    let mut document: toml_edit::DocumentMut = text
+        .parse()
+        .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+    let device = document["device"].or_insert(toml_edit::table());
+    device["device_id"] = toml_edit::value(device_id);
+    device["iid"] = toml_edit::value(iid);
+    device["openudid"] = toml_edit::value(openudid);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, document.to_string())?;
+    Ok(path)
+}
+
+/// Probes every session ID of `TIKTOK_SESSIONID` against the configured hosts.
+async fn check() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_ids, api_root_url) = crate::load_credentials()?;
+    let sessions: Vec<&str> = crate::sessions::pool(&session_ids).collect();
+    let mut failed = 0;
+    for (number, session_id) in sessions.iter().enumerate() {
+        // Session IDs are secrets, so they are only numbered
+        let label = format!("session {} of {}", number + 1, sessions.len());
+        match crate::request_tts_chunk_with(
+            crate::doctor::PROBE_TEXT,
+            crate::doctor::PROBE_VOICE,
+            session_id,
+            &api_root_url,
+        )
+        .await
+        {
+            Ok(_) => crate::term::status(crate::term::Style::Green, "Accepted", label),
+            Err(crate::ChunkError::Session(e) | crate::ChunkError::Host(e)) => {
+                failed += 1;
+                crate::term::status(
+                    crate::term::Style::Red,
+                    "Failed",
+                    format!("{}: {}", label, e),
+                );
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} session IDs failed", failed, sessions.len()).into()),
+    }
+}
+
+pub async fn run(command: AuthCommand) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match command {
+        AuthCommand::Check => check().await?,
+        AuthCommand::RegisterDevice { force, endpoint } => {
+            let device = &crate::config::get().device;
+            if let Some(device_id) = &device.device_id
+                && !force
+            {
+                println!(
+                    "Already registered as device {} (use --force to register again)",
+                    device_id
+                );
+                return Ok(());
+            }
+
+            let openudid = random_hex(16);
+            let (device_id, iid) = register(&endpoint, &openudid).await?;
+            let path = save_device(&device_id, &iid, &openudid)?;
+            println!("Registered device {} (iid {})", device_id, iid);
+            println!("Saved to {}", path.display());
+        }
+    }
+    Ok(())
+}
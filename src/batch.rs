@@ -0,0 +1,506 @@
+//! Batch and split-output modes, which write one file per input file or per chunk.
+//!
+//! With `--chapters-by` each input is written as one file per chapter instead (see
+//! [`crate::chapters`]). File names come from a template such as `{index:03}_{voice}_{hash8}.mp3`.
+
+use regex::{Captures, Regex};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const OUTPUT_CONFLICTS: [&str; 5] = ["output", "url_only", "play", "cast", "to_mpd"];
+
+#[derive(clap::Args)]
+pub struct BatchArgs {
+    /// Synthesize each text file into its own audio file, and each row of .csv and .ndjson
+    /// files (a `text` field, optional `stem`, other fields for placeholders)
+    #[arg(long = "batch", value_name = "FILE", num_args = 1.., conflicts_with_all = ["text"])]
+    #[arg(conflicts_with_all = OUTPUT_CONFLICTS)]
+    pub files: Vec<PathBuf>,
+
+    /// Write every chunk to its own file instead of joining them
+    #[arg(long, conflicts_with_all = OUTPUT_CONFLICTS)]
+    pub split_output: bool,
+
+    /// Write one file per chapter of each input, starting at `heading` lines (Markdown
+    /// headings, "Chapter 3", "Part II") or at lines matching `regex:PATTERN`; unchanged
+    /// chapters from an earlier run are kept
+    #[arg(long, value_name = "RULE", value_parser = crate::chapters::parse_rule)]
+    #[arg(conflicts_with_all = OUTPUT_CONFLICTS, conflicts_with = "split_output")]
+    pub chapters_by: Option<crate::chapters::Rule>,
+
+    /// Also write an SRT subtitle file next to each chapter
+    #[arg(long, requires = "chapters_by")]
+    pub chapter_subtitles: bool,
+
+    /// File name template; fields: {index}, {voice}, {stem}, {title}, {date}, {hash},
+    /// {hash8}, with zero padding as in {index:03} (default: {stem}.mp3, or
+    /// {stem}_{index:03}.mp3 when split into chunks or chapters)
+    #[arg(long, value_name = "TEMPLATE")]
+    pub name_template: Option<String>,
+
+    /// Directory the files are written to
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub out_dir: PathBuf,
+
+    #[command(flatten)]
+    pub podcast: crate::podcast::PodcastArgs,
+}
+
+impl BatchArgs {
+    pub fn enabled(&self) -> bool {
+        !self.files.is_empty() || self.split_output || self.chapters_by.is_some()
+    }
+}
+
+/// One text to synthesize, with its batch row's fields for `{{name}}` placeholders.
+struct Input {
+    stem: String,
+    text: String,
+    fields: BTreeMap<String, String>,
+}
+
+/// The inputs in a `--batch` file: the whole file, or for `.csv` and `.ndjson`/`.jsonl`
+/// files one per row. Rows need a `text` field; a `stem` field names the output, else it
+/// is the file's stem and the row number. Other fields fill placeholders in the text.
+fn read_inputs(path: &Path) -> Result<Vec<Input>, Box<dyn std::error::Error + Send + Sync>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let stem = path
+        .file_stem()
+        .map_or("text".into(), |stem| stem.to_string_lossy())
+        .into_owned();
+    let invalid = |row: usize, e: &dyn std::fmt::Display| {
+        format!("Invalid row {} in {}: {}", row, path.display(), e)
+    };
+
+    let rows: Vec<BTreeMap<String, String>> =
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => csv::Reader::from_reader(contents.as_bytes())
+                .deserialize()
+                .enumerate()
+                .map(|(index, row)| row.map_err(|e| invalid(index + 1, &e)))
+                .collect::<Result<_, _>>()?,
+            Some("ndjson" | "jsonl") => contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(index, line)| {
+                    let row: serde_json::Map<String, serde_json::Value> =
+                        serde_json::from_str(line).map_err(|e| invalid(index + 1, &e))?;
+                    let fields = row.into_iter().map(|(name, value)| match value {
+                        serde_json::Value::String(value) => (name, value),
+                        value => (name, value.to_string()),
+                    });
+                    Ok::<_, String>(fields.collect())
+                })
+                .collect::<Result<_, _>>()?,
+            _ => {
+                return Ok(vec![Input {
+                    stem,
+                    text: contents.trim().to_string(),
+                    fields: BTreeMap::new(),
+                }]);
+            }
+        };
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(index, mut fields)| {
+            let text = fields
+                .remove("text")
+                .ok_or_else(|| invalid(index + 1, &"no `text` field"))?;
+            let stem = fields
+                .remove("stem")
+                .filter(|stem| !stem.is_empty())
+                .unwrap_or_else(|| format!("{}_{:03}", stem, index + 1));
+            Ok(Input {
+                stem,
+                text: text.trim().to_string(),
+                fields,
+            })
+        })
+        .collect()
+}
+
+/// Values available to the name template for one output file.
+struct NameFields<'a> {
+    /// 1-based chunk or chapter number when splitting, otherwise the input file number
+    index: usize,
+    voice: &'a str,
+    /// Input file name without extension (`text` for command line input)
+    stem: &'a str,
+    /// Chapter title with `--chapters-by`, otherwise the stem; made safe for file names
+    title: &'a str,
+    date: &'a str,
+    /// SHA-256 of the audio, in hex
+    hash: String,
+}
+
+fn render(
+    template: &str,
+    fields: &NameFields,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let field_regex = Regex::new(r"\{(\w+)(?::0(\d+))?\}").unwrap();
+    let mut unknown = None;
+    let name = field_regex.replace_all(template, |caps: &Captures| {
+        let value = match &caps[1] {
+            "index" => fields.index.to_string(),
+            "voice" => fields.voice.to_string(),
+            "stem" => fields.stem.to_string(),
+            "title" => fields.title.to_string(),
+            "date" => fields.date.to_string(),
+            "hash" => fields.hash.clone(),
+            "hash8" => fields.hash[..8].to_string(),
+            other => {
+                unknown.get_or_insert_with(|| other.to_string());
+                String::new()
+            }
+        };
+        match caps.get(2).and_then(|width| width.as_str().parse().ok()) {
+            Some(width) => format!("{:0>width$}", value),
+            None => value,
+        }
+    });
+
+    match unknown {
+        Some(field) => Err(format!("Unknown name template field {{{}}}", field).into()),
+        None => Ok(name.into_owned()),
+    }
+}
+
+/// `title` with everything but letters and digits turned into single dashes.
+fn slug(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+fn hex_digest(audio: &[u8]) -> String {
+    Sha256::digest(audio)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// One file to write, with the chunks it is made of.
+struct Output<'a> {
+    index: usize,
+    /// Episode title, and the `{title}` template field
+    title: String,
+    stem: &'a str,
+    speaker: &'a str,
+    /// Text recorded in the sidecar
+    source: &'a str,
+    chunks: &'a [String],
+    audio_chunks: Vec<Vec<u8>>,
+}
+
+/// Writes the files of a run, and collects their podcast episodes.
+struct Writer<'a> {
+    args: &'a BatchArgs,
+    template: &'a str,
+    date: &'a str,
+    sidecar: bool,
+    written: HashSet<String>,
+    episodes: Vec<crate::podcast::Episode>,
+}
+
+impl Writer<'_> {
+    /// Writes `output`, returning its file name and audio.
+    fn write(
+        &mut self,
+        output: Output,
+    ) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+        let audio = output.audio_chunks.concat();
+        let name = render(
+            self.template,
+            &NameFields {
+                index: output.index,
+                voice: output.speaker,
+                stem: output.stem,
+                title: &slug(&output.title),
+                date: self.date,
+                hash: hex_digest(&audio),
+            },
+        )?;
+        let (name, audio) = crate::sniff::fit(&name, audio)?;
+        if !self.written.insert(name.clone()) {
+            return Err(format!(
+                "Name template produced {} more than once; add {{index}} or {{hash8}}",
+                name
+            )
+            .into());
+        }
+
+        let path = self.args.out_dir.join(&name);
+        if let Some(parent) = path.parent().filter(|p| *p != Path::new("")) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &audio)?;
+        if crate::checksum::enabled() {
+            crate::checksum::write(&path, &audio)?;
+        }
+        crate::term::status(crate::term::Style::Green, "Wrote", path.display());
+        crate::hooks::wrote(
+            Some(&path.to_string_lossy()),
+            output.chunks.len(),
+            audio.len(),
+        );
+        self.episodes.push(crate::podcast::Episode::new(
+            output.title,
+            name.clone(),
+            name.clone(),
+            &audio,
+            chrono::Utc::now(),
+        ));
+
+        if self.sidecar {
+            let metadata = crate::sidecar::metadata(
+                output.source,
+                output.speaker,
+                output.chunks,
+                &output.audio_chunks,
+            );
+            let metadata = crate::sidecar::written_as(metadata, &audio);
+            let mut sidecar_path = path.into_os_string();
+            sidecar_path.push(".json");
+            fs::write(sidecar_path, serde_json::to_vec_pretty(&metadata)?)?;
+        }
+        Ok((name, audio))
+    }
+}
+
+/// Synthesizes `text` (or every `--batch` file) and writes the results to `--out-dir`.
+pub async fn run(
+    args: &BatchArgs,
+    text: Option<String>,
+    speaker: &str,
+    sidecar: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    args.podcast.base_url()?;
+
+    let inputs = match text {
+        Some(text) => vec![Input {
+            stem: "text".to_string(),
+            text,
+            fields: BTreeMap::new(),
+        }],
+        None => {
+            let mut inputs = Vec::new();
+            for path in &args.files {
+                inputs.extend(read_inputs(path)?);
+            }
+            inputs
+        }
+    };
+
+    let template = args.name_template.as_deref().unwrap_or(
+        if args.split_output || args.chapters_by.is_some() {
+            "{stem}_{index:03}.mp3"
+        } else {
+            "{stem}.mp3"
+        },
+    );
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+
+    // Catch template typos before spending any requests
+    render(
+        template,
+        &NameFields {
+            index: 1,
+            voice: speaker,
+            stem: "text",
+            title: "text",
+            date: &date,
+            hash: "0".repeat(64),
+        },
+    )?;
+
+    let mut writer = Writer {
+        args,
+        template,
+        date: &date,
+        sidecar,
+        written: HashSet::new(),
+        episodes: Vec::new(),
+    };
+
+    for (file_index, input) in inputs.iter().enumerate() {
+        let stem = &input.stem;
+        let text = &crate::vars::render(&input.text, &input.fields)?;
+        if text.is_empty() {
+            crate::term::status(
+                crate::term::Style::Yellow,
+                "Skipping",
+                format!("{}: no text", stem),
+            );
+            continue;
+        }
+
+        let speaker = &crate::catalog::pick(speaker)?;
+        if let Some(rule) = &args.chapters_by {
+            write_chapters(
+                &mut writer,
+                rule,
+                stem,
+                text,
+                speaker,
+                &session_id,
+                &api_root_url,
+            )
+            .await?;
+            continue;
+        }
+        let chunks = crate::split_text(text, crate::BYTE_LIMIT)?;
+        let audio_chunks =
+            crate::synthesize_chunks(&chunks, speaker, &session_id, &api_root_url).await?;
+        if args.split_output {
+            for (i, audio) in audio_chunks.into_iter().enumerate() {
+                writer.write(Output {
+                    index: i + 1,
+                    title: format!("{} {}", stem, i + 1),
+                    stem,
+                    speaker,
+                    source: &chunks[i],
+                    chunks: &chunks[i..=i],
+                    audio_chunks: vec![audio],
+                })?;
+            }
+        } else {
+            writer.write(Output {
+                index: file_index + 1,
+                title: stem.clone(),
+                stem,
+                speaker,
+                source: text,
+                chunks: &chunks,
+                audio_chunks,
+            })?;
+        }
+    }
+
+    crate::podcast::write(&args.podcast, "tktts", &mut writer.episodes)
+}
+
+/// Writes each chapter of `text` that an earlier run did not already write, and updates
+/// the input's manifest after every one.
+async fn write_chapters(
+    writer: &mut Writer<'_>,
+    rule: &crate::chapters::Rule,
+    stem: &str,
+    text: &str,
+    speaker: &str,
+    session_id: &str,
+    api_root_url: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let args = writer.args;
+    let chapters = crate::chapters::split(text, rule, stem);
+    if chapters.len() < 2 {
+        crate::term::status(
+            crate::term::Style::Yellow,
+            "Warning",
+            format!("{}: no chapter headings found, writing one file", stem),
+        );
+    }
+    fs::create_dir_all(&args.out_dir)?;
+    let manifest_path = crate::chapters::manifest_path(&args.out_dir, stem);
+    let previous = crate::chapters::load(&manifest_path);
+    let mut manifest = crate::chapters::Manifest {
+        title: stem.to_string(),
+        voice: speaker.to_string(),
+        normalization: tktts::NORMALIZATION_VERSION,
+        ..Default::default()
+    };
+
+    for (i, chapter) in chapters.iter().enumerate() {
+        let index = i + 1;
+        let text_sha256 = hex_digest(chapter.text.as_bytes());
+        if let Some(entry) = crate::chapters::resumable(
+            previous.as_ref(),
+            speaker,
+            index,
+            &text_sha256,
+            &args.out_dir,
+            args.chapter_subtitles,
+        ) {
+            crate::term::status(
+                crate::term::Style::Dim,
+                "Skipping",
+                format!("{} (chapter {} is unchanged)", entry.file, index),
+            );
+            let audio = fs::read(args.out_dir.join(&entry.file))?;
+            writer.written.insert(entry.file.clone());
+            writer.episodes.push(crate::podcast::Episode::new(
+                chapter.title.clone(),
+                entry.file.clone(),
+                entry.file.clone(),
+                &audio,
+                chrono::Utc::now(),
+            ));
+            manifest.chapters.push(entry.clone());
+            continue;
+        }
+
+        let chunks = crate::split_text(&chapter.text, crate::BYTE_LIMIT)?;
+        let audio_chunks =
+            crate::synthesize_chunks(&chunks, speaker, session_id, api_root_url).await?;
+        let subtitles = args
+            .chapter_subtitles
+            .then(|| crate::chapters::subtitles(&chunks, &audio_chunks));
+        // Taken from the MP3 chunks, as the file may be converted to WAV
+        let duration_secs: f64 = audio_chunks
+            .iter()
+            .map(|audio| crate::mp3::info(audio).duration_secs)
+            .sum();
+        let (name, audio) = writer.write(Output {
+            index,
+            title: chapter.title.clone(),
+            stem,
+            speaker,
+            source: &chapter.text,
+            chunks: &chunks,
+            audio_chunks,
+        })?;
+        let subtitles = match subtitles {
+            Some(srt) => {
+                let file = Path::new(&name).with_extension("srt");
+                fs::write(args.out_dir.join(&file), srt)?;
+                Some(file.to_string_lossy().into_owned())
+            }
+            None => None,
+        };
+        manifest.chapters.push(crate::chapters::Entry {
+            index,
+            title: chapter.title.clone(),
+            file: name,
+            subtitles,
+            duration_secs: (duration_secs * 1000.0).round() / 1000.0,
+            bytes: audio.len(),
+            sha256: hex_digest(&audio),
+            text_sha256,
+        });
+        // Saved as it goes, so an interrupted run resumes after the last whole chapter
+        crate::chapters::save(&manifest_path, &mut manifest)?;
+    }
+
+    crate::chapters::save(&manifest_path, &mut manifest)?;
+    crate::term::status(
+        crate::term::Style::Green,
+        "Wrote",
+        format!(
+            "{} ({} chapters)",
+            manifest_path.display(),
+            manifest.chapters.len()
+        ),
+    );
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! `tktts bench`: measures request throughput and latency across concurrency levels.
+//!
+//! Runs against the configured endpoint, or with `--mock` against an in-process server
+//! that answers with silent audio after a fixed delay, to see what the client itself
+//! can sustain.
+
+use axum::Router;
+use axum::routing::post;
+use base64::{Engine as _, engine::general_purpose};
+use futures_util::StreamExt;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+#[derive(clap::Args)]
+pub struct BenchArgs {
+    /// Requests per concurrency level
+    #[arg(long, default_value_t = 50)]
+    chunks: usize,
+
+    /// Concurrency levels: a range like 1..16 (powers of two) or a list like 1,4,8
+    #[arg(long, default_value = "1..16", value_parser = parse_levels)]
+    concurrency: Levels,
+
+    /// TikTok speaker voice
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+
+    /// Benchmark against a local mock backend instead of the real endpoint
+    #[arg(long)]
+    mock: bool,
+
+    /// Response delay of the mock backend, in milliseconds
+    #[arg(long, default_value_t = 200, value_name = "MS", requires = "mock")]
+    mock_latency: u64,
+}
+
+#[derive(Clone)]
+struct Levels(Vec<usize>);
+
+fn parse_levels(value: &str) -> Result<Levels, String> {
+    let invalid = || {
+        format!(
+            "invalid concurrency '{}', expected e.g. 1..16 or 1,4,8",
+            value
+        )
+    };
+    let levels = match value.split_once("..") {
+        Some((from, to)) => {
+            let from: usize = from.trim().parse().map_err(|_| invalid())?;
+            let to: usize = to.trim().parse().map_err(|_| invalid())?;
+            let mut levels = Vec::new();
+            let mut level = from.max(1);
+            while level <= to {
+                levels.push(level);
+                level *= 2;
+            }
+            if levels.last() != Some(&to) && to >= from.max(1) {
+                levels.push(to);
+            }
+            levels
+        }
+        None => value
+            .split(',')
+            .map(|level| level.trim().parse::<usize>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    if levels.is_empty() || levels.contains(&0) {
+        return Err(invalid());
+    }
+    Ok(Levels(levels))
+}
+
+/// Starts the mock backend on a free local port and returns its base URL.
+async fn start_mock(latency: Duration) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    // One silent 128 kbit/s MPEG-1 Layer III frame
+    let mut frame = vec![0xff, 0xfb, 0x90, 0x64];
+    frame.resize(417, 0);
+    let body = serde_json::json!({
+        "data": { "v_str": general_purpose::STANDARD.encode(&frame) },
+        "message": "success",
+        "status_code": 0,
+    })
+    .to_string();
+
+    let app = Router::new().route(
+        crate::API_BASE_URL,
+        post(move || {
+            let body = body.clone();
+            async move {
+                tokio::time::sleep(latency).await;
+                body
+            }
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok(format!("http://{}", address))
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index].as_secs_f64() * 1000.0
+}
+
+pub async fn run(args: BenchArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = if args.mock {
+        let url = start_mock(Duration::from_millis(args.mock_latency)).await?;
+        ("mock".to_string(), url)
+    } else {
+        crate::load_credentials()?
+    };
+
+    // Per-request response dumps would drown the table
+    crate::PROGRESS.store(false, Ordering::Relaxed);
+
+    println!(
+        "{:>11} {:>10} {:>8} {:>8} {:>8} {:>7}",
+        "concurrency", "chunks/s", "p50 ms", "p90 ms", "p99 ms", "errors"
+    );
+    for &level in &args.concurrency.0 {
+        let started = Instant::now();
+        let results: Vec<(Duration, bool)> = futures_util::stream::iter(0..args.chunks)
+            .map(|i| {
+                // Distinct texts so no layer in between can serve a repeat
+                let text = format!("Benchmark sentence {} at concurrency {}.", i + 1, level);
+                let (speaker, session_id, api_root_url) =
+                    (&args.speaker, &session_id, &api_root_url);
+                async move {
+                    let started = Instant::now();
+                    let ok = crate::request_tts_chunk(&text, speaker, session_id, api_root_url)
+                        .await
+                        .is_ok();
+                    (started.elapsed(), ok)
+                }
+            })
+            .buffer_unordered(level)
+            .collect()
+            .await;
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let mut latencies: Vec<Duration> = results
+            .iter()
+            .filter(|(_, ok)| *ok)
+            .map(|(latency, _)| *latency)
+            .collect();
+        latencies.sort();
+        let errors = results.len() - latencies.len();
+        println!(
+            "{:>11} {:>10.1} {:>8.0} {:>8.0} {:>8.0} {:>7}",
+            level,
+            latencies.len() as f64 / elapsed,
+            percentile(&latencies, 0.5),
+            percentile(&latencies, 0.9),
+            percentile(&latencies, 0.99),
+            errors
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+//! Right-to-left and mixed-direction text.
+//!
+//! Text is always stored in reading order, so speaking it needs no reordering; what
+//! goes wrong is the invisible formatting around it. Directional marks and embeddings
+//! only matter for display and are dropped, chunk boundaries stay out of numbers
+//! embedded in Arabic or Hebrew text, and combining marks such as harakat and niqqud
+//! stay with their letter.
+
+/// Whether `c` is a directional formatting character: marks, embeddings, overrides
+/// and isolates.
+pub(crate) fn is_control(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// `text` without directional formatting characters.
+pub(crate) fn strip_controls(text: &str) -> String {
+    text.chars().filter(|&c| !is_control(c)).collect()
+}
+
+/// Whether `c` belongs to the character before it: combining marks (including Arabic
+/// harakat and Hebrew niqqud), joiners, variation selectors and skin tone modifiers.
+pub(crate) fn is_joining(c: char) -> bool {
+    matches!(
+        c,
+        '\u{300}'..='\u{36f}'
+            | '\u{591}'..='\u{5bd}'
+            | '\u{5bf}'
+            | '\u{5c1}'..='\u{5c2}'
+            | '\u{5c4}'..='\u{5c5}'
+            | '\u{5c7}'
+            | '\u{610}'..='\u{61a}'
+            | '\u{64b}'..='\u{65f}'
+            | '\u{670}'
+            | '\u{6d6}'..='\u{6dc}'
+            | '\u{6df}'..='\u{6e4}'
+            | '\u{6e7}'..='\u{6e8}'
+            | '\u{6ea}'..='\u{6ed}'
+            | '\u{200c}'
+            | '\u{200d}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{1f3fb}'..='\u{1f3ff}'
+    )
+}
+
+/// Whether a segment ending in `segment` and followed by `next` would cut a number
+/// apart at its separator, as in `1,500`, `3.5`, `12:30` or `2024-05-01`.
+pub(crate) fn splits_number(segment: &str, next: &str) -> bool {
+    let mut tail = segment.chars().rev();
+    let separator = tail.next();
+    let digit_before = tail.next().is_some_and(|c| c.is_numeric());
+    let digit_after = next.chars().next().is_some_and(|c| c.is_numeric());
+    digit_before && digit_after && separator.is_some_and(|c| matches!(c, '.' | ',' | ':' | '-'))
+}
@@ -0,0 +1,137 @@
+//! A blocking version of [`client::Client`](crate::client::Client), for programs that do
+//! not run an async runtime themselves (built with the `blocking` feature).
+//!
+//! Each client owns a small tokio runtime and blocks the calling thread on it, like
+//! reqwest's blocking client. It must therefore not be used from within an async runtime,
+//! where blocking would stall other tasks; use the async client there.
+
+use crate::client;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+pub use crate::TktsError;
+pub use crate::client::{
+    AudioChunk, CancellationToken, ChunkInfo, DEFAULT_BYTE_LIMIT, DEFAULT_CONCURRENCY, Format,
+    SynthesisRequest, SynthesisResult,
+};
+
+#[derive(Clone)]
+pub struct Client {
+    inner: client::Client,
+    runtime: Arc<Runtime>,
+}
+
+impl Client {
+    /// A client for the API host `api_root_url`, authenticated with a TikTok session ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the runtime cannot be started, as when the process is out of threads.
+    pub fn new(session_id: &str, api_root_url: &str) -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to start the tokio runtime");
+        Client {
+            inner: client::Client::new(session_id, api_root_url),
+            runtime: Arc::new(runtime),
+        }
+    }
+
+    /// Sends the requests with `transport` instead of the default reqwest one.
+    pub fn transport(mut self, transport: impl crate::transport::Transport + 'static) -> Self {
+        self.inner = self.inner.transport(transport);
+        self
+    }
+
+    /// Wraps the transport in `layer`'s policy; see [`layers`](crate::layers).
+    pub fn layer(mut self, layer: impl crate::layers::Layer) -> Self {
+        self.inner = self.inner.layer(layer);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.inner = self.inner.user_agent(user_agent);
+        self
+    }
+
+    pub fn aid(mut self, aid: &str) -> Self {
+        self.inner = self.inner.aid(aid);
+        self
+    }
+
+    /// Maximum chunk size in bytes; clamped to [`MIN_BYTE_LIMIT`](crate::MIN_BYTE_LIMIT).
+    pub fn byte_limit(mut self, byte_limit: usize) -> Self {
+        self.inner = self.inner.byte_limit(byte_limit);
+        self
+    }
+
+    /// Number of chunk requests in flight at once (at least 1).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.inner = self.inner.concurrency(concurrency);
+        self
+    }
+
+    /// Requests one chunk and returns its decoded MP3 audio.
+    pub fn synthesize_chunk(&self, text: &str, voice: &str) -> Result<Vec<u8>, TktsError> {
+        self.runtime
+            .block_on(self.inner.synthesize_chunk(text, voice))
+    }
+
+    /// Synthesizes `text` and yields each chunk's audio in order. The requests run while
+    /// the iterator is advanced.
+    pub fn synthesize_stream(&self, text: &str, voice: &str) -> Stream {
+        self.synthesize_stream_with_cancel(text, voice, CancellationToken::new())
+    }
+
+    /// [`synthesize_stream`](Self::synthesize_stream) until `token` is cancelled, which
+    /// may happen from another thread; the last item is then a [`TktsError::Cancelled`] error.
+    pub fn synthesize_stream_with_cancel(
+        &self,
+        text: &str,
+        voice: &str,
+        token: CancellationToken,
+    ) -> Stream {
+        let _runtime = self.runtime.enter();
+        Stream {
+            chunks: self.inner.synthesize_stream_with_cancel(text, voice, token),
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Synthesizes `text` of any length and returns the joined MP3 audio.
+    pub fn synthesize(&self, text: &str, voice: &str) -> Result<Vec<u8>, TktsError> {
+        self.runtime.block_on(self.inner.synthesize(text, voice))
+    }
+
+    /// [`synthesize`](Self::synthesize) until `token` is cancelled, which may happen from
+    /// another thread; the call then returns a [`TktsError::Cancelled`] error at once.
+    pub fn synthesize_with_cancel(
+        &self,
+        text: &str,
+        voice: &str,
+        token: &CancellationToken,
+    ) -> Result<Vec<u8>, TktsError> {
+        self.runtime
+            .block_on(self.inner.synthesize_with_cancel(text, voice, token))
+    }
+
+    /// Synthesizes `request` and returns its audio with the details of each chunk.
+    pub fn execute(&self, request: &SynthesisRequest) -> Result<SynthesisResult, TktsError> {
+        self.runtime.block_on(self.inner.execute(request))
+    }
+}
+
+/// The chunks of [`Client::synthesize_stream`].
+pub struct Stream {
+    chunks: client::ChunkStream,
+    runtime: Arc<Runtime>,
+}
+
+impl Iterator for Stream {
+    type Item = Result<AudioChunk, TktsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.chunks.recv())
+    }
+}
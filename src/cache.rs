@@ -0,0 +1,374 @@
+//! On-disk cache of decoded chunk audio, keyed by voice and sanitized chunk text.
+//!
+//! Entries live under `<cache dir>/chunks/<aa>/<key>.mp3`. Their modification time is
+//! refreshed on every hit, so `cache gc` evicts the least recently used entries first.
+
+use clap::Subcommand;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Show the number of entries, total size and hit rate
+    Stats,
+
+    /// Remove all cached audio and reset the statistics
+    Clear,
+
+    /// Evict entries that are too old or exceed the size budget, oldest first
+    Gc {
+        /// Keep the cache below this size, e.g. 500MB
+        #[arg(long, value_parser = parse_size)]
+        max_size: Option<u64>,
+
+        /// Remove entries not used for this long, e.g. 30d
+        #[arg(long, value_parser = parse_age)]
+        max_age: Option<Duration>,
+    },
+
+    /// Write all cached audio into a tar archive
+    Export { file: PathBuf },
+
+    /// Add the entries of an archive created by `cache export`
+    Import { file: PathBuf },
+}
+
+/// Turns the cache off for this process.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("tktts"))
+}
+
+fn chunks_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("chunks"))
+}
+
+fn stats_path() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("stats.json"))
+}
+
+/// The cache key of a chunk: a SHA-256 of the normalization version, the voice and the
+/// text as sent to the API.
+pub fn key(text: &str, speaker: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(tktts::NORMALIZATION_VERSION.to_le_bytes())
+        .chain_update(speaker.as_bytes())
+        .chain_update([0])
+        .chain_update(crate::sanitize_text(text, speaker).as_bytes())
+        .finalize();
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn entry_path(key: &str) -> Option<PathBuf> {
+    chunks_dir().map(|dir| dir.join(&key[..2]).join(format!("{}.mp3", key)))
+}
+
+fn is_entry_name(name: &str) -> bool {
+    name.strip_suffix(".mp3")
+        .is_some_and(|key| key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Whether a chunk's audio is cached, without reading it.
+pub fn contains(text: &str, speaker: &str) -> bool {
+    ENABLED.load(Ordering::Relaxed)
+        && entry_path(&key(text, speaker)).is_some_and(|path| path.exists())
+}
+
+/// Returns the cached audio for a chunk, if any.
+pub fn get(text: &str, speaker: &str) -> Option<Vec<u8>> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let path = entry_path(&key(text, speaker))?;
+    let audio = fs::read(&path).ok()?;
+
+    // Mark the entry as recently used for gc
+    if let Ok(file) = File::options().write(true).open(&path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+    Some(audio)
+}
+
+/// Stores the audio of a chunk. Failures only cost a future cache miss, so they are not fatal.
+pub fn put(text: &str, speaker: &str, audio: &[u8]) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(path) = entry_path(&key(text, speaker)) else {
+        return;
+    };
+
+    // Write to a temporary file of its own first, so concurrent runs never read a
+    // partial entry nor write into each other's
+    let temp = path.with_extension(format!("{:016x}.tmp", fastrand::u64(..)));
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&temp, audio))
+        .and_then(|_| fs::rename(&temp, &path));
+    if let Err(e) = result {
+        progress!("Could not write cache entry: {}", e);
+    }
+}
+
+fn read_stats() -> (u64, u64) {
+    let stats = stats_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok());
+    match stats {
+        Some(stats) => (
+            stats["hits"].as_u64().unwrap_or(0),
+            stats["misses"].as_u64().unwrap_or(0),
+        ),
+        None => (0, 0),
+    }
+}
+
+/// Adds the hits and misses of one synthesis run to the persistent statistics.
+pub fn record(hits: u64, misses: u64) {
+    if !ENABLED.load(Ordering::Relaxed) || hits + misses == 0 {
+        return;
+    }
+    let Some(path) = stats_path() else {
+        return;
+    };
+    let (total_hits, total_misses) = read_stats();
+    let stats = json!({ "hits": total_hits + hits, "misses": total_misses + misses });
+    let _ = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, stats.to_string()));
+}
+
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+fn list_entries(chunks_dir: &Path) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let Ok(shards) = fs::read_dir(chunks_dir) else {
+        return entries;
+    };
+    for shard in shards.flatten() {
+        let Ok(files) = fs::read_dir(shard.path()) else {
+            continue;
+        };
+        for file in files.flatten() {
+            if !is_entry_name(&file.file_name().to_string_lossy()) {
+                continue;
+            }
+            if let Ok(metadata) = file.metadata() {
+                entries.push(Entry {
+                    path: file.path(),
+                    size: metadata.len(),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// Parses sizes like `500MB`, `1.5G`, `64k` or a plain byte count.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim().to_ascii_uppercase();
+    let value = value.strip_suffix('B').unwrap_or(&value);
+    let (number, multiplier) = match value.chars().last() {
+        Some('K') => (&value[..value.len() - 1], 1u64 << 10),
+        Some('M') => (&value[..value.len() - 1], 1 << 20),
+        Some('G') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parses ages like `30d`, `12h`, `45m` or `90s`.
+fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, seconds) = [('d', 86_400), ('h', 3_600), ('m', 60), ('s', 1)]
+        .into_iter()
+        .find_map(|(unit, seconds)| Some((value.strip_suffix(unit)?, seconds)))
+        .ok_or_else(|| format!("invalid age '{}', expected e.g. 30d", value))?;
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid age '{}', expected e.g. 30d", value))?;
+    Ok(Duration::from_secs(number * seconds))
+}
+
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 30 => format!("{:.1} GB", b as f64 / (1u64 << 30) as f64),
+        b if b >= 1 << 20 => format!("{:.1} MB", b as f64 / (1u64 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KB", b as f64 / (1u64 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
+
+fn gc(
+    chunks_dir: &Path,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut entries = list_entries(chunks_dir);
+    entries.sort_by_key(|entry| entry.modified);
+
+    let now = SystemTime::now();
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let (mut removed, mut freed) = (0, 0);
+    for entry in entries {
+        let too_old = max_age.is_some_and(|max_age| {
+            now.duration_since(entry.modified).unwrap_or_default() > max_age
+        });
+        let too_big = max_size.is_some_and(|max_size| total > max_size);
+        if !too_old && !too_big {
+            continue;
+        }
+        fs::remove_file(&entry.path)?;
+        total -= entry.size;
+        removed += 1;
+        freed += entry.size;
+    }
+
+    println!(
+        "Removed {} entries ({}), {} remaining",
+        removed,
+        format_size(freed),
+        format_size(total)
+    );
+    Ok(())
+}
+
+fn import(cache_dir: &Path, file: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut archive = tar::Archive::new(File::open(file)?);
+    let mut imported = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+
+        // Only accept chunks/<aa>/<key>.mp3, whatever else the archive contains
+        let components: Vec<_> = path.iter().map(|c| c.to_string_lossy()).collect();
+        let valid = components.len() == 3
+            && components[0] == "chunks"
+            && is_entry_name(&components[2])
+            && components[2].starts_with(components[1].as_ref());
+        if valid && entry.unpack_in(cache_dir)? {
+            imported += 1;
+        }
+    }
+    println!("Imported {} entries", imported);
+    Ok(())
+}
+
+pub fn run(command: CacheCommand) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let cache_dir = cache_dir().ok_or("Could not determine the cache directory")?;
+    let chunks_dir = cache_dir.join("chunks");
+
+    match command {
+        CacheCommand::Stats => {
+            let entries = list_entries(&chunks_dir);
+            let size: u64 = entries.iter().map(|entry| entry.size).sum();
+            let (hits, misses) = read_stats();
+            let hit_rate = if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 * 100.0 / (hits + misses) as f64
+            };
+            println!("Location: {}", cache_dir.display());
+            println!("Entries:  {}", entries.len());
+            println!("Size:     {}", format_size(size));
+            println!(
+                "Hit rate: {:.1}% ({} hits, {} misses)",
+                hit_rate, hits, misses
+            );
+        }
+        CacheCommand::Clear => {
+            if chunks_dir.exists() {
+                fs::remove_dir_all(&chunks_dir)?;
+            }
+            if let Some(stats) = stats_path().filter(|path| path.exists()) {
+                fs::remove_file(stats)?;
+            }
+            println!("Cache cleared");
+        }
+        CacheCommand::Gc { max_size, max_age } => {
+            if max_size.is_none() && max_age.is_none() {
+                return Err("Pass --max-size and/or --max-age".into());
+            }
+            gc(&chunks_dir, max_size, max_age)?;
+        }
+        CacheCommand::Export { file } => {
+            let mut archive = tar::Builder::new(File::create(&file)?);
+            let entries = list_entries(&chunks_dir);
+            for entry in &entries {
+                let relative = entry.path.strip_prefix(&cache_dir)?;
+                archive.append_path_with_name(&entry.path, relative)?;
+            }
+            archive.finish()?;
+            println!("Exported {} entries to {}", entries.len(), file.display());
+        }
+        CacheCommand::Import { file } => import(&cache_dir, &file)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_take_binary_units_with_or_without_b() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("2k").unwrap(), 2048);
+        assert_eq!(parse_size("1.5 MB").unwrap(), 3 << 19);
+        assert_eq!(parse_size("1G").unwrap(), 1 << 30);
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn ages_take_a_unit() {
+        assert_eq!(parse_age("30d").unwrap(), Duration::from_secs(30 * 86_400));
+        assert_eq!(parse_age(" 12h ").unwrap(), Duration::from_secs(12 * 3_600));
+        assert_eq!(parse_age("45m").unwrap(), Duration::from_secs(45 * 60));
+        assert_eq!(parse_age("90s").unwrap(), Duration::from_secs(90));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("30µ").is_err());
+        assert!(parse_age("µ").is_err());
+    }
+
+    #[test]
+    fn keys_name_entries_by_text_and_voice() {
+        let key = key("Hello there", "en_us_002");
+        assert_eq!(key, super::key("Hello there", "en_us_002"));
+        assert_ne!(key, super::key("Hello there", "en_us_001"));
+        assert_ne!(key, super::key("Hello", "en_us_002"));
+        assert!(is_entry_name(&format!("{}.mp3", key)));
+    }
+
+    #[test]
+    fn only_mp3_entries_are_entry_names() {
+        let key = "ab".repeat(32);
+        assert!(is_entry_name(&format!("{}.mp3", key)));
+        assert!(!is_entry_name(&format!("{}.0123456789abcdef.tmp", key)));
+        assert!(!is_entry_name(&format!("{}.mp3", &key[1..])));
+        assert!(!is_entry_name(&format!("{}.mp3", "zz".repeat(32))));
+        assert!(!is_entry_name("stats.json"));
+    }
+}
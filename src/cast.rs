@@ -0,0 +1,334 @@
+//! Casting to smart speakers.
+//!
+//! The audio is served from a temporary local HTTP endpoint and handed to a Chromecast
+//! (found via mDNS, controlled over the Cast protocol) or a DLNA/UPnP media renderer
+//! (found via SSDP, controlled over AVTransport SOAP). Playback is awaited before the
+//! endpoint shuts down.
+
+use axum::Router;
+use axum::http::header;
+use axum::routing::get;
+use regex::Regex;
+use rust_cast::CastDevice;
+use rust_cast::ChannelMessage;
+use rust_cast::channels::heartbeat::HeartbeatResponse;
+use rust_cast::channels::media::{IdleReason, Media, MediaResponse, PlayerState, StreamType};
+use rust_cast::channels::receiver::CastDeviceApp;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// How long to listen for mDNS and SSDP announcements.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Upper bound on how long a single announcement may play before we stop serving it.
+const PLAYBACK_TIMEOUT: Duration = Duration::from_secs(600);
+const CHROMECAST_SERVICE: &str = "_googlecast._tcp.local.";
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+const AV_TRANSPORT: &str = "urn:schemas-upnp-org:service:AVTransport:1";
+
+enum Renderer {
+    Chromecast { addr: SocketAddr },
+    Dlna { addr: SocketAddr, control_url: Url },
+}
+
+impl Renderer {
+    fn addr(&self) -> SocketAddr {
+        match self {
+            Renderer::Chromecast { addr } | Renderer::Dlna { addr, .. } => *addr,
+        }
+    }
+}
+
+fn find_chromecast(name: &str) -> Option<Renderer> {
+    let daemon = mdns_sd::ServiceDaemon::new().ok()?;
+    let receiver = daemon.browse(CHROMECAST_SERVICE).ok()?;
+    let deadline = Instant::now() + DISCOVERY_TIMEOUT;
+
+    let mut found = None;
+    while let Ok(event) = receiver.recv_deadline(deadline) {
+        if let mdns_sd::ServiceEvent::ServiceResolved(info) = event
+            && info
+                .get_property_val_str("fn")
+                .is_some_and(|friendly| friendly.eq_ignore_ascii_case(name))
+            && let Some(ip) = info.get_addresses().iter().next()
+        {
+            found = Some(Renderer::Chromecast {
+                addr: SocketAddr::new(*ip, info.get_port()),
+            });
+            break;
+        }
+    }
+
+    let _ = daemon.shutdown();
+    found
+}
+
+/// Returns the `LOCATION` header of every renderer answering an SSDP search.
+async fn ssdp_search() -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+        SSDP_ADDR, AV_TRANSPORT
+    );
+    socket.send_to(search.as_bytes(), SSDP_ADDR).await?;
+
+    let mut locations = Vec::new();
+    let mut buffer = [0u8; 2048];
+    let deadline = tokio::time::Instant::now() + DISCOVERY_TIMEOUT;
+    while let Ok(Ok((len, _))) =
+        tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await
+    {
+        let response = String::from_utf8_lossy(&buffer[..len]);
+        let location = response.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case("location")
+                .then(|| value.trim().to_string())
+        });
+        if let Some(location) = location
+            && !locations.contains(&location)
+        {
+            locations.push(location);
+        }
+    }
+
+    Ok(locations)
+}
+
+async fn find_dlna(
+    name: &str,
+) -> Result<Option<Renderer>, Box<dyn std::error::Error + Send + Sync>> {
+    let friendly_name = Regex::new(r"<friendlyName>([^<]*)</friendlyName>")?;
+    let service = Regex::new(r"(?s)<service>(.*?)</service>")?;
+    let control_url = Regex::new(r"<controlURL>([^<]*)</controlURL>")?;
+
+    for location in ssdp_search().await? {
+        let Ok(location) = Url::parse(&location) else {
+            continue;
+        };
+        let Ok(response) = reqwest::get(location.clone()).await else {
+            continue;
+        };
+        let description = response.text().await?;
+
+        let matches_name = friendly_name
+            .captures(&description)
+            .is_some_and(|caps| caps[1].trim().eq_ignore_ascii_case(name));
+        if !matches_name {
+            continue;
+        }
+
+        let control = service
+            .captures_iter(&description)
+            .filter(|caps| caps[1].contains(AV_TRANSPORT))
+            .find_map(|caps| {
+                control_url
+                    .captures(&caps[1])
+                    .map(|c| c[1].trim().to_string())
+            });
+        let (Some(control), Some(host)) = (control, location.host_str()) else {
+            continue;
+        };
+
+        let ip: IpAddr = host.trim_matches(['[', ']']).parse()?;
+        let port = location.port_or_known_default().unwrap_or(80);
+        return Ok(Some(Renderer::Dlna {
+            addr: SocketAddr::new(ip, port),
+            control_url: location.join(&control)?,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// The local address the renderer can reach us on: the one the OS routes towards it.
+async fn local_ip_towards(
+    addr: SocketAddr,
+) -> Result<IpAddr, Box<dyn std::error::Error + Send + Sync>> {
+    let socket = UdpSocket::bind(if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    })
+    .await?;
+    socket.connect(addr).await?;
+    Ok(socket.local_addr()?.ip())
+}
+
+fn play_on_chromecast(
+    addr: SocketAddr,
+    media_url: String,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let device = CastDevice::connect_without_host_verification(addr.ip().to_string(), addr.port())?;
+    device.connection.connect("receiver-0")?;
+    device.heartbeat.ping()?;
+
+    let app = device
+        .receiver
+        .launch_app(&CastDeviceApp::DefaultMediaReceiver)?;
+    device.connection.connect(app.transport_id.as_str())?;
+    device.media.load(
+        app.transport_id.as_str(),
+        app.session_id.as_str(),
+        &Media {
+            content_id: media_url,
+            stream_type: StreamType::Buffered,
+            content_type: "audio/mpeg".to_string(),
+            metadata: None,
+            duration: None,
+        },
+    )?;
+
+    let started = Instant::now();
+    while started.elapsed() < PLAYBACK_TIMEOUT {
+        match device.receive()? {
+            ChannelMessage::Heartbeat(HeartbeatResponse::Ping) => device.heartbeat.pong()?,
+            ChannelMessage::Media(MediaResponse::Status(status)) => {
+                let finished = status.entries.iter().any(|entry| {
+                    entry.player_state == PlayerState::Idle && entry.idle_reason.is_some()
+                });
+                if let Some(entry) = status.entries.first()
+                    && entry.idle_reason == Some(IdleReason::Error)
+                {
+                    return Err("Chromecast failed to play the audio".into());
+                }
+                if finished {
+                    break;
+                }
+            }
+            ChannelMessage::Media(MediaResponse::LoadFailed(_)) => {
+                return Err("Chromecast failed to load the audio".into());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn soap_call(
+    control_url: &Url,
+    action: &str,
+    arguments: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><u:{action} xmlns:u="{AV_TRANSPORT}"><InstanceID>0</InstanceID>{arguments}</u:{action}></s:Body></s:Envelope>"#
+    );
+    let response = crate::http::client()
+        .post(control_url.clone())
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header("SOAPAction", format!("\"{}#{}\"", AV_TRANSPORT, action))
+        .body(body)
+        .send()
+        .await?;
+
+    let status = response.status();
+    let text = response.text().await?;
+    if !status.is_success() {
+        return Err(format!("DLNA {} failed ({})", action, status).into());
+    }
+    Ok(text)
+}
+
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+async fn play_on_dlna(
+    control_url: &Url,
+    media_url: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Several renderers refuse a URI without DIDL-Lite metadata describing its MIME type
+    let metadata = format!(
+        r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/"><item id="0" parentID="-1" restricted="1"><dc:title>tktts</dc:title><upnp:class>object.item.audioItem.musicTrack</upnp:class><res protocolInfo="http-get:*:audio/mpeg:*">{}</res></item></DIDL-Lite>"#,
+        xml_escape(media_url)
+    );
+    soap_call(
+        control_url,
+        "SetAVTransportURI",
+        &format!(
+            "<CurrentURI>{}</CurrentURI><CurrentURIMetaData>{}</CurrentURIMetaData>",
+            xml_escape(media_url),
+            xml_escape(&metadata)
+        ),
+    )
+    .await?;
+    soap_call(control_url, "Play", "<Speed>1</Speed>").await?;
+
+    // Wait until the renderer has started and then left the PLAYING/TRANSITIONING states
+    let state = Regex::new(r"<CurrentTransportState>([^<]*)</CurrentTransportState>")?;
+    let started = Instant::now();
+    let mut was_playing = false;
+    while started.elapsed() < PLAYBACK_TIMEOUT {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let info = soap_call(control_url, "GetTransportInfo", "").await?;
+        let current = state
+            .captures(&info)
+            .map(|caps| caps[1].to_string())
+            .unwrap_or_default();
+        match current.as_str() {
+            "PLAYING" | "TRANSITIONING" => was_playing = true,
+            _ if was_playing => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Serves `audio` at a temporary URL reachable from `peer` until the returned task is aborted.
+pub async fn serve_audio(
+    audio: &[u8],
+    peer: SocketAddr,
+) -> Result<(String, JoinHandle<io::Result<()>>), Box<dyn std::error::Error + Send + Sync>> {
+    let local_ip = local_ip_towards(peer).await?;
+    let listener = TcpListener::bind(SocketAddr::new(local_ip, 0)).await?;
+    let media_url = format!("http://{}/tktts.mp3", listener.local_addr()?);
+
+    let audio = audio.to_vec();
+    let app = Router::new().route(
+        "/tktts.mp3",
+        get(move || async move { ([(header::CONTENT_TYPE, "audio/mpeg")], audio) }),
+    );
+    let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+    Ok((media_url, server))
+}
+
+/// Casts `audio` to the Chromecast or DLNA renderer named `device` and waits for playback.
+pub async fn cast(
+    audio: &[u8],
+    device: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = device.to_string();
+    let chromecast = tokio::task::spawn_blocking(move || find_chromecast(&name));
+    // A failed SSDP search only rules out DLNA; a Chromecast found meanwhile still plays
+    let dlna = find_dlna(device).await.unwrap_or_else(|e| {
+        progress!("DLNA discovery failed: {}", e);
+        None
+    });
+    let renderer = match chromecast.await? {
+        Some(renderer) => renderer,
+        None => {
+            dlna.ok_or_else(|| format!("No Chromecast or DLNA renderer named '{}' found", device))?
+        }
+    };
+
+    let (media_url, server) = serve_audio(audio, renderer.addr()).await?;
+
+    progress!("Casting to {} via {}", device, media_url);
+    let result = match renderer {
+        Renderer::Chromecast { addr } => {
+            tokio::task::spawn_blocking(move || play_on_chromecast(addr, media_url)).await?
+        }
+        Renderer::Dlna { control_url, .. } => play_on_dlna(&control_url, &media_url).await,
+    };
+
+    server.abort();
+    result
+}
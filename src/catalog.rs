@@ -0,0 +1,495 @@
+//! Listing and searching the voice catalog, `--speaker random` picks, and `tktts voices
+//! update`.
+//!
+//! The catalog is embedded, and extended by a voice manifest: a JSON file published with
+//! the releases that lists voices found since and aliases for them. `tktts voices update`
+//! downloads it and its minisign signature, checked like `self-update` checks releases,
+//! and keeps it in the local data directory, where every later run reads it. A manifest
+//! that is not signed by a known key is refused, unless `--no-verify` accepts it.
+
+use crate::term::{self, Style};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tktts::voices::{self, Voice};
+
+/// Generator behind `random` speakers, seeded by `--seed` for repeatable picks.
+static RNG: Mutex<Option<fastrand::Rng>> = Mutex::new(None);
+
+/// The downloaded voice manifest, kept for the run so its voices can borrow from it.
+static MANIFEST: OnceLock<Manifest> = OnceLock::new();
+
+/// The tags of each manifest voice, as the catalog's [`Voice`] takes them.
+static MANIFEST_TAGS: OnceLock<Vec<Vec<&'static str>>> = OnceLock::new();
+
+/// The `[voices]` config section.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct VoicesConfig {
+    /// Where `tktts voices update` gets the manifest (default: the latest release's)
+    pub manifest_url: Option<String>,
+    /// minisign public key the manifest is signed with (default: the release key)
+    pub public_key: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Manifest {
+    #[serde(default)]
+    voices: Vec<ManifestVoice>,
+    /// Alias to voice code, like the config's `[aliases]`
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct ManifestVoice {
+    code: String,
+    name: String,
+    language: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(clap::Args)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct VoicesArgs {
+    #[command(subcommand)]
+    command: Option<VoicesCommand>,
+
+    #[command(flatten)]
+    filter: VoiceFilter,
+}
+
+#[derive(clap::Subcommand)]
+enum VoicesCommand {
+    /// Synthesize a short sample with every matching voice, to save or play
+    Preview(PreviewArgs),
+    /// Download the latest voice manifest with voices added since this release
+    Update(UpdateArgs),
+    /// Tag a voice with your own words, e.g. `tag en_us_006 narrator calm`
+    Tag(TagArgs),
+    /// Rate a voice with 1 to 5 stars, or 0 to clear its rating
+    Rate(RateArgs),
+}
+
+#[derive(clap::Args)]
+struct TagArgs {
+    /// Voice code or alias
+    voice: String,
+
+    /// Tags to add
+    #[arg(required = true)]
+    tags: Vec<String>,
+
+    /// Remove the tags instead
+    #[arg(long)]
+    remove: bool,
+}
+
+#[derive(clap::Args)]
+struct RateArgs {
+    /// Voice code or alias
+    voice: String,
+
+    #[arg(value_parser = clap::value_parser!(u8).range(0..=5))]
+    stars: u8,
+}
+
+#[derive(clap::Args)]
+struct UpdateArgs {
+    /// Manifest URL, instead of `[voices] manifest_url` or the latest release's
+    #[arg(long)]
+    url: Option<String>,
+
+    /// Use the manifest even though no public key is known to check its signature with
+    #[arg(long)]
+    no_verify: bool,
+}
+
+#[derive(clap::Args)]
+struct VoiceFilter {
+    /// Only voices whose language tag starts with this, e.g. en or pt-BR
+    #[arg(long, short, value_name = "LANG")]
+    lang: Option<String>,
+
+    /// Only the best fuzzy matches for these words, e.g. "french male"
+    #[arg(long, short, value_name = "QUERY")]
+    search: Option<String>,
+
+    /// Only voices with this catalog or own tag; repeatable, all must match
+    #[arg(long, short, value_name = "TAG")]
+    tag: Vec<String>,
+}
+
+impl VoiceFilter {
+    fn voices(&self) -> Result<Vec<&'static Voice>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut matches = match &self.search {
+            Some(query) => voices::search(query),
+            None => voices::all().collect(),
+        };
+        if let Some(lang) = &self.lang {
+            let lang = lang.to_lowercase();
+            matches.retain(|voice| voice.language.to_lowercase().starts_with(&lang));
+        }
+        matches.retain(|voice| self.tag.iter().all(|tag| crate::tags::has(voice, tag)));
+        if matches.is_empty() {
+            return Err("No voices match the given --lang, --search and --tag".into());
+        }
+        Ok(matches)
+    }
+}
+
+#[derive(clap::Args)]
+struct PreviewArgs {
+    #[command(flatten)]
+    filter: VoiceFilter,
+
+    /// Sample text (default: "Hello, this is <voice name>.")
+    #[arg(long, short, value_name = "TEXT")]
+    text: Option<String>,
+
+    /// Directory for the preview_<voice>.mp3 files
+    #[arg(long, value_name = "DIR", default_value = ".", conflicts_with = "play")]
+    out_dir: PathBuf,
+
+    /// Play the samples one after another instead of saving them
+    #[arg(short, long)]
+    play: bool,
+
+    /// Player command that reads audio from stdin (default: mpv or ffplay)
+    #[arg(long, value_name = "COMMAND", requires = "play")]
+    player: Option<String>,
+
+    /// Pause between voices, to stay under the API's rate limit
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    delay: u64,
+}
+
+/// One voice per line; the display name goes last as many are not in Latin script.
+/// Your own tags follow the catalog's, and ratings get a column once there are any.
+fn print(voices: &[&Voice]) {
+    let code_width = voices
+        .iter()
+        .map(|voice| voice.code.len())
+        .max()
+        .unwrap_or(0);
+    let marks: Vec<crate::tags::Mark> = voices
+        .iter()
+        .map(|voice| crate::tags::mark(voice.code))
+        .collect();
+    let tags: Vec<String> = voices
+        .iter()
+        .zip(&marks)
+        .map(|(voice, mark)| {
+            let own = mark
+                .tags
+                .iter()
+                .filter(|tag| !voice.tags.contains(&tag.as_str()));
+            let tags: Vec<&str> = voice
+                .tags
+                .iter()
+                .copied()
+                .chain(own.map(String::as_str))
+                .collect();
+            tags.join(",")
+        })
+        .collect();
+    let tags_width = tags.iter().map(String::len).max().unwrap_or(0);
+    let rated = marks.iter().any(|mark| mark.rating.is_some());
+    for ((voice, tags), mark) in voices.iter().zip(&tags).zip(&marks) {
+        let stars = match rated {
+            true => format!("{:<5}  ", "★".repeat(mark.rating.unwrap_or(0).into())),
+            false => String::new(),
+        };
+        println!(
+            "{:<code_width$}  {:<5}  {:<tags_width$}  {}{}",
+            voice.code, voice.language, tags, stars, voice.name
+        );
+    }
+}
+
+/// The catalog code for a voice given as code or alias, which must exist.
+fn known_voice(voice: &str) -> Result<&'static str, Box<dyn std::error::Error + Send + Sync>> {
+    check(voice)?;
+    let code = crate::config::resolve_voice(voice);
+    Ok(voices::find(code)
+        .ok_or_else(|| format!("Unknown voice '{}'", code))?
+        .code)
+}
+
+/// Synthesizes the sample with each voice in turn. A voice that fails is reported and
+/// skipped, so one unavailable voice does not end the audition.
+async fn preview(args: PreviewArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let voices = args.filter.voices()?;
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    if !args.play {
+        std::fs::create_dir_all(&args.out_dir)?;
+    }
+
+    let mut failed = 0;
+    for (index, voice) in voices.iter().enumerate() {
+        if index > 0 {
+            tokio::time::sleep(Duration::from_millis(args.delay)).await;
+        }
+        let text = match &args.text {
+            Some(text) => text.clone(),
+            None => format!("Hello, this is {}.", voice.name),
+        };
+        let audio = match crate::synthesize(&text, voice.code, &session_id, &api_root_url).await {
+            Ok(audio) => audio,
+            Err(e) => {
+                crate::term::error(format!("Failed to preview {}: {}", voice.code, e));
+                failed += 1;
+                continue;
+            }
+        };
+        if args.play {
+            let line = format!("{} ({})", voice.code, voice.name);
+            crate::term::status(crate::term::Style::Green, "Playing", line);
+            crate::playback::play(&audio, args.player.as_deref()).await?;
+        } else {
+            let path = args.out_dir.join(format!("preview_{}.mp3", voice.code));
+            std::fs::write(&path, &audio)?;
+            crate::term::status(crate::term::Style::Green, "Wrote", path.display());
+        }
+    }
+    if failed == voices.len() {
+        return Err("No voice could be previewed".into());
+    }
+    Ok(())
+}
+
+/// Where the downloaded manifest is kept.
+fn manifest_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("tktts").join("voices.json"))
+}
+
+/// Adds the voices and aliases of the downloaded manifest, if there is one. A broken
+/// manifest is reported and skipped, leaving the embedded catalog.
+pub fn load() {
+    let Some(path) = manifest_path().filter(|path| path.exists()) else {
+        return;
+    };
+    let manifest = std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| serde_json::from_slice::<Manifest>(&data).map_err(|e| e.to_string()));
+    let manifest = match manifest {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            term::status(
+                Style::Yellow,
+                "Ignoring",
+                format!("voice manifest {}: {}", path.display(), e),
+            );
+            return;
+        }
+    };
+    let manifest = MANIFEST.get_or_init(|| manifest);
+    let tags = MANIFEST_TAGS.get_or_init(|| {
+        manifest
+            .voices
+            .iter()
+            .map(|voice| voice.tags.iter().map(String::as_str).collect())
+            .collect()
+    });
+    let added = manifest
+        .voices
+        .iter()
+        .zip(tags)
+        .map(|(voice, tags)| Voice {
+            code: &voice.code,
+            name: &voice.name,
+            language: &voice.language,
+            tags,
+        })
+        .collect();
+    voices::extend(added);
+}
+
+/// The voice code an alias in the voice manifest stands for.
+pub fn alias(name: &str) -> Option<&'static str> {
+    MANIFEST.get()?.aliases.get(name).map(String::as_str)
+}
+
+async fn update(args: UpdateArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = crate::config::get();
+    let url = match args.url.or_else(|| config.voices.manifest_url.clone()) {
+        Some(url) => url,
+        None => {
+            let repo = config
+                .update
+                .repo
+                .as_deref()
+                .unwrap_or(crate::self_update::DEFAULT_REPO);
+            format!(
+                "https://github.com/{}/releases/latest/download/voices.json",
+                repo
+            )
+        }
+    };
+    let data = crate::self_update::get(&url).await?.bytes().await?;
+    let key = config
+        .voices
+        .public_key
+        .as_deref()
+        .or(crate::self_update::RELEASE_PUBLIC_KEY);
+    match key {
+        Some(key) => {
+            let signature_url = format!("{}.minisig", url);
+            let signature = crate::self_update::get(&signature_url)
+                .await
+                .map_err(|e| format!("The manifest has no signature: {}", e))?
+                .text()
+                .await?;
+            crate::self_update::verify_minisign(&data, &signature, key, "voices.json")?;
+        }
+        None if args.no_verify => term::status(
+            Style::Yellow,
+            "Unverified",
+            "--no-verify: the manifest's signature is not checked",
+        ),
+        None => {
+            return Err(
+                "No public key is known to check the manifest's signature with. Set \
+                        [voices] public_key to the minisign key it is signed with, or pass \
+                        --no-verify to use it unchecked"
+                    .into(),
+            );
+        }
+    }
+    let manifest: Manifest = serde_json::from_slice(&data)
+        .map_err(|e| format!("The manifest at {} is not valid: {}", url, e))?;
+
+    let path = manifest_path().ok_or("No local data directory to keep the manifest in")?;
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("{} is not in a directory", path.display()))?;
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&path, &data)?;
+    let new = manifest
+        .voices
+        .iter()
+        .filter(|voice| !voices::VOICES.iter().any(|known| known.code == voice.code))
+        .count();
+    term::status(
+        Style::Green,
+        "Updated",
+        format!(
+            "voice catalog with {} new voices and {} aliases ({})",
+            new,
+            manifest.aliases.len(),
+            path.display()
+        ),
+    );
+    Ok(())
+}
+
+pub async fn run(args: VoicesArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match args.command {
+        Some(VoicesCommand::Preview(args)) => preview(args).await,
+        Some(VoicesCommand::Update(args)) => update(args).await,
+        Some(VoicesCommand::Tag(args)) => {
+            let code = known_voice(&args.voice)?;
+            let mark = crate::tags::tag(code, &args.tags, args.remove)?;
+            let tags: Vec<&str> = mark.tags.iter().map(String::as_str).collect();
+            let tags = match tags.is_empty() {
+                true => "no tags of yours".to_string(),
+                false => tags.join(", "),
+            };
+            term::status(Style::Green, "Tagged", format!("{}: {}", code, tags));
+            Ok(())
+        }
+        Some(VoicesCommand::Rate(args)) => {
+            let code = known_voice(&args.voice)?;
+            crate::tags::rate(code, args.stars)?;
+            let rating = match args.stars {
+                0 => "no rating".to_string(),
+                stars => "★".repeat(stars.into()),
+            };
+            term::status(Style::Green, "Rated", format!("{}: {}", code, rating));
+            Ok(())
+        }
+        None => {
+            print(&args.filter.voices()?);
+            Ok(())
+        }
+    }
+}
+
+/// Seeds the generator for `random` speakers; without a seed every run picks anew.
+pub fn seed(seed: Option<u64>) {
+    let rng = seed.map_or_else(fastrand::Rng::new, fastrand::Rng::with_seed);
+    *RNG.lock().unwrap() = Some(rng);
+}
+
+/// Resolves `random` to a random catalog voice, and `random:<lang>` to one whose language
+/// tag starts with `<lang>` (`random:en`, `random:pt-BR`). `tag:<tag>` picks among the
+/// best rated voices with that tag. Other speakers are returned as is.
+pub fn pick(speaker: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let candidates: Vec<&Voice> = match speaker.split_once(':') {
+        Some(("random", prefix)) => {
+            let prefix = prefix.to_lowercase();
+            let candidates: Vec<&Voice> = voices::all()
+                .filter(|voice| voice.language.to_lowercase().starts_with(&prefix))
+                .collect();
+            if candidates.is_empty() {
+                return Err(format!("No voices with a language starting with '{}'", prefix).into());
+            }
+            candidates
+        }
+        Some(("tag", tag)) => {
+            let tagged: Vec<&Voice> = voices::all()
+                .filter(|voice| crate::tags::has(voice, tag))
+                .collect();
+            let rating = |voice: &Voice| crate::tags::mark(voice.code).rating.unwrap_or(0);
+            let Some(best) = tagged.iter().map(|voice| rating(voice)).max() else {
+                return Err(
+                    format!("No voices are tagged '{}' (see `tktts voices tag`)", tag).into(),
+                );
+            };
+            tagged
+                .into_iter()
+                .filter(|voice| rating(voice) == best)
+                .collect()
+        }
+        None if speaker == "random" => voices::all().collect(),
+        _ => return Ok(speaker.to_string()),
+    };
+    let mut rng = RNG.lock().unwrap();
+    let index = rng
+        .get_or_insert_with(fastrand::Rng::new)
+        .usize(..candidates.len());
+    let voice = candidates[index];
+    term::status(
+        Style::Cyan,
+        "Picked",
+        format!("{} ({})", voice.code, voice.name),
+    );
+    Ok(voice.code.to_string())
+}
+
+/// Fails unless `speaker` is a catalog voice, an alias of one, a `random` or `tag:` pick, so a
+/// typo is caught before any requests are spent on it.
+pub fn check(speaker: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let code = crate::config::resolve_voice(speaker);
+    if speaker == "random"
+        || speaker.starts_with("random:")
+        || speaker.starts_with("tag:")
+        || voices::find(code).is_some()
+    {
+        return Ok(());
+    }
+    let mut message = format!("Unknown voice '{}'", code);
+    let suggestions = voices::suggestions(code);
+    if let Some((last, rest)) = suggestions.split_last() {
+        message.push_str("; did you mean ");
+        if !rest.is_empty() {
+            message.push_str(&format!("{} or ", rest.join(", ")));
+        }
+        message.push_str(&format!("{}?", last));
+    }
+    message.push_str(" (see `tktts voices`, or pass --force-speaker to use it anyway)");
+    Err(message.into())
+}
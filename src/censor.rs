@@ -0,0 +1,125 @@
+//! Blocked word censoring for `--censor`, applied before text is split into chunks.
+//!
+//! Word lists have one word per line; `#` starts a comment, and a trailing `*` also
+//! matches longer words (`damn*` catches `damned`). Matching ignores case and only
+//! whole words are replaced. `--censor default` selects the built-in list.
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Chunk text standing for a beep tone in `tone` mode. It is never sent to the API.
+pub const TONE: &str = "\u{7}";
+
+/// 1 kHz tone, 0.35 s, 24 kHz mono like the API's audio.
+const BEEP_MP3: &[u8] = include_bytes!("audio/beep.mp3");
+
+const DEFAULT_WORDS: &str = "\
+arse
+ass
+asshole*
+bastard*
+bitch*
+bollocks
+bullshit*
+cock
+cocks
+crap*
+cunt*
+damn*
+dick
+dickhead*
+fuck*
+motherfuck*
+piss*
+prick
+shit*
+slut*
+twat*
+wank*
+whore*
+";
+
+static CENSOR: OnceLock<Censor> = OnceLock::new();
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+pub enum CensorMode {
+    /// Say "beep" in place of each blocked word
+    #[default]
+    Replace,
+    /// Insert a beep tone into the audio in place of each blocked word
+    Tone,
+    /// Leave blocked words out
+    Remove,
+}
+
+struct Censor {
+    words: Regex,
+    mode: CensorMode,
+}
+
+fn pattern(line: &str) -> Option<String> {
+    let word = line.split('#').next().unwrap_or_default().trim();
+    match word.strip_suffix('*') {
+        _ if word.is_empty() => None,
+        Some(prefix) => Some(format!(r"{}\w*", regex::escape(prefix))),
+        None => Some(regex::escape(word)),
+    }
+}
+
+/// Loads the word lists (paths, or `default`) once for the whole process.
+pub fn init(
+    lists: &[String],
+    mode: CensorMode,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if lists.is_empty() {
+        return Ok(());
+    }
+    let mut patterns = Vec::new();
+    for list in lists {
+        let text = match list.as_str() {
+            "default" => DEFAULT_WORDS.to_string(),
+            path => std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read word list {}: {}", path, e))?,
+        };
+        patterns.extend(text.lines().filter_map(pattern));
+    }
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    let words = Regex::new(&format!(r"(?i)\b(?:{})\b", patterns.join("|")))?;
+    let _ = CENSOR.set(Censor { words, mode });
+    Ok(())
+}
+
+/// `text` with the blocked words replaced as the mode says.
+pub fn apply(text: &str) -> String {
+    let Some(censor) = CENSOR.get() else {
+        return text.to_string();
+    };
+    let replacement = match censor.mode {
+        CensorMode::Replace => "beep",
+        CensorMode::Tone => TONE,
+        CensorMode::Remove => "",
+    };
+    censor.words.replace_all(text, replacement).into_owned()
+}
+
+/// Splits `chunk` around [`TONE`] marks, so each beep becomes a chunk of its own.
+pub fn split_tones(chunk: &str) -> Vec<String> {
+    if !chunk.contains(TONE) {
+        return vec![chunk.to_string()];
+    }
+    let mut parts = Vec::new();
+    for (index, part) in chunk.split(TONE).enumerate() {
+        if index > 0 {
+            parts.push(TONE.to_string());
+        }
+        parts.push(part.to_string());
+    }
+    parts
+}
+
+/// The beep audio, when `chunk` is a [`TONE`] chunk.
+pub fn tone(chunk: &str) -> Option<Vec<u8>> {
+    (chunk == TONE).then(|| BEEP_MP3.to_vec())
+}
@@ -0,0 +1,208 @@
+//! `--chapters-by`: long documents written as one file per chapter.
+//!
+//! Chapters start at heading lines (Markdown headings, or lines like `Chapter 12` and
+//! `Part II: The Return`) or at lines matching a regex. Every input gets a
+//! `<stem>.chapters.json` manifest listing its chapter files, which a later run reads to
+//! skip the chapters whose text, voice and normalization are unchanged, so an
+//! interrupted audiobook picks up at the chapter it stopped in.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// A Markdown heading, capturing its text.
+static MARKDOWN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^#{1,6}[ \t]+(.+?)[ \t#]*$").unwrap());
+
+/// A line naming a chapter, part or book, or the front and back matter.
+static NAMED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:(?:chapter|part|book)[ \t]+(?:\d+|[ivxlcdm]+|one|two|three|four|five|six|seven|eight|nine|ten|eleven|twelve|thirteen|fourteen|fifteen|sixteen|seventeen|eighteen|nineteen|twenty)|prologue|epilogue|preface|introduction|afterword)\b(?:[ \t]*[:.—-][ \t]*.{0,80})?$",
+    )
+    .unwrap()
+});
+
+/// How chapter boundaries are found.
+#[derive(Clone)]
+pub enum Rule {
+    Heading,
+    Regex(Regex),
+}
+
+/// Parses `heading` or `regex:PATTERN`.
+pub fn parse_rule(value: &str) -> Result<Rule, String> {
+    match value.split_once(':') {
+        None if value == "heading" => Ok(Rule::Heading),
+        Some(("regex", pattern)) => Regex::new(pattern)
+            .map(Rule::Regex)
+            .map_err(|e| format!("invalid pattern: {}", e)),
+        _ => Err(format!(
+            "expected 'heading' or 'regex:PATTERN', got '{}'",
+            value
+        )),
+    }
+}
+
+pub struct Chapter {
+    pub title: String,
+    /// The title line and the text up to the next chapter
+    pub text: String,
+}
+
+/// The title of `line` when it starts a chapter.
+fn heading(line: &str, rule: &Rule) -> Option<String> {
+    let line = line.trim();
+    match rule {
+        Rule::Regex(regex) => regex.is_match(line).then(|| line.to_string()),
+        Rule::Heading => match MARKDOWN.captures(line) {
+            Some(captures) => Some(captures[1].to_string()),
+            None => NAMED.is_match(line).then(|| line.to_string()),
+        },
+    }
+}
+
+/// `text` cut into chapters at its heading lines. Text before the first heading becomes
+/// a chapter titled `front_title`; text without headings is a single chapter.
+pub fn split(text: &str, rule: &Rule, front_title: &str) -> Vec<Chapter> {
+    let mut chapters = vec![Chapter {
+        title: front_title.to_string(),
+        text: String::new(),
+    }];
+    for line in text.lines() {
+        match heading(line, rule) {
+            Some(title) => chapters.push(Chapter {
+                text: format!("{}\n", title),
+                title,
+            }),
+            None => {
+                let chapter = chapters.last_mut().unwrap();
+                chapter.text.push_str(line);
+                chapter.text.push('\n');
+            }
+        }
+    }
+    for chapter in &mut chapters {
+        chapter.text = chapter.text.trim().to_string();
+    }
+    // An empty front matter, but also a heading with nothing after it, is not a chapter
+    chapters.retain(|chapter| chapter.text.chars().any(char::is_alphanumeric));
+    chapters
+}
+
+/// The `<stem>.chapters.json` file of one input.
+#[derive(Deserialize, Serialize, Default)]
+pub struct Manifest {
+    pub title: String,
+    pub voice: String,
+    pub normalization: u32,
+    pub duration_secs: f64,
+    pub chapters: Vec<Entry>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Entry {
+    /// 1-based chapter number
+    pub index: usize,
+    pub title: String,
+    /// Audio file, relative to the manifest
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtitles: Option<String>,
+    pub duration_secs: f64,
+    pub bytes: usize,
+    pub sha256: String,
+    /// SHA-256 of the chapter text, to tell whether it changed since
+    pub text_sha256: String,
+}
+
+pub fn manifest_path(out_dir: &Path, stem: &str) -> PathBuf {
+    out_dir.join(format!("{}.chapters.json", stem))
+}
+
+/// The manifest left by an earlier run, if any.
+pub fn load(path: &Path) -> Option<Manifest> {
+    let contents = fs::read(path).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+pub fn save(
+    path: &Path,
+    manifest: &mut Manifest,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    manifest.chapters.sort_by_key(|entry| entry.index);
+    let total: f64 = manifest
+        .chapters
+        .iter()
+        .map(|entry| entry.duration_secs)
+        .sum();
+    manifest.duration_secs = (total * 1000.0).round() / 1000.0;
+    fs::write(path, serde_json::to_vec_pretty(manifest)?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e).into())
+}
+
+/// The entry of an earlier run for this chapter, when its files are still there and
+/// would come out the same.
+pub fn resumable<'a>(
+    previous: Option<&'a Manifest>,
+    voice: &str,
+    index: usize,
+    text_sha256: &str,
+    out_dir: &Path,
+    subtitles: bool,
+) -> Option<&'a Entry> {
+    let previous = previous.filter(|manifest| {
+        manifest.voice == voice && manifest.normalization == tktts::NORMALIZATION_VERSION
+    })?;
+    previous.chapters.iter().find(|entry| {
+        entry.index == index
+            && entry.text_sha256 == text_sha256
+            && out_dir.join(&entry.file).is_file()
+            && (!subtitles
+                || entry
+                    .subtitles
+                    .as_ref()
+                    .is_some_and(|file| out_dir.join(file).is_file()))
+    })
+}
+
+fn srt_time(secs: f64) -> String {
+    let millis = (secs * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// SRT subtitles with one cue per chunk, timed by the chunks' audio.
+pub fn subtitles(chunks: &[String], audio_chunks: &[Vec<u8>]) -> String {
+    let mut srt = String::new();
+    let mut start = 0.0;
+    let mut number = 0;
+    for (chunk, audio) in chunks.iter().zip(audio_chunks) {
+        let end = start + crate::mp3::info(audio).duration_secs;
+        // A blank line would end the cue
+        let text = crate::markdown::unmarked(chunk)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        // Beeps and sound effects take time but have no words to show
+        let effect = text.starts_with("[sfx:") && text.ends_with(']');
+        if chunk != crate::censor::TONE && !effect && !text.is_empty() {
+            number += 1;
+            srt.push_str(&format!(
+                "{}\n{} --> {}\n{}\n\n",
+                number,
+                srt_time(start),
+                srt_time(end),
+                text
+            ));
+        }
+        start = end;
+    }
+    srt
+}
@@ -0,0 +1,180 @@
+//! `--checksum` files and `tktts verify`.
+//!
+//! With `--checksum`, every written audio file gets a `<file>.sha256` next to it in the
+//! format of `sha256sum`, so `sha256sum -c` works as well as `tktts verify`. Verification
+//! also accepts the `sha256` of a `--sidecar` file when there is no `.sha256` file.
+
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn init(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The `.sha256` file contents for audio with digest `hex` written to `target`.
+pub fn contents(hex: &str, target: &str) -> String {
+    let name = target.rsplit(['/', '\\']).next().unwrap_or(target);
+    format!("{}  {}\n", hex, name)
+}
+
+/// Writes `<path>.sha256` for a local file.
+pub fn write(path: &Path, audio: &[u8]) -> io::Result<()> {
+    write_hex(path, &sha256_hex(audio))
+}
+
+fn write_hex(path: &Path, hex: &str) -> io::Result<()> {
+    let mut checksum_path = path.as_os_str().to_owned();
+    checksum_path.push(".sha256");
+    fs::write(checksum_path, contents(hex, &path.to_string_lossy()))
+}
+
+/// Hashes everything written through it, for output that is streamed to a file.
+pub struct Hashing<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Hashing<W> {
+    pub fn new(inner: W) -> Self {
+        Hashing {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Writes `<path>.sha256` for what went through, if `--checksum` is on.
+    pub fn finish(self, path: &Path) -> io::Result<()> {
+        if !enabled() {
+            return Ok(());
+        }
+        write_hex(path, &hex(&self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(clap::Args)]
+pub struct VerifyArgs {
+    /// Audio files to check, or `.sha256` files listing them
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+/// The files listed in a `sha256sum`-style file, relative to its directory.
+fn listed(
+    checksum_file: &Path,
+) -> Result<Vec<(PathBuf, String)>, Box<dyn std::error::Error + Send + Sync>> {
+    let text = fs::read_to_string(checksum_file)
+        .map_err(|e| format!("Failed to read {}: {}", checksum_file.display(), e))?;
+    let base = checksum_file.parent().unwrap_or(Path::new(""));
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (hex, name) = line.split_once(char::is_whitespace).ok_or_else(|| {
+                format!("Invalid line in {}: '{}'", checksum_file.display(), line)
+            })?;
+            // sha256sum marks binary mode with a `*` before the name
+            let name = name.trim_start().trim_start_matches('*');
+            Ok((base.join(name), hex.to_lowercase()))
+        })
+        .collect()
+}
+
+/// The expected digest of `file`, from `<file>.sha256` or else the `<file>.json` sidecar.
+fn expected(file: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let with_suffix = |suffix: &str| {
+        let mut path = file.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    };
+    let checksum_file = with_suffix(".sha256");
+    if checksum_file.exists() {
+        let listed = listed(&checksum_file)?;
+        return match listed.as_slice() {
+            [(_, hex)] => Ok(hex.clone()),
+            _ => Err(format!("{} lists more than one file", checksum_file.display()).into()),
+        };
+    }
+    let sidecar_file = with_suffix(".json");
+    if let Ok(text) = fs::read_to_string(&sidecar_file) {
+        let metadata: serde_json::Value = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid sidecar {}: {}", sidecar_file.display(), e))?;
+        if let Some(hex) = metadata["sha256"].as_str() {
+            return Ok(hex.to_lowercase());
+        }
+    }
+    Err("no .sha256 or sidecar file".into())
+}
+
+pub fn run(args: VerifyArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut checks = Vec::new();
+    for file in &args.files {
+        if file
+            .extension()
+            .is_some_and(|extension| extension == "sha256")
+        {
+            for (path, hex) in listed(file)? {
+                checks.push((path, Ok(hex)));
+            }
+        } else {
+            checks.push((file.clone(), expected(file)));
+        }
+    }
+
+    let mut failed = 0;
+    for (path, expected) in &checks {
+        let result = expected
+            .as_ref()
+            .map_err(|e| e.to_string())
+            .and_then(|expected| {
+                let audio = fs::read(path).map_err(|e| e.to_string())?;
+                match sha256_hex(&audio) == *expected {
+                    true => Ok(()),
+                    false => Err("checksum mismatch".to_string()),
+                }
+            });
+        match result {
+            Ok(()) => crate::term::status(crate::term::Style::Green, "OK", path.display()),
+            Err(e) => {
+                failed += 1;
+                crate::term::status(
+                    crate::term::Style::Red,
+                    "Failed",
+                    format!("{}: {}", path.display(), e),
+                );
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} files failed verification", failed, checks.len()).into()),
+    }
+}
@@ -0,0 +1,462 @@
+//! A small asynchronous TTS client for embedding synthesis in other programs.
+//!
+//! It covers the core request flow of the CLI: split, request the chunks concurrently,
+//! decode and join them. CLI-only layers such as the cache, presets and signing are not
+//! part of it.
+//!
+//! The `_with_cancel` variants stop when a [`CancellationToken`] is cancelled, for bots
+//! and GUIs whose users skip or delete a message while it is being synthesized.
+//!
+//! Failures are [`TktsError`]s: a rejected session is `Auth`, an API error `Upstream`, a
+//! host that does not answer `Network` with the transport's error as its source.
+//!
+//! [`Client::execute`] takes all options of a synthesis as one [`SynthesisRequest`] and
+//! returns a [`SynthesisResult`] with the audio and what each chunk took:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), tktts::TktsError> {
+//! use tktts::client::{Client, Format, SynthesisRequest};
+//!
+//! let client = Client::new("session-id", "https://api16-normal-c-useast1a.tiktokv.com");
+//! let request = SynthesisRequest::new()
+//!     .text("Hello there.")
+//!     .voice("en_us_006")
+//!     .format(Format::Wav)
+//!     .concurrency(2);
+//! let result = client.execute(&request).await?;
+//! std::fs::write("hello.wav", &result.audio)?;
+//! println!("{} chunks in {:?}", result.chunks.len(), result.elapsed);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::TktsError;
+use crate::layers::Layer;
+use crate::schema::{Decoded, Reason};
+use crate::transport::{Request, ReqwestTransport, Transport};
+use base64::{Engine as _, engine::general_purpose};
+use futures_util::StreamExt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+pub use tokio_util::sync::CancellationToken;
+
+/// Chunk size used when none is given, matching the CLI.
+pub const DEFAULT_BYTE_LIMIT: usize = 300;
+
+/// Requests in flight at once when none is given.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Audio format of a [`SynthesisResult`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// The MP3 frames of the chunks, joined as the API returns them
+    #[default]
+    Mp3,
+    /// Decoded into a 16-bit mono WAV file
+    Wav,
+}
+
+/// Everything a synthesis needs, for [`Client::execute`]. Options left unset use the
+/// client's settings.
+#[derive(Clone, Debug)]
+pub struct SynthesisRequest {
+    text: String,
+    voice: String,
+    byte_limit: Option<usize>,
+    concurrency: Option<usize>,
+    format: Format,
+    normalize: bool,
+}
+
+impl Default for SynthesisRequest {
+    fn default() -> Self {
+        SynthesisRequest {
+            text: String::new(),
+            voice: "en_us_002".to_string(),
+            byte_limit: None,
+            concurrency: None,
+            format: Format::Mp3,
+            normalize: true,
+        }
+    }
+}
+
+impl SynthesisRequest {
+    /// An empty request for the `en_us_002` voice, with MP3 output and normalized text.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Text of any length to synthesize.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    /// Voice code sent as `text_speaker`.
+    pub fn voice(mut self, voice: impl Into<String>) -> Self {
+        self.voice = voice.into();
+        self
+    }
+
+    /// Maximum chunk size in bytes; clamped to [`MIN_BYTE_LIMIT`](crate::MIN_BYTE_LIMIT).
+    pub fn byte_limit(mut self, byte_limit: usize) -> Self {
+        self.byte_limit = Some(byte_limit.max(crate::MIN_BYTE_LIMIT));
+        self
+    }
+
+    /// Number of chunk requests in flight at once (at least 1).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency.max(1));
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Whether the text is rewritten with [`sanitize_text_with`](crate::sanitize_text_with)
+    /// and the voice's profile before sending, as the CLI does (the default); without it
+    /// the chunks are sent exactly as split.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+}
+
+/// The audio of one chunk, as [`Client::synthesize_stream`] yields them.
+#[derive(Clone, Debug)]
+pub struct AudioChunk {
+    /// Position of the chunk in the text, from 0
+    pub index: usize,
+    /// The chunk as split from the text, before normalization
+    pub text: String,
+    /// Its MP3 audio
+    pub bytes: Vec<u8>,
+    /// Playing time of the audio
+    pub duration: Duration,
+}
+
+type ChunkResult = Result<AudioChunk, TktsError>;
+
+/// The chunks of a synthesis in order, from [`Client::synthesize_stream`]. It is a
+/// [`Stream`](futures_util::Stream), and [`recv`](Self::recv) needs no stream traits.
+pub struct ChunkStream {
+    receiver: mpsc::Receiver<ChunkResult>,
+}
+
+impl ChunkStream {
+    /// The next chunk, or `None` once the text is done or after an error.
+    pub async fn recv(&mut self) -> Option<ChunkResult> {
+        self.receiver.recv().await
+    }
+}
+
+impl futures_util::Stream for ChunkStream {
+    type Item = ChunkResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(context)
+    }
+}
+
+/// One chunk of a [`SynthesisResult`].
+#[derive(Clone, Debug)]
+pub struct ChunkInfo {
+    /// The chunk as split from the text, before normalization
+    pub text: String,
+    /// Size of its MP3 audio
+    pub audio_bytes: usize,
+    /// From sending its request to having its audio decoded from the response
+    pub request_time: Duration,
+}
+
+/// The audio of a [`SynthesisRequest`], with what each of its chunks took.
+#[derive(Clone, Debug)]
+pub struct SynthesisResult {
+    pub audio: Vec<u8>,
+    pub format: Format,
+    /// The chunks in order
+    pub chunks: Vec<ChunkInfo>,
+    /// The whole synthesis, including joining and converting the audio
+    pub elapsed: Duration,
+}
+
+#[derive(Clone)]
+pub struct Client {
+    transport: Arc<dyn Transport>,
+    session_id: String,
+    api_root_url: String,
+    user_agent: String,
+    aid: String,
+    byte_limit: usize,
+    concurrency: usize,
+}
+
+impl Client {
+    /// A client for the API host `api_root_url`, authenticated with a TikTok session ID.
+    pub fn new(session_id: &str, api_root_url: &str) -> Self {
+        Client {
+            transport: Arc::new(ReqwestTransport::new()),
+            session_id: session_id.to_string(),
+            api_root_url: api_root_url.trim_end_matches('/').to_string(),
+            user_agent: crate::USER_AGENT.to_string(),
+            aid: "1233".to_string(),
+            byte_limit: DEFAULT_BYTE_LIMIT,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// Sends the requests with `transport` instead of the default [`ReqwestTransport`].
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// Wraps the transport in `layer`'s policy; see [`layers`](crate::layers).
+    pub fn layer(mut self, layer: impl Layer) -> Self {
+        self.transport = layer.layer(self.transport);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn aid(mut self, aid: &str) -> Self {
+        self.aid = aid.to_string();
+        self
+    }
+
+    /// Maximum chunk size in bytes; clamped to [`MIN_BYTE_LIMIT`](crate::MIN_BYTE_LIMIT).
+    pub fn byte_limit(mut self, byte_limit: usize) -> Self {
+        self.byte_limit = byte_limit.max(crate::MIN_BYTE_LIMIT);
+        self
+    }
+
+    /// Number of chunk requests in flight at once (at least 1).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Requests one chunk and returns its decoded MP3 audio.
+    pub async fn synthesize_chunk(&self, text: &str, voice: &str) -> Result<Vec<u8>, TktsError> {
+        self.request_chunk(text, voice, true).await
+    }
+
+    async fn request_chunk(
+        &self,
+        text: &str,
+        voice: &str,
+        normalize: bool,
+    ) -> Result<Vec<u8>, TktsError> {
+        let url = if normalize {
+            crate::tts_url(&self.api_root_url, text, voice, &self.aid)?
+        } else {
+            crate::raw_tts_url(&self.api_root_url, text, voice, &self.aid)?
+        };
+        let request = Request {
+            url,
+            headers: vec![
+                ("User-Agent".to_string(), self.user_agent.clone()),
+                (
+                    "Cookie".to_string(),
+                    format!("sessionid={}", self.session_id),
+                ),
+            ],
+            body: String::new(),
+        };
+        let response = self.transport.send(request).await?;
+
+        let status = response.status;
+        let body = match response.body.decode_audio(Vec::new()).await? {
+            Decoded::Audio(audio) => return Ok(audio),
+            Decoded::Other(body) => body,
+        };
+        let parsed =
+            crate::schema::parse(&body).map_err(|msg| TktsError::Upstream { status, msg })?;
+        let v_str = match parsed.outcome {
+            crate::schema::Outcome::Audio(v_str) => v_str,
+            crate::schema::Outcome::Rejected {
+                message,
+                reason: Reason::MissingSession | Reason::Session | Reason::Region,
+            } => return Err(TktsError::Auth(message)),
+            crate::schema::Outcome::Rejected {
+                message,
+                reason: Reason::Voice,
+            } => {
+                return Err(TktsError::Upstream {
+                    status,
+                    msg: format!("voice {} is not available: {}", voice, message),
+                });
+            }
+            crate::schema::Outcome::Rejected { message, .. } => {
+                return Err(TktsError::Upstream {
+                    status,
+                    msg: message,
+                });
+            }
+        };
+        general_purpose::STANDARD
+            .decode(v_str)
+            .map_err(|e| TktsError::Decode(e.into()))
+    }
+
+    /// Synthesizes `text` in the background and yields each chunk's audio in order, so
+    /// playback or forwarding can start with the first chunk.
+    ///
+    /// At most `concurrency` finished chunks wait to be taken. Must be called within a
+    /// tokio runtime.
+    pub fn synthesize_stream(&self, text: &str, voice: &str) -> ChunkStream {
+        self.synthesize_stream_with_cancel(text, voice, CancellationToken::new())
+    }
+
+    /// [`synthesize_stream`](Self::synthesize_stream) until `token` is cancelled. Then the
+    /// requests in flight are dropped, which closes their connections, and the last item
+    /// is a [`TktsError::Cancelled`] error.
+    pub fn synthesize_stream_with_cancel(
+        &self,
+        text: &str,
+        voice: &str,
+        token: CancellationToken,
+    ) -> ChunkStream {
+        let (sender, receiver) = mpsc::channel(self.concurrency);
+        let client = self.clone();
+        let text = text.to_string();
+        let voice = voice.to_string();
+        tokio::spawn(async move {
+            let chunks = crate::split_text(&text, client.byte_limit);
+            if chunks.is_empty() {
+                let _ = sender.send(Err(no_text())).await;
+                return;
+            }
+            let requests: Vec<_> = chunks
+                .into_iter()
+                .enumerate()
+                .map(|(index, text)| {
+                    let client = &client;
+                    let voice = &voice;
+                    async move {
+                        let bytes = client.synthesize_chunk(&text, voice).await?;
+                        let duration =
+                            Duration::from_secs_f64(crate::mp3::info(&bytes).duration_secs);
+                        Ok(AudioChunk {
+                            index,
+                            text,
+                            bytes,
+                            duration,
+                        })
+                    }
+                })
+                .collect();
+            let mut parts = futures_util::stream::iter(requests).buffered(client.concurrency);
+            let send_all = async {
+                while let Some(part) = parts.next().await {
+                    let failed = part.is_err();
+                    if sender.send(part).await.is_err() || failed {
+                        return;
+                    }
+                }
+            };
+            if token.run_until_cancelled(send_all).await.is_none() {
+                let _ = sender.send(Err(TktsError::Cancelled)).await;
+            }
+        });
+        ChunkStream { receiver }
+    }
+
+    /// Synthesizes `text` of any length and returns the joined MP3 audio.
+    pub async fn synthesize(&self, text: &str, voice: &str) -> Result<Vec<u8>, TktsError> {
+        let chunks = crate::split_text(text, self.byte_limit);
+        if chunks.is_empty() {
+            return Err(no_text());
+        }
+
+        // Creating the futures up front keeps the returned future `Send` for any lifetime
+        let requests: Vec<_> = chunks
+            .iter()
+            .map(|chunk| self.synthesize_chunk(chunk, voice))
+            .collect();
+        let mut parts = futures_util::stream::iter(requests).buffered(self.concurrency);
+        let mut audio = Vec::new();
+        while let Some(part) = parts.next().await {
+            audio.extend(part?);
+        }
+        Ok(audio)
+    }
+
+    /// [`synthesize`](Self::synthesize) until `token` is cancelled, failing with
+    /// [`TktsError::Cancelled`] as soon as it is. The requests in flight are dropped, which closes
+    /// their connections, and the audio of the chunks already finished is discarded.
+    pub async fn synthesize_with_cancel(
+        &self,
+        text: &str,
+        voice: &str,
+        token: &CancellationToken,
+    ) -> Result<Vec<u8>, TktsError> {
+        match token
+            .run_until_cancelled(self.synthesize(text, voice))
+            .await
+        {
+            Some(audio) => audio,
+            None => Err(TktsError::Cancelled),
+        }
+    }
+
+    /// Synthesizes `request` and returns its audio with the details of each chunk.
+    pub async fn execute(&self, request: &SynthesisRequest) -> Result<SynthesisResult, TktsError> {
+        let started = Instant::now();
+        let byte_limit = request.byte_limit.unwrap_or(self.byte_limit);
+        let chunks = crate::split_text(&request.text, byte_limit);
+        if chunks.is_empty() {
+            return Err(no_text());
+        }
+
+        let requests: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| async move {
+                let sent = Instant::now();
+                let audio = self
+                    .request_chunk(&chunk, &request.voice, request.normalize)
+                    .await?;
+                let info = ChunkInfo {
+                    text: chunk,
+                    audio_bytes: audio.len(),
+                    request_time: sent.elapsed(),
+                };
+                Ok::<_, TktsError>((audio, info))
+            })
+            .collect();
+        let concurrency = request.concurrency.unwrap_or(self.concurrency);
+        let mut parts = futures_util::stream::iter(requests).buffered(concurrency);
+        let (mut audio, mut infos) = (Vec::new(), Vec::new());
+        while let Some(part) = parts.next().await {
+            let (part, info) = part?;
+            audio.extend(part);
+            infos.push(info);
+        }
+
+        if request.format == Format::Wav {
+            audio = crate::pcm::offload(move || {
+                crate::pcm::decode(&audio).map(|pcm| crate::pcm::wav(&pcm))
+            })
+            .await?;
+        }
+        Ok(SynthesisResult {
+            audio,
+            format: request.format,
+            chunks: infos,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+fn no_text() -> TktsError {
+    TktsError::Split("No text to synthesize".to_string())
+}
@@ -0,0 +1,39 @@
+//! Shell completion scripts, generated from the command line definition.
+//!
+//! Voice options complete from the embedded catalog. The candidates only exist in the
+//! generated script; parsing still accepts voices missing from the catalog.
+
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap_complete::Shell;
+
+#[derive(clap::Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate the script for
+    shell: Shell,
+}
+
+/// Offers the catalog voices for every `--speaker` option, in subcommands too.
+fn with_voices(mut command: clap::Command) -> clap::Command {
+    if command.get_arguments().any(|arg| arg.get_id() == "speaker") {
+        let voices =
+            crate::voices::all().map(|voice| PossibleValue::new(voice.code).help(voice.name));
+        command = command.mut_arg("speaker", |arg| {
+            arg.value_parser(PossibleValuesParser::new(voices))
+        });
+    }
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
+        .collect();
+    for name in subcommands {
+        command = command.mut_subcommand(&name, with_voices);
+    }
+    command
+}
+
+pub fn run(args: CompletionsArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut command = with_voices(crate::cli());
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut std::io::stdout());
+    Ok(())
+}
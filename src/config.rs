@@ -0,0 +1,267 @@
+//! User configuration, read from `~/.config/tktts/config.toml` (or `$TKTTS_CONFIG`).
+//!
+//! Every section is optional; a missing file means defaults everywhere.
+
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Voice used when `--speaker` is not given
+    pub speaker: Option<String>,
+    /// Friendly voice names, e.g. `narrator = "en_us_006"`
+    pub aliases: BTreeMap<String, String>,
+    /// Text profile overrides per voice code, e.g. `[voice_profiles.de_001]`
+    pub voice_profiles: BTreeMap<String, ProfileConfig>,
+    /// Voice per detected language for `--auto-voice` and `--multilingual`, e.g. `de = "de_002"`
+    pub language_voices: BTreeMap<String, String>,
+    /// Requests in flight when `--jobs` is not given
+    pub jobs: Option<usize>,
+    /// Input size limit when `--max-chars` is not given
+    pub max_chars: Option<usize>,
+    /// Request count limit when `--max-chunks` is not given
+    pub max_chunks: Option<usize>,
+    /// Policy when `--on-limit` is not given
+    pub on_limit: Option<crate::limits::OnLimit>,
+    /// Requests per session and day when `--daily-limit` is not given
+    pub daily_limit: Option<u64>,
+    /// Preset used when `--preset` is not given
+    pub preset: Option<String>,
+    /// Shell command run before each synthesis job
+    pub pre_hook: Option<String>,
+    /// Shell command run after each synthesis job, with a JSON summary on stdin
+    pub post_hook: Option<String>,
+    /// Command run when a job ends, when `--notify-command` is not given
+    pub notify_command: Option<String>,
+    pub presets: crate::preset::PresetsConfig,
+    pub upload: UploadConfig,
+    pub update: crate::self_update::UpdateConfig,
+    pub voices: crate::catalog::VoicesConfig,
+    pub signing: crate::signing::SigningConfig,
+    pub requests: crate::http::RequestsConfig,
+    pub device: crate::auth::DeviceConfig,
+    /// Text filters applied in order before splitting
+    pub filters: Vec<crate::filter::FilterConfig>,
+    /// Voices for `twitch`, `mqtt` and `exec` messages by a regex on their source line
+    pub sources: Vec<crate::flood::SourceConfig>,
+    pub scripting: crate::script::ScriptingConfig,
+    pub translation: crate::translate::TranslationConfig,
+    pub server: crate::server::ServerConfig,
+    pub markdown: crate::markdown::MarkdownConfig,
+    /// Voice and stereo position per character of `tktts dialogue` scripts
+    pub characters: BTreeMap<String, crate::dialogue::CharacterConfig>,
+}
+
+/// Overrides of a voice's catalog text profile; unset fields keep the catalog's choice.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileConfig {
+    /// Replace ä, ö, ü and ß with ae, oe, ue and ss
+    pub replace_umlauts: Option<bool>,
+    /// Spell out numbers as English words
+    pub spell_numbers: Option<bool>,
+}
+
+/// Settings for remote `--output` targets.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct UploadConfig {
+    /// Extra headers sent with HTTP PUT uploads, e.g. `Authorization`
+    pub headers: BTreeMap<String, String>,
+    /// S3 region, when not set through `AWS_REGION`
+    pub s3_region: Option<String>,
+    /// Endpoint of an S3-compatible service; buckets are then addressed path-style
+    pub s3_endpoint: Option<String>,
+}
+
+pub fn path() -> Option<PathBuf> {
+    match env::var_os("TKTTS_CONFIG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dirs::config_dir().map(|dir| dir.join("tktts").join("config.toml")),
+    }
+}
+
+/// Loads the configuration file, falling back to defaults when it does not exist.
+fn load() -> Result<Config, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(path) = path().filter(|path| path.exists()) else {
+        return Ok(Config::default());
+    };
+    let text = fs::read_to_string(&path)?;
+    parse(&text, &path)
+}
+
+fn parse(text: &str, path: &Path) -> Result<Config, Box<dyn std::error::Error + Send + Sync>> {
+    toml::from_str(text)
+        .map_err(|e| format!("Invalid config file {}: {}", path.display(), e).into())
+}
+
+/// Loads the configuration once for the whole process; see [`get`].
+pub fn init() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = load()?;
+    let _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// The configuration loaded by [`init`], or defaults before that.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// The voice code for `name`: its `[aliases]` entry or voice manifest alias, or `name` itself.
+pub fn resolve_voice(name: &str) -> &str {
+    match get().aliases.get(name) {
+        Some(code) => code,
+        None => crate::catalog::alias(name).unwrap_or(name),
+    }
+}
+
+/// The text profile for voice `code`: the catalog's, with `[voice_profiles]` overrides.
+pub fn profile(code: &str) -> tktts::Profile {
+    let mut profile = tktts::voices::profile(code);
+    if let Some(overrides) = get().voice_profiles.get(code) {
+        if let Some(replace_umlauts) = overrides.replace_umlauts {
+            profile.replace_umlauts = replace_umlauts;
+        }
+        if let Some(spell_numbers) = overrides.spell_numbers {
+            profile.spell_numbers = spell_numbers;
+        }
+    }
+    profile
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print where the configuration is loaded from
+    Path,
+
+    /// Print the effective configuration, defaults included, as TOML
+    Show,
+
+    /// Print one value by dotted key, e.g. `upload.s3_region`
+    Get { key: String },
+
+    /// Set one value by dotted key in the config file, keeping its comments
+    Set {
+        key: String,
+        /// TOML value; anything that does not parse as one is taken as a string
+        value: String,
+    },
+
+    /// Open the config file in $VISUAL or $EDITOR, then check it
+    Edit,
+}
+
+fn required_path() -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    path().ok_or_else(|| "Could not determine the config directory".into())
+}
+
+fn set(key: &str, value: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let path = required_path()?;
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    let mut document: toml_edit::DocumentMut = text
+        .parse()
+        .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+    let (parents, name) = match key.rsplit_once('.') {
+        Some((parents, name)) => (parents.split('.').collect(), name),
+        None => (Vec::new(), key),
+    };
+    let mut table = document.as_table_mut();
+    for parent in parents {
+        table = table
+            .entry(parent)
+            .or_insert(toml_edit::table())
+            .as_table_mut()
+            .ok_or_else(|| format!("{} is not a table", parent))?;
+    }
+    let value = value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| value.into());
+    table[name] = toml_edit::Item::Value(value);
+
+    // Refuse to write a file that would fail to load
+    let text = document.to_string();
+    parse(&text, &path)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+fn edit() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = required_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Through the shell, so editors configured with arguments (`code --wait`) work
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .map_err(|e| format!("Failed to start editor '{}': {}", editor, e))?;
+    if !status.success() {
+        return Err(format!("Editor '{}' exited with {}", editor, status).into());
+    }
+    load()?;
+    Ok(())
+}
+
+pub fn run(command: ConfigCommand) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match command {
+        ConfigCommand::Path => {
+            let path = required_path()?;
+            println!("{}", path.display());
+            if env::var_os("TKTTS_CONFIG").is_some() {
+                progress!("(set by TKTTS_CONFIG)");
+            }
+            if !path.exists() {
+                progress!("(does not exist yet; built-in defaults apply)");
+            }
+        }
+        ConfigCommand::Show => {
+            print!("{}", toml::to_string_pretty(&load()?)?);
+            // Options set through the environment win over the file
+            let mut overrides: Vec<_> = env::vars()
+                .filter(|(name, _)| name.starts_with("TKTTS_") && name != "TKTTS_CONFIG")
+                .collect();
+            overrides.sort();
+            for (name, value) in overrides {
+                println!("# overridden by {}={}", name, value);
+            }
+        }
+        ConfigCommand::Get { key } => {
+            let mut value = toml::Value::try_from(load()?)?;
+            for part in key.split('.') {
+                value = value
+                    .get(part)
+                    .cloned()
+                    .ok_or_else(|| format!("{} is not set", key))?;
+            }
+            match value {
+                toml::Value::String(value) => println!("{}", value),
+                toml::Value::Table(table) => print!("{}", toml::to_string_pretty(&table)?),
+                value => println!("{}", value),
+            }
+        }
+        ConfigCommand::Set { key, value } => {
+            let path = set(&key, &value)?;
+            println!("Saved {} to {}", key, path.display());
+        }
+        ConfigCommand::Edit => edit()?,
+    }
+    Ok(())
+}
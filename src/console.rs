@@ -0,0 +1,127 @@
+//! What differs on Windows: console colors, the encoding of piped text and binary stdout.
+//!
+//! Rust already reads and writes console text as UTF-16 and leaves the bytes of pipes and
+//! files alone, so audio piped out is never newline-translated. What remains is text piped
+//! in, which Windows shells send in the console's legacy code page unless it is UTF-8,
+//! ANSI colors, which older consoles only show once asked to, and consoles as stdout,
+//! which take only UTF-8 text and no audio. Elsewhere these functions do nothing.
+
+use std::io::{self, Read};
+
+/// Turns on ANSI escapes for stderr if the console needs it; false if it cannot show them.
+pub fn enable_ansi() -> bool {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{
+            ENABLE_VIRTUAL_TERMINAL_PROCESSING, GetConsoleMode, GetStdHandle, STD_ERROR_HANDLE,
+            SetConsoleMode,
+        };
+        // SAFETY: the handle is the process' own stderr, and a failed call leaves it unchanged
+        unsafe {
+            let handle = GetStdHandle(STD_ERROR_HANDLE);
+            let mut mode = 0;
+            if GetConsoleMode(handle, &mut mode) == 0 {
+                return false;
+            }
+            mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+        }
+    }
+    #[cfg(not(windows))]
+    true
+}
+
+/// Fails if stdout is a Windows console, which cannot take binary audio, before any
+/// requests are made for it.
+pub fn binary_stdout() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    #[cfg(windows)]
+    {
+        use std::io::IsTerminal;
+        if io::stdout().is_terminal() {
+            return Err(
+                "Audio cannot be written to the console. Pass -o FILE or --play, or pipe the output into a player"
+                    .into(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// All of stdin as text; see [`decode`].
+pub fn read_stdin() -> io::Result<String> {
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
+    decode(&bytes)
+}
+
+/// Text piped in: UTF-8 or UTF-16 with a byte order mark, UTF-8, or on Windows, text in
+/// the console's code page, such as what `cmd` and Windows PowerShell pipe by default.
+pub fn decode(bytes: &[u8]) -> io::Result<String> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        return utf8(rest);
+    }
+    let utf16 = match bytes {
+        [0xFF, 0xFE, rest @ ..] => Some((rest, true)),
+        [0xFE, 0xFF, rest @ ..] => Some((rest, false)),
+        _ => None,
+    };
+    if let Some((rest, little_endian)) = utf16 {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|pair| match little_endian {
+                true => u16::from_le_bytes([pair[0], pair[1]]),
+                false => u16::from_be_bytes([pair[0], pair[1]]),
+            })
+            .collect();
+        return String::from_utf16(&units)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "stdin is not valid UTF-16"));
+    }
+    #[cfg(windows)]
+    if std::str::from_utf8(bytes).is_err() {
+        return code_page(bytes);
+    }
+    utf8(bytes)
+}
+
+fn utf8(bytes: &[u8]) -> io::Result<String> {
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "stdin is not valid UTF-8"))
+}
+
+/// Decodes `bytes` from the console's input code page, or without a console the ANSI one.
+#[cfg(windows)]
+fn code_page(bytes: &[u8]) -> io::Result<String> {
+    use windows_sys::Win32::Globalization::{GetACP, MB_ERR_INVALID_CHARS, MultiByteToWideChar};
+    use windows_sys::Win32::System::Console::GetConsoleCP;
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "stdin is not valid text");
+    let length = i32::try_from(bytes.len()).map_err(|_| invalid())?;
+    // SAFETY: both buffers are valid for the lengths passed, and the first call only measures
+    unsafe {
+        let code_page = match GetConsoleCP() {
+            0 => GetACP(),
+            console => console,
+        };
+        let units = MultiByteToWideChar(
+            code_page,
+            MB_ERR_INVALID_CHARS,
+            bytes.as_ptr(),
+            length,
+            std::ptr::null_mut(),
+            0,
+        );
+        if units <= 0 {
+            return Err(invalid());
+        }
+        let mut wide = vec![0u16; units as usize];
+        MultiByteToWideChar(
+            code_page,
+            MB_ERR_INVALID_CHARS,
+            bytes.as_ptr(),
+            length,
+            wide.as_mut_ptr(),
+            units,
+        );
+        String::from_utf16(&wide).map_err(|_| invalid())
+    }
+}
@@ -0,0 +1,284 @@
+//! `tktts dialogue`: a script of `Name: line` lines, each character read by its own voice.
+//!
+//! Lines are placed one after another with a short gap and mixed into one WAV file.
+//! With `--stereo-split` every character gets a place in the stereo field (hard left
+//! and right for two), and `--multitrack DIR` writes one time-aligned track per
+//! character, silent while the others speak, for mixing in a DAW.
+
+use crate::pcm::{self, Pcm};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// A character's entry in the `[characters]` config section.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct CharacterConfig {
+    /// Voice or alias reading the character's lines
+    pub voice: Option<String>,
+    /// Place in the stereo field with `--stereo-split`, from -1 (left) to 1 (right)
+    pub pan: Option<f32>,
+}
+
+#[derive(clap::Args)]
+#[command(group = clap::ArgGroup::new("target").args(["output", "multitrack"]).required(true).multiple(true))]
+pub struct DialogueArgs {
+    /// Script with lines like `Alice: Hello there.`; other lines continue the last one
+    script: PathBuf,
+
+    /// WAV file with all characters mixed together
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Voice for a character, e.g. Alice=en_us_001; repeatable
+    #[arg(long = "voice", value_name = "NAME=VOICE", value_parser = crate::vars::parse)]
+    voices: Vec<(String, String)>,
+
+    /// Write a stereo file with each character panned to its own position
+    #[arg(long, requires = "output")]
+    stereo_split: bool,
+
+    /// Position of a character for --stereo-split, from -1 (left) to 1 (right); repeatable
+    #[arg(long = "pan", value_name = "NAME=POSITION", value_parser = parse_pan, requires = "stereo_split")]
+    pans: Vec<(String, f32)>,
+
+    /// Also write one time-aligned WAV track per character into DIR
+    #[arg(long, value_name = "DIR")]
+    multitrack: Option<PathBuf>,
+
+    /// Silence between lines, in seconds
+    #[arg(long, value_name = "SECS", default_value_t = 0.3)]
+    gap: f64,
+}
+
+fn parse_pan(assignment: &str) -> Result<(String, f32), String> {
+    let (name, value) = crate::vars::parse(assignment)?;
+    match value.trim().parse::<f32>() {
+        Ok(pan) if (-1.0..=1.0).contains(&pan) => Ok((name, pan)),
+        _ => Err(format!(
+            "expected a position from -1 to 1, got '{}'",
+            value.trim()
+        )),
+    }
+}
+
+struct Line {
+    character: usize,
+    text: String,
+}
+
+/// The characters in order of appearance, and the lines.
+fn parse(
+    script: &str,
+) -> Result<(Vec<String>, Vec<Line>), Box<dyn std::error::Error + Send + Sync>> {
+    let speaker = Regex::new(r"^([^\s:#][^:]{0,39}?)\s*:\s*(.*)$").unwrap();
+    let mut characters: Vec<String> = Vec::new();
+    let mut lines: Vec<Line> = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match speaker.captures(line) {
+            Some(captures) => {
+                let name = &captures[1];
+                let character = match characters.iter().position(|known| known == name) {
+                    Some(index) => index,
+                    None => {
+                        characters.push(name.to_string());
+                        characters.len() - 1
+                    }
+                };
+                lines.push(Line {
+                    character,
+                    text: captures[2].to_string(),
+                });
+            }
+            None => match lines.last_mut() {
+                Some(last) => {
+                    last.text.push(' ');
+                    last.text.push_str(line);
+                }
+                None => {
+                    return Err(format!(
+                        "Line {} has no speaker; start it with a name like 'Alice:'",
+                        number + 1
+                    )
+                    .into());
+                }
+            },
+        }
+    }
+    lines.retain(|line| !line.text.trim().is_empty());
+    if lines.is_empty() {
+        return Err("The script has no lines".into());
+    }
+    Ok((characters, lines))
+}
+
+/// Positions from the arguments or config, else hard left and right for two characters
+/// and evenly spread ones for more.
+fn pans(characters: &[String], args: &DialogueArgs) -> Vec<f32> {
+    let config = &crate::config::get().characters;
+    let count = characters.len();
+    characters
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let configured = args
+                .pans
+                .iter()
+                .rev()
+                .find(|(character, _)| character == name)
+                .map(|&(_, pan)| pan)
+                .or_else(|| config.get(name).and_then(|character| character.pan));
+            configured.unwrap_or(match count {
+                1 => 0.0,
+                2 => [-1.0, 1.0][index],
+                _ => -0.8 + 1.6 * index as f32 / (count - 1) as f32,
+            })
+        })
+        .collect()
+}
+
+fn file_name(index: usize, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("{:02}-{}.wav", index + 1, name.trim_matches('-'))
+}
+
+pub async fn run(args: DialogueArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let script = fs::read_to_string(&args.script)
+        .map_err(|e| format!("Failed to read {}: {}", args.script.display(), e))?;
+    let (characters, lines) = parse(&script)?;
+
+    let config = &crate::config::get().characters;
+    let mut voices = Vec::new();
+    for name in &characters {
+        let voice = args
+            .voices
+            .iter()
+            .rev()
+            .find(|(character, _)| character == name)
+            .map(|(_, voice)| voice.clone())
+            .or_else(|| {
+                config
+                    .get(name)
+                    .and_then(|character| character.voice.clone())
+            })
+            .ok_or_else(|| {
+                format!(
+                    "{} has no voice; pass --voice '{}=en_us_001' or add a [characters] entry",
+                    name, name
+                )
+            })?;
+        voices.push(crate::config::resolve_voice(&voice).to_string());
+    }
+
+    // Each character's lines are requested together, sharing the parallelism and dedup
+    let mut speech: Vec<Vec<Pcm>> = lines.iter().map(|_| Vec::new()).collect();
+    for (character, voice) in voices.iter().enumerate() {
+        let mut chunks = Vec::new();
+        let mut chunk_lines = Vec::new();
+        for (index, line) in lines.iter().enumerate() {
+            if line.character == character {
+                for chunk in crate::split_text(&line.text, crate::BYTE_LIMIT)? {
+                    chunks.push(chunk);
+                    chunk_lines.push(index);
+                }
+            }
+        }
+        let audio = crate::synthesize_chunks(&chunks, voice, &session_id, &api_root_url).await?;
+        for (pcm, index) in pcm::decode_all(&audio).await?.into_iter().zip(chunk_lines) {
+            speech[index].push(pcm);
+        }
+    }
+    let sample_rate = speech
+        .iter()
+        .flatten()
+        .map(|pcm| pcm.sample_rate)
+        .next()
+        .unwrap_or(pcm::API_SAMPLE_RATE);
+
+    // One track per character, with each line after the previous one
+    let gap = (args.gap.max(0.0) * sample_rate as f64) as usize;
+    let mut tracks: Vec<Vec<f32>> = characters.iter().map(|_| Vec::new()).collect();
+    let mut cursor = 0;
+    for (line, parts) in lines.iter().zip(speech) {
+        let track = &mut tracks[line.character];
+        track.resize(cursor, 0.0);
+        for part in parts {
+            track.extend(pcm::resample(part, sample_rate).samples);
+        }
+        cursor = track.len() + gap;
+    }
+    let length = cursor.saturating_sub(gap);
+    for track in &mut tracks {
+        track.resize(length, 0.0);
+    }
+
+    if let Some(dir) = &args.multitrack {
+        fs::create_dir_all(dir)?;
+        for (index, (name, track)) in characters.iter().zip(&tracks).enumerate() {
+            let path = dir.join(file_name(index, name));
+            let track = Pcm {
+                sample_rate,
+                samples: track.clone(),
+            };
+            fs::write(&path, pcm::wav(&track))
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            crate::term::status(
+                crate::term::Style::Green,
+                "Wrote",
+                format!("{} ({})", path.display(), name),
+            );
+        }
+    }
+
+    if let Some(output) = &args.output {
+        let wav = if args.stereo_split {
+            let (mut left, mut right) = (vec![0.0f32; length], vec![0.0f32; length]);
+            for (track, pan) in tracks.iter().zip(pans(&characters, &args)) {
+                // Constant power, so a centered voice is as loud as a hard panned one
+                let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+                let (gain_left, gain_right) = (angle.cos(), angle.sin());
+                for (i, sample) in track.iter().enumerate() {
+                    left[i] += sample * gain_left;
+                    right[i] += sample * gain_right;
+                }
+            }
+            pcm::stereo_wav(sample_rate, &left, &right)
+        } else {
+            let mut mix = vec![0.0f32; length];
+            for track in &tracks {
+                for (mixed, sample) in mix.iter_mut().zip(track) {
+                    *mixed += sample;
+                }
+            }
+            pcm::wav(&Pcm {
+                sample_rate,
+                samples: mix,
+            })
+        };
+        fs::write(output, wav)
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        crate::term::status(
+            crate::term::Style::Green,
+            "Wrote",
+            format!(
+                "{} ({} lines, {} characters, {})",
+                output.display(),
+                lines.len(),
+                characters.len(),
+                crate::estimate::format_duration(std::time::Duration::from_secs_f64(
+                    length as f64 / sample_rate as f64
+                ))
+            ),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! Discord bot mode.
+//!
+//! Messages posted in the configured text channel, and the `/tts` slash command, are
+//! synthesized with the regular chunked pipeline and played in the voice channel of the
+//! one who sent them. The bot joins that channel, and its audio is encoded to Opus by
+//! songbird; messages from someone not in a voice channel are answered with an `.mp3`
+//! attachment instead.
+
+use serenity::all::{
+    ChannelId, Command, CommandInteraction, CommandOptionType, Context, CreateAttachment,
+    CreateCommand, CreateCommandOption, CreateInteractionResponseFollowup, CreateMessage,
+    EventHandler, GatewayIntents, GuildId, Interaction, Mentionable, Message, Ready, ResolvedValue,
+    UserId,
+};
+use serenity::{Client, async_trait};
+use songbird::SerenityInit;
+use std::env;
+
+#[derive(clap::Args)]
+pub struct DiscordArgs {
+    /// Bot token (falls back to the DISCORD_TOKEN environment variable)
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Text channel ID whose messages are spoken automatically
+    #[arg(long)]
+    channel: Option<u64>,
+
+    /// Voice used when a message or command does not pick one
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+}
+
+struct Handler {
+    channel: Option<ChannelId>,
+    speaker: String,
+    session_id: String,
+    api_root_url: String,
+}
+
+/// Where spoken text went.
+enum Spoken {
+    /// Queued in this voice channel
+    Voice(ChannelId),
+    /// Not in a voice channel, so to be sent as a file
+    Attachment(CreateAttachment),
+}
+
+/// The voice channel `user` is in on `guild`, as the cache last saw it.
+fn voice_channel(
+    ctx: &Context,
+    guild: Option<GuildId>,
+    user: UserId,
+) -> Option<(GuildId, ChannelId)> {
+    let guild_id = guild?;
+    let guild = ctx.cache.guild(guild_id)?;
+    let channel = guild.voice_states.get(&user)?.channel_id?;
+    Some((guild_id, channel))
+}
+
+impl Handler {
+    /// Speaks `text` in the voice channel `user` is in, joining it first. Audio queues up
+    /// behind what is still playing there.
+    async fn speak(
+        &self,
+        ctx: &Context,
+        guild: Option<GuildId>,
+        user: UserId,
+        text: &str,
+        speaker: &str,
+    ) -> Result<Spoken, Box<dyn std::error::Error + Send + Sync>> {
+        let audio = crate::synthesize(text, speaker, &self.session_id, &self.api_root_url).await?;
+        let Some((guild, channel)) = voice_channel(ctx, guild, user) else {
+            return Ok(Spoken::Attachment(CreateAttachment::bytes(
+                audio, "tts.mp3",
+            )));
+        };
+        let voice = songbird::get(ctx)
+            .await
+            .ok_or("The voice client is not set up")?;
+        let call = voice.join(guild, channel).await?;
+        call.lock().await.enqueue_input(audio.into()).await;
+        Ok(Spoken::Voice(channel))
+    }
+
+    async fn handle_command(&self, ctx: &Context, command: &CommandInteraction) {
+        if let Err(e) = command.defer(&ctx.http).await {
+            crate::term::error(format!("Failed to defer /tts: {}", e));
+            return;
+        }
+
+        let mut text = "";
+        let mut speaker = self.speaker.as_str();
+        for option in command.data.options() {
+            match (option.name, option.value) {
+                ("text", ResolvedValue::String(value)) => text = value,
+                ("voice", ResolvedValue::String(value)) => speaker = value,
+                _ => {}
+            }
+        }
+
+        let spoken = self
+            .speak(ctx, command.guild_id, command.user.id, text, speaker)
+            .await;
+        let followup = match spoken {
+            Ok(Spoken::Voice(channel)) => CreateInteractionResponseFollowup::new()
+                .content(format!("Speaking in {}", channel.mention())),
+            Ok(Spoken::Attachment(attachment)) => {
+                CreateInteractionResponseFollowup::new().add_file(attachment)
+            }
+            Err(e) => CreateInteractionResponseFollowup::new().content(format!("Error: {}", e)),
+        };
+        if let Err(e) = command.create_followup(&ctx.http, followup).await {
+            crate::term::error(format!("Failed to answer /tts: {}", e));
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        progress!("Connected to Discord as {}", ready.user.name);
+
+        let command = CreateCommand::new("tts")
+            .description("Speak text with a TikTok voice")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "text", "Text to speak")
+                    .required(true),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "voice",
+                "Voice code, e.g. en_us_002",
+            ));
+        if let Err(e) = Command::create_global_command(&ctx.http, command).await {
+            crate::term::error(format!("Failed to register /tts command: {}", e));
+        }
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.author.bot || Some(msg.channel_id) != self.channel || msg.content.trim().is_empty() {
+            return;
+        }
+
+        let spoken = self
+            .speak(
+                &ctx,
+                msg.guild_id,
+                msg.author.id,
+                &msg.content,
+                &self.speaker,
+            )
+            .await;
+        let reply = match spoken {
+            Ok(Spoken::Voice(_)) => return,
+            Ok(Spoken::Attachment(attachment)) => CreateMessage::new().add_file(attachment),
+            Err(e) => CreateMessage::new().content(format!("Error: {}", e)),
+        };
+        if let Err(e) = msg
+            .channel_id
+            .send_message(&ctx.http, reply.reference_message(&msg))
+            .await
+        {
+            crate::term::error(format!("Failed to send audio: {}", e));
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::Command(command) = interaction
+            && command.data.name == "tts"
+        {
+            self.handle_command(&ctx, &command).await;
+        }
+    }
+}
+
+pub async fn run(args: DiscordArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let token = match args.token {
+        Some(token) => token,
+        None => env::var("DISCORD_TOKEN")
+            .map_err(|_| "No Discord token given. Pass --token or set DISCORD_TOKEN.")?,
+    };
+    let (session_id, api_root_url) = crate::load_credentials()?;
+
+    // Reading message content is a privileged intent, so only ask for it when needed.
+    // Voice states tell which voice channel a message's author is in.
+    let mut intents = GatewayIntents::GUILDS | GatewayIntents::GUILD_VOICE_STATES;
+    if args.channel.is_some() {
+        intents |= GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+    }
+
+    let handler = Handler {
+        channel: args.channel.map(ChannelId::new),
+        speaker: args.speaker,
+        session_id,
+        api_root_url,
+    };
+
+    let mut client = Client::builder(&token, intents)
+        .event_handler(handler)
+        .register_songbird()
+        .await?;
+    client.start().await?;
+
+    Ok(())
+}
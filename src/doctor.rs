@@ -0,0 +1,120 @@
+//! `tktts doctor`: checks the setup and probes what the configured endpoint answers.
+//!
+//! Every API host gets one short request, and its response is matched against the known
+//! response variants (see [`tktts::schema`]). `--schema` limits the run to that probe and
+//! also prints the structure of each response, which is what to attach to a bug report
+//! when TikTok has changed its format again.
+
+use tktts::schema::{self, Outcome, Reason};
+
+/// Text of the probe request; short, so it costs as little quota as possible.
+pub const PROBE_TEXT: &str = "Hello.";
+pub const PROBE_VOICE: &str = "en_us_002";
+
+#[derive(clap::Args)]
+pub struct DoctorArgs {
+    /// Only probe the response schema of each API host, printing the responses' structure
+    #[arg(long)]
+    schema: bool,
+}
+
+/// Prints the setup checks that need no request.
+fn setup(session_ids: &str, hosts: &[&str]) {
+    match crate::config::path() {
+        Some(path) if path.is_file() => println!("Config        {}", path.display()),
+        Some(path) => println!(
+            "Config        {} (not found, using defaults)",
+            path.display()
+        ),
+        None => println!("Config        none (no config directory)"),
+    }
+    match crate::preset::selected() {
+        Some(preset) => println!("Preset        aid {} ({})", preset.aid, preset.app_name),
+        None => println!("Preset        none"),
+    }
+    println!(
+        "Sessions      {}",
+        crate::sessions::pool(session_ids).count()
+    );
+    println!("Hosts         {}", hosts.len());
+}
+
+pub async fn run(args: DoctorArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_ids, api_root_url) = crate::load_credentials()?;
+    let hosts: Vec<&str> = api_root_url
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .collect();
+    if !args.schema {
+        setup(&session_ids, &hosts);
+    }
+    let session_id = crate::sessions::pool(&session_ids)
+        .next()
+        .ok_or("TIKTOK_SESSIONID lists no session ID")?;
+
+    let mut failures = 0;
+    for host in &hosts {
+        println!("Host {}", host);
+        let body = match crate::request_tts_body(PROBE_TEXT, PROBE_VOICE, session_id, host).await {
+            Ok(response) => match response.body.text().await {
+                Ok(body) => body,
+                Err(e) => {
+                    println!("  request     failed: {}", crate::term::redact(e));
+                    failures += 1;
+                    continue;
+                }
+            },
+            Err(crate::ChunkError::Host(e) | crate::ChunkError::Session(e)) => {
+                println!("  request     failed: {}", crate::term::redact(e));
+                failures += 1;
+                continue;
+            }
+        };
+        match schema::parse(&body) {
+            Ok(parsed) => {
+                println!(
+                    "  schema      {} (audio in {})",
+                    parsed.schema.name(),
+                    parsed.schema.location()
+                );
+                match parsed.outcome {
+                    Outcome::Audio(audio) => {
+                        println!("  result      audio, {} base64 bytes", audio.len())
+                    }
+                    Outcome::Rejected { message, reason } => {
+                        let cause = match reason {
+                            Reason::MissingSession => "no session",
+                            Reason::Session => "session rejected",
+                            Reason::Region => "region rejected",
+                            Reason::Voice => "voice unavailable",
+                            Reason::TextTooLong => "text too long",
+                            Reason::Other => "rejected",
+                        };
+                        println!("  result      {}: {}", cause, message);
+                        failures += 1;
+                    }
+                }
+            }
+            Err(e) => {
+                println!("  schema      unknown: {}", e);
+                failures += 1;
+            }
+        }
+        if args.schema {
+            match serde_json::from_str(&body) {
+                Ok(json) => println!("  shape       {}", schema::shape(&json)),
+                Err(_) => {
+                    let start: String = body.chars().take(200).collect();
+                    println!("  body        {}", start);
+                }
+            }
+        }
+    }
+
+    match failures {
+        0 => Ok(()),
+        1 => Err("1 host failed the probe".into()),
+        _ => Err(format!("{} hosts failed the probe", failures).into()),
+    }
+}
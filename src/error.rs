@@ -0,0 +1,44 @@
+//! The error type of the library.
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Why a synthesis or one of its steps failed. The underlying error, where there is one,
+/// is kept as the [`source`](std::error::Error::source).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TktsError {
+    /// The API rejected the session ID as missing, invalid, expired or from another region
+    #[error("TikTok rejected the session ID: {0}")]
+    Auth(String),
+    /// The API answered, but with an error or a response that could not be read
+    #[error("API error (HTTP {status}): {msg}")]
+    Upstream { status: u16, msg: String },
+    /// No answer from the API host
+    #[error("Request failed: {0}")]
+    Network(#[source] BoxError),
+    /// Audio that could not be decoded, converted or encoded
+    #[error("Bad audio: {0}")]
+    Decode(#[source] BoxError),
+    /// Text that cannot be split into requests, such as empty text
+    #[error("{0}")]
+    Split(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Invalid settings, such as an API host that is not a URL
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    /// A custom [`Transport`](crate::transport::Transport), layer or signer failed, or a
+    /// layer refused the request, such as an open
+    /// [`CircuitBreaker`](crate::layers::CircuitBreaker); their own error is the source
+    #[error("{0}")]
+    Transport(#[source] BoxError),
+    /// The synthesis' cancellation token was cancelled
+    #[error("Synthesis cancelled")]
+    Cancelled,
+}
+
+impl From<url::ParseError> for TktsError {
+    fn from(e: url::ParseError) -> Self {
+        TktsError::Config(format!("invalid API URL: {}", e))
+    }
+}
@@ -0,0 +1,207 @@
+//! `tktts espeak`: a stand-in for the `espeak` command line.
+//!
+//! Takes the common espeak flags (`-v voice`, `-s speed`, `-a amplitude`, `-p pitch`,
+//! `-w out.wav`, `--stdout`), so tools written against espeak can call tktts instead.
+//! Invoked through a link named `espeak`, `espeak-ng` or `tktts-espeak`, the binary
+//! behaves as `tktts espeak`. espeak voices are languages, so `-v en-us` or `-v de+f3`
+//! picks a catalog voice for that language (female for a `+f` variant, male for `+m`).
+//! Output is WAV, as with espeak, and is played unless `-w`, `--stdout` or `-q` is given.
+
+use crate::pcm;
+use regex::Regex;
+use std::io::Write;
+use std::path::PathBuf;
+use tktts::voices;
+
+/// espeak's default speed in words per minute.
+const NORMAL_SPEED: f64 = 175.0;
+
+/// espeak's default pitch on its 0 to 99 scale.
+const NORMAL_PITCH: f64 = 50.0;
+
+#[derive(clap::Args)]
+pub struct EspeakArgs {
+    /// Text to speak; read from -f or stdin when not given
+    text: Vec<String>,
+
+    /// Voice: a language like en-us or de+f3, or a voice code, alias or name
+    #[arg(short, long, value_name = "VOICE")]
+    voice: Option<String>,
+
+    /// Speed in words per minute (175 is normal)
+    #[arg(short, value_name = "WPM")]
+    speed: Option<f64>,
+
+    /// Amplitude from 0 to 200 (100 is normal)
+    #[arg(short, value_name = "AMPLITUDE")]
+    amplitude: Option<f64>,
+
+    /// Pitch from 0 to 99 (50 is normal); each 50 steps are half an octave
+    #[arg(short, value_name = "PITCH")]
+    pitch: Option<f64>,
+
+    /// Write the speech to this WAV file instead of playing it
+    #[arg(short = 'w', value_name = "FILE")]
+    wave: Option<PathBuf>,
+
+    /// Write the speech as WAV to stdout
+    #[arg(long, conflicts_with = "wave")]
+    stdout: bool,
+
+    /// Read the text from this file
+    #[arg(short = 'f', value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Read the text from stdin
+    #[arg(long)]
+    stdin: bool,
+
+    /// Text contains SSML or other markup, which is left out
+    #[arg(short = 'm')]
+    markup: bool,
+
+    /// List the voices, optionally for a language only
+    #[arg(long, value_name = "LANGUAGE", num_args = 0..=1, default_missing_value = "", require_equals = true)]
+    voices: Option<String>,
+
+    /// No sound output (espeak's -q, which tktts takes for --quiet elsewhere)
+    #[arg(long, hide = true)]
+    no_audio: bool,
+
+    // espeak options without an equivalent, accepted so calls using them still work
+    #[arg(short = 'g', hide = true)]
+    gap: Option<String>,
+    #[arg(short = 'k', hide = true)]
+    capitals: Option<String>,
+    #[arg(short = 'l', hide = true)]
+    line_length: Option<String>,
+    #[arg(short = 'b', hide = true)]
+    encoding: Option<String>,
+    #[arg(short = 'z', hide = true)]
+    no_final_pause: bool,
+    #[arg(long, hide = true, num_args = 0..=1, require_equals = true)]
+    punct: Option<String>,
+}
+
+/// Arguments after `tktts espeak` with espeak's `-q` (no sound) turned into `--no-audio`,
+/// as `-q` is tktts's own `--quiet`.
+pub fn rewrite(args: &mut [std::ffi::OsString]) {
+    for arg in args {
+        if arg == "-q" {
+            *arg = "--no-audio".into();
+        }
+    }
+}
+
+/// The catalog voice for an espeak voice such as `en`, `en-us`, `en+f3` or `mb-de1`.
+fn language_voice(name: &str) -> Option<String> {
+    let (base, variant) = name.split_once('+').unwrap_or((name, ""));
+    let base = base
+        .trim_start_matches("mb-")
+        .trim_end_matches(|c: char| c.is_ascii_digit());
+    let language = match base.to_lowercase().replace('_', "-").as_str() {
+        "en-uk" => "en-gb".to_string(),
+        language => language.to_string(),
+    };
+    let gender = match variant.chars().next() {
+        Some('f') => Some("female"),
+        Some('m') => Some("male"),
+        _ => None,
+    };
+    if gender.is_none() && !language.contains('-') {
+        return crate::lang::voice_for(&language);
+    }
+    crate::lang::gendered_voice(&language, gender)
+}
+
+/// Prints the voices in the columns of `espeak --voices`.
+fn list(language: &str) {
+    println!(
+        "Pty Language       Age/Gender VoiceName          File                 Other Languages"
+    );
+    let language = language.to_lowercase();
+    for voice in voices::all() {
+        let tag = voice.language.to_lowercase();
+        if !tag.starts_with(&language) {
+            continue;
+        }
+        let gender = match () {
+            _ if voice.tags.contains(&"female") => "F",
+            _ if voice.tags.contains(&"male") => "M",
+            _ => "-",
+        };
+        println!(
+            " 5  {:<15} --/{:<7} {:<18} tktts/{}",
+            tag, gender, voice.code, voice.code
+        );
+    }
+}
+
+fn text(args: &EspeakArgs) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut text = match (&args.file, args.text.is_empty() || args.stdin) {
+        (Some(path), _) => std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        (None, false) => args.text.join(" "),
+        (None, true) => crate::console::read_stdin()?,
+    };
+    if args.markup {
+        text = Regex::new(r"<[^>]*>")
+            .unwrap()
+            .replace_all(&text, " ")
+            .into_owned();
+    }
+    Ok(text)
+}
+
+/// `audio` with espeak's speed, pitch and amplitude applied.
+fn shaped(
+    audio: &[u8],
+    args: &EspeakArgs,
+) -> Result<pcm::Pcm, Box<dyn std::error::Error + Send + Sync>> {
+    let speed = args.speed.unwrap_or(NORMAL_SPEED).max(1.0) / NORMAL_SPEED;
+    // Half an octave for every 50 steps of espeak's pitch scale
+    let pitch =
+        2f64.powf((args.pitch.unwrap_or(NORMAL_PITCH).clamp(0.0, 99.0) - NORMAL_PITCH) / 100.0);
+    let gain = args
+        .amplitude
+        .map_or(1.0, |amplitude| amplitude.clamp(0.0, 200.0) / 100.0);
+    let pcm = pcm::decode(audio)?;
+    Ok(pcm::voiced(pcm, speed, pitch, gain as f32))
+}
+
+pub async fn run(args: EspeakArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(language) = &args.voices {
+        list(language);
+        return Ok(());
+    }
+    let speaker = match &args.voice {
+        Some(name) => match language_voice(name) {
+            Some(voice) => voice,
+            None => crate::say::voice(name)?,
+        },
+        None => crate::config::get()
+            .speaker
+            .clone()
+            .unwrap_or_else(|| "en_us_002".to_string()),
+    };
+    let text = text(&args)?;
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let audio = crate::synthesize(&text, &speaker, &session_id, &api_root_url).await?;
+    let wav = pcm::wav(&shaped(&audio, &args)?);
+
+    if let Some(path) = &args.wave {
+        std::fs::write(path, &wav)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    } else if args.stdout {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&wav)?;
+        stdout.flush()?;
+    } else if !args.no_audio {
+        crate::playback::play(&wav, None).await?;
+    }
+    Ok(())
+}
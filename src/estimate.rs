@@ -0,0 +1,104 @@
+//! Preflight estimate of a job's request count and audio length, for `--estimate`,
+//! `--max-requests` and `--max-duration`.
+
+use crate::term::{self, Style};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static PREFLIGHT: OnceLock<Preflight> = OnceLock::new();
+
+#[derive(Default)]
+struct Preflight {
+    print: bool,
+    max_requests: Option<usize>,
+    max_duration: Option<Duration>,
+}
+
+pub fn init(print: bool, max_requests: Option<usize>, max_duration: Option<Duration>) {
+    let _ = PREFLIGHT.set(Preflight {
+        print,
+        max_requests,
+        max_duration,
+    });
+}
+
+/// Whether jobs need their chunks up front, so streaming has to wait for the whole input.
+pub fn enabled() -> bool {
+    PREFLIGHT.get().is_some_and(|preflight| {
+        preflight.print || preflight.max_requests.is_some() || preflight.max_duration.is_some()
+    })
+}
+
+/// Parses `90`, `90s`, `15m` or `1h30m`.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("expected a duration like 90s, 15m or 1h30m, got '{}'", text);
+    if let Ok(secs) = text.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let mut total = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        let unit = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        total += number.parse::<u64>().map_err(|_| invalid())? * unit;
+        number.clear();
+    }
+    if !number.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Checks one job before any request, given each chunk with its voice and how many
+/// requests remain after repeats and cached chunks.
+pub fn check(
+    chunks: &[(&str, &str)],
+    requests: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(preflight) = PREFLIGHT.get() else {
+        return Ok(());
+    };
+    let secs: f64 = chunks
+        .iter()
+        .map(|(chunk, voice)| chunk.chars().count() as f64 / tktts::voices::speaking_rate(voice))
+        .sum();
+    let duration = Duration::from_secs_f64(secs);
+    let estimate = format!(
+        "{} request{}, about {} of audio",
+        requests,
+        if requests == 1 { "" } else { "s" },
+        format_duration(duration)
+    );
+    if preflight.print {
+        term::status(Style::Cyan, "Estimated", &estimate);
+    }
+    if let Some(max) = preflight.max_requests
+        && requests > max
+    {
+        return Err(format!("Job needs {}, over --max-requests {}", estimate, max).into());
+    }
+    if let Some(max) = preflight.max_duration
+        && duration > max
+    {
+        let max = format_duration(max);
+        return Err(format!("Job needs {}, over --max-duration {}", estimate, max).into());
+    }
+    Ok(())
+}
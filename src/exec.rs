@@ -0,0 +1,188 @@
+//! `tktts exec -- COMMAND`: runs a command and speaks its output.
+//!
+//! The command's output is passed through to the terminal unchanged, and every
+//! completed line of it is spoken as well, or with `--summary` only how the command
+//! ended. Lines that arrive while one is being spoken are read together next, so the
+//! speech never falls further behind than one announcement. The wrapper exits with the
+//! command's exit code, so it can stand in for the command in scripts.
+
+use crate::playback::PlaybackArgs;
+use regex::Regex;
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::{Arc, LazyLock};
+use std::time::Instant;
+use tokio::io::AsyncBufReadExt;
+
+#[derive(clap::Args)]
+pub struct ExecArgs {
+    /// Command to run, after `--`
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+
+    /// Only speak how the command ended and its last line of output
+    #[arg(long)]
+    summary: bool,
+
+    /// Only speak lines matching this regex
+    #[arg(long = "match", value_name = "REGEX", conflicts_with = "summary")]
+    pattern: Option<Regex>,
+
+    /// Voice used for the output
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+
+    #[command(flatten)]
+    flood: crate::flood::FloodArgs,
+
+    // Plays through a local player unless another target is given, as stdout is the command's
+    #[command(flatten)]
+    playback: PlaybackArgs,
+}
+
+/// Terminal color and cursor escapes: CSI sequences and OSC strings.
+static ESCAPE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b(?:\[[0-9;?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\)?)").unwrap()
+});
+
+/// `line` without terminal color and cursor escapes.
+fn plain(line: &str) -> String {
+    ESCAPE.replace_all(line, "").trim().to_string()
+}
+
+/// Queues `text`, with its voice picked by the text itself.
+fn push(queue: &crate::flood::Queue, text: String) {
+    let source = text.clone();
+    queue.push(&source, crate::flood::Message { text, voice: None });
+}
+
+/// `secs` as it reads aloud, like "2 minutes 5 seconds".
+fn spoken_duration(secs: u64) -> String {
+    let unit = |count: u64, name: &str| match count {
+        1 => format!("1 {}", name),
+        _ => format!("{} {}s", count, name),
+    };
+    match secs {
+        0..60 => unit(secs, "second"),
+        60..3600 => format!(
+            "{} {}",
+            unit(secs / 60, "minute"),
+            unit(secs % 60, "second")
+        ),
+        _ => format!(
+            "{} {}",
+            unit(secs / 3600, "hour"),
+            unit(secs % 3600 / 60, "minute")
+        ),
+    }
+}
+
+/// Speaks announcements one after another, reading all that queued up meanwhile at once
+/// (in one go per run of lines with the same `[[sources]]` voice).
+async fn announce(
+    queue: Arc<crate::flood::Queue>,
+    speaker: String,
+    playback: PlaybackArgs,
+    session_id: String,
+    api_root_url: String,
+) {
+    while let Some(message) = queue.next().await {
+        let mut runs: Vec<(Option<String>, String)> = Vec::new();
+        for message in std::iter::once(message).chain(queue.drain()) {
+            match runs.last_mut() {
+                Some((voice, text)) if *voice == message.voice => {
+                    text.push('\n');
+                    text.push_str(&message.text);
+                }
+                _ => runs.push((message.voice, message.text)),
+            }
+        }
+        for (voice, text) in runs {
+            let voice = voice.as_deref().unwrap_or(&speaker);
+            let result = match crate::synthesize(&text, voice, &session_id, &api_root_url).await {
+                Ok(audio) => playback.output(&audio).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                crate::term::error(format!("Failed to speak output: {}", e));
+            }
+        }
+        queue.spoken();
+    }
+}
+
+pub async fn run(args: ExecArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let mut playback = args.playback.clone();
+    if playback.writes_stdout() {
+        playback.play = true;
+    }
+
+    let program = &args.command[0];
+    let started = Instant::now();
+    let mut child = tokio::process::Command::new(program)
+        .args(&args.command[1..])
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    let mut stdout = tokio::io::BufReader::new(child.stdout.take().unwrap());
+
+    let queue = Arc::new(crate::flood::Queue::new(&args.flood, None));
+    let speaking = tokio::spawn(announce(
+        queue.clone(),
+        args.speaker.clone(),
+        playback,
+        session_id,
+        api_root_url,
+    ));
+
+    // Read as bytes, so output that is not UTF-8 still reaches the terminal unchanged
+    let mut line = Vec::new();
+    let mut last = None;
+    while stdout.read_until(b'\n', &mut line).await? > 0 {
+        let mut terminal = std::io::stdout().lock();
+        terminal.write_all(&line)?;
+        terminal.flush()?;
+        let text = plain(&String::from_utf8_lossy(&line));
+        line.clear();
+        if !text.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+        let wanted = args
+            .pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(&text));
+        if !args.summary && wanted {
+            push(&queue, text.clone());
+        }
+        last = Some(text);
+    }
+    let status = child.wait().await?;
+
+    let name = std::path::Path::new(program)
+        .file_name()
+        .map_or(program.into(), |name| name.to_string_lossy());
+    let elapsed = spoken_duration(started.elapsed().as_secs());
+    let outcome = match status.code() {
+        Some(0) => format!("{} finished after {}", name, elapsed),
+        Some(code) => format!("{} failed with exit code {} after {}", name, code, elapsed),
+        None => format!("{} was stopped after {}", name, elapsed),
+    };
+    // Without --summary the lines were heard already, but a failure still deserves a mention
+    if args.summary {
+        let summary = match &last {
+            Some(last) => format!("{}.\n{}", outcome, last),
+            None => format!("{}.", outcome),
+        };
+        push(&queue, summary);
+    } else if !status.success() {
+        push(&queue, format!("{}.", outcome));
+    }
+    queue.close();
+    speaking.await?;
+
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
@@ -0,0 +1,220 @@
+//! `tktts feed`: one audio file per entry of an RSS or Atom feed.
+//!
+//! Each entry's title and readable text (its content, else its summary, with the HTML
+//! stripped) are synthesized into `<date>_<title>.mp3`. Converted entries are recorded by
+//! ID in a state file in the output directory, so running the command again, e.g. from
+//! cron, only converts new entries.
+
+use feed_rs::model::Entry;
+use html2text::render::TrivialDecorator;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Longest title part of a file name, in characters.
+const MAX_SLUG: usize = 60;
+
+#[derive(clap::Args)]
+pub struct FeedArgs {
+    /// URL of the RSS or Atom feed
+    url: String,
+
+    /// Directory the audio files are written to
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    out_dir: PathBuf,
+
+    /// File recording converted entries (default: .tktts-feed.json in --out-dir)
+    #[arg(long, value_name = "FILE")]
+    state: Option<PathBuf>,
+
+    /// Only consider the newest N entries, e.g. on the first run over a long feed
+    #[arg(long, value_name = "N")]
+    latest: Option<usize>,
+
+    /// Voice used for the entries
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+
+    #[command(flatten)]
+    podcast: crate::podcast::PodcastArgs,
+}
+
+/// Converted entries: entry ID to the file it was written to.
+type State = BTreeMap<String, String>;
+
+fn load_state(path: &Path) -> Result<State, Box<dyn std::error::Error + Send + Sync>> {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid feed state {}: {}", path.display(), e).into()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e).into()),
+    }
+}
+
+/// Plain text of an HTML fragment, without link, emphasis or list markup.
+fn readable(html: &str) -> String {
+    html2text::config::with_decorator(TrivialDecorator::new())
+        .string_from_read(html.as_bytes(), usize::MAX)
+        .unwrap_or_else(|_| html.to_string())
+}
+
+fn title(entry: &Entry) -> String {
+    entry
+        .title
+        .as_ref()
+        .map(|title| readable(&title.content).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// The text spoken for an entry: its title, then its content or summary.
+fn entry_text(entry: &Entry) -> String {
+    let body = entry
+        .content
+        .as_ref()
+        .and_then(|content| content.body.as_deref())
+        .or(entry
+            .summary
+            .as_ref()
+            .map(|summary| summary.content.as_str()))
+        .map(readable)
+        .unwrap_or_default();
+    let title = title(entry);
+    match (title.is_empty(), body.trim().is_empty()) {
+        (_, true) => title,
+        (true, false) => body.trim().to_string(),
+        // A pause after the title, unless it ends in punctuation already
+        (false, false) if title.ends_with(['.', '!', '?']) => {
+            format!("{}\n\n{}", title, body.trim())
+        }
+        (false, false) => format!("{}.\n\n{}", title, body.trim()),
+    }
+}
+
+/// Hex of the first bytes of the SHA-256 of an entry ID, to tell entries apart in names.
+fn id_hash(id: &str) -> String {
+    Sha256::digest(id.as_bytes())[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// `<date>_<title slug>.mp3`, with a hash of the entry ID when the title gives no slug.
+fn file_name(entry: &Entry) -> String {
+    let date = entry
+        .published
+        .or(entry.updated)
+        .map_or("undated".to_string(), |date| {
+            date.format("%Y-%m-%d").to_string()
+        });
+    let mut slug = String::new();
+    for c in title(entry).chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let mut slug: String = slug.chars().take(MAX_SLUG).collect();
+    slug = slug.trim_end_matches('-').to_string();
+    if slug.is_empty() {
+        slug = id_hash(&entry.id);
+    }
+    format!("{}_{}.mp3", date, slug)
+}
+
+pub async fn run(args: FeedArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let state_path = args
+        .state
+        .unwrap_or_else(|| args.out_dir.join(".tktts-feed.json"));
+    let mut state = load_state(&state_path)?;
+    args.podcast.base_url()?;
+
+    let response = crate::http::client().get(&args.url).send().await?;
+    if !response.status().is_success() {
+        return Err(format!("Fetching {} failed with {}", args.url, response.status()).into());
+    }
+    let body = response.bytes().await?;
+    let feed = feed_rs::parser::parse(&body[..])
+        .map_err(|e| format!("Invalid feed {}: {}", args.url, e))?;
+
+    // Feeds list the newest entry first; convert oldest first
+    let latest = args.latest.unwrap_or(feed.entries.len());
+    let entries: Vec<&Entry> = feed.entries.iter().take(latest).rev().collect();
+    let new: Vec<&Entry> = entries
+        .into_iter()
+        .filter(|entry| !state.contains_key(&entry.id))
+        .collect();
+    if new.is_empty() {
+        crate::term::status(crate::term::Style::Green, "Finished", "no new entries");
+    } else {
+        fs::create_dir_all(&args.out_dir)?;
+    }
+
+    for entry in new {
+        let text = entry_text(entry);
+        if text.trim().is_empty() {
+            crate::term::status(
+                crate::term::Style::Yellow,
+                "Skipping",
+                format!("{}: no text", entry.id),
+            );
+            continue;
+        }
+        let mut name = file_name(entry);
+        if state.values().any(|written| *written == name) {
+            // Two entries with the same date and title
+            name = format!(
+                "{}_{}.mp3",
+                name.trim_end_matches(".mp3"),
+                id_hash(&entry.id)
+            );
+        }
+
+        let audio = crate::synthesize(&text, &args.speaker, &session_id, &api_root_url).await?;
+        let path = args.out_dir.join(&name);
+        fs::write(&path, &audio)?;
+        crate::term::status(crate::term::Style::Green, "Wrote", path.display());
+
+        // Saved after every entry, so an interrupted run resumes where it stopped
+        state.insert(entry.id.clone(), name);
+        fs::write(&state_path, serde_json::to_vec_pretty(&state)?)?;
+    }
+
+    if args.podcast.podcast_out.is_none() {
+        return Ok(());
+    }
+    // Every converted entry still in the feed, including those of earlier runs
+    let mut episodes = Vec::new();
+    for entry in &feed.entries {
+        let Some(name) = state.get(&entry.id) else {
+            continue;
+        };
+        let Ok(audio) = fs::read(args.out_dir.join(name)) else {
+            continue;
+        };
+        let title = Some(title(entry))
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| name.trim_end_matches(".mp3").to_string());
+        let date = entry
+            .published
+            .or(entry.updated)
+            .unwrap_or_else(chrono::Utc::now);
+        episodes.push(crate::podcast::Episode::new(
+            title,
+            entry.id.clone(),
+            name.clone(),
+            &audio,
+            date,
+        ));
+    }
+    let feed_title = feed
+        .title
+        .map(|title| readable(&title.content).trim().to_string());
+    crate::podcast::write(
+        &args.podcast,
+        feed_title.as_deref().unwrap_or("tktts"),
+        &mut episodes,
+    )
+}
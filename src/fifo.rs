@@ -0,0 +1,87 @@
+//! `--listen-fifo PATH`: stays running and speaks each line written to a named pipe.
+//!
+//! Lines are plain text or JSON like `{"text": "...", "voice": "en_us_006"}`, as in MQTT
+//! mode, and are spoken one after another through the playback flags or added to the
+//! `--append` file. The FIFO is created if it does not exist. Each writer's end is the end
+//! of its input only; the next `echo hi > PATH` is simply waited for.
+
+use crate::playback::PlaybackArgs;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+
+/// Creates the FIFO at `path` unless one is already there.
+fn create(path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => return Ok(()),
+        Ok(_) => return Err(format!("{} exists and is not a FIFO", path.display()).into()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: `c_path` is a valid NUL-terminated path for the duration of the call
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        let e = io::Error::last_os_error();
+        return Err(format!("Failed to create FIFO {}: {}", path.display(), e).into());
+    }
+    Ok(())
+}
+
+/// Sends the lines of every writer in turn, on a blocking thread.
+fn read_lines(path: PathBuf, lines: mpsc::Sender<String>) -> io::Result<()> {
+    loop {
+        // Opening blocks until a writer opens the FIFO too
+        let mut reader = BufReader::new(File::open(&path)?);
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            if lines
+                .blocking_send(String::from_utf8_lossy(&line).into_owned())
+                .is_err()
+            {
+                return Ok(());
+            }
+            line.clear();
+        }
+    }
+}
+
+pub async fn run(
+    path: &Path,
+    speaker: &str,
+    playback: &PlaybackArgs,
+    append: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    create(path)?;
+    let (sender, mut lines) = mpsc::channel(64);
+    let reader = tokio::task::spawn_blocking({
+        let path = path.to_path_buf();
+        move || read_lines(path, sender)
+    });
+    progress!("Listening on {}", path.display());
+
+    while let Some(line) = lines.recv().await {
+        let Some(message) = crate::mqtt::parse_payload(line.as_bytes()) else {
+            continue;
+        };
+        let voice = message.voice.as_deref().unwrap_or(speaker);
+        let result = match append {
+            Some(file) => crate::append_tts(&message.text, voice, file).await,
+            None => match crate::synthesize(&message.text, voice, &session_id, &api_root_url).await
+            {
+                Ok(audio) => playback.output(&audio).await,
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            crate::term::error(format!("Failed to speak line: {}", e));
+        }
+    }
+    // The reader only stops early when the FIFO cannot be opened or read
+    reader.await??;
+    Ok(())
+}
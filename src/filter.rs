@@ -0,0 +1,139 @@
+//! User text filters, applied in order before text is split into chunks.
+//!
+//! Filters are listed in the config file as `[[filters]]` tables with one of:
+//!
+//! - `command`: run through `sh -c` with the text on stdin; its stdout replaces the text.
+//! - `wasm`: a WebAssembly module exporting `memory`, `alloc(len: i32) -> i32` and
+//!   `filter(ptr: i32, len: i32) -> i64`. The text is written to the buffer returned by
+//!   `alloc`, and `filter` returns the result's `ptr << 32 | len` in the same memory.
+//!   Modules get no imports, so they cannot reach the file system or network.
+//!
+//! Filters run synchronously, so they should be quick.
+
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+static FILTERS: OnceLock<Vec<Loaded>> = OnceLock::new();
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Shell command line reading text on stdin and printing the filtered text
+    pub command: Option<String>,
+    /// Path to a WebAssembly filter module, relative to the config file
+    pub wasm: Option<PathBuf>,
+}
+
+enum Loaded {
+    Command(String),
+    Wasm {
+        path: PathBuf,
+        engine: wasmi::Engine,
+        module: wasmi::Module,
+    },
+}
+
+fn load(
+    filter: &FilterConfig,
+    base: &Path,
+) -> Result<Loaded, Box<dyn std::error::Error + Send + Sync>> {
+    match (&filter.command, &filter.wasm) {
+        (Some(command), None) => Ok(Loaded::Command(command.clone())),
+        (None, Some(path)) => {
+            let path = base.join(path);
+            let bytes = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read filter {}: {}", path.display(), e))?;
+            let engine = wasmi::Engine::default();
+            let module = wasmi::Module::new(&engine, bytes)
+                .map_err(|e| format!("Invalid filter module {}: {}", path.display(), e))?;
+            Ok(Loaded::Wasm {
+                path,
+                engine,
+                module,
+            })
+        }
+        _ => Err("Each [[filters]] entry needs exactly one of `command` or `wasm`".into()),
+    }
+}
+
+/// Loads the configured filters once, compiling WASM modules up front.
+pub fn init(filters: &[FilterConfig]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config_path = crate::config::path().unwrap_or_default();
+    let base = config_path.parent().unwrap_or(Path::new(""));
+    let loaded = filters
+        .iter()
+        .map(|filter| load(filter, base))
+        .collect::<Result<Vec<_>, _>>()?;
+    let _ = FILTERS.set(loaded);
+    Ok(())
+}
+
+fn run_command(
+    command: &str,
+    text: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start filter '{}': {}", command, e))?;
+
+    // Written from a thread so a filter printing before it has read everything cannot deadlock
+    let mut stdin = child.stdin.take().ok_or("Filter stdin unavailable")?;
+    let input = text.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(format!("Filter '{}' exited with {}", command, output.status).into());
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| format!("Filter '{}' printed invalid UTF-8", command).into())
+}
+
+fn run_wasm(
+    engine: &wasmi::Engine,
+    module: &wasmi::Module,
+    text: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    // A fresh instance per call keeps filters stateless
+    let mut store = wasmi::Store::new(engine, ());
+    let instance = wasmi::Linker::<()>::new(engine).instantiate_and_start(&mut store, module)?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or("module does not export `memory`")?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc")?;
+    let filter = instance.get_typed_func::<(i32, i32), i64>(&store, "filter")?;
+
+    let len = i32::try_from(text.len()).map_err(|_| "text too long")?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory.write(&mut store, ptr as u32 as usize, text.as_bytes())?;
+    let result = filter.call(&mut store, (ptr, len))? as u64;
+
+    let mut output = vec![0; (result & 0xffff_ffff) as usize];
+    memory.read(&store, (result >> 32) as usize, &mut output)?;
+    String::from_utf8(output).map_err(|_| "module returned invalid UTF-8".into())
+}
+
+/// Runs `text` through every configured filter in order.
+pub fn apply(text: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut text = text.to_string();
+    for filter in FILTERS.get().into_iter().flatten() {
+        text = match filter {
+            Loaded::Command(command) => run_command(command, &text)?,
+            Loaded::Wasm {
+                path,
+                engine,
+                module,
+            } => run_wasm(engine, module, &text)
+                .map_err(|e| format!("Filter {} failed: {}", path.display(), e))?,
+        };
+    }
+    Ok(text)
+}
@@ -0,0 +1,322 @@
+//! Flood control for the modes that speak a stream of messages (`twitch`, `mqtt`, `exec`).
+//!
+//! Messages wait in a queue and are spoken one at a time. `--dedup-window` leaves out a
+//! message identical to one heard (or waiting) within that time, `--min-gap` keeps a
+//! pause between two messages, and `--max-queue` bounds the backlog: when it is full,
+//! `drop-oldest` forgets the oldest waiting message and `summarize` collapses the
+//! backlog into a "N messages skipped" notice before the newest one.
+//!
+//! Messages without a voice of their own get one from the `[[sources]]` config entries:
+//! the first whose pattern matches the message's source line (`alice: hi` for a chat
+//! message, the topic and payload for MQTT, the output line for `exec`) picks it.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+static SOURCES: OnceLock<Vec<(Regex, String)>> = OnceLock::new();
+
+/// A `[[sources]]` config entry.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourceConfig {
+    /// Regex matched against the source line, e.g. `^ERROR` or `^alice:`
+    pub pattern: String,
+    /// Voice or alias for matching messages
+    pub voice: String,
+}
+
+/// Compiles the `[[sources]]` patterns.
+pub fn init(sources: &[SourceConfig]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let compiled = sources
+        .iter()
+        .map(|source| {
+            Regex::new(&source.pattern)
+                .map(|pattern| (pattern, source.voice.clone()))
+                .map_err(|e| format!("Invalid [[sources]] pattern '{}': {}", source.pattern, e))
+        })
+        .collect::<Result<_, _>>()?;
+    let _ = SOURCES.set(compiled);
+    Ok(())
+}
+
+/// The voice of the first `[[sources]]` entry matching `line`.
+fn source_voice(line: &str) -> Option<String> {
+    SOURCES
+        .get()?
+        .iter()
+        .find(|(pattern, _)| pattern.is_match(line))
+        .map(|(_, voice)| voice.clone())
+}
+
+#[derive(clap::Args, Clone)]
+pub struct FloodArgs {
+    /// Shortest pause between two spoken messages, e.g. 2s
+    #[arg(long, value_name = "DURATION", value_parser = crate::estimate::parse_duration)]
+    pub min_gap: Option<Duration>,
+
+    /// Skip a message identical to one spoken or queued within this time, e.g. 30s
+    #[arg(long, value_name = "DURATION", value_parser = crate::estimate::parse_duration)]
+    pub dedup_window: Option<Duration>,
+
+    /// Messages that may wait to be spoken before --on-overflow applies
+    #[arg(long, value_name = "N", alias = "queue-size")]
+    pub max_queue: Option<usize>,
+
+    /// What happens to a new message when the queue is full
+    #[arg(long, value_enum, default_value_t = Overflow::DropOldest)]
+    pub on_overflow: Overflow,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+pub enum Overflow {
+    /// Forget the oldest waiting message
+    #[default]
+    DropOldest,
+    /// Replace the waiting messages with a spoken count of them
+    Summarize,
+}
+
+/// A message to speak, optionally in a voice of its own.
+pub struct Message {
+    pub text: String,
+    pub voice: Option<String>,
+}
+
+struct State {
+    waiting: VecDeque<Message>,
+    /// Messages collapsed by `summarize` and not yet announced
+    skipped: usize,
+    /// When each recent message was accepted, by its normalized text
+    recent: HashMap<String, Instant>,
+    last_spoken: Option<Instant>,
+    closed: bool,
+}
+
+impl State {
+    /// The "N messages skipped" notice for the messages collapsed since the last one.
+    fn skipped_notice(&mut self) -> Option<Message> {
+        let text = match std::mem::take(&mut self.skipped) {
+            0 => return None,
+            1 => "1 message skipped.".to_string(),
+            skipped => format!("{} messages skipped.", skipped),
+        };
+        Some(Message { text, voice: None })
+    }
+}
+
+pub struct Queue {
+    args: FloodArgs,
+    max_queue: Option<usize>,
+    state: Mutex<State>,
+    ready: Notify,
+}
+
+/// `text` lowercased with its whitespace collapsed, so repeats differing in spacing or
+/// case count as the same message.
+fn normalized(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl Queue {
+    /// A queue for `args`, holding `default_max` messages when `--max-queue` is not given
+    /// (unbounded for `None`).
+    pub fn new(args: &FloodArgs, default_max: Option<usize>) -> Self {
+        Queue {
+            args: args.clone(),
+            max_queue: args.max_queue.or(default_max).map(|max| max.max(1)),
+            state: Mutex::new(State {
+                waiting: VecDeque::new(),
+                skipped: 0,
+                recent: HashMap::new(),
+                last_spoken: None,
+                closed: false,
+            }),
+            ready: Notify::new(),
+        }
+    }
+
+    /// Queues `message` from `source`, the line its voice is picked by, unless it repeats
+    /// a recent one.
+    pub fn push(&self, source: &str, mut message: Message) {
+        if message.voice.is_none() {
+            message.voice = source_voice(source);
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some(window) = self.args.dedup_window {
+            let now = Instant::now();
+            state
+                .recent
+                .retain(|_, at| now.duration_since(*at) < window);
+            let key = normalized(&message.text);
+            if state.recent.contains_key(&key) {
+                progress!("Skipping a repeated message");
+                return;
+            }
+            state.recent.insert(key, now);
+        }
+        if let Some(max) = self.max_queue
+            && state.waiting.len() >= max
+        {
+            match self.args.on_overflow {
+                Overflow::DropOldest => {
+                    state.waiting.pop_front();
+                    progress!("Speech queue full, dropping the oldest message");
+                }
+                Overflow::Summarize => {
+                    state.skipped += state.waiting.len();
+                    state.waiting.clear();
+                    progress!("Speech queue full, skipping {} messages", state.skipped);
+                }
+            }
+        }
+        state.waiting.push_back(message);
+        drop(state);
+        self.ready.notify_one();
+    }
+
+    /// Lets [`next`](Self::next) return `None` once the waiting messages are spoken.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_one();
+    }
+
+    /// The next message to speak, after the `--min-gap` since the last one.
+    pub async fn next(&self) -> Option<Message> {
+        let message = loop {
+            let notified = self.ready.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if let Some(notice) = state.skipped_notice() {
+                    break notice;
+                }
+                if let Some(message) = state.waiting.pop_front() {
+                    break message;
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            notified.await;
+        };
+        let last_spoken = self.state.lock().unwrap().last_spoken;
+        if let (Some(gap), Some(last)) = (self.args.min_gap, last_spoken) {
+            tokio::time::sleep_until(last + gap).await;
+        }
+        Some(message)
+    }
+
+    /// All messages waiting right now, to be spoken along with the one from `next`: the
+    /// notice of messages skipped before them first, if any.
+    pub fn drain(&self) -> Vec<Message> {
+        let mut state = self.state.lock().unwrap();
+        let notice = state.skipped_notice();
+        notice.into_iter().chain(state.waiting.drain(..)).collect()
+    }
+
+    /// Marks the end of speaking a message, from which `--min-gap` counts.
+    pub fn spoken(&self) {
+        self.state.lock().unwrap().last_spoken = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(configure: impl FnOnce(&mut FloodArgs)) -> Queue {
+        let mut args = FloodArgs {
+            min_gap: None,
+            dedup_window: None,
+            max_queue: None,
+            on_overflow: Overflow::DropOldest,
+        };
+        configure(&mut args);
+        Queue::new(&args, None)
+    }
+
+    fn push(queue: &Queue, text: &str) {
+        let message = Message {
+            text: text.to_string(),
+            voice: None,
+        };
+        queue.push(text, message);
+    }
+
+    fn texts(messages: Vec<Message>) -> Vec<String> {
+        messages.into_iter().map(|message| message.text).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn repeats_are_skipped_within_the_dedup_window() {
+        let queue = queue(|args| args.dedup_window = Some(Duration::from_secs(30)));
+        push(&queue, "Hello  there");
+        push(&queue, "hello there");
+        tokio::time::advance(Duration::from_secs(31)).await;
+        push(&queue, "HELLO there");
+        assert_eq!(texts(queue.drain()), ["Hello  there", "HELLO there"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn messages_keep_the_min_gap() {
+        let queue = queue(|args| args.min_gap = Some(Duration::from_secs(2)));
+        push(&queue, "one");
+        push(&queue, "two");
+        let started = Instant::now();
+        assert_eq!(queue.next().await.unwrap().text, "one");
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        queue.spoken();
+        assert_eq!(queue.next().await.unwrap().text, "two");
+        assert_eq!(started.elapsed(), Duration::from_millis(2500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_full_queue_drops_its_oldest_message() {
+        let queue = queue(|args| args.max_queue = Some(2));
+        for text in ["one", "two", "three"] {
+            push(&queue, text);
+        }
+        assert_eq!(texts(queue.drain()), ["two", "three"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_full_queue_is_summarized_before_the_newest_message() {
+        let queue = queue(|args| {
+            args.max_queue = Some(2);
+            args.on_overflow = Overflow::Summarize;
+        });
+        for text in ["one", "two", "three"] {
+            push(&queue, text);
+        }
+        assert_eq!(queue.next().await.unwrap().text, "2 messages skipped.");
+        assert_eq!(queue.next().await.unwrap().text, "three");
+
+        for text in ["four", "five", "six"] {
+            push(&queue, text);
+        }
+        assert_eq!(queue.next().await.unwrap().text, "2 messages skipped.");
+        // "six" still waits, so "eight" overflows the queue again
+        for text in ["seven", "eight"] {
+            push(&queue, text);
+        }
+        assert_eq!(texts(queue.drain()), ["2 messages skipped.", "eight"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn next_ends_once_closed_and_empty() {
+        let queue = queue(|_| ());
+        push(&queue, "last");
+        queue.close();
+        assert_eq!(queue.next().await.unwrap().text, "last");
+        assert!(queue.next().await.is_none());
+    }
+}
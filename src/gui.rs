@@ -0,0 +1,374 @@
+//! Desktop front-end (`tktts gui`, built with the `gui` feature).
+//!
+//! A text box, a voice picker with preview, a speed slider and a choice of MP3 or WAV
+//! output. Text files dropped onto the window replace the text, and Stop cancels the
+//! synthesis or playback in progress. Synthesis goes through
+//! the library's [`Client`], with the first session ID and API host of the environment.
+
+use crate::pcm;
+use eframe::egui;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+use tktts::TktsError;
+use tktts::client::{CancellationToken, Client, DEFAULT_BYTE_LIMIT};
+use tktts::voices::{self, Voice};
+use tokio::runtime::Handle;
+
+#[derive(clap::Args)]
+pub struct GuiArgs {
+    /// Player command that reads audio from stdin (default: mpv or ffplay)
+    #[arg(long, value_name = "COMMAND")]
+    player: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Mp3,
+    Wav,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Mp3 => "mp3",
+            Format::Wav => "wav",
+        }
+    }
+}
+
+/// What to do with the audio once it is ready.
+enum Target {
+    Play,
+    Save(String),
+}
+
+/// Messages from background synthesis tasks to the UI thread.
+enum UiEvent {
+    Status(String),
+    Progress { done: usize, total: usize },
+    Finished,
+}
+
+struct App {
+    text: String,
+    voice: &'static Voice,
+    search: String,
+    /// Playback speed; 1 is the voice's own
+    speed: f32,
+    format: Format,
+    path: String,
+    status: String,
+    progress: Option<(usize, usize)>,
+    busy: bool,
+    /// Cancels the synthesis or playback in progress
+    cancel: CancellationToken,
+    client: Client,
+    player: Option<String>,
+    runtime: Handle,
+    events: Sender<UiEvent>,
+    incoming: Receiver<UiEvent>,
+}
+
+/// `audio` at `speed` in `format`.
+fn render(
+    audio: Vec<u8>,
+    speed: f32,
+    format: Format,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    if format == Format::Mp3 && (speed - 1.0).abs() < 0.01 {
+        return Ok(audio);
+    }
+    let pcm = pcm::stretch(&pcm::decode(&audio)?, 1.0 / f64::from(speed));
+    match format {
+        Format::Mp3 => Ok(pcm::mp3(&pcm)?),
+        Format::Wav => Ok(pcm::wav(&pcm)),
+    }
+}
+
+impl App {
+    fn matching_voices(&self) -> Vec<&'static Voice> {
+        let search = self.search.to_lowercase();
+        voices::all()
+            .filter(|voice| {
+                search.is_empty()
+                    || voice.code.contains(&search)
+                    || voice.name.to_lowercase().contains(&search)
+                    || voice.language.to_lowercase().contains(&search)
+                    || voice.tags.iter().any(|tag| tag.contains(&search))
+            })
+            .collect()
+    }
+
+    /// Synthesizes `text` with the selected voice, reporting progress, then plays or saves it.
+    fn start(&mut self, ctx: &egui::Context, text: String, target: Target) {
+        if self.busy || text.trim().is_empty() {
+            return;
+        }
+        let total = tktts::split_text(&text, DEFAULT_BYTE_LIMIT).len();
+        self.busy = true;
+        self.progress = Some((0, total));
+        self.cancel = CancellationToken::new();
+        let cancel = self.cancel.clone();
+
+        let client = self.client.clone();
+        let voice = self.voice.code;
+        let (speed, format) = (self.speed, self.format);
+        let player = self.player.clone();
+        let events = self.events.clone();
+        let ctx = ctx.clone();
+        self.runtime.spawn(async move {
+            let send = |event| {
+                let _ = events.send(event);
+                ctx.request_repaint();
+            };
+            let mut parts = client.synthesize_stream_with_cancel(&text, voice, cancel.clone());
+            let mut audio = Vec::new();
+            let mut done = 0;
+            while let Some(part) = parts.recv().await {
+                match part {
+                    Ok(part) => audio.extend(part.bytes),
+                    Err(TktsError::Cancelled) => {
+                        send(UiEvent::Status("Cancelled".to_string()));
+                        send(UiEvent::Finished);
+                        return;
+                    }
+                    Err(e) => {
+                        send(UiEvent::Status(format!("Error: {}", e)));
+                        send(UiEvent::Finished);
+                        return;
+                    }
+                }
+                done += 1;
+                send(UiEvent::Progress { done, total });
+            }
+
+            let result = match target {
+                Target::Play => {
+                    send(UiEvent::Status(format!("Playing with {}", voice)));
+                    match tokio::task::spawn_blocking(move || render(audio, speed, Format::Mp3))
+                        .await
+                    {
+                        Ok(Ok(audio)) => {
+                            let playing = crate::playback::play(&audio, player.as_deref());
+                            match cancel.run_until_cancelled(playing).await {
+                                Some(played) => played.map(|_| "Played".to_string()),
+                                None => Ok("Stopped".to_string()),
+                            }
+                        }
+                        Ok(Err(e)) => Err(e),
+                        Err(e) => Err(e.into()),
+                    }
+                }
+                Target::Save(path) => {
+                    match tokio::task::spawn_blocking(move || render(audio, speed, format)).await {
+                        Ok(Ok(audio)) => tokio::fs::write(&path, &audio)
+                            .await
+                            .map(|_| {
+                                format!(
+                                    "Saved {} ({})",
+                                    path,
+                                    crate::cache::format_size(audio.len() as u64)
+                                )
+                            })
+                            .map_err(|e| format!("Failed to write {}: {}", path, e).into()),
+                        Ok(Err(e)) => Err(e),
+                        Err(e) => Err(e.into()),
+                    }
+                }
+            };
+            send(UiEvent::Status(match result {
+                Ok(status) => status,
+                Err(e) => format!("Error: {}", e),
+            }));
+            send(UiEvent::Finished);
+        });
+    }
+
+    /// Loads the first dropped file into the text box.
+    fn take_dropped(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|input| input.raw.dropped_files.first().cloned());
+        let Some(file) = dropped else {
+            return;
+        };
+        let contents = match (&file.path, &file.bytes) {
+            (Some(path), _) => std::fs::read(path),
+            (None, Some(bytes)) => Ok(bytes.to_vec()),
+            (None, None) => return,
+        };
+        match contents {
+            Ok(contents) => {
+                self.text = String::from_utf8_lossy(&contents).into_owned();
+                if let Some(path) = &file.path {
+                    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                    self.path = format!("{}.{}", stem, self.format.extension());
+                    self.status = format!("Loaded {}", path.display());
+                }
+            }
+            Err(e) => self.status = format!("Failed to read {}: {}", file.name, e),
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        while let Ok(event) = self.incoming.try_recv() {
+            match event {
+                UiEvent::Status(status) => self.status = status,
+                UiEvent::Progress { done, total } => self.progress = Some((done, total)),
+                UiEvent::Finished => {
+                    self.busy = false;
+                    self.progress = None;
+                }
+            }
+        }
+        self.take_dropped(ctx);
+
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if let Some((done, total)) = self.progress {
+                    let ratio = done as f32 / total.max(1) as f32;
+                    ui.add(
+                        egui::ProgressBar::new(ratio)
+                            .desired_width(160.0)
+                            .text(format!("{}/{} chunks", done, total)),
+                    );
+                }
+                ui.label(&self.status);
+            });
+        });
+
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Voice");
+                egui::ComboBox::from_id_salt("voice")
+                    .width(260.0)
+                    .selected_text(format!("{} ({})", self.voice.name, self.voice.code))
+                    .show_ui(ui, |ui| {
+                        ui.add(egui::TextEdit::singleline(&mut self.search).hint_text("Search"));
+                        for voice in self.matching_voices() {
+                            let label =
+                                format!("{} ({}, {})", voice.name, voice.code, voice.language);
+                            if ui
+                                .selectable_label(voice.code == self.voice.code, label)
+                                .clicked()
+                            {
+                                self.voice = voice;
+                            }
+                        }
+                    });
+                if ui
+                    .add_enabled(!self.busy, egui::Button::new("Preview"))
+                    .clicked()
+                {
+                    let sample = format!("Hello, this is {}.", self.voice.name);
+                    self.status = format!("Previewing {}", self.voice.code);
+                    self.start(ctx, sample, Target::Play);
+                }
+                ui.separator();
+                ui.label("Speed");
+                ui.add(egui::Slider::new(&mut self.speed, 0.5..=2.0).suffix("×"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Format");
+                for (format, name) in [(Format::Mp3, "MP3"), (Format::Wav, "WAV")] {
+                    if ui.radio_value(&mut self.format, format, name).changed() {
+                        let stem = self
+                            .path
+                            .rsplit_once('.')
+                            .map_or(self.path.as_str(), |(stem, _)| stem);
+                        self.path = format!("{}.{}", stem, format.extension());
+                    }
+                }
+                ui.separator();
+                ui.label("Save as");
+                ui.text_edit_singleline(&mut self.path);
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let idle = !self.busy && !self.text.trim().is_empty();
+                if ui.add_enabled(idle, egui::Button::new("Speak")).clicked() {
+                    self.status = format!("Speaking with {}", self.voice.code);
+                    self.start(ctx, self.text.clone(), Target::Play);
+                }
+                if ui
+                    .add_enabled(idle && !self.path.is_empty(), egui::Button::new("Save"))
+                    .clicked()
+                {
+                    self.status = format!("Synthesizing {}", self.path);
+                    self.start(ctx, self.text.clone(), Target::Save(self.path.clone()));
+                }
+                if ui
+                    .add_enabled(self.busy, egui::Button::new("Stop"))
+                    .clicked()
+                {
+                    self.cancel.cancel();
+                }
+                ui.label(format!(
+                    "{} characters, {} requests",
+                    self.text.chars().count(),
+                    tktts::split_text(&self.text, DEFAULT_BYTE_LIMIT).len()
+                ));
+            });
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.add_sized(
+                    ui.available_size(),
+                    egui::TextEdit::multiline(&mut self.text)
+                        .hint_text("Type or drop a text file here"),
+                );
+            });
+        });
+
+        // Keeps polling while a job runs, in case a repaint request was missed
+        if self.busy {
+            ctx.request_repaint_after(Duration::from_millis(250));
+        }
+    }
+}
+
+pub async fn run(args: GuiArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let first = |list: &str| {
+        list.split(',')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    let client = Client::new(&first(&session_id), &first(&api_root_url));
+
+    let (events, incoming) = mpsc::channel();
+    let app = App {
+        text: String::new(),
+        voice: voices::all()
+            .find(|voice| voice.code == "en_us_002")
+            .unwrap_or(&voices::VOICES[0]),
+        search: String::new(),
+        speed: 1.0,
+        format: Format::Mp3,
+        path: "speech.mp3".to_string(),
+        status: "Type some text or drop a file, pick a voice, press Speak.".to_string(),
+        progress: None,
+        busy: false,
+        cancel: CancellationToken::new(),
+        client,
+        player: args.player,
+        runtime: Handle::current(),
+        events,
+        incoming,
+    };
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("tktts")
+            .with_inner_size([720.0, 520.0])
+            .with_drag_and_drop(true),
+        ..Default::default()
+    };
+    // Windowing has to stay on the main thread, which runs this future
+    tokio::task::block_in_place(|| {
+        eframe::run_native("tktts", options, Box::new(|_| Ok(Box::new(app))))
+    })
+    .map_err(|e| format!("Failed to open the window: {}", e).into())
+}
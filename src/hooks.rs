@@ -0,0 +1,115 @@
+//! `pre_hook` and `post_hook` shell commands that run around a synthesis job.
+//!
+//! The pre hook runs before any request and aborts the job when it fails. The post hook
+//! runs once all audio is written and reads a JSON summary on stdin:
+//!
+//! ```json
+//! {"voice": "en_us_002", "elapsed_secs": 1.2,
+//!  "outputs": [{"path": "out.mp3", "chunks": 3, "bytes": 48213}]}
+//! ```
+//!
+//! Both see `TKTTS_VOICE`, and `TKTTS_OUTPUT` with the (first) output path when there is
+//! one. A `path` of `null` means the audio went to stdout or a player.
+
+use serde::Serialize;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+static OUTPUTS: Mutex<Vec<Output>> = Mutex::new(Vec::new());
+
+#[derive(Serialize)]
+struct Output {
+    path: Option<String>,
+    chunks: usize,
+    bytes: usize,
+}
+
+/// Notes an output of the current job for the post hook's summary.
+pub fn wrote(path: Option<&str>, chunks: usize, bytes: usize) {
+    let output = Output {
+        path: path.map(str::to_string),
+        chunks,
+        bytes,
+    };
+    OUTPUTS.lock().unwrap().push(output);
+}
+
+/// The paths written by the current job so far, leaving out stdout and players.
+pub fn paths() -> Vec<String> {
+    OUTPUTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|output| output.path.clone())
+        .collect()
+}
+
+fn command(line: &str, voice: &str, output: Option<&str>) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line).env("TKTTS_VOICE", voice);
+    if let Some(output) = output {
+        command.env("TKTTS_OUTPUT", output);
+    }
+    command
+}
+
+/// Runs the configured `pre_hook`, if any. `output` is the requested `--output` target.
+pub async fn pre(
+    voice: &str,
+    output: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(line) = &crate::config::get().pre_hook else {
+        return Ok(());
+    };
+    // The job's own input may still be waiting on stdin
+    let status = command(line, voice, output)
+        .stdin(Stdio::null())
+        .status()
+        .await
+        .map_err(|e| format!("Failed to start pre_hook '{}': {}", line, e))?;
+    if !status.success() {
+        return Err(format!("pre_hook '{}' exited with {}", line, status).into());
+    }
+    Ok(())
+}
+
+/// Runs the configured `post_hook`, if any, with the summary of everything written since
+/// the job `started`.
+pub async fn post(
+    voice: &str,
+    started: Instant,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(line) = &crate::config::get().post_hook else {
+        return Ok(());
+    };
+    // Left in place for a `--notify` after the hook
+    let (first, summary) = {
+        let outputs = OUTPUTS.lock().unwrap();
+        let summary = serde_json::json!({
+            "voice": voice,
+            "elapsed_secs": started.elapsed().as_secs_f64(),
+            "outputs": *outputs,
+        });
+        (
+            outputs.first().and_then(|output| output.path.clone()),
+            summary,
+        )
+    };
+
+    let mut child = command(line, voice, first.as_deref())
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start post_hook '{}': {}", line, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores the summary may exit before reading it
+        let _ = stdin.write_all(summary.to_string().as_bytes()).await;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(format!("post_hook '{}' exited with {}", line, status).into());
+    }
+    Ok(())
+}
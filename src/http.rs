@@ -0,0 +1,194 @@
+//! The HTTP client shared by all outgoing requests, built once from the TLS and DNS
+//! options, and the request policies of the TTS requests made with it.
+
+use hyper_014::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::tls::{Certificate, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tktts::layers::{CircuitBreaker, Layer, Logging, RateLimit, Retry, Sign};
+use tktts::transport::{ReqwestTransport, Transport};
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+static TRANSPORT: OnceLock<Arc<dyn Transport>> = OnceLock::new();
+
+/// Policies of the TTS requests, the `[requests]` config section. Everything is off
+/// unless set.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RequestsConfig {
+    /// Attempts after a request fails, is rate limited or meets a server error
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it (default 500)
+    pub retry_delay_ms: Option<u64>,
+    /// Most requests started per second, over all sessions and hosts
+    pub rate_limit: Option<f64>,
+    /// Failed requests in a row after which no more are sent for a while
+    pub breaker_threshold: Option<u32>,
+    /// How long requests stay off after the breaker opens (default 30)
+    pub breaker_cooldown_secs: Option<u64>,
+    /// Print every request with its status and time
+    pub log: bool,
+}
+
+#[derive(clap::Args)]
+pub struct ClientArgs {
+    /// Also trust the certificates in this PEM bundle, e.g. a corporate proxy's CA
+    #[arg(long, global = true, value_name = "PATH")]
+    ca_cert: Option<PathBuf>,
+
+    /// Accept invalid TLS certificates and host names (debugging only)
+    #[arg(long, global = true)]
+    insecure: bool,
+
+    /// Minimum TLS version: 1.0, 1.1, 1.2 or 1.3
+    #[arg(long, global = true, value_name = "VERSION", value_parser = parse_tls_version)]
+    tls_min: Option<Version>,
+
+    /// Connect to IP for HOST instead of resolving it, curl style: HOST:PORT:IP or HOST:IP
+    #[arg(long, global = true, value_name = "HOST:PORT:IP", value_parser = parse_resolve)]
+    resolve: Vec<(String, IpAddr)>,
+
+    /// Try IPv4 addresses before IPv6 ones
+    #[arg(long, global = true, conflicts_with = "prefer_ipv6")]
+    prefer_ipv4: bool,
+
+    /// Try IPv6 addresses before IPv4 ones
+    #[arg(long, global = true)]
+    prefer_ipv6: bool,
+}
+
+/// Parses `HOST:PORT:IP` or `HOST:IP`; IPv6 addresses may be written in brackets.
+///
+/// reqwest overrides whole host names, so the port is accepted for curl compatibility
+/// but applies to every port of the host.
+fn parse_resolve(value: &str) -> Result<(String, IpAddr), String> {
+    let (host, rest) = value
+        .split_once(':')
+        .filter(|(host, _)| !host.is_empty())
+        .ok_or_else(|| format!("expected HOST:PORT:IP, got '{}'", value))?;
+    let ip = match rest.split_once(':') {
+        Some((port, ip)) if port.parse::<u16>().is_ok() => ip,
+        _ => rest,
+    };
+    let ip = ip.trim_start_matches('[').trim_end_matches(']');
+    let ip = ip
+        .parse()
+        .map_err(|_| format!("invalid IP address '{}' in '{}'", ip, value))?;
+    Ok((host.to_string(), ip))
+}
+
+/// System DNS resolution with results ordered by the preferred address family.
+struct PreferFamily {
+    ipv6: bool,
+}
+
+impl Resolve for PreferFamily {
+    fn resolve(&self, name: Name) -> Resolving {
+        let ipv6 = self.ipv6;
+        Box::pin(async move {
+            let mut addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            addrs.sort_by_key(|addr| addr.is_ipv6() != ipv6);
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn parse_tls_version(value: &str) -> Result<Version, String> {
+    match value {
+        "1.0" => Ok(Version::TLS_1_0),
+        "1.1" => Ok(Version::TLS_1_1),
+        "1.2" => Ok(Version::TLS_1_2),
+        "1.3" => Ok(Version::TLS_1_3),
+        _ => Err(format!(
+            "unknown TLS version '{}' (use 1.0, 1.1, 1.2 or 1.3)",
+            value
+        )),
+    }
+}
+
+/// Builds the shared client; must run before the first request to take effect.
+pub fn configure(args: &ClientArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(path) = &args.ca_cert {
+        let pem = fs::read(path)
+            .map_err(|e| format!("Failed to read CA bundle {}: {}", path.display(), e))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA bundle {}: {}", path.display(), e))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    if args.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(version) = args.tls_min {
+        builder = builder.min_tls_version(version);
+    }
+
+    let mut overrides: HashMap<&str, Vec<SocketAddr>> = HashMap::new();
+    for (host, ip) in &args.resolve {
+        overrides
+            .entry(host)
+            .or_default()
+            .push(SocketAddr::new(*ip, 0));
+    }
+    for (host, addrs) in overrides {
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    if args.prefer_ipv4 || args.prefer_ipv6 {
+        builder = builder.dns_resolver(Arc::new(PreferFamily {
+            ipv6: args.prefer_ipv6,
+        }));
+    }
+
+    let client = builder
+        .build()
+        .map_err(|e| format!("Invalid TLS or DNS options: {}", e))?;
+    let _ = CLIENT.set(client);
+    Ok(())
+}
+
+/// The shared client. Clones are cheap and share the connection pool.
+pub fn client() -> reqwest::Client {
+    CLIENT.get_or_init(reqwest::Client::new).clone()
+}
+
+/// The transport of the TTS requests: the shared client wrapped in signing and the
+/// `[requests]` policies.
+pub fn transport() -> Arc<dyn Transport> {
+    TRANSPORT
+        .get_or_init(|| {
+            let config = &crate::config::get().requests;
+            let mut transport: Arc<dyn Transport> = Arc::new(ReqwestTransport::from(client()));
+            transport = Sign::new(crate::signing::ConfiguredSigner).layer(transport);
+            if crate::report::enabled() {
+                transport = crate::report::Tries.layer(transport);
+            }
+            if config.log {
+                transport = Logging::new(|line| {
+                    crate::term::status(crate::term::Style::Dim, "Request", line)
+                })
+                .layer(transport);
+            }
+            if let Some(rate) = config.rate_limit {
+                transport = RateLimit::per_second(rate).layer(transport);
+            }
+            if config.retries > 0 {
+                let delay = Duration::from_millis(config.retry_delay_ms.unwrap_or(500));
+                transport = Retry::new(config.retries).delay(delay).layer(transport);
+            }
+            if let Some(threshold) = config.breaker_threshold {
+                let cooldown = Duration::from_secs(config.breaker_cooldown_secs.unwrap_or(30));
+                transport = CircuitBreaker::new(threshold, cooldown).layer(transport);
+            }
+            transport
+        })
+        .clone()
+}
@@ -0,0 +1,212 @@
+//! Input language detection: a warning when the text does not look like the voice's
+//! language, and with `--auto-voice` a voice for the detected language instead.
+//!
+//! With `--multilingual`, the language is detected per sentence instead: text is split
+//! into runs of sentences in one language before it is chunked, and every chunk of a run
+//! in another language than the voice's is read by the voice for that language.
+
+use crate::term::{self, Style};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use tktts::voices;
+
+static AUTO_VOICE: AtomicBool = AtomicBool::new(false);
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// `--multilingual` voices by language, set when the flag is given
+static MULTILINGUAL: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+/// The language of each chunk of a multilingual run, by its text
+static CHUNKS: LazyLock<Mutex<HashMap<String, &'static str>>> = LazyLock::new(Default::default);
+/// Languages already announced as switched to
+static SWITCHED: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+/// Sentences, ending after their punctuation or line break.
+static SENTENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s).*?[.!?…\n؟۔׃]+|.+").unwrap());
+
+/// Lowest whatlang confidence (0 to 1) at which a sentence's language is taken.
+const SENTENCE_CONFIDENCE: f64 = 0.5;
+
+/// Switches voices to match the detected language instead of only warning.
+pub fn set_auto_voice(enabled: bool) {
+    AUTO_VOICE.store(enabled, Ordering::Relaxed);
+}
+
+/// Turns on per-sentence switching, with `voices` over `[language_voices]` by language.
+pub fn set_multilingual(voices: Vec<(String, String)>) {
+    let _ = MULTILINGUAL.set(voices.into_iter().collect());
+}
+
+/// Parses a `--multilingual` entry, `de=de_002`.
+pub fn parse_language_voice(entry: &str) -> Result<(String, String), String> {
+    match entry.split_once('=') {
+        Some((language, voice)) if !language.is_empty() && !voice.is_empty() => {
+            Ok((language.trim().to_lowercase(), voice.trim().to_string()))
+        }
+        _ => Err(format!(
+            "expected LANG=VOICE, e.g. de=de_002, got '{}'",
+            entry
+        )),
+    }
+}
+
+/// `text` as runs of sentences in one language, or whole without `--multilingual`.
+/// Sentences too short to detect stay with the run before them, or at the start, the
+/// one after.
+pub fn runs(text: &str) -> Vec<(Option<&'static str>, &str)> {
+    if MULTILINGUAL.get().is_none() {
+        return vec![(None, text)];
+    }
+    let mut runs: Vec<(Option<&'static str>, usize, usize)> = Vec::new();
+    for sentence in SENTENCE.find_iter(text) {
+        let language = detect_sentence(sentence.as_str());
+        match runs.last_mut() {
+            Some((last, _, end)) if language.is_none() || last.is_none() || *last == language => {
+                *last = last.or(language);
+                *end = sentence.end();
+            }
+            _ => runs.push((language, sentence.start(), sentence.end())),
+        }
+    }
+    runs.into_iter()
+        .map(|(language, start, end)| (language, &text[start..end]))
+        .collect()
+}
+
+/// Remembers `chunks` as split from a run in `language`, for [`chunk_voice`].
+pub fn record(chunks: &[String], language: Option<&'static str>) {
+    let Some(language) = language else {
+        return;
+    };
+    let mut recorded = CHUNKS.lock().unwrap();
+    for chunk in chunks {
+        recorded.insert(chunk.clone(), language);
+    }
+}
+
+/// The voice to read `chunk` with under `--multilingual`: `speaker`, unless the chunk's
+/// run is in another language, which has its own voice. Other than `speaker`, the first
+/// use of each language's voice is announced.
+pub fn chunk_voice(chunk: &str, speaker: &str) -> String {
+    let Some(map) = MULTILINGUAL.get() else {
+        return speaker.to_string();
+    };
+    let Some(&language) = CHUNKS.lock().unwrap().get(chunk) else {
+        return speaker.to_string();
+    };
+    let speaks = voices::find(crate::config::resolve_voice(speaker))
+        .and_then(|voice| voice.language.split('-').next());
+    if speaks == Some(language) {
+        return speaker.to_string();
+    }
+    let Some(voice) = map.get(language).cloned().or_else(|| voice_for(language)) else {
+        return speaker.to_string();
+    };
+    if SWITCHED.lock().unwrap().insert(language) {
+        let name = voices::language_name(language).unwrap_or(language);
+        term::status(
+            Style::Cyan,
+            "Detected",
+            format!("{}, using {}", name, voice),
+        );
+    }
+    voice
+}
+
+/// The ISO 639-1 code of the language of `text`, when detection is confident and the
+/// language is one the catalog has voices for.
+pub fn detect(text: &str) -> Option<&'static str> {
+    code(
+        whatlang::detect(text)
+            .filter(|info| info.is_reliable())?
+            .lang(),
+    )
+}
+
+/// [`detect`] for a single sentence, which is rarely long enough to be "reliable", so a
+/// fair confidence is taken instead.
+fn detect_sentence(text: &str) -> Option<&'static str> {
+    let info = whatlang::detect(text).filter(|info| info.confidence() >= SENTENCE_CONFIDENCE)?;
+    code(info.lang())
+}
+
+fn code(lang: whatlang::Lang) -> Option<&'static str> {
+    let code = match lang {
+        whatlang::Lang::Cmn => "zh",
+        whatlang::Lang::Deu => "de",
+        whatlang::Lang::Eng => "en",
+        whatlang::Lang::Fra => "fr",
+        whatlang::Lang::Ind => "id",
+        whatlang::Lang::Ita => "it",
+        whatlang::Lang::Jpn => "ja",
+        whatlang::Lang::Kor => "ko",
+        whatlang::Lang::Por => "pt",
+        whatlang::Lang::Spa => "es",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// The voice for `language`: its `[language_voices]` entry, else the first catalog voice.
+pub fn voice_for(language: &str) -> Option<String> {
+    if let Some(voice) = crate::config::get().language_voices.get(language) {
+        return Some(voice.clone());
+    }
+    voices::all()
+        .find(|voice| voice.language.split('-').next() == Some(language))
+        .map(|voice| voice.code.to_string())
+}
+
+/// The first catalog voice for the language tag `language` (`en` or `en-gb`, in any case)
+/// that carries the tag `gender` ("female" or "male"), or any voice without a `gender`.
+pub fn gendered_voice(language: &str, gender: Option<&str>) -> Option<String> {
+    let language = language.to_lowercase();
+    voices::all()
+        .filter(|voice| {
+            let tag = voice.language.to_lowercase();
+            tag == language || tag.starts_with(&format!("{}-", language))
+        })
+        .find(|voice| gender.is_none_or(|gender| voice.tags.contains(&gender)))
+        .map(|voice| voice.code.to_string())
+}
+
+/// The voice to read `text` with: `speaker`, unless the text is detected to be in
+/// another language and `--auto-voice` is on. Otherwise that mismatch is warned about
+/// once per run. Voices missing from the catalog are left alone, and with `--multilingual`
+/// every voice is, as chunks switch on their own.
+pub fn select(text: &str, speaker: &str) -> String {
+    if MULTILINGUAL.get().is_some() {
+        return speaker.to_string();
+    }
+    let Some(voice) = voices::find(crate::config::resolve_voice(speaker)) else {
+        return speaker.to_string();
+    };
+    let Some(detected) = detect(text) else {
+        return speaker.to_string();
+    };
+    if voice.language.split('-').next() == Some(detected) {
+        return speaker.to_string();
+    }
+    let name = voices::language_name(detected).unwrap_or(detected);
+
+    if AUTO_VOICE.load(Ordering::Relaxed)
+        && let Some(auto) = voice_for(detected)
+    {
+        term::status(Style::Cyan, "Detected", format!("{}, using {}", name, auto));
+        return auto;
+    }
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        term::status(
+            Style::Yellow,
+            "Warning",
+            format!(
+                "text looks {} but {} speaks {} (--auto-voice switches voices)",
+                name,
+                voice.code,
+                voices::language_name(voice.language).unwrap_or(voice.language)
+            ),
+        );
+    }
+    speaker.to_string()
+}
@@ -0,0 +1,354 @@
+//! Request policies composed around a [`Transport`], in the manner of tower's layers.
+//!
+//! A [`Layer`] wraps a transport in another transport that applies its policy and hands
+//! the request on, so policies stack in any order and new ones need no change to the
+//! request code. [`Client::layer`](crate::client::Client::layer) wraps the client's
+//! transport, and the layer added last is the outermost:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tktts::client::Client;
+//! use tktts::layers::{CircuitBreaker, Logging, RateLimit, Retry};
+//!
+//! let client = Client::new("session-id", "https://api16-normal-c-useast1a.tiktokv.com")
+//!     .layer(Logging::new(|line| eprintln!("{}", line)))
+//!     .layer(RateLimit::per_second(4.0))
+//!     .layer(Retry::new(2))
+//!     .layer(CircuitBreaker::new(5, Duration::from_secs(30)));
+//! ```
+//!
+//! Here every attempt is logged and rate limited, and the breaker counts a request as
+//! failed only once its retries have failed too.
+
+use crate::TktsError;
+use crate::transport::{Request, Response, ResponseFuture, Transport};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Wraps a transport in a policy.
+pub trait Layer {
+    fn layer(&self, inner: Arc<dyn Transport>) -> Arc<dyn Transport>;
+}
+
+/// Whether a request failed in a way worth retrying: no answer, rate limited or a server
+/// error. API errors come with status 200 and are not failures here.
+fn failed(result: &Result<Response, TktsError>) -> bool {
+    match result {
+        Ok(response) => response.status == 429 || response.status >= 500,
+        Err(_) => true,
+    }
+}
+
+/// Sends a failed request again, waiting longer before each attempt.
+#[derive(Clone)]
+pub struct Retry {
+    retries: u32,
+    delay: Duration,
+}
+
+impl Retry {
+    /// Up to `retries` more attempts, the first after 500 ms and each further one after
+    /// twice the wait of the one before.
+    pub fn new(retries: u32) -> Self {
+        Retry {
+            retries,
+            delay: Duration::from_millis(500),
+        }
+    }
+
+    /// The wait before the first retry.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+struct Retrying {
+    inner: Arc<dyn Transport>,
+    policy: Retry,
+}
+
+impl Layer for Retry {
+    fn layer(&self, inner: Arc<dyn Transport>) -> Arc<dyn Transport> {
+        Arc::new(Retrying {
+            inner,
+            policy: self.clone(),
+        })
+    }
+}
+
+impl Transport for Retrying {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let mut delay = self.policy.delay;
+            for attempt in 1..=self.policy.retries {
+                let result = self
+                    .inner
+                    .send(request.clone())
+                    .instrument(tracing::info_span!("attempt", attempt))
+                    .await;
+                if !failed(&result) {
+                    return result;
+                }
+                tracing::warn!(
+                    retry_in_ms = delay.as_millis() as u64,
+                    "request failed, retrying"
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            let attempt = self.policy.retries + 1;
+            self.inner
+                .send(request)
+                .instrument(tracing::info_span!("attempt", attempt))
+                .await
+        })
+    }
+}
+
+/// Starts requests no faster than a given rate, over all requests through the layer.
+#[derive(Clone)]
+pub struct RateLimit {
+    interval: Duration,
+}
+
+impl RateLimit {
+    /// At most `requests` request starts per second (at least one every 1000 seconds).
+    pub fn per_second(requests: f64) -> Self {
+        RateLimit {
+            interval: Duration::from_secs_f64(1.0 / requests.max(0.001)),
+        }
+    }
+}
+
+struct RateLimited {
+    inner: Arc<dyn Transport>,
+    interval: Duration,
+    /// When the next request may start
+    next: Mutex<Instant>,
+}
+
+impl Layer for RateLimit {
+    fn layer(&self, inner: Arc<dyn Transport>) -> Arc<dyn Transport> {
+        Arc::new(RateLimited {
+            inner,
+            interval: self.interval,
+            next: Mutex::new(Instant::now()),
+        })
+    }
+}
+
+impl Transport for RateLimited {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let wait = {
+                let mut next = self.next.lock().unwrap();
+                let now = Instant::now();
+                let start = (*next).max(now);
+                *next = start + self.interval;
+                start - now
+            };
+            tokio::time::sleep(wait).await;
+            self.inner.send(request).await
+        })
+    }
+}
+
+/// Stops sending after a run of failed requests, failing at once with [`CircuitOpen`] as
+/// the source of a [`TktsError::Transport`] until a cooldown has passed; then requests
+/// are tried again.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    /// Opens after `threshold` failed requests in a row (at least 1), for `cooldown`.
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            cooldown,
+        }
+    }
+}
+
+/// The error of a request refused by an open [`CircuitBreaker`].
+#[derive(Debug)]
+pub struct CircuitOpen {
+    pub failures: u32,
+    /// Until requests are tried again
+    pub retry_in: Duration,
+}
+
+impl std::fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requests in a row failed; not sending more for {}s",
+            self.failures,
+            self.retry_in.as_secs().max(1)
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+struct Breaker {
+    inner: Arc<dyn Transport>,
+    policy: CircuitBreaker,
+    /// Failures in a row, and until when the circuit is open
+    state: Mutex<(u32, Option<Instant>)>,
+}
+
+impl Layer for CircuitBreaker {
+    fn layer(&self, inner: Arc<dyn Transport>) -> Arc<dyn Transport> {
+        Arc::new(Breaker {
+            inner,
+            policy: self.clone(),
+            state: Mutex::new((0, None)),
+        })
+    }
+}
+
+impl Transport for Breaker {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            if let (failures, Some(until)) = *self.state.lock().unwrap() {
+                let now = Instant::now();
+                if now < until {
+                    return Err(TktsError::Transport(Box::new(CircuitOpen {
+                        failures,
+                        retry_in: until - now,
+                    })));
+                }
+            }
+            let result = self.inner.send(request).await;
+            let mut state = self.state.lock().unwrap();
+            if failed(&result) {
+                state.0 += 1;
+                // After the cooldown a single failure opens the circuit again
+                if state.0 >= self.policy.threshold {
+                    state.1 = Some(Instant::now() + self.policy.cooldown);
+                }
+            } else {
+                *state = (0, None);
+            }
+            result
+        })
+    }
+}
+
+/// Reports every request with its status or error and how long it took, as a line like
+/// `POST https://host/media/api/text/speech/invoke/: 200 in 312 ms`. The query, which
+/// holds the text, is left out.
+#[derive(Clone)]
+pub struct Logging {
+    log: Arc<dyn Fn(&str) + Send + Sync>,
+}
+
+impl Logging {
+    pub fn new(log: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Logging { log: Arc::new(log) }
+    }
+}
+
+struct Logged {
+    inner: Arc<dyn Transport>,
+    log: Arc<dyn Fn(&str) + Send + Sync>,
+}
+
+impl Layer for Logging {
+    fn layer(&self, inner: Arc<dyn Transport>) -> Arc<dyn Transport> {
+        Arc::new(Logged {
+            inner,
+            log: self.log.clone(),
+        })
+    }
+}
+
+impl Transport for Logged {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let target = request.url[..url::Position::AfterPath].to_string();
+            let started = Instant::now();
+            let result = self.inner.send(request).await;
+            let elapsed = started.elapsed().as_millis();
+            (self.log)(&match &result {
+                Ok(response) => format!("POST {}: {} in {} ms", target, response.status, elapsed),
+                Err(e) => format!("POST {}: failed after {} ms: {}", target, elapsed, e),
+            });
+            result
+        })
+    }
+}
+
+/// What [`Signer::sign`] returns: the headers to add.
+pub type Signature<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<(String, String)>, BoxError>> + Send + 'a>>;
+
+/// Computes signature headers (such as `X-Gorgon` and `X-Khronos`) for a request.
+pub trait Signer: Send + Sync {
+    fn sign<'a>(&'a self, request: &'a Request) -> Signature<'a>;
+}
+
+/// Adds a [`Signer`]'s headers to every request, so each retry is signed anew when the
+/// layer is inside [`Retry`].
+#[derive(Clone)]
+pub struct Sign {
+    signer: Arc<dyn Signer>,
+}
+
+impl Sign {
+    pub fn new(signer: impl Signer + 'static) -> Self {
+        Sign {
+            signer: Arc::new(signer),
+        }
+    }
+}
+
+/// The error of a [`Signer`], as the source of a [`TktsError::Transport`], told apart
+/// from failures to reach the host.
+#[derive(Debug)]
+pub struct SignError(pub BoxError);
+
+impl std::fmt::Display for SignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for SignError {}
+
+struct Signed {
+    inner: Arc<dyn Transport>,
+    signer: Arc<dyn Signer>,
+}
+
+impl Layer for Sign {
+    fn layer(&self, inner: Arc<dyn Transport>) -> Arc<dyn Transport> {
+        Arc::new(Signed {
+            inner,
+            signer: self.signer.clone(),
+        })
+    }
+}
+
+impl Transport for Signed {
+    fn send(&self, mut request: Request) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let headers = self
+                .signer
+                .sign(&request)
+                .await
+                .map_err(|e| TktsError::Transport(Box::new(SignError(e))))?;
+            request.headers.extend(headers);
+            self.inner.send(request).await
+        })
+    }
+}
@@ -0,0 +1,464 @@
+//! Library half of tktts: the network-independent core shared by the CLI and other
+//! front-ends.
+//!
+//! - [`split_text`] decides how input is cut into API requests, with guarantees
+//!   downstream users can rely on.
+//! - [`sanitize_text`] and [`tts_url`] build requests exactly as the CLI sends them.
+//! - [`voices`] is the embedded voice catalog.
+//! - [`client::Client`] performs complete syntheses, for embedding (not on wasm32), and
+//!   `blocking::Client` does the same without async code (with the `blocking` feature).
+//!   Its requests go through a [`transport::Transport`], reqwest unless another is given,
+//!   wrapped in the policies of [`layers`] such as retries and rate limits.
+//! - [`TktsError`] tells failures apart, for callers that handle them differently.
+//!
+//! Built for `wasm32-unknown-unknown`, the same functions are exported to JavaScript with
+//! wasm-bindgen, together with a `fetch`-based request helper.
+
+pub use error::TktsError;
+use regex::Regex;
+use url::Url;
+
+mod bidi;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod client;
+mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod layers;
+pub mod mp3;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pcm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod schema;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transport;
+pub mod voices;
+#[cfg(target_arch = "wasm32")]
+mod wasm;
+
+/// User-Agent of the app version whose endpoint parameters are used.
+pub const USER_AGENT: &str = "com.zhiliaoapp.musically/2022600030 (Linux; U; Android 7.1.2; es_ES; SM-G988N; Build/NRD90M;tt-ok/3.12.13.1)";
+
+/// Path of the TTS endpoint, relative to the API host.
+pub const API_BASE_URL: &str = "/media/api/text/speech/invoke/";
+
+/// Per-voice choices for how [`sanitize_text_with`] rewrites text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// Replace ä, ö, ü and ß with ae, oe, ue and ss
+    pub replace_umlauts: bool,
+    /// Spell out numbers as English words, for voices that skip or garble digits
+    pub spell_numbers: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            replace_umlauts: true,
+            spell_numbers: false,
+        }
+    }
+}
+
+/// Version of the text normalization: what [`sanitize_text_with`] sends for a text and
+/// how [`split_text`] chunks it.
+///
+/// It is part of cache keys and recorded in plans and sidecars, and is raised whenever
+/// either function's output changes for some input, so audio made from the old text is
+/// never mistaken for the new.
+pub const NORMALIZATION_VERSION: u32 = 3;
+
+/// Rewrites characters the TTS endpoint mishandles into speakable text, with the
+/// default [`Profile`].
+pub fn sanitize_text(text: &str) -> String {
+    sanitize_text_with(text, &Profile::default())
+}
+
+/// [`sanitize_text`] following `profile`; [`voices::profile`] has the one for a voice.
+pub fn sanitize_text_with(text: &str, profile: &Profile) -> String {
+    let mut text = bidi::strip_controls(text)
+        .replace("+", "plus")
+        .replace("&", "and");
+    if profile.replace_umlauts {
+        text = text
+            .replace("ä", "ae")
+            .replace("ö", "oe")
+            .replace("ü", "ue")
+            .replace("ß", "ss");
+    }
+    if profile.spell_numbers {
+        text = spell_numbers(&text);
+    }
+    text
+}
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+const SCALES: [(u64, &str); 4] = [
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+/// English words for `n`, e.g. `forty-two` or `three thousand one hundred`.
+fn number_words(n: u64) -> String {
+    if let Some(&(scale, name)) = SCALES.iter().find(|&&(scale, _)| n >= scale) {
+        let rest = n % scale;
+        let head = format!("{} {}", number_words(n / scale), name);
+        return match rest {
+            0 => head,
+            _ => format!("{} {}", head, number_words(rest)),
+        };
+    }
+    match n {
+        0..20 => ONES[n as usize].to_string(),
+        20..100 => match n % 10 {
+            0 => TENS[n as usize / 10].to_string(),
+            ones => format!("{}-{}", TENS[n as usize / 10], ONES[ones as usize]),
+        },
+        _ => match n % 100 {
+            0 => format!("{} hundred", ONES[n as usize / 100]),
+            rest => format!("{} hundred {}", ONES[n as usize / 100], number_words(rest)),
+        },
+    }
+}
+
+/// Digits one by one, for fractions and numbers too long to read as a whole.
+fn digit_words(digits: &str) -> String {
+    let words: Vec<&str> = digits
+        .bytes()
+        .map(|digit| ONES[(digit - b'0') as usize])
+        .collect();
+    words.join(" ")
+}
+
+fn spell_numbers(text: &str) -> String {
+    let number_regex = Regex::new(r"([0-9]+)(?:\.([0-9]+))?").unwrap();
+    number_regex
+        .replace_all(text, |captures: &regex::Captures| {
+            let whole = &captures[1];
+            let mut words = match whole.parse::<u64>() {
+                Ok(n) if whole.len() <= 15 => number_words(n),
+                _ => digit_words(whole),
+            };
+            if let Some(fraction) = captures.get(2) {
+                words = format!("{} point {}", words, digit_words(fraction.as_str()));
+            }
+            words
+        })
+        .into_owned()
+}
+
+/// The TTS request URL for one chunk on `root_url`, with the text sanitized following
+/// the voice's catalog [`Profile`].
+pub fn tts_url(
+    root_url: &str,
+    text: &str,
+    speaker: &str,
+    aid: &str,
+) -> Result<Url, url::ParseError> {
+    tts_url_with(root_url, text, speaker, aid, &voices::profile(speaker))
+}
+
+/// [`tts_url`] with the text sanitized following `profile`.
+pub fn tts_url_with(
+    root_url: &str,
+    text: &str,
+    speaker: &str,
+    aid: &str,
+    profile: &Profile,
+) -> Result<Url, url::ParseError> {
+    raw_tts_url(root_url, &sanitize_text_with(text, profile), speaker, aid)
+}
+
+/// [`tts_url`] with `text` sent exactly as given, not sanitized.
+pub fn raw_tts_url(
+    root_url: &str,
+    text: &str,
+    speaker: &str,
+    aid: &str,
+) -> Result<Url, url::ParseError> {
+    let mut url = Url::parse(&format!("{root_url}{API_BASE_URL}"))?;
+    url.query_pairs_mut()
+        .append_pair("text_speaker", speaker)
+        .append_pair("req_text", text)
+        .append_pair("speaker_map_type", "0")
+        .append_pair("aid", aid);
+    Ok(url)
+}
+
+/// Smallest `byte_limit` accepted by [`split_text`]: the longest UTF-8 sequence.
+pub const MIN_BYTE_LIMIT: usize = 4;
+
+/// Where [`split_text_with`] prefers to end chunks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Split {
+    /// Only after sentence ends (and line breaks), for the most natural pauses; longer
+    /// sentences are split as with `Clause`
+    Sentence,
+    /// After any punctuation, merging the pieces up to the limit
+    #[default]
+    Clause,
+    /// Between any words, ignoring punctuation
+    Word,
+    /// Exactly at the limit, even inside words: the fewest requests
+    Fixed,
+}
+
+impl Split {
+    pub const NAMES: [&str; 4] = ["sentence", "clause", "word", "fixed"];
+}
+
+impl std::str::FromStr for Split {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sentence" => Ok(Split::Sentence),
+            "clause" => Ok(Split::Clause),
+            "word" => Ok(Split::Word),
+            "fixed" => Ok(Split::Fixed),
+            _ => Err(format!(
+                "Unknown split strategy '{}' (expected one of: {})",
+                s,
+                Split::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Splits `text` into chunks of at most `byte_limit` bytes for separate TTS requests.
+///
+/// Chunks end after punctuation where possible to keep natural pauses, and short
+/// sentences are merged up to the limit. Segments longer than the limit are split
+/// between words. Single words longer than the limit, such as URLs, are read in short
+/// pieces, broken after separators, at case changes or at syllable-like points.
+///
+/// Guarantees:
+///
+/// - no chunk is longer than `byte_limit` bytes;
+/// - chunks are `String`s, so they never end inside a UTF-8 sequence;
+/// - every chunk contains a non-whitespace character;
+/// - concatenating the chunks gives back `text`, modulo whitespace: runs of
+///   whitespace inside long segments become a single space, and whitespace at chunk
+///   boundaries may be dropped, but all other characters are kept in order, except
+///   invisible directional formatting such as right-to-left marks;
+/// - a chunk never ends inside a number like `1,500`, or between a letter and its
+///   combining marks, unless the number or word alone is over the limit.
+///
+/// # Panics
+///
+/// Panics if `byte_limit` is smaller than [`MIN_BYTE_LIMIT`].
+pub fn split_text(text: &str, byte_limit: usize) -> Vec<String> {
+    split_text_with(text, byte_limit, Split::Clause)
+}
+
+/// [`split_text`] with another [`Split`] strategy, with the same guarantees.
+///
+/// # Panics
+///
+/// Panics if `byte_limit` is smaller than [`MIN_BYTE_LIMIT`].
+pub fn split_text_with(text: &str, byte_limit: usize, split: Split) -> Vec<String> {
+    assert!(
+        byte_limit >= MIN_BYTE_LIMIT,
+        "byte_limit must be at least {} bytes",
+        MIN_BYTE_LIMIT
+    );
+
+    let text = &bidi::strip_controls(text);
+    let mut merged_chunks = Vec::new();
+    let mut current_chunk = String::new();
+
+    let pattern = match split {
+        Split::Fixed => {
+            return cut_at_chars(text, byte_limit)
+                .into_iter()
+                .map(str::to_string)
+                .filter(|chunk| !chunk.trim().is_empty())
+                .collect();
+        }
+        // Also after the Arabic question mark and full stop and the Hebrew sof pasuq
+        Split::Sentence => r".*?[.!?…\n؟۔׃]|.+",
+        // Extended punctuation and symbols for chunk splitting, with Arabic commas too
+        Split::Clause => r".*?[.,!?:;\-—…(){}<>\[\]\n،؛؟۔׃]|.+",
+        Split::Word => r"(?s).+",
+    };
+    let punctuation_regex = Regex::new(pattern).unwrap();
+
+    // Split text based on punctuation and symbols to maintain natural pauses, but
+    // not inside numbers
+    let mut segments: Vec<&str> = Vec::new();
+    let mut start = 0;
+    for m in punctuation_regex.find_iter(text) {
+        if !bidi::splits_number(&text[start..m.end()], &text[m.end()..]) {
+            segments.push(&text[start..m.end()]);
+            start = m.end();
+        }
+    }
+    if start < text.len() {
+        segments.push(&text[start..]);
+    }
+
+    for segment in segments {
+        if segment.len() > byte_limit && split == Split::Sentence {
+            push_chunk(&mut merged_chunks, &mut current_chunk);
+            merged_chunks.extend(split_text_with(segment, byte_limit, Split::Clause));
+        } else if segment.len() > byte_limit {
+            // Split the segment further if it exceeds byte limit
+            for word in segment.split_whitespace() {
+                for piece in split_word(word, byte_limit) {
+                    if current_chunk.len() + piece.len() + 1 > byte_limit {
+                        push_chunk(&mut merged_chunks, &mut current_chunk);
+                        current_chunk.push_str(piece);
+                    } else {
+                        if !current_chunk.is_empty() {
+                            current_chunk.push(' ');
+                        }
+                        current_chunk.push_str(piece);
+                    }
+                }
+            }
+        } else if current_chunk.len() + segment.len() > byte_limit {
+            push_chunk(&mut merged_chunks, &mut current_chunk);
+            current_chunk.push_str(segment);
+        } else {
+            current_chunk.push_str(segment);
+        }
+    }
+    push_chunk(&mut merged_chunks, &mut current_chunk);
+
+    merged_chunks
+}
+
+/// Moves `current` into `chunks`, dropping it if there is nothing to speak.
+fn push_chunk(chunks: &mut Vec<String>, current: &mut String) {
+    let chunk = std::mem::take(current);
+    if !chunk.trim().is_empty() {
+        chunks.push(chunk);
+    }
+}
+
+/// Longest piece, in bytes, an overlong word is broken into, so that it is read out in
+/// groups rather than as one blur.
+const WORD_PIECE: usize = 24;
+
+/// Cuts `text` into pieces of at most `byte_limit` bytes at character boundaries.
+fn cut_at_chars(text: &str, byte_limit: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > byte_limit {
+        let mut end = byte_limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+/// Breaks a word longer than `byte_limit`, such as a URL, a hash or a chemical name,
+/// into pieces of at most [`WORD_PIECE`] bytes, preferably after separators, then at
+/// case and letter/digit changes, then before syllable-like consonant-vowel onsets.
+fn split_word(word: &str, byte_limit: usize) -> Vec<&str> {
+    if word.len() <= byte_limit {
+        return vec![word];
+    }
+    let target = byte_limit.min(WORD_PIECE);
+    let mut pieces = Vec::new();
+    let mut rest = word;
+    while rest.len() > target {
+        let (piece, tail) = rest.split_at(break_point(rest, target));
+        pieces.push(piece);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+/// Where the next piece of `word` ends: the last of the best kind of break in the
+/// second half of its first `target` bytes, or else the last character boundary.
+fn break_point(word: &str, target: usize) -> usize {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut best = (0, 0);
+    let mut last_boundary = chars.get(1).map_or(word.len(), |&(i, _)| i);
+    for k in 1..chars.len() {
+        let (end, next) = chars[k];
+        if end > target {
+            break;
+        }
+        let prev = chars[k - 1].1;
+        // Never between a character and the marks or joiners that belong to it
+        if bidi::is_joining(next) || prev == '\u{200d}' {
+            continue;
+        }
+        last_boundary = end;
+        if end * 2 < target {
+            continue;
+        }
+        let after_next = chars.get(k + 1).map(|&(_, c)| c);
+        let rank = if "-/._:?&=#+~@,;|".contains(prev) {
+            3
+        } else if (prev.is_lowercase() && next.is_uppercase())
+            || (prev.is_alphanumeric()
+                && next.is_alphanumeric()
+                && prev.is_alphabetic() != next.is_alphabetic())
+        {
+            2
+        } else if prev.is_alphabetic()
+            && is_consonant(next)
+            && after_next.is_some_and(is_vowel)
+            && chars[..k].iter().any(|&(_, c)| is_vowel(c))
+        {
+            1
+        } else {
+            0
+        };
+        if rank > 0 && rank >= best.0 {
+            best = (rank, end);
+        }
+    }
+    match best {
+        (0, _) => last_boundary,
+        (_, end) => end,
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    "aeiouyäöüáéíóúàèìòùâêîôûAEIOUYÄÖÜÁÉÍÓÚÀÈÌÒÙÂÊÎÔÛ".contains(c)
+}
+
+fn is_consonant(c: char) -> bool {
+    c.is_alphabetic() && !is_vowel(c)
+}
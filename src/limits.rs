@@ -0,0 +1,161 @@
+//! `--max-chars` and `--max-chunks` guards against synthesizing far more than intended,
+//! such as a large file piped in by mistake.
+
+use crate::term::{self, Style};
+use std::io::{BufRead, Write};
+use std::sync::OnceLock;
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+/// What happens when the input is over a limit.
+#[derive(clap::ValueEnum, serde::Deserialize, serde::Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OnLimit {
+    /// Stop before making any request
+    #[default]
+    Fail,
+    /// Keep the input up to the limit, cut at a sentence end
+    Truncate,
+    /// Ask on the terminal whether to go on with all of it
+    Prompt,
+}
+
+#[derive(Default)]
+struct Limits {
+    max_chars: Option<usize>,
+    max_chunks: Option<usize>,
+    on_limit: OnLimit,
+}
+
+pub fn init(max_chars: Option<usize>, max_chunks: Option<usize>, on_limit: OnLimit) {
+    let _ = LIMITS.set(Limits {
+        max_chars,
+        max_chunks,
+        on_limit,
+    });
+}
+
+/// How much of the limits one job has used; input read in blocks shares one budget.
+#[derive(Default)]
+pub struct Budget {
+    chars: usize,
+    chunks: usize,
+    /// The user agreed to go over the limits
+    approved: bool,
+    /// Input was truncated, so any further input is dropped
+    exhausted: bool,
+}
+
+/// The longest prefix of `text` within `max_chars` characters that ends a sentence, or
+/// failing that a word.
+fn truncate(text: &str, max_chars: usize) -> &str {
+    let end = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(index, _)| index);
+    let head = &text[..end];
+    if end == text.len() {
+        return head;
+    }
+    let sentence = head
+        .char_indices()
+        .filter(|&(_, c)| matches!(c, '.' | '!' | '?' | '…' | '\n'))
+        .map(|(index, c)| index + c.len_utf8())
+        .next_back();
+    match sentence.or_else(|| head.rfind(char::is_whitespace)) {
+        Some(cut) => &head[..cut],
+        None => head,
+    }
+}
+
+/// Asks on the terminal, since stdin may be the input itself.
+fn confirm(question: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|_| "--on-limit prompt needs a terminal to ask on")?;
+    let mut writer = &tty;
+    write!(writer, "{} [y/N] ", question)?;
+    writer.flush()?;
+    let mut answer = String::new();
+    std::io::BufReader::new(&tty).read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+impl Budget {
+    pub fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Handles going over `limit`; returns whether to truncate to it.
+    fn over(
+        &mut self,
+        what: &str,
+        limit: usize,
+        on_limit: OnLimit,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        match on_limit {
+            OnLimit::Fail => Err(format!(
+                "Input is over {} {} (--max-{}); raise the limit, or pass --on-limit truncate",
+                limit, what, what
+            )
+            .into()),
+            OnLimit::Truncate => {
+                let message = format!("input to {} {} (--max-{})", limit, what, what);
+                term::status(Style::Yellow, "Truncating", message);
+                self.exhausted = true;
+                Ok(true)
+            }
+            OnLimit::Prompt => {
+                let question = format!("Input is over {} {}. Synthesize all of it?", limit, what);
+                if !confirm(&question)? {
+                    return Err("Aborted".into());
+                }
+                self.approved = true;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Accounts for `text` before it is split, returning the part that may be synthesized.
+    pub fn text(
+        &mut self,
+        text: String,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let limits = LIMITS.get_or_init(Limits::default);
+        if self.exhausted {
+            return Ok(String::new());
+        }
+        let chars = text.chars().count();
+        self.chars += chars;
+        match limits.max_chars {
+            Some(max) if self.chars > max && !self.approved => {
+                let remaining = max.saturating_sub(self.chars - chars);
+                if self.over("chars", max, limits.on_limit)? {
+                    return Ok(truncate(&text, remaining).to_string());
+                }
+                Ok(text)
+            }
+            _ => Ok(text),
+        }
+    }
+
+    /// Accounts for the chunks of one text, returning those that may be synthesized.
+    pub fn chunks(
+        &mut self,
+        mut chunks: Vec<String>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let limits = LIMITS.get_or_init(Limits::default);
+        let count = chunks.len();
+        self.chunks += count;
+        if let Some(max) = limits.max_chunks
+            && self.chunks > max
+            && !self.approved
+            && self.over("chunks", max, limits.on_limit)?
+        {
+            chunks.truncate(max.saturating_sub(self.chunks - count));
+        }
+        Ok(chunks)
+    }
+}
@@ -1,289 +1,1488 @@
 use base64::{Engine as _, engine::general_purpose};
-use clap::Parser;
-use regex::Regex;
-use reqwest;
+use clap::builder::FalseyValueParser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::collections::HashMap;
 use std::env;
-use std::io::{self, Read};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+use tktts::schema::{Decoded, Reason};
+use tktts::{API_BASE_URL, TktsError, USER_AGENT, mp3, pcm, voices};
 use tokio::task::JoinSet;
-use url::Url;
+use tracing::Instrument;
+
+/// Whether pipeline progress is written to stderr. Front-ends that own the
+/// terminal, like the TUI, switch it off.
+static PROGRESS: AtomicBool = AtomicBool::new(true);
+
+/// The `--split` strategy, for every front-end.
+static SPLIT: std::sync::OnceLock<tktts::Split> = std::sync::OnceLock::new();
+
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if crate::PROGRESS.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!("{}", crate::term::redact(format_args!($($arg)*)));
+        }
+    };
+}
+
+mod anki;
+mod append;
+mod auth;
+mod batch;
+mod bench;
+mod cache;
+mod cast;
+mod catalog;
+mod censor;
+mod chapters;
+mod checksum;
+mod completions;
+mod config;
+mod console;
+mod dialogue;
+#[cfg(feature = "discord")]
+mod discord;
+mod doctor;
+mod espeak;
+mod estimate;
+mod exec;
+mod feed;
+#[cfg(unix)]
+mod fifo;
+mod filter;
+mod flood;
+#[cfg(feature = "gui")]
+mod gui;
+mod hooks;
+mod http;
+mod lang;
+mod limits;
+mod man;
+mod markdown;
+mod matrix;
+mod mpd;
+mod mqtt;
+mod notify;
+mod otel;
+mod output;
+mod peaks;
+mod pipeline;
+mod plan;
+mod playback;
+mod podcast;
+mod preset;
+mod queue;
+mod quota;
+mod report;
+mod say;
+mod script;
+mod self_update;
+mod server;
+mod sessions;
+mod sfx;
+mod sidecar;
+mod signing;
+mod sniff;
+mod speechd;
+mod srt;
+mod stt;
+mod tags;
+mod tape;
+mod term;
+mod translate;
+#[cfg(feature = "tui")]
+mod tui;
+mod twitch;
+mod vars;
 
 #[derive(Parser)]
-#[command(name = "tktts")]
+#[command(name = "tktts", version)]
 #[command(about = "Generate TikTok TTS URLs for audio playback")]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(group = clap::ArgGroup::new("file_output").args(["output", "append", "files", "split_output", "chapters_by"]).multiple(true))]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Text to convert to speech
     text: Vec<String>,
 
-    /// TikTok speaker voice (default: en_us_002)
+    /// TikTok speaker voice (default: en_us_002); `random` or `random:<lang>` picks one
     #[arg(short, long, default_value = "en_us_002")]
     speaker: String,
 
+    /// Use --speaker even when it is not in the voice catalog, e.g. for newer voices
+    #[arg(long)]
+    force_speaker: bool,
+
+    /// Read text in another language than the voice's with a voice for that language
+    #[arg(long)]
+    auto_voice: bool,
+
+    /// Detect the language of every sentence and read each with a voice for it, from
+    /// these LANG=VOICE entries (`de=de_002,fr=fr_001`), else `[language_voices]`
+    #[arg(
+        long,
+        value_name = "LANG=VOICE",
+        num_args = 0..,
+        require_equals = true,
+        value_delimiter = ',',
+        value_parser = lang::parse_language_voice
+    )]
+    multilingual: Option<Vec<(String, String)>>,
+
+    /// Translate the text into these languages first, writing one output per language
+    /// (`out.mp3` becomes `out.de.mp3`, ...) when there are several
+    #[arg(long, value_name = "LANG", value_delimiter = ',', conflicts_with_all = ["files", "split_output"])]
+    translate_to: Vec<String>,
+
+    /// Seed for `--speaker random`, to pick the same voices again
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+
     /// Output the audio data URL instead of making HTTP request
     #[arg(short, long)]
     url_only: bool,
+
+    /// Stay running and speak each line written to this named pipe, creating it if needed
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["text", "output", "url_only", "translate_to", "estimate", "files", "split_output", "chapters_by"])]
+    listen_fifo: Option<PathBuf>,
+
+    /// Write the audio to a file, an HTTP(S) URL (PUT) or s3://bucket/key instead of stdout
+    ///
+    /// S3 credentials come from the AWS chain: AWS_* variables, ~/.aws/credentials, web
+    /// identity, container or instance roles. SSO profiles are not read.
+    #[arg(short, long, value_name = "TARGET", conflicts_with_all = ["play", "cast", "to_mpd"])]
+    output: Option<String>,
+
+    /// Add the audio to the end of this MP3 or WAV file, creating it if needed
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["output", "url_only", "play", "cast", "to_mpd", "sidecar"])]
+    append: Option<PathBuf>,
+
+    /// Also write <file>.json with the text, voice, duration and chunk hashes of each output
+    #[arg(long, requires = "file_output")]
+    sidecar: bool,
+
+    /// Also write <file>.sha256 for each output, to check with `tktts verify` later
+    #[arg(long, requires = "file_output")]
+    checksum: bool,
+
+    /// Endpoint preset bundling API hosts, aid and User-Agent (us, eu, musically, or from config)
+    #[arg(long, global = true, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Send TTS parameters in the query string or a form body (default: from the preset)
+    #[arg(long, global = true, value_enum, value_name = "STYLE")]
+    request_style: Option<preset::RequestStyle>,
+
+    /// Record all API exchanges to a tape file (session cookie redacted); disables the cache
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer API requests from a tape written by --record, without network access
+    #[arg(long, global = true, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Where chunks end: sentence (natural pauses), clause, word, or fixed (fewest requests)
+    #[arg(
+        long,
+        global = true,
+        value_name = "STRATEGY",
+        default_value = "clause",
+        value_parser = clap::builder::TypedValueParser::map(
+            clap::builder::PossibleValuesParser::new(tktts::Split::NAMES),
+            |name| name.parse::<tktts::Split>().unwrap()
+        )
+    )]
+    split: tktts::Split,
+
+    /// Read the input as Markdown, with emphasis cues from the `[markdown]` config section
+    #[arg(long)]
+    markdown: bool,
+
+    /// Splice the sound files of `[sfx:ding.wav]` tags into the audio, found in DIR
+    #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = ".")]
+    sfx: Option<PathBuf>,
+
+    /// Requests in flight at once when streaming to stdout or a file
+    #[arg(short, long, default_value_t = 8, value_name = "N")]
+    jobs: usize,
+
+    /// Neither read nor write the chunk audio cache
+    #[arg(long)]
+    no_cache: bool,
+
+    #[command(flatten)]
+    peaks: peaks::PeaksArgs,
+
+    /// Write a report of the run's chunks, tries, durations and waveforms (.html or .md)
+    #[arg(long, value_name = "FILE", conflicts_with = "url_only")]
+    report: Option<PathBuf>,
+
+    /// Print the request count and estimated audio length before synthesizing
+    #[arg(long)]
+    estimate: bool,
+
+    /// Transcribe each fetched chunk with this whisper.cpp model and flag mismatches
+    #[arg(long, value_name = "MODEL")]
+    verify: Option<PathBuf>,
+
+    /// Lowest transcript similarity (0 to 1) that passes --verify
+    #[arg(long, value_name = "RATIO", default_value_t = 0.6, requires = "verify")]
+    verify_threshold: f64,
+
+    /// whisper.cpp command line tool used by --verify
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        default_value = "whisper-cli",
+        requires = "verify"
+    )]
+    whisper_command: String,
+
+    /// Refuse jobs needing more requests than this (cached chunks are free)
+    #[arg(long, value_name = "N")]
+    max_requests: Option<usize>,
+
+    /// Refuse jobs estimated to give more audio than this, e.g. 90s or 15m
+    #[arg(long, value_name = "DURATION", value_parser = estimate::parse_duration)]
+    max_duration: Option<std::time::Duration>,
+
+    /// Refuse to send more than this many requests per session and day
+    #[arg(long, global = true, value_name = "N")]
+    daily_limit: Option<u64>,
+
+    /// Fill `{{name}}` placeholders in the text with this value; repeatable
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = vars::parse)]
+    vars: Vec<(String, String)>,
+
+    /// Refuse (or see --on-limit) input longer than this many characters
+    #[arg(long, value_name = "N")]
+    max_chars: Option<usize>,
+
+    /// Refuse (or see --on-limit) input needing more than this many requests
+    #[arg(long, value_name = "N")]
+    max_chunks: Option<usize>,
+
+    /// What to do with input over --max-chars or --max-chunks
+    #[arg(long, value_enum, value_name = "POLICY")]
+    on_limit: Option<limits::OnLimit>,
+
+    /// Censor the words in this list file, or the built-in list with `default`; repeatable
+    #[arg(long, global = true, value_name = "FILE")]
+    censor: Vec<String>,
+
+    /// How censored words are treated
+    #[arg(long, global = true, value_enum, default_value_t, value_name = "MODE")]
+    censor_mode: censor::CensorMode,
+
+    /// Plain stderr output without colors (also when NO_COLOR is set)
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Only print errors; give it twice to print nothing at all
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    quiet: u8,
+
+    /// Hide session IDs, cookies and request URLs with text in messages (off for debugging)
+    #[arg(long, global = true, value_enum, value_name = "MODE", default_value_t)]
+    log_redaction: term::Redaction,
+
+    #[command(flatten)]
+    notify: notify::NotifyArgs,
+
+    #[command(flatten)]
+    playback: playback::PlaybackArgs,
+
+    #[command(flatten)]
+    batch: batch::BatchArgs,
+
+    #[command(flatten)]
+    http: http::ClientArgs,
+
+    #[command(flatten)]
+    otel: otel::OtelArgs,
 }
 
-const API_BASE_URL: &str = "/media/api/text/speech/invoke/";
-const USER_AGENT: &str = "com.zhiliaoapp.musically/2022600030 (Linux; U; Android 7.1.2; es_ES; SM-G988N; Build/NRD90M;tt-ok/3.12.13.1)";
-const BYTE_LIMIT: usize = 300;
+#[derive(Subcommand)]
+enum Command {
+    /// Synthesize the cards of a CSV file into Anki media files and reference them
+    Anki(anki::AnkiArgs),
+
+    /// Check session IDs and manage device identifiers sent with TTS requests
+    Auth {
+        #[command(subcommand)]
+        command: auth::AuthCommand,
+    },
+
+    /// Measure throughput and latency percentiles across concurrency levels
+    Bench(bench::BenchArgs),
+
+    /// Inspect and manage the chunk audio cache
+    Cache {
+        #[command(subcommand)]
+        command: cache::CacheCommand,
+    },
+
+    /// Print a shell completion script, e.g. `tktts completions bash > /etc/bash_completion.d/tktts`
+    Completions(completions::CompletionsArgs),
+
+    /// Show, query and change the configuration file
+    Config {
+        #[command(subcommand)]
+        command: config::ConfigCommand,
+    },
+
+    /// Read a `Name: line` script with one voice per character, in mono, stereo or multitrack
+    Dialogue(dialogue::DialogueArgs),
+
+    /// Run a Discord bot that speaks messages and `/tts` commands
+    #[cfg(feature = "discord")]
+    Discord(discord::DiscordArgs),
+
+    /// Check the setup and which response schema each API host speaks
+    Doctor(doctor::DoctorArgs),
+
+    /// Speak text taking espeak's flags, also run under the name espeak or espeak-ng
+    Espeak(espeak::EspeakArgs),
+
+    /// Run a command and speak each line of its output, or how it ended with --summary
+    Exec(exec::ExecArgs),
+
+    /// Convert each new entry of an RSS or Atom feed into an audio file
+    Feed(feed::FeedArgs),
+
+    /// Desktop window with a text box, voice picker, speed and format controls
+    #[cfg(feature = "gui")]
+    Gui(gui::GuiArgs),
+
+    /// Assemble audio for a plan purely from cached chunks, without network access
+    Join(plan::JoinArgs),
+
+    /// Print the man page, or write one per subcommand with --out-dir
+    Man(man::ManArgs),
+
+    /// Run a Matrix bot that answers `!tts <text>` with audio messages
+    Matrix(matrix::MatrixArgs),
+
+    /// Speak every message published to the given MQTT topics
+    Mqtt(mqtt::MqttArgs),
+
+    /// Write the chunk plan for a text as JSON, for a later `tktts join`
+    Plan(plan::PlanArgs),
+
+    /// Show how many requests the current session made today and on earlier days
+    Quota(quota::QuotaArgs),
+
+    /// Speak like the macOS `say` command, taking its -v, -o, -f and -r flags
+    Say(say::SayArgs),
+
+    /// Replace this binary with the latest GitHub release, after checking its checksum
+    SelfUpdate(self_update::SelfUpdateArgs),
+
+    /// Run an HTTP server with a Home Assistant compatible TTS endpoint
+    Serve(server::ServeArgs),
+
+    /// Run as a speech-dispatcher output module, for screen readers like Orca
+    Speechd(speechd::SpeechdArgs),
+
+    /// Voice an SRT subtitle file into a WAV track aligned to the cue timings
+    Srt(srt::SrtArgs),
+
+    /// Interactive terminal UI for trying out voices
+    #[cfg(feature = "tui")]
+    Tui(tui::TuiArgs),
+
+    /// Read a Twitch channel's chat aloud
+    Twitch(twitch::TwitchArgs),
 
-fn sanitize_text(text: &str) -> String {
-    text.replace("+", "plus")
-        .replace("&", "and")
-        .replace("ä", "ae")
-        .replace("ö", "oe")
-        .replace("ü", "ue")
-        .replace("ß", "ss")
+    /// Check audio files against their .sha256 or sidecar checksums
+    Verify(checksum::VerifyArgs),
+
+    /// List, search and preview the voice catalog
+    Voices(catalog::VoicesArgs),
 }
 
-fn split_text(text: &str, byte_limit: usize) -> Vec<String> {
-    let mut merged_chunks = Vec::new();
-    let mut current_chunk = String::new();
-    let mut current_byte_length = 0;
+const BYTE_LIMIT: usize = 300;
 
-    // Extended punctuation and symbols for chunk splitting
-    let punctuation_regex = Regex::new(r".*?[.,!?:;\-—…(){}<>\[\]\n]|.+").unwrap();
+/// The command line, with every option also read from a `TKTTS_*` environment variable.
+///
+/// Top-level options map to `TKTTS_<OPTION>` (`--speaker` is `TKTTS_SPEAKER`), subcommand
+/// options to `TKTTS_<SUBCOMMAND>_<OPTION>` (`serve --port` is `TKTTS_SERVE_PORT`).
+/// Arguments given on the command line win over the environment.
+fn cli() -> clap::Command {
+    with_env(Args::command(), "TKTTS")
+}
 
-    // Split text based on punctuation and symbols to maintain natural pauses
-    let separated_chunks: Vec<&str> = punctuation_regex
-        .find_iter(text)
-        .map(|m| m.as_str())
+fn with_env(command: clap::Command, prefix: &str) -> clap::Command {
+    let env_name = |name: &str| format!("{}_{}", prefix, name.to_uppercase().replace('-', "_"));
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_string())
         .collect();
+    let mut command = command.mut_args(|arg| {
+        if arg.is_positional() || arg.get_env().is_some() {
+            return arg;
+        }
+        let name = env_name(arg.get_id().as_str());
+        match arg.get_action() {
+            // Lets flags be switched with the usual 1/0, yes/no, on/off
+            clap::ArgAction::SetTrue => arg.env(name).value_parser(FalseyValueParser::new()),
+            _ => arg.env(name),
+        }
+    });
+    for name in subcommands {
+        let prefix = env_name(&name);
+        command = command.mut_subcommand(&name, |subcommand| with_env(subcommand, &prefix));
+    }
+    command
+}
 
-    for chunk in separated_chunks {
-        let chunk_byte_length = chunk.len();
-
-        if chunk_byte_length > byte_limit {
-            // Split the chunk further if it exceeds byte limit
-            let words: Vec<&str> = chunk.split_whitespace().collect();
-            for word in words {
-                let word_byte_length = word.len();
-                if current_byte_length + word_byte_length + 1 > byte_limit {
-                    if !current_chunk.is_empty() {
-                        merged_chunks.push(current_chunk.clone());
-                        eprintln!(
-                            "Chunk created: {} (Bytes: {})",
-                            current_chunk, current_byte_length
-                        );
-                    }
-                    current_chunk = word.to_string();
-                    current_byte_length = word_byte_length;
-                } else {
-                    if !current_chunk.is_empty() {
-                        current_chunk.push(' ');
-                        current_chunk.push_str(word);
-                        current_byte_length += word_byte_length + 1; // +1 for space
-                    } else {
-                        current_chunk = word.to_string();
-                        current_byte_length = word_byte_length;
+/// [`tktts::split_text_with`] the `--split` strategy after the configured text filters, script hooks and censoring,
+/// listing the chunks on stderr.
+fn split_text(
+    text: &str,
+    byte_limit: usize,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    split_text_within(text, byte_limit, &mut limits::Budget::default())
+}
+
+/// [`split_text`] for one part of a larger input, counted against its `budget`.
+fn split_text_within(
+    text: &str,
+    byte_limit: usize,
+    budget: &mut limits::Budget,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let text = censor::apply(&script::on_text(filter::apply(&markdown::speakable(
+        text,
+    ))?)?);
+    let text = budget.text(text)?;
+    let mut chunks = Vec::new();
+    let split = SPLIT.get().copied().unwrap_or_default();
+    let mut index = 0;
+    for (voiced, span) in markdown::spans(&text) {
+        // Chunks of a voice span have room for its mark
+        let limit = byte_limit - usize::from(voiced);
+        for part in sfx::parts(span) {
+            let part = match part {
+                sfx::Part::Effect(tag) => {
+                    chunks.push(tag.to_string());
+                    continue;
+                }
+                sfx::Part::Text(part) => part,
+            };
+            // With --multilingual, no chunk holds sentences of two languages
+            for (language, run) in lang::runs(part) {
+                let first = chunks.len();
+                for chunk in tktts::split_text_with(run, limit, split) {
+                    let chunk = script::on_chunk(chunk, index)?;
+                    index += 1;
+                    for chunk in censor::split_tones(&chunk) {
+                        if chunk.len() > limit {
+                            chunks.extend(
+                                tktts::split_text_with(&chunk, limit, split)
+                                    .into_iter()
+                                    .map(|chunk| markdown::mark(span, chunk)),
+                            );
+                        } else if !chunk.trim().is_empty() {
+                            chunks.push(markdown::mark(span, chunk));
+                        }
                     }
                 }
+                lang::record(&chunks[first..], language);
             }
-        } else {
-            if current_byte_length + chunk_byte_length > byte_limit {
-                if !current_chunk.is_empty() {
-                    merged_chunks.push(current_chunk.clone());
-                    eprintln!(
-                        "Chunk created: {} (Bytes: {})",
-                        current_chunk, current_byte_length
-                    );
+        }
+    }
+    let chunks = budget.chunks(chunks)?;
+    term::chunk_table(&chunks);
+    Ok(chunks)
+}
+
+async fn request_tts_chunk(
+    text: &str,
+    speaker: &str,
+    session_ids: &str,
+    root_url: &str,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    // With a pool of sessions, one the API rejects hands the request to the next best
+    let sessions = sessions::ranked(session_ids);
+    let mut last_error = None;
+    for session_id in &sessions {
+        let request = sessions::start(session_id);
+        match request_tts_chunk_with(text, speaker, session_id, root_url).await {
+            Ok(audio) => {
+                request.finish(true);
+                return Ok(audio);
+            }
+            Err(ChunkError::Session(e)) => {
+                request.finish(false);
+                if sessions.len() > 1 {
+                    term::status(term::Style::Yellow, "Failed", format!("session: {}", e));
                 }
-                current_chunk = chunk.to_string();
-                current_byte_length = chunk_byte_length;
-            } else {
-                current_chunk.push_str(chunk);
-                current_byte_length += chunk_byte_length;
+                last_error = Some(e);
             }
+            Err(ChunkError::Host(e)) => return Err(e),
         }
     }
+    Err(last_error.unwrap_or_else(|| "TIKTOK_SESSIONID lists no session ID".into()))
+}
 
-    if !current_chunk.is_empty() {
-        merged_chunks.push(current_chunk.clone());
-        eprintln!(
-            "Chunk created: {} (Bytes: {})",
-            current_chunk, current_byte_length
-        );
+/// Requests a chunk with one session. `root_url` may list several hosts; later ones are
+/// only tried when a host fails.
+async fn request_tts_chunk_with(
+    text: &str,
+    speaker: &str,
+    session_id: &str,
+    root_url: &str,
+) -> Result<Vec<u8>, ChunkError> {
+    let mut last_error = None;
+    for root_url in root_url.split(',').map(str::trim) {
+        match request_tts_chunk_from(text, speaker, session_id, root_url).await {
+            Ok(audio) => return Ok(audio),
+            Err(ChunkError::Session(e)) => return Err(ChunkError::Session(e)),
+            Err(ChunkError::Host(e)) => {
+                term::status(
+                    term::Style::Yellow,
+                    "Failed",
+                    format!("{}: {}", root_url, e),
+                );
+                last_error = Some(e);
+            }
+        }
     }
+    Err(ChunkError::Host(
+        last_error.unwrap_or_else(|| "No API base URL configured".into()),
+    ))
+}
 
-    merged_chunks
+/// The error of a chunk that could not be synthesized, numbered from 1.
+#[derive(Debug, thiserror::Error)]
+#[error("Chunk {number} failed: {source}")]
+struct ChunkFailed {
+    number: usize,
+    source: Box<dyn std::error::Error + Send + Sync>,
 }
 
-async fn request_tts_chunk(
+/// The exit code for `error`, by the first [`TktsError`] among it and its sources:
+/// 3 for `Auth`, 4 `Upstream`, 5 `Network` or `Transport`, 6 `Decode`, 7 `Split`, 8 `Io`
+/// and 9 `Config`; 1 for anything else.
+fn exit_code(error: &(dyn std::error::Error + 'static)) -> i32 {
+    let mut next = Some(error);
+    while let Some(error) = next {
+        if let Some(error) = error.downcast_ref::<TktsError>() {
+            return match error {
+                TktsError::Auth(_) => 3,
+                TktsError::Upstream { .. } => 4,
+                TktsError::Network(_) | TktsError::Transport(_) => 5,
+                TktsError::Decode(_) => 6,
+                TktsError::Split(_) => 7,
+                TktsError::Io(_) => 8,
+                TktsError::Config(_) => 9,
+                _ => 1,
+            };
+        }
+        next = error.source();
+    }
+    1
+}
+
+/// A failure of the setup, such as a broken config file or an unknown voice, as a
+/// [`TktsError::Config`] unless it already is a [`TktsError`] of its own, such as `Io`.
+fn as_config_error(
+    error: Box<dyn std::error::Error + Send + Sync>,
+) -> Box<dyn std::error::Error + Send + Sync> {
+    if exit_code(error.as_ref()) == 1 {
+        Box::new(TktsError::Config(error.to_string()))
+    } else {
+        error
+    }
+}
+
+/// Prints `error` and exits with its [`exit_code`].
+fn exit_with(error: Box<dyn std::error::Error + Send + Sync>) -> ! {
+    let code = exit_code(error.as_ref());
+    term::error(error);
+    process::exit(code);
+}
+
+/// Failures of a single host, split by whether another host (or session) could do better.
+enum ChunkError {
+    Host(Box<dyn std::error::Error + Send + Sync>),
+    Session(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl<E: Into<Box<dyn std::error::Error + Send + Sync>>> From<E> for ChunkError {
+    fn from(e: E) -> Self {
+        ChunkError::Host(e.into())
+    }
+}
+
+#[tracing::instrument(name = "request", skip_all, fields(host = root_url, status))]
+async fn request_tts_chunk_from(
     text: &str,
     speaker: &str,
     session_id: &str,
     root_url: &str,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let sanitized_text = sanitize_text(text);
-
-    let mut url = Url::parse(&format!("{root_url}{API_BASE_URL}"))?;
-    url.query_pairs_mut()
-        .append_pair("text_speaker", speaker)
-        .append_pair("req_text", &sanitized_text)
-        .append_pair("speaker_map_type", "0")
-        .append_pair("aid", "1233");
-
-    let client = reqwest::Client::new();
-    let response = client
-        .post(url)
-        .header("User-Agent", USER_AGENT)
-        .header("Cookie", format!("sessionid={}", session_id))
-        .send()
-        .await?;
+) -> Result<Vec<u8>, ChunkError> {
+    let response = request_tts_body(text, speaker, session_id, root_url).await?;
 
-    let json: serde_json::Value = response.json().await?;
-
-    dbg!(&json);
+    let status = response.status;
+    // The audio is decoded as the body comes in; only other answers are parsed whole
+    let body = match response.body.decode_audio(Vec::new()).await? {
+        Decoded::Audio(audio) => return Ok(audio),
+        Decoded::Other(body) => body,
+    };
+    let parsed = tktts::schema::parse(&body).map_err(|e| TktsError::Upstream {
+        status,
+        msg: format!(
+            "{}; the API may have changed, `tktts doctor --schema` shows what it answers",
+            e
+        ),
+    })?;
+    match parsed.outcome {
+        tktts::schema::Outcome::Audio(v_str) => Ok(general_purpose::STANDARD
+            .decode(v_str)
+            .map_err(|e| TktsError::Decode(e.into()))?),
+        tktts::schema::Outcome::Rejected { message, reason } => {
+            Err(rejected(reason, message, status, speaker, root_url))
+        }
+    }
+}
 
-    if let Some(message) = json.get("message") {
-        if message == "Couldn't load speech. Try again." {
-            return Err("Invalid TikTok Session ID or API error.".into());
+/// The error for a request the API declined, saying what to do about it.
+fn rejected(
+    reason: Reason,
+    message: String,
+    status: u16,
+    speaker: &str,
+    root_url: &str,
+) -> ChunkError {
+    let advice = match reason {
+        Reason::MissingSession => format!(
+            "none reached {} ({}). Set TIKTOK_SESSIONID to the sessionid cookie of a \
+             logged-in tiktok.com session",
+            root_url, message
+        ),
+        Reason::Session => format!(
+            "it is invalid or expired, or from another region than {} ({}). Log in to \
+             tiktok.com again for a fresh sessionid cookie and check it with `tktts auth \
+             check`; if the browser session works, try the hosts of another preset, e.g. \
+             `--preset eu`",
+            root_url, message
+        ),
+        Reason::Region => format!(
+            "it is from another region than {} ({}). Try the hosts of another preset, \
+             e.g. `--preset eu` or `--preset us`",
+            root_url, message
+        ),
+        Reason::Voice => {
+            return ChunkError::Host(
+                TktsError::Upstream {
+                    status,
+                    msg: format!(
+                        "voice {} is not available on {} ({}). `tktts voices` lists the \
+                         voices; other hosts may offer it, e.g. those of `--preset eu`",
+                        speaker, root_url, message
+                    ),
+                }
+                .into(),
+            );
+        }
+        Reason::TextTooLong | Reason::Other => {
+            return ChunkError::Host(
+                TktsError::Upstream {
+                    status,
+                    msg: message,
+                }
+                .into(),
+            );
         }
+    };
+    let error = TktsError::Auth(advice).into();
+    // A region mismatch is the host's, so the next host may take the same session
+    match reason {
+        Reason::Region => ChunkError::Host(error),
+        _ => ChunkError::Session(error),
     }
+}
 
-    // if we have "status_msg" output that
+/// Sends the request for a chunk with one session and host, and returns the response
+/// as it came (or as recorded on a `--replay` tape).
+async fn request_tts_body(
+    text: &str,
+    speaker: &str,
+    session_id: &str,
+    root_url: &str,
+) -> Result<tktts::transport::Response, ChunkError> {
+    let preset = preset::active();
+    let text = markdown::unmarked(text);
+    let sanitized_text = sanitize_text(text, speaker);
 
-    let v_str = json["data"]["v_str"]
-        .as_str()
-        .ok_or("Missing v_str in response")?;
+    let profile = config::profile(speaker);
+    let mut url = tktts::tts_url_with(root_url, text, speaker, &preset.aid, &profile)?;
+    auth::append_params(&mut url.query_pairs_mut());
 
-    Ok(v_str.to_string())
-}
+    // Form-style mirrors want the same parameters in the body instead
+    let body = match preset::request_style() {
+        preset::RequestStyle::Query => String::new(),
+        preset::RequestStyle::Form => {
+            let body = url.query().unwrap_or_default().to_string();
+            url.set_query(None);
+            body
+        }
+    };
 
-fn generate_tts_url(text: &str, speaker: &str) -> String {
-    let sanitized_text = sanitize_text(text);
-    let mut url = Url::parse(API_BASE_URL).unwrap();
-    url.query_pairs_mut()
-        .append_pair("text_speaker", speaker)
-        .append_pair("req_text", &sanitized_text)
-        .append_pair("speaker_map_type", "0")
-        .append_pair("aid", "1233");
+    let response = match tape::replay(speaker, &sanitized_text) {
+        Some(replayed) => {
+            let (status, body) = replayed.map_err(ChunkError::Session)?;
+            tktts::transport::Response {
+                status,
+                body: body.into(),
+            }
+        }
+        None => {
+            quota::count(session_id)
+                .await
+                .map_err(ChunkError::Session)?;
+            let mut headers = vec![
+                ("User-Agent".to_string(), preset.user_agent.clone()),
+                ("Cookie".to_string(), format!("sessionid={}", session_id)),
+            ];
+            if !body.is_empty() {
+                headers.push((
+                    "Content-Type".to_string(),
+                    "application/x-www-form-urlencoded".to_string(),
+                ));
+            }
+            let request = tktts::transport::Request {
+                url: url.clone(),
+                headers,
+                body: body.clone(),
+            };
+            let response = http::transport().send(request).await.map_err(|e| {
+                // A signer that fails would fail on every host
+                match &e {
+                    TktsError::Transport(source) if source.is::<tktts::layers::SignError>() => {
+                        ChunkError::Session(e.into())
+                    }
+                    _ => ChunkError::Host(e.into()),
+                }
+            })?;
+            tracing::Span::current().record("status", response.status);
+            if !tape::recording() {
+                return Ok(response);
+            }
+            // A tape holds the whole body, so it is read in before it is decoded
+            let response_body = response
+                .body
+                .text()
+                .await
+                .map_err(|e| ChunkError::Host(e.into()))?;
+            tape::record(
+                speaker,
+                &sanitized_text,
+                &url,
+                &preset.user_agent,
+                &body,
+                (response.status, &response_body),
+            );
+            tktts::transport::Response {
+                status: response.status,
+                body: response_body.into(),
+            }
+        }
+    };
+    Ok(response)
+}
 
-    url.to_string()
+/// The text as sent to the API for `speaker`, following its text profile.
+fn sanitize_text(text: &str, speaker: &str) -> String {
+    tktts::sanitize_text_with(markdown::unmarked(text), &config::profile(speaker))
 }
 
-async fn process_tts(
+fn generate_tts_url(
     text: &str,
     speaker: &str,
-    url_only: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    if url_only {
-        // Just output the URL for the first chunk
-        let chunks = split_text(text, BYTE_LIMIT);
-        if let Some(first_chunk) = chunks.first() {
-            println!("{}", generate_tts_url(first_chunk, speaker));
-        }
-        return Ok(());
+    root_url: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let root_url = root_url.split(',').next().unwrap_or_default().trim();
+    let speaker = config::resolve_voice(speaker);
+    let profile = config::profile(speaker);
+    let url = tktts::tts_url_with(root_url, text, speaker, &preset::active().aid, &profile)?;
+
+    Ok(url.to_string())
+}
+
+/// The API host(s): those of the selected preset, else `TIKTOK_API_BASEURL`, which may
+/// list several comma-separated hosts.
+fn api_root_url() -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    dotenv::dotenv().ok();
+    match preset::selected() {
+        Some(preset) => Ok(preset.base_urls.join(",")),
+        None => Ok(env::var("TIKTOK_API_BASEURL")
+            .map_err(|_| "TIKTOK_API_BASEURL is not set; set it or pick a --preset")?),
     }
+}
 
-    // Load session ID from environment
+/// Reads the TikTok session ID and API base URL from the environment (or `.env`).
+fn load_credentials() -> Result<(String, String), Box<dyn std::error::Error + Send + Sync>> {
+    if tape::replaying() {
+        return Ok(("replay".to_string(), "http://replay.invalid".to_string()));
+    }
     dotenv::dotenv().ok();
     let session_id = env::var("TIKTOK_SESSIONID")
         .map_err(|_| "TIKTOK_SESSIONID environment variable not set. Please set it in .env file or export it.")?;
+    for session_id in sessions::pool(&session_id) {
+        term::secret(session_id);
+    }
+
+    Ok((session_id, api_root_url()?))
+}
+
+/// Requests and decodes the audio of one chunk, storing it in the cache.
+async fn fetch_chunk(
+    text: &str,
+    speaker: &str,
+    session_id: &str,
+    api_root_url: &str,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(audio) = censor::tone(text) {
+        return Ok(audio);
+    }
+    if let Some(audio) = sfx::audio(text)? {
+        return Ok(audio);
+    }
+    let audio = request_tts_chunk(text, speaker, session_id, api_root_url).await?;
+    let span = tracing::info_span!("decode", audio_bytes = audio.len());
+    let audio = pcm::offload(move || span.in_scope(|| sniff::to_mp3(audio))).await?;
+    // Audio that fails verification is still used, but requested again next time
+    if stt::check(text, &audio).await? {
+        cache::put(text, speaker, &audio);
+    }
+    Ok(audio)
+}
+
+/// The voice for one chunk: a `--markdown` cue's, else what the scripts select.
+fn chunk_voice(
+    chunk: &str,
+    index: usize,
+    speaker: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let voice = match markdown::voice(chunk) {
+        Some(voice) => voice,
+        None => script::on_voice_select(chunk, index, &lang::chunk_voice(chunk, speaker))?,
+    };
+    Ok(config::resolve_voice(&voice).to_string())
+}
 
-    let api_root_url = env::var("TIKTOK_API_BASEURL").map_err(|_| "Invalid API root URL")?;
-    let chunks = split_text(text, BYTE_LIMIT);
+/// Splits `text` into chunks, synthesizes them in parallel and returns the decoded audio.
+async fn synthesize(
+    text: &str,
+    speaker: &str,
+    session_id: &str,
+    api_root_url: &str,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let chunks = split_text(text, BYTE_LIMIT)?;
+    let audio_chunks = synthesize_chunks(&chunks, speaker, session_id, api_root_url).await?;
+
+    // Each chunk is decoded on its own, so the audio is simply concatenated
+    Ok(audio_chunks.concat())
+}
 
-    if chunks.len() > 1 {
-        eprintln!("Processing {} chunks in parallel...", chunks.len());
+/// Synthesizes already split `chunks` in parallel, returning each chunk's decoded audio.
+#[tracing::instrument(skip_all, fields(chunks = chunks.len(), voice = speaker))]
+async fn synthesize_chunks(
+    chunks: &[String],
+    speaker: &str,
+    session_id: &str,
+    api_root_url: &str,
+) -> Result<Vec<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+    let started = Instant::now();
+    let speaker = &lang::select(&chunks.join(" "), speaker);
+
+    // Repeated chunks (choruses, templated lines) are synthesized once and reused
+    let mut unique_chunks: Vec<(&String, String, Vec<usize>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let voice = chunk_voice(chunk, index, speaker)?;
+        let position = *positions
+            .entry(cache::key(chunk, &voice))
+            .or_insert_with(|| {
+                unique_chunks.push((chunk, voice, Vec::new()));
+                unique_chunks.len() - 1
+            });
+        unique_chunks[position].2.push(index);
+    }
+    let voiced: Vec<(&str, &str)> = unique_chunks
+        .iter()
+        .flat_map(|(chunk, voice, indices)| {
+            std::iter::repeat_n((chunk.as_str(), voice.as_str()), indices.len())
+        })
+        .collect();
+    let requests = unique_chunks
+        .iter()
+        .filter(|(chunk, voice, _)| !cache::contains(chunk, voice))
+        .count();
+    estimate::check(&voiced, requests)?;
+    quota::check(session_id, requests)?;
+    if unique_chunks.len() < chunks.len() {
+        term::status(
+            term::Style::Cyan,
+            "Reusing",
+            format!(
+                "{} of {} chunks that are repeats",
+                chunks.len() - unique_chunks.len(),
+                chunks.len()
+            ),
+        );
     }
 
-    // Process chunks in parallel
+    // Process chunks in parallel, skipping those already in the cache
     let mut join_set = JoinSet::new();
-    let mut audio_chunks: Vec<Option<String>> = vec![None; chunks.len()];
+    let mut audio_chunks: Vec<Option<Vec<u8>>> = vec![None; chunks.len()];
     let total_chunks = chunks.len();
+    let mut cache_hits = 0;
+    let mut cache_misses = 0;
+    let mut rows = Vec::new();
 
-    for (index, chunk) in chunks.iter().enumerate() {
-        let chunk_text = chunk.clone();
-        let speaker_voice = speaker.to_string();
-        let session_id_clone = session_id.clone();
-        let api_root_url = api_root_url.clone();
-
-        join_set.spawn(async move {
-            eprintln!(
-                "Processing chunk {}/{}: {}",
-                index + 1,
-                total_chunks,
-                chunk_text
+    for (chunk, voice, indices) in unique_chunks {
+        let index = indices[0];
+        if let Some(audio) = cache::get(chunk, &voice) {
+            term::status(
+                term::Style::Green,
+                "Cached",
+                format!("chunk {}/{}", index + 1, total_chunks),
             );
-            match request_tts_chunk(
-                &chunk_text,
-                &speaker_voice,
-                &session_id_clone,
-                &api_root_url,
-            )
-            .await
-            {
-                Ok(base64_data) => (index, Some(base64_data)),
-                Err(e) => {
-                    eprintln!("Error processing chunk {}: {}", index + 1, e);
-                    (index, None)
+            if report::enabled() {
+                let row = report::Row::new(
+                    index + 1,
+                    chunk,
+                    &voice,
+                    report::Status::Cached,
+                    0,
+                    Duration::ZERO,
+                    &audio,
+                );
+                rows.extend(indices[1..].iter().map(|&i| row.repeat(i + 1)));
+                rows.push(row);
+            }
+            for &i in &indices {
+                audio_chunks[i] = Some(audio.clone());
+            }
+            cache_hits += 1;
+            continue;
+        }
+        cache_misses += 1;
+
+        let chunk_text = chunk.clone();
+        let speaker_voice = voice;
+        let session_id_clone = session_id.to_string();
+        let api_root_url = api_root_url.to_string();
+
+        let span = tracing::info_span!("chunk", index = index + 1, voice = %speaker_voice);
+        join_set.spawn(
+            async move {
+                term::status(
+                    term::Style::Cyan,
+                    "Requesting",
+                    format!("chunk {}/{}", index + 1, total_chunks),
+                );
+                let started = Instant::now();
+                let (result, tries) = report::counting(fetch_chunk(
+                    &chunk_text,
+                    &speaker_voice,
+                    &session_id_clone,
+                    &api_root_url,
+                ))
+                .await;
+                if let Err(e) = &result {
+                    term::status(
+                        term::Style::Red,
+                        "Failed",
+                        format!("chunk {}: {}", index + 1, e),
+                    );
                 }
+                let row = report::enabled().then(|| {
+                    let (status, audio) = match &result {
+                        Ok(audio) => (report::Status::Requested, audio.as_slice()),
+                        Err(e) => (report::Status::Failed(e.to_string()), &[][..]),
+                    };
+                    let row = report::Row::new(
+                        index + 1,
+                        &chunk_text,
+                        &speaker_voice,
+                        status,
+                        tries,
+                        started.elapsed(),
+                        audio,
+                    );
+                    let repeats: Vec<_> = indices[1..].iter().map(|&i| row.repeat(i + 1)).collect();
+                    (row, repeats)
+                });
+                (indices, result, row)
             }
-        });
+            .instrument(span),
+        );
     }
 
-    // Collect results
+    // Collect results, keeping the error of the first failed chunk
+    let mut failure: Option<ChunkFailed> = None;
     while let Some(result) = join_set.join_next().await {
+        let result = result.map(|(indices, result, row)| {
+            if let Some((row, repeats)) = row {
+                rows.push(row);
+                rows.extend(repeats);
+            }
+            (indices, result)
+        });
         match result {
-            Ok((index, data)) => {
-                audio_chunks[index] = data;
+            Ok((indices, Ok(data))) => {
+                for i in indices {
+                    audio_chunks[i] = Some(data.clone());
+                }
+            }
+            Ok((indices, Err(source))) => {
+                let number = indices[0] + 1;
+                if failure
+                    .as_ref()
+                    .is_none_or(|failure| number < failure.number)
+                {
+                    failure = Some(ChunkFailed { number, source });
+                }
             }
             Err(e) => {
-                eprintln!("Task join error: {}", e);
+                term::status(term::Style::Red, "Failed", format!("chunk task: {}", e));
             }
         }
     }
 
-    // Check if any chunks failed
-    if audio_chunks.iter().any(|chunk| chunk.is_none()) {
-        return Err("Some audio chunks failed to generate".into());
+    cache::record(cache_hits, cache_misses);
+    if report::enabled() {
+        report::job(rows, started.elapsed());
     }
 
-    // Concatenate all base64 strings and decode
-    let concatenated_base64: String = audio_chunks
+    if let Some(failure) = failure {
+        return Err(failure.into());
+    }
+    let audio_chunks = audio_chunks
         .into_iter()
-        .filter_map(|chunk| chunk)
-        .collect::<Vec<String>>()
-        .join("");
+        .collect::<Option<Vec<_>>>()
+        .ok_or("Some audio chunks failed to generate")?;
+    let bytes = audio_chunks.iter().map(Vec::len).sum();
+    term::summary(chunks.len(), cache_hits as usize, bytes, started.elapsed());
+    script::on_complete(chunks.len(), bytes, speaker);
+    Ok(audio_chunks)
+}
 
-    let audio_data = general_purpose::STANDARD.decode(concatenated_base64)?;
+async fn process_tts(
+    text: &str,
+    speaker: &str,
+    url_only: bool,
+    output: Option<&str>,
+    sidecar: bool,
+    playback: &playback::PlaybackArgs,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if url_only {
+        // Just output the URL for the first chunk
+        let chunks = split_text(text, BYTE_LIMIT)?;
+        if let Some(first_chunk) = chunks.first() {
+            println!(
+                "{}",
+                generate_tts_url(first_chunk, speaker, &api_root_url()?)?
+            );
+        }
+        return Ok(());
+    }
+
+    let (session_id, api_root_url) = load_credentials()?;
+    let chunks = split_text(text, BYTE_LIMIT)?;
+    let audio_chunks = synthesize_chunks(&chunks, speaker, &session_id, &api_root_url).await?;
+    let audio_data = audio_chunks.concat();
+
+    if let Some(target) = output {
+        let target = target.to_string();
+        let (target, audio_data) = pcm::offload(move || sniff::fit(&target, audio_data)).await?;
+        let target = target.as_str();
+        let config = config::get();
+        output::write(target, &audio_data, &config.upload).await?;
+        hooks::wrote(Some(target), chunks.len(), audio_data.len());
+        if sidecar {
+            let metadata = sidecar::metadata(text, speaker, &chunks, &audio_chunks);
+            let metadata = sidecar::written_as(metadata, &audio_data);
+            output::write_sidecar(target, &metadata, &config.upload).await?;
+        }
+        if checksum::enabled() {
+            output::write_checksum(target, &audio_data, &config.upload).await?;
+        }
+        return peaks::write(&audio_data);
+    }
+
+    // Output raw audio data to stdout (can be piped to mpv/ffplay) or play it directly
+    playback.output(&audio_data).await?;
+    hooks::wrote(None, chunks.len(), audio_data.len());
+    peaks::write(&audio_data)
+}
+
+async fn stream_tts(
+    source: pipeline::Source,
+    args: &Args,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = load_credentials()?;
+    let path = args.output.as_deref().filter(|path| *path != "-");
+    let sink: Box<dyn io::Write> = match path {
+        None => Box::new(io::stdout().lock()),
+        Some(path) => Box::new(std::fs::File::create(path)?),
+    };
+    let mut sink = checksum::Hashing::new(sink);
+    let (chunks, bytes) = pipeline::run(
+        source,
+        &args.speaker,
+        &session_id,
+        &api_root_url,
+        args.jobs,
+        &mut sink,
+    )
+    .await?;
+    if let Some(path) = path {
+        sink.finish(std::path::Path::new(path))?;
+    }
+    hooks::wrote(path, chunks, bytes);
+    Ok(())
+}
+
+/// Replaces built-in option defaults with those from the config file; values given on
+/// the command line or through `TKTTS_*` variables are kept.
+fn apply_config_defaults(args: &mut Args, matches: &clap::ArgMatches) {
+    let is_default = |id| matches.value_source(id) == Some(ValueSource::DefaultValue);
+    let config = config::get();
+    if let Some(speaker) = &config.speaker
+        && is_default("speaker")
+    {
+        args.speaker = speaker.clone();
+    }
+    if let Some(jobs) = config.jobs
+        && is_default("jobs")
+    {
+        args.jobs = jobs;
+    }
+}
 
-    // Output raw audio data to stdout (can be piped to mpv/ffplay)
-    use std::io::{self, Write};
-    io::stdout().write_all(&audio_data)?;
+/// The text from the arguments, or else all of stdin, with `--var` placeholders filled.
+fn read_text(args: &Args) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if !args.text.is_empty() {
+        return vars::render(&args.text.join(" "), &Default::default());
+    }
+    let buffer = console::read_stdin().map_err(|e| format!("Failed to read stdin: {}", e))?;
+    let trimmed = buffer.trim();
+    if trimmed.is_empty() {
+        return Err("No text provided via arguments or stdin".into());
+    }
+    vars::render(trimmed, &Default::default())
+}
 
+/// Translates the text into each `--translate-to` language and synthesizes every
+/// translation with a voice for its language.
+async fn run_translated(args: &Args) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = read_text(args)?;
+    let several = args.translate_to.len() > 1;
+    for language in &args.translate_to {
+        let translated = translate::translate(&text, language).await?;
+        let speaker = translate::voice(&args.speaker, language);
+        let output = match &args.output {
+            Some(output) if several => Some(translate::localized_output(output, language)),
+            output => output.clone(),
+        };
+        process_tts(
+            &translated,
+            &speaker,
+            args.url_only,
+            output.as_deref(),
+            args.sidecar,
+            &args.playback,
+        )
+        .await?;
+    }
     Ok(())
 }
 
+/// Synthesizes `text` onto the end of the `--append` file.
+async fn append_tts(
+    text: &str,
+    speaker: &str,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = load_credentials()?;
+    let chunks = split_text(text, BYTE_LIMIT)?;
+    let audio = synthesize_chunks(&chunks, speaker, &session_id, &api_root_url)
+        .await?
+        .concat();
+    let bytes = audio.len();
+    let joined = pcm::offload({
+        let path = path.to_path_buf();
+        move || append::write(&path, &audio)
+    })
+    .await?;
+    if checksum::enabled() {
+        checksum::write(path, &joined)?;
+    }
+    peaks::write(&joined)?;
+    term::status(
+        term::Style::Green,
+        "Appended",
+        format!(
+            "{} ({} in total)",
+            path.display(),
+            cache::format_size(joined.len() as u64)
+        ),
+    );
+    hooks::wrote(Some(&path.to_string_lossy()), chunks.len(), bytes);
+    Ok(())
+}
+
+/// Synthesizes the text from the arguments, stdin or `--batch` files.
+async fn run_job(args: &Args) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(path) = &args.listen_fifo {
+        #[cfg(unix)]
+        return fifo::run(path, &args.speaker, &args.playback, args.append.as_deref()).await;
+        #[cfg(not(unix))]
+        return Err(format!("--listen-fifo {} needs a Unix system", path.display()).into());
+    }
+    if args.batch.podcast.podcast_out.is_some() && !args.batch.enabled() {
+        return Err("--podcast-out needs --batch or --split-output".into());
+    }
+    if !args.url_only
+        && !args.batch.enabled()
+        && !estimate::enabled()
+        && args.playback.writes_stdout()
+        && args.output.as_deref().is_none_or(|output| output == "-")
+    {
+        console::binary_stdout()?;
+    }
+    if !args.batch.files.is_empty() {
+        return batch::run(&args.batch, None, &args.speaker, args.sidecar).await;
+    }
+    if !args.translate_to.is_empty() {
+        return run_translated(args).await;
+    }
+
+    // Plain stdout/file output streams through the bounded pipeline
+    let streaming = !args.url_only
+        && !args.sidecar
+        && !estimate::enabled()
+        && !report::enabled()
+        && !peaks::enabled()
+        && !args.batch.enabled()
+        && args.append.is_none()
+        && args.playback.writes_stdout()
+        && !args.output.as_deref().is_some_and(output::is_remote)
+        && args.output.as_deref().is_none_or(sniff::fits);
+    if streaming {
+        let source = if args.text.is_empty() {
+            pipeline::Source::Stdin
+        } else {
+            pipeline::Source::Text(args.text.join(" "))
+        };
+        return stream_tts(source, args).await;
+    }
+
+    let text = read_text(args)?;
+    if args.batch.enabled() {
+        return batch::run(&args.batch, Some(text), &args.speaker, args.sidecar).await;
+    }
+    if let Some(path) = &args.append {
+        return append_tts(&text, &args.speaker, path).await;
+    }
+
+    process_tts(
+        &text,
+        &args.speaker,
+        args.url_only,
+        args.output.as_deref(),
+        args.sidecar,
+        &args.playback,
+    )
+    .await
+}
+
+async fn run_command(command: Command) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match command {
+        Command::Anki(args) => anki::run(args).await,
+        Command::Auth { command } => auth::run(command).await,
+        Command::Bench(args) => bench::run(args).await,
+        Command::Cache { command } => cache::run(command),
+        Command::Completions(args) => completions::run(args),
+        Command::Config { command } => config::run(command),
+        Command::Dialogue(args) => dialogue::run(args).await,
+        #[cfg(feature = "discord")]
+        Command::Discord(args) => discord::run(args).await,
+        Command::Doctor(args) => doctor::run(args).await,
+        Command::Espeak(args) => espeak::run(args).await,
+        Command::Exec(args) => exec::run(args).await,
+        Command::Feed(args) => feed::run(args).await,
+        #[cfg(feature = "gui")]
+        Command::Gui(args) => gui::run(args).await,
+        Command::Join(args) => plan::join(args).await,
+        Command::Man(args) => man::run(args),
+        Command::Matrix(args) => matrix::run(args).await,
+        Command::Mqtt(args) => mqtt::run(args).await,
+        Command::Plan(args) => plan::plan(args),
+        Command::Quota(args) => quota::run(args),
+        Command::Say(args) => say::run(args).await,
+        Command::SelfUpdate(args) => self_update::run(args).await,
+        Command::Serve(args) => server::run(args).await,
+        Command::Speechd(args) => speechd::run(args).await,
+        Command::Srt(args) => srt::run(args).await,
+        #[cfg(feature = "tui")]
+        Command::Tui(args) => tui::run(args).await,
+        Command::Twitch(args) => twitch::run(args).await,
+        Command::Verify(args) => checksum::run(args),
+        Command::Voices(args) => catalog::run(args).await,
+    }
+}
+
+/// The command line, with the subcommand filled in when the binary was started under the
+/// name of a command it stands in for, like `say` or `espeak`, or of a speech-dispatcher
+/// module.
+fn command_line() -> Vec<std::ffi::OsString> {
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let invoked = args
+        .first()
+        .and_then(|program| std::path::Path::new(program).file_stem())
+        .and_then(|name| name.to_str());
+    let subcommand = match invoked {
+        Some("say" | "tktts-say") => Some("say"),
+        Some("espeak" | "espeak-ng" | "tktts-espeak") => Some("espeak"),
+        Some("sd_tktts") => Some("speechd"),
+        _ => None,
+    };
+    if let Some(subcommand) = subcommand {
+        args[0] = "tktts".into();
+        args.insert(1, subcommand.into());
+    }
+    if args.get(1).is_some_and(|arg| arg == "espeak") {
+        espeak::rewrite(&mut args[2..]);
+    }
+    args
+}
+
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
-
-    let text = if args.text.is_empty() {
-        // Read from stdin if no arguments provided
-        let mut buffer = String::new();
-        match io::stdin().read_to_string(&mut buffer) {
-            Ok(_) => {
-                let trimmed = buffer.trim();
-                if trimmed.is_empty() {
-                    eprintln!("Error: No text provided via arguments or stdin");
-                    process::exit(1);
-                }
-                trimmed.to_string()
-            }
-            Err(e) => {
-                eprintln!("Error reading from stdin: {}", e);
-                process::exit(1);
-            }
+    let matches = cli().get_matches_from(command_line());
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    term::init(args.no_color, args.quiet, args.log_redaction);
+
+    // Runs before the config is loaded, so a broken file can still be inspected and fixed
+    if let Some(Command::Config { command }) = args.command {
+        if let Err(e) = config::run(command) {
+            exit_with(as_config_error(e));
+        }
+        return;
+    }
+
+    // Tapes should capture and replay every request, so cached chunks are bypassed
+    if args.no_cache || args.record.is_some() || args.replay.is_some() {
+        cache::disable();
+    }
+
+    if let Err(e) = config::init()
+        .and_then(|()| signing::check(&config::get().signing))
+        .and_then(|()| filter::init(&config::get().filters))
+        .and_then(|()| flood::init(&config::get().sources))
+        .and_then(|()| script::init(&config::get().scripting))
+        .and_then(|()| censor::init(&args.censor, args.censor_mode))
+        .and_then(|()| preset::select(args.preset.as_deref(), args.request_style))
+        .and_then(|()| http::configure(&args.http))
+        .and_then(|()| otel::init(&args.otel))
+        .and_then(|()| match (&args.record, &args.replay) {
+            (Some(path), _) => tape::record_to(path),
+            (_, Some(path)) => tape::replay_from(path),
+            _ => Ok(()),
+        })
+    {
+        exit_with(as_config_error(e));
+    }
+    apply_config_defaults(&mut args, &matches);
+    vars::init(std::mem::take(&mut args.vars));
+    estimate::init(args.estimate, args.max_requests, args.max_duration);
+    checksum::init(args.checksum);
+    report::init(args.report.take());
+    peaks::init(args.peaks.clone());
+    let _ = SPLIT.set(args.split);
+    markdown::init(args.markdown, &config::get().markdown);
+    sfx::init(args.sfx.take());
+    stt::init(
+        args.verify.take(),
+        args.whisper_command.clone(),
+        args.verify_threshold,
+    );
+    let config = config::get();
+    quota::init(args.daily_limit.or(config.daily_limit));
+    limits::init(
+        args.max_chars.or(config.max_chars),
+        args.max_chunks.or(config.max_chunks),
+        args.on_limit.or(config.on_limit).unwrap_or_default(),
+    );
+    catalog::load();
+    catalog::seed(args.seed);
+    lang::set_auto_voice(args.auto_voice);
+    if let Some(voices) = args.multilingual.clone() {
+        lang::set_multilingual(voices);
+    }
+
+    if let Some(command) = args.command {
+        let result = run_command(command).await;
+        otel::shutdown();
+        if let Err(e) = result {
+            exit_with(e);
         }
+        return;
+    }
+
+    // `--batch` files each get their own pick
+    let speaker = if args.batch.files.is_empty() {
+        catalog::pick(&args.speaker)
     } else {
-        args.text.join(" ")
+        Ok(args.speaker.clone())
     };
+    let speaker = speaker.and_then(|speaker| {
+        if !args.force_speaker {
+            catalog::check(&speaker)?;
+        }
+        Ok(speaker)
+    });
+    match speaker {
+        Ok(speaker) => args.speaker = speaker,
+        Err(e) => exit_with(as_config_error(e)),
+    }
 
-    if let Err(e) = process_tts(&text, &args.speaker, args.url_only).await {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    // `-u` only prints a URL, so it is not a synthesis job for the hooks
+    let hooked = !args.url_only;
+    let started = Instant::now();
+    let result = async {
+        if hooked {
+            hooks::pre(&args.speaker, args.output.as_deref()).await?;
+        }
+        run_job(&args).await?;
+        stt::report();
+        if hooked {
+            hooks::post(&args.speaker, started).await?;
+        }
+        Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+    }
+    .await;
+    if hooked {
+        let error = result.as_ref().err().map(|e| e.to_string());
+        notify::send(&args.notify, &args.speaker, started, error).await;
+    }
+    if let Err(e) = report::write(started.elapsed()) {
+        term::error(format!("Failed to write the report: {}", e));
+    }
+    otel::shutdown();
+    if let Err(e) = result {
+        exit_with(e);
     }
 }
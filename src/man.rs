@@ -0,0 +1,23 @@
+//! Roff man pages, generated from the command line definition.
+
+use std::path::PathBuf;
+
+#[derive(clap::Args)]
+pub struct ManArgs {
+    /// Write tktts.1 and a tktts-<subcommand>.1 page per subcommand into this directory
+    /// instead of printing tktts.1
+    #[arg(long, value_name = "DIR")]
+    out_dir: Option<PathBuf>,
+}
+
+pub fn run(args: ManArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match args.out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(crate::cli(), &dir)?;
+            progress!("Wrote man pages to {}", dir.display());
+        }
+        None => clap_mangen::Man::new(crate::cli()).render(&mut std::io::stdout())?,
+    }
+    Ok(())
+}
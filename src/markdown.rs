@@ -0,0 +1,282 @@
+//! `--markdown`: Markdown input read as prose, with emphasis turned into spoken cues.
+//!
+//! Markup, link targets, HTML and code blocks are left out, headings and list items end
+//! in a pause, and `*emphasis*` and `**strong**` text is marked as the `[markdown]`
+//! config section says: a pause around it, "quote" and "unquote" words, or another
+//! voice. Text for another voice is split into chunks of its own, which start with a
+//! mark that [`voice`] recognizes and [`unmarked`] removes before the text is sent.
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Starts a span (and its chunks) for the emphasis voice.
+const EMPHASIS_VOICE: char = '\u{1}';
+/// Starts a span (and its chunks) for the strong voice.
+const STRONG_VOICE: char = '\u{2}';
+/// Ends a voice span.
+const END_VOICE: char = '\u{3}';
+
+static MARKDOWN: OnceLock<MarkdownConfig> = OnceLock::new();
+
+/// How emphasized text is set off in the audio.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Cue {
+    /// Read like the text around it
+    None,
+    /// A short pause before and after
+    Pause,
+    /// The `quote` words before and after
+    Quote,
+    /// Read with this voice
+    Voice(String),
+}
+
+impl TryFrom<String> for Cue {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "none" => Ok(Cue::None),
+            "pause" => Ok(Cue::Pause),
+            "quote" => Ok(Cue::Quote),
+            _ => match value.strip_prefix("voice:") {
+                Some(voice) if !voice.is_empty() => Ok(Cue::Voice(voice.to_string())),
+                _ => Err(format!(
+                    "Unknown cue '{}' (expected none, pause, quote or voice:<name>)",
+                    value
+                )),
+            },
+        }
+    }
+}
+
+impl From<Cue> for String {
+    fn from(cue: Cue) -> Self {
+        match cue {
+            Cue::None => "none".to_string(),
+            Cue::Pause => "pause".to_string(),
+            Cue::Quote => "quote".to_string(),
+            Cue::Voice(voice) => format!("voice:{}", voice),
+        }
+    }
+}
+
+/// The `[markdown]` config section.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkdownConfig {
+    /// Cue for `*emphasis*`: none, pause, quote or voice:<name>
+    pub emphasis: Cue,
+    /// Cue for `**strong**` text
+    pub strong: Cue,
+    /// Words spoken before and after text with the quote cue
+    pub quote: [String; 2],
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        MarkdownConfig {
+            emphasis: Cue::Pause,
+            strong: Cue::Pause,
+            quote: ["quote".to_string(), "unquote".to_string()],
+        }
+    }
+}
+
+/// Reads input as Markdown from now on, with the cues from `config`.
+pub fn init(enabled: bool, config: &MarkdownConfig) {
+    if enabled {
+        let _ = MARKDOWN.set(config.clone());
+    }
+}
+
+/// Writes prose, keeping track of what the next text has to be separated by.
+struct Speech<'a> {
+    config: &'a MarkdownConfig,
+    out: String,
+    /// A pause cue ended, and gets its comma unless punctuation follows anyway
+    pending_pause: bool,
+    /// The cue of each open emphasis, and whether it opened a voice span
+    open: Vec<(&'a Cue, bool)>,
+    /// Code blocks, HTML blocks and metadata are not read
+    skipping: usize,
+}
+
+impl<'a> Speech<'a> {
+    fn text(&mut self, text: &str) {
+        if self.skipping > 0 {
+            return;
+        }
+        let text: String = text
+            .chars()
+            .filter(|&c| !matches!(c, EMPHASIS_VOICE | STRONG_VOICE | END_VOICE))
+            .collect();
+        if std::mem::take(&mut self.pending_pause)
+            && !text.starts_with(|c: char| c.is_ascii_punctuation())
+        {
+            self.out.push(',');
+        }
+        self.out.push_str(&text);
+    }
+
+    fn word(&mut self, word: &str) {
+        if !self.out.is_empty() && !self.out.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+        self.out.push_str(word);
+        self.out.push(' ');
+    }
+
+    fn in_voice(&self) -> bool {
+        self.open.iter().any(|&(_, voiced)| voiced)
+    }
+
+    fn open(&mut self, cue: &'a Cue, mark: char) {
+        let mut voiced = false;
+        match cue {
+            Cue::None => {}
+            Cue::Pause => {
+                let before = self.out.trim_end();
+                if before.ends_with(|c: char| c.is_alphanumeric()) {
+                    self.out.insert(before.len(), ',');
+                }
+            }
+            Cue::Quote => {
+                let config = self.config;
+                self.word(&config.quote[0]);
+            }
+            Cue::Voice(_) if self.in_voice() => {}
+            Cue::Voice(_) => {
+                self.out.push(mark);
+                voiced = true;
+            }
+        }
+        self.open.push((cue, voiced));
+    }
+
+    fn close(&mut self) {
+        let Some((cue, voiced)) = self.open.pop() else {
+            return;
+        };
+        match cue {
+            Cue::Pause => self.pending_pause = true,
+            Cue::Quote => {
+                let close = &self.config.quote[1];
+                if !self.out.ends_with(char::is_whitespace) {
+                    self.out.push(' ');
+                }
+                self.out.push_str(close);
+            }
+            _ if voiced => self.out.push(END_VOICE),
+            _ => {}
+        }
+    }
+
+    /// Ends a heading, list item or table row with a pause, as a sentence end.
+    fn end_block(&mut self) {
+        self.pending_pause = false;
+        // Table cells each end in a comma
+        let trimmed = self.out.trim_end().trim_end_matches(',').len();
+        self.out.truncate(trimmed);
+        // Inside a voice span, so the pause is not a chunk of its own
+        let end = self.out.len() - usize::from(self.out.ends_with(END_VOICE));
+        if self.out[..end].ends_with(|c: char| c.is_alphanumeric()) {
+            self.out.insert(end, '.');
+        }
+        self.out.push('\n');
+    }
+}
+
+/// `text` read as Markdown, as prose to be spoken; unchanged without `--markdown`.
+pub fn speakable(text: &str) -> String {
+    let Some(config) = MARKDOWN.get() else {
+        return text.to_string();
+    };
+    let mut speech = Speech {
+        config,
+        out: String::with_capacity(text.len()),
+        pending_pause: false,
+        open: Vec::new(),
+        skipping: 0,
+    };
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    for event in Parser::new_ext(text, options) {
+        match event {
+            Event::Text(text) | Event::Code(text) => speech.text(&text),
+            Event::SoftBreak => speech.text(" "),
+            Event::HardBreak => speech.text("\n"),
+            Event::Start(Tag::Emphasis) => speech.open(&config.emphasis, EMPHASIS_VOICE),
+            Event::Start(Tag::Strong) => speech.open(&config.strong, STRONG_VOICE),
+            Event::End(TagEnd::Emphasis | TagEnd::Strong) => speech.close(),
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)) => {
+                speech.skipping += 1
+            }
+            Event::End(TagEnd::CodeBlock | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)) => {
+                speech.skipping -= 1
+            }
+            Event::End(TagEnd::TableCell) => speech.text(", "),
+            Event::End(
+                TagEnd::Heading(_) | TagEnd::Item | TagEnd::TableHead | TagEnd::TableRow,
+            ) => speech.end_block(),
+            Event::End(TagEnd::Paragraph | TagEnd::BlockQuote(_)) => {
+                speech.pending_pause = false;
+                speech.out.push_str("\n\n");
+            }
+            _ => {}
+        }
+    }
+    speech.out.trim().to_string()
+}
+
+/// Splits `text` from [`speakable`] into the parts for the main voice (`false`) and for
+/// a cue's voice (`true`, with the voice mark still in front).
+pub fn spans(text: &str) -> Vec<(bool, &str)> {
+    if MARKDOWN.get().is_none() {
+        return vec![(false, text)];
+    }
+    let mut spans = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find([EMPHASIS_VOICE, STRONG_VOICE]) {
+        let (before, voiced) = rest.split_at(start);
+        let end = voiced.find(END_VOICE).unwrap_or(voiced.len());
+        spans.push((false, before));
+        spans.push((true, &voiced[..end]));
+        rest = voiced.get(end + 1..).unwrap_or_default();
+    }
+    spans.push((false, rest));
+    // Punctuation left between voice spans would be a chunk of its own
+    spans.retain(|(_, span)| span.chars().any(char::is_alphanumeric));
+    spans
+}
+
+/// Puts the voice mark of `span` in front of one of its chunks.
+pub fn mark(span: &str, chunk: String) -> String {
+    match span.chars().next() {
+        _ if chunk == crate::censor::TONE => chunk,
+        Some(mark @ (EMPHASIS_VOICE | STRONG_VOICE)) if !chunk.starts_with(mark) => {
+            format!("{}{}", mark, chunk)
+        }
+        _ => chunk,
+    }
+}
+
+/// The text of `chunk` without its voice mark.
+pub fn unmarked(chunk: &str) -> &str {
+    chunk.trim_start_matches([EMPHASIS_VOICE, STRONG_VOICE])
+}
+
+/// The cue voice for `chunk`, when it is one of a voice span's chunks.
+pub fn voice(chunk: &str) -> Option<String> {
+    let config = MARKDOWN.get()?;
+    let cue = match chunk.chars().next()? {
+        EMPHASIS_VOICE => &config.emphasis,
+        STRONG_VOICE => &config.strong,
+        _ => return None,
+    };
+    match cue {
+        Cue::Voice(voice) => Some(voice.clone()),
+        _ => None,
+    }
+}
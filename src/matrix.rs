@@ -0,0 +1,265 @@
+//! Matrix bot mode.
+//!
+//! Talks to the client-server API directly: joins the configured rooms, long-polls
+//! `/sync` for `!tts <text>` messages and answers each with an uploaded `m.audio` event.
+
+use reqwest::Client;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+const COMMAND_PREFIX: &str = "!tts ";
+/// How long the server may hold a `/sync` request open, in milliseconds.
+const SYNC_TIMEOUT_MS: &str = "30000";
+
+#[derive(clap::Args)]
+pub struct MatrixArgs {
+    /// Homeserver base URL, e.g. https://matrix.org
+    #[arg(long)]
+    homeserver: String,
+
+    /// Access token of the bot account (falls back to MATRIX_ACCESS_TOKEN)
+    #[arg(long)]
+    access_token: Option<String>,
+
+    /// Room ID or alias to join and listen in (repeatable)
+    #[arg(long = "room", value_name = "ROOM", required = true)]
+    rooms: Vec<String>,
+
+    /// Default voice for a room, as ROOM=VOICE (repeatable)
+    #[arg(long = "room-voice", value_name = "ROOM=VOICE")]
+    room_voices: Vec<String>,
+
+    /// Voice used in rooms without a default
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+}
+
+struct MatrixClient {
+    http: Client,
+    homeserver: Url,
+    access_token: String,
+    txn_counter: u64,
+}
+
+impl MatrixClient {
+    /// Builds an API URL from path segments, percent-encoding room IDs and aliases.
+    fn endpoint(&self, segments: &[&str]) -> Result<Url, Box<dyn std::error::Error + Send + Sync>> {
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| "Homeserver URL cannot be a base")?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn send_json(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let response = request.bearer_auth(&self.access_token).send().await?;
+        let status = response.status();
+        let json: Value = response.json().await?;
+        if !status.is_success() {
+            let error = json["error"].as_str().unwrap_or("unknown error");
+            return Err(format!("Matrix API error ({}): {}", status, error).into());
+        }
+        Ok(json)
+    }
+
+    async fn whoami(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let url = self.endpoint(&["_matrix", "client", "v3", "account", "whoami"])?;
+        let json = self.send_json(self.http.get(url)).await?;
+        Ok(json["user_id"]
+            .as_str()
+            .ok_or("Missing user_id in whoami response")?
+            .to_string())
+    }
+
+    /// Joins a room by ID or alias and returns its room ID.
+    async fn join(&self, room: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let url = self.endpoint(&["_matrix", "client", "v3", "join", room])?;
+        let json = self.send_json(self.http.post(url).json(&json!({}))).await?;
+        Ok(json["room_id"]
+            .as_str()
+            .ok_or("Missing room_id in join response")?
+            .to_string())
+    }
+
+    async fn sync(
+        &self,
+        since: Option<&str>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let mut url = self.endpoint(&["_matrix", "client", "v3", "sync"])?;
+        {
+            let mut query = url.query_pairs_mut();
+            match since {
+                Some(since) => {
+                    query
+                        .append_pair("since", since)
+                        .append_pair("timeout", SYNC_TIMEOUT_MS);
+                }
+                // The first sync only fetches a token, so old messages are not replayed
+                None => {
+                    query.append_pair("filter", r#"{"room":{"timeline":{"limit":0}}}"#);
+                }
+            }
+        }
+        self.send_json(self.http.get(url)).await
+    }
+
+    async fn upload(
+        &self,
+        audio: Vec<u8>,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut url = self.endpoint(&["_matrix", "media", "v3", "upload"])?;
+        url.query_pairs_mut().append_pair("filename", "tts.mp3");
+        let request = self
+            .http
+            .post(url)
+            .header("Content-Type", "audio/mpeg")
+            .body(audio);
+        let json = self.send_json(request).await?;
+        Ok(json["content_uri"]
+            .as_str()
+            .ok_or("Missing content_uri in upload response")?
+            .to_string())
+    }
+
+    async fn send_audio(
+        &mut self,
+        room_id: &str,
+        content_uri: &str,
+        size: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.txn_counter += 1;
+        let txn_id = format!(
+            "tktts-{}-{}",
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis(),
+            self.txn_counter
+        );
+        let url = self.endpoint(&[
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            room_id,
+            "send",
+            "m.room.message",
+            &txn_id,
+        ])?;
+        let content = json!({
+            "msgtype": "m.audio",
+            "body": "tts.mp3",
+            "url": content_uri,
+            "info": { "mimetype": "audio/mpeg", "size": size },
+        });
+        self.send_json(self.http.put(url).json(&content)).await?;
+        Ok(())
+    }
+}
+
+/// Extracts the `!tts` commands addressed to the bot from a sync response.
+fn collect_commands(sync: &Value, own_user: &str) -> Vec<(String, String)> {
+    let mut commands = Vec::new();
+    let Some(rooms) = sync["rooms"]["join"].as_object() else {
+        return commands;
+    };
+
+    for (room_id, room) in rooms {
+        let Some(events) = room["timeline"]["events"].as_array() else {
+            continue;
+        };
+        for event in events {
+            if event["type"] != "m.room.message"
+                || event["sender"] == own_user
+                || event["content"]["msgtype"] != "m.text"
+            {
+                continue;
+            }
+            if let Some(text) = event["content"]["body"]
+                .as_str()
+                .and_then(|body| body.strip_prefix(COMMAND_PREFIX))
+                && !text.trim().is_empty()
+            {
+                commands.push((room_id.clone(), text.trim().to_string()));
+            }
+        }
+    }
+
+    commands
+}
+
+pub async fn run(args: MatrixArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let access_token = match args.access_token {
+        Some(token) => token,
+        None => env::var("MATRIX_ACCESS_TOKEN").map_err(
+            |_| "No Matrix access token given. Pass --access-token or set MATRIX_ACCESS_TOKEN.",
+        )?,
+    };
+    let (session_id, api_root_url) = crate::load_credentials()?;
+
+    let mut room_voices = HashMap::new();
+    for mapping in &args.room_voices {
+        let (room, voice) = mapping
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Invalid --room-voice '{}', expected ROOM=VOICE", mapping))?;
+        room_voices.insert(room.to_string(), voice.to_string());
+    }
+
+    let mut client = MatrixClient {
+        http: crate::http::client(),
+        homeserver: Url::parse(&args.homeserver)?,
+        access_token,
+        txn_counter: 0,
+    };
+    let own_user = client.whoami().await?;
+
+    // Map joined room IDs to their voice, whether they were given by ID or alias
+    let mut voices_by_room_id = HashMap::new();
+    for room in &args.rooms {
+        let room_id = client.join(room).await?;
+        let voice = room_voices.get(room).unwrap_or(&args.speaker).clone();
+        progress!("Listening in {} ({}) with voice {}", room, room_id, voice);
+        voices_by_room_id.insert(room_id, voice);
+    }
+
+    let mut since = client.sync(None).await?["next_batch"]
+        .as_str()
+        .ok_or("Missing next_batch in sync response")?
+        .to_string();
+
+    loop {
+        let sync = match client.sync(Some(&since)).await {
+            Ok(sync) => sync,
+            Err(e) => {
+                crate::term::error(format!("Sync failed, retrying: {}", e));
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        if let Some(next_batch) = sync["next_batch"].as_str() {
+            since = next_batch.to_string();
+        }
+
+        for (room_id, text) in collect_commands(&sync, &own_user) {
+            let Some(voice) = voices_by_room_id.get(&room_id) else {
+                continue;
+            };
+            progress!("{}: {}", room_id, text);
+
+            let result = async {
+                let audio = crate::synthesize(&text, voice, &session_id, &api_root_url).await?;
+                let size = audio.len();
+                let content_uri = client.upload(audio).await?;
+                client.send_audio(&room_id, &content_uri, size).await
+            }
+            .await;
+            if let Err(e) = result {
+                crate::term::error(format!("Failed to answer in {}: {}", room_id, e));
+            }
+        }
+    }
+}
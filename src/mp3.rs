@@ -0,0 +1,146 @@
+//! Minimal MPEG audio frame walker, used to report duration and sample rate and to find
+//! where the frames of a file start and end.
+
+/// Summary of an MP3 stream.
+pub struct Mp3Info {
+    pub frames: usize,
+    pub sample_rate: u32,
+    /// 1 or 2, as in the last frame
+    pub channels: u8,
+    pub duration_secs: f64,
+}
+
+/// Bitrates in kbit/s, indexed by `[version is MPEG-1][layer - 1][bitrate index]`.
+const BITRATES: [[[u32; 15]; 3]; 2] = [
+    [
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ],
+    [
+        [
+            0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ],
+];
+
+struct FrameHeader {
+    length: usize,
+    samples: u32,
+    sample_rate: u32,
+    channels: u8,
+}
+
+fn parse_header(bytes: &[u8]) -> Option<FrameHeader> {
+    if bytes.len() < 4 || bytes[0] != 0xff || bytes[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+    let version = (bytes[1] >> 3) & 0b11; // 0 = 2.5, 2 = 2, 3 = 1
+    let layer = match (bytes[1] >> 1) & 0b11 {
+        0b11 => 1,
+        0b10 => 2,
+        0b01 => 3,
+        _ => return None,
+    };
+    let bitrate_index = (bytes[2] >> 4) as usize;
+    let sample_rate_index = ((bytes[2] >> 2) & 0b11) as usize;
+    if version == 1 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+    let bitrate = BITRATES[mpeg1 as usize][layer - 1][bitrate_index] * 1000;
+    let sample_rate = [44100, 48000, 32000][sample_rate_index]
+        >> match version {
+            3 => 0,
+            2 => 1,
+            _ => 2,
+        };
+    let padding = ((bytes[2] >> 1) & 1) as usize;
+
+    let (samples, length) = match layer {
+        1 => (384, (12 * bitrate / sample_rate) as usize * 4 + padding * 4),
+        2 => (1152, (144 * bitrate / sample_rate) as usize + padding),
+        _ if mpeg1 => (1152, (144 * bitrate / sample_rate) as usize + padding),
+        _ => (576, (72 * bitrate / sample_rate) as usize + padding),
+    };
+
+    Some(FrameHeader {
+        length,
+        samples,
+        sample_rate,
+        channels: if bytes[3] >> 6 == 0b11 { 1 } else { 2 },
+    })
+}
+
+/// Size of a leading ID3v2 tag, if any.
+pub fn id3v2_len(bytes: &[u8]) -> usize {
+    if bytes.len() < 10 || &bytes[..3] != b"ID3" {
+        return 0;
+    }
+    let size = bytes[6..10]
+        .iter()
+        .fold(0usize, |size, b| (size << 7) | (*b & 0x7f) as usize);
+    10 + size
+}
+
+/// Calls `frame` with the position and header of every whole frame of `audio`.
+/// Unparseable bytes between frames are skipped, so concatenated chunks with their own
+/// tags are handled too.
+fn walk(audio: &[u8], mut frame: impl FnMut(usize, &FrameHeader)) {
+    let mut position = id3v2_len(audio);
+    while position + 4 <= audio.len() {
+        match parse_header(&audio[position..]) {
+            Some(header) if position + header.length <= audio.len() => {
+                frame(position, &header);
+                position += header.length;
+            }
+            Some(_) => break,
+            None => position += id3v2_len(&audio[position..]).max(1),
+        }
+    }
+}
+
+/// Walks the frames of `audio`.
+pub fn info(audio: &[u8]) -> Mp3Info {
+    let mut info = Mp3Info {
+        frames: 0,
+        sample_rate: 0,
+        channels: 0,
+        duration_secs: 0.0,
+    };
+    walk(audio, |_, header| {
+        info.frames += 1;
+        info.duration_secs += header.samples as f64 / header.sample_rate as f64;
+        info.sample_rate = header.sample_rate;
+        info.channels = header.channels;
+    });
+    info
+}
+
+/// The bytes of `audio` from its first to the end of its last whole audio frame. A
+/// leading ID3v2 tag, a Xing or Info header frame (whose frame count would go stale
+/// when frames are added) and a trailing ID3v1 tag or cut-off frame are left out.
+pub fn frame_span(audio: &[u8]) -> std::ops::Range<usize> {
+    let mut span: Option<std::ops::Range<usize>> = None;
+    walk(audio, |position, header| {
+        let end = position + header.length;
+        match &mut span {
+            Some(span) => span.end = end,
+            None => {
+                let head = &audio[position + 4..end.min(position + 44)];
+                let xing = head.windows(4).any(|tag| tag == b"Xing" || tag == b"Info");
+                span = Some(if xing { end..end } else { position..end });
+            }
+        }
+    });
+    span.unwrap_or(0..0)
+}
@@ -0,0 +1,153 @@
+//! MPD output target.
+//!
+//! Hands the audio to a Music Player Daemon: either by saving it into MPD's music
+//! directory and adding it from the database, or by serving it over a temporary HTTP
+//! endpoint and adding the stream URL. Either way the new song is played right away.
+
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+/// Subdirectory of the music directory that generated files are written to.
+const MPD_SUBDIR: &str = "tktts";
+
+struct MpdConnection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+/// Quotes a command argument as the MPD protocol expects.
+fn quote(argument: &str) -> String {
+    format!(
+        "\"{}\"",
+        argument.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+impl MpdConnection {
+    async fn connect(stream: TcpStream) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let (reader, writer) = stream.into_split();
+        let mut connection = MpdConnection {
+            reader: BufReader::new(reader),
+            writer,
+        };
+
+        let mut greeting = String::new();
+        connection.reader.read_line(&mut greeting).await?;
+        if !greeting.starts_with("OK MPD") {
+            return Err(format!("Unexpected MPD greeting: {}", greeting.trim()).into());
+        }
+        Ok(connection)
+    }
+
+    /// Sends a command and returns the `key: value` pairs of its response.
+    async fn command(
+        &mut self,
+        command: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+        self.writer
+            .write_all(format!("{}\n", command).as_bytes())
+            .await?;
+
+        let mut pairs = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).await? == 0 {
+                return Err("MPD closed the connection".into());
+            }
+            let line = line.trim_end();
+            if line == "OK" {
+                return Ok(pairs);
+            }
+            if let Some(error) = line.strip_prefix("ACK ") {
+                return Err(format!("MPD error: {}", error).into());
+            }
+            if let Some((key, value)) = line.split_once(": ") {
+                pairs.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+
+    async fn value(
+        &mut self,
+        command: &str,
+        key: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self
+            .command(command)
+            .await?
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v))
+    }
+
+    /// Adds `uri` to the queue and starts playing it, returning its song ID.
+    async fn play_uri(
+        &mut self,
+        uri: &str,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let id = self
+            .value(&format!("addid {}", quote(uri)), "Id")
+            .await?
+            .ok_or("MPD did not return a song id")?;
+        self.command(&format!("playid {}", id)).await?;
+        Ok(id)
+    }
+}
+
+/// Sends `audio` to the MPD server at `address` and starts playing it.
+pub async fn play(
+    audio: &[u8],
+    address: &str,
+    music_dir: Option<&Path>,
+    password: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let stream = TcpStream::connect(address).await?;
+    let peer = stream.peer_addr()?;
+    let mut mpd = MpdConnection::connect(stream).await?;
+    if let Some(password) = password {
+        mpd.command(&format!("password {}", quote(password)))
+            .await?;
+    }
+
+    if let Some(music_dir) = music_dir {
+        let digest = Sha256::digest(audio);
+        let name: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+        let uri = format!("{}/{}.mp3", MPD_SUBDIR, name);
+        tokio::fs::create_dir_all(music_dir.join(MPD_SUBDIR)).await?;
+        tokio::fs::write(music_dir.join(&uri), audio).await?;
+
+        // The file can only be added once MPD's database update has picked it up
+        mpd.command(&format!("update {}", quote(MPD_SUBDIR)))
+            .await?;
+        while mpd.value("status", "updating_db").await?.is_some() {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        mpd.play_uri(&uri).await?;
+        progress!("Playing {} on MPD at {}", uri, address);
+        return Ok(());
+    }
+
+    // Without access to the music directory, MPD streams the file from us
+    let (media_url, server) = crate::cast::serve_audio(audio, peer).await?;
+    let id = mpd.play_uri(&media_url).await?;
+    progress!("Playing {} on MPD at {}", media_url, address);
+
+    // Keep serving until MPD has moved on from our song
+    loop {
+        mpd.command("idle player").await?;
+        let status = mpd.command("status").await?;
+        let current = status.iter().find(|(k, _)| k == "songid").map(|(_, v)| v);
+        let stopped = status.iter().any(|(k, v)| k == "state" && v == "stop");
+        if stopped || current != Some(&id) {
+            break;
+        }
+    }
+
+    server.abort();
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! MQTT subscriber mode.
+//!
+//! Subscribes to one or more topics and speaks every published message through the
+//! playback/output pipeline, which makes tktts usable as a home-automation announcer.
+
+use crate::flood::Message;
+use crate::playback::PlaybackArgs;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+#[derive(clap::Args)]
+pub struct MqttArgs {
+    /// Broker address as HOST or HOST:PORT
+    #[arg(long)]
+    broker: String,
+
+    /// Topic filter to subscribe to (repeatable, wildcards allowed)
+    #[arg(long = "topic", value_name = "TOPIC", required = true)]
+    topics: Vec<String>,
+
+    /// MQTT client ID
+    #[arg(long, default_value = "tktts")]
+    client_id: String,
+
+    /// Broker username
+    #[arg(long)]
+    username: Option<String>,
+
+    /// Broker password
+    #[arg(long, requires = "username")]
+    password: Option<String>,
+
+    /// Voice used when a message does not pick one
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+
+    #[command(flatten)]
+    flood: crate::flood::FloodArgs,
+
+    #[command(flatten)]
+    playback: PlaybackArgs,
+}
+
+/// Payloads are either plain text or JSON like `{"text": "...", "voice": "en_us_006"}`.
+pub fn parse_payload(payload: &[u8]) -> Option<Message> {
+    let payload = String::from_utf8_lossy(payload);
+    let payload = payload.trim();
+
+    if payload.starts_with('{')
+        && let Ok(json) = serde_json::from_str::<serde_json::Value>(payload)
+    {
+        let text = json["text"].as_str()?.trim().to_string();
+        let voice = json["voice"].as_str().map(str::to_string);
+        return (!text.is_empty()).then_some(Message { text, voice });
+    }
+
+    (!payload.is_empty()).then(|| Message {
+        text: payload.to_string(),
+        voice: None,
+    })
+}
+
+fn parse_broker(broker: &str) -> Result<(String, u16), Box<dyn std::error::Error + Send + Sync>> {
+    let broker = broker.trim_start_matches("mqtt://");
+    match broker.rsplit_once(':') {
+        Some((host, port)) => Ok((host.to_string(), port.parse()?)),
+        None => Ok((broker.to_string(), DEFAULT_MQTT_PORT)),
+    }
+}
+
+pub async fn run(args: MqttArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let (host, port) = parse_broker(&args.broker)?;
+
+    let mut options = MqttOptions::new(&args.client_id, host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &args.username {
+        options.set_credentials(username, args.password.clone().unwrap_or_default());
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 10);
+
+    // Speak announcements sequentially so they never overlap
+    let queue = Arc::new(crate::flood::Queue::new(&args.flood, None));
+    let speaker = args.speaker.clone();
+    let playback = args.playback.clone();
+    tokio::spawn({
+        let queue = queue.clone();
+        async move {
+            while let Some(announcement) = queue.next().await {
+                let voice = announcement.voice.as_deref().unwrap_or(&speaker);
+                let result =
+                    match crate::synthesize(&announcement.text, voice, &session_id, &api_root_url)
+                        .await
+                    {
+                        Ok(audio) => playback.output(&audio).await,
+                        Err(e) => Err(e),
+                    };
+                queue.spoken();
+                if let Err(e) = result {
+                    crate::term::error(format!("Failed to speak announcement: {}", e));
+                }
+            }
+        }
+    });
+
+    loop {
+        match eventloop.poll().await {
+            // Subscriptions do not survive a reconnect with a clean session, so renew them
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                progress!("Connected to {}", args.broker);
+                for topic in &args.topics {
+                    client.try_subscribe(topic, QoS::AtLeastOnce)?;
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                if let Some(announcement) = parse_payload(&publish.payload) {
+                    progress!("{}: {}", publish.topic, announcement.text);
+                    let source = format!("{}: {}", publish.topic, announcement.text);
+                    queue.push(&source, announcement);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                crate::term::error(format!("MQTT connection error, reconnecting: {}", e));
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+}
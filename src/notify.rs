@@ -0,0 +1,132 @@
+//! `--notify` and `--notify-command`: a notice when a synthesis job ends.
+//!
+//! `--notify` shows a desktop notification saying whether the job worked, how long it
+//! took and where the audio went. `--notify-command` runs a shell command instead (or
+//! as well), for machines without a desktop: it sees `TKTTS_STATUS` (`success` or
+//! `failure`), `TKTTS_TITLE` and `TKTTS_MESSAGE` with the notification's text,
+//! `TKTTS_ELAPSED_SECS`, `TKTTS_VOICE`, `TKTTS_OUTPUT` with the (first) output path and
+//! `TKTTS_ERROR` for a failed job. Failing to notify is reported but keeps the job's result.
+
+use std::time::Instant;
+
+#[derive(clap::Args)]
+pub struct NotifyArgs {
+    /// Show a desktop notification when the job ends
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Shell command to run when the job ends, with the outcome in TKTTS_* variables
+    #[arg(long, value_name = "COMMAND")]
+    pub notify_command: Option<String>,
+}
+
+impl NotifyArgs {
+    fn enabled(&self) -> bool {
+        self.notify || self.command().is_some()
+    }
+
+    /// `--notify-command`, or `notify_command` from the config file.
+    fn command(&self) -> Option<&str> {
+        self.notify_command
+            .as_deref()
+            .or(crate::config::get().notify_command.as_deref())
+    }
+}
+
+/// The time a job took, like "4.2s" or "3m 05s".
+fn elapsed(secs: f64) -> String {
+    match secs {
+        ..60.0 => format!("{:.1}s", secs),
+        _ => {
+            let secs = secs.round() as u64;
+            match secs {
+                ..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+                _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+            }
+        }
+    }
+}
+
+/// Where the job's audio went, for the notification text.
+fn destination(paths: &[String]) -> Option<String> {
+    match paths {
+        [] => None,
+        [path] => Some(path.clone()),
+        [path, rest @ ..] => Some(format!("{} and {} more", path, rest.len())),
+    }
+}
+
+/// Notifies about the end of the job `started` earlier, which failed with `error` if given.
+pub async fn send(args: &NotifyArgs, voice: &str, started: Instant, error: Option<String>) {
+    if !args.enabled() {
+        return;
+    }
+    let secs = started.elapsed().as_secs_f64();
+    let paths = crate::hooks::paths();
+    let (title, message) = match &error {
+        None => (
+            "Speech ready",
+            match destination(&paths) {
+                Some(destination) => format!("Finished in {}: {}", elapsed(secs), destination),
+                None => format!("Finished in {}", elapsed(secs)),
+            },
+        ),
+        Some(error) => (
+            "Speech failed",
+            format!("Failed after {}: {}", elapsed(secs), error),
+        ),
+    };
+
+    if args.notify {
+        let (summary, body) = (format!("tktts: {}", title), message.clone());
+        let shown = tokio::task::spawn_blocking(move || {
+            notify_rust::Notification::new()
+                .appname("tktts")
+                .summary(&summary)
+                .body(&body)
+                .show()
+                .map(drop)
+        })
+        .await;
+        match shown {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => crate::term::error(format!("Failed to show a notification: {}", e)),
+            Err(e) => crate::term::error(format!("Failed to show a notification: {}", e)),
+        }
+    }
+
+    if let Some(line) = args.command() {
+        let mut command = tokio::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(line)
+            .stdin(std::process::Stdio::null())
+            .env(
+                "TKTTS_STATUS",
+                if error.is_some() {
+                    "failure"
+                } else {
+                    "success"
+                },
+            )
+            .env("TKTTS_TITLE", title)
+            .env("TKTTS_MESSAGE", &message)
+            .env("TKTTS_ELAPSED_SECS", format!("{:.3}", secs))
+            .env("TKTTS_VOICE", voice);
+        if let Some(path) = paths.first() {
+            command.env("TKTTS_OUTPUT", path);
+        }
+        if let Some(error) = &error {
+            command.env("TKTTS_ERROR", error);
+        }
+        match command.status().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                crate::term::error(format!("notify command '{}' exited with {}", line, status))
+            }
+            Err(e) => {
+                crate::term::error(format!("Failed to start notify command '{}': {}", line, e))
+            }
+        }
+    }
+}
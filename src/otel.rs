@@ -0,0 +1,69 @@
+//! Tracing spans for the synthesis stages, exported over OTLP with `--otel-endpoint`.
+//!
+//! Chunk requests, their attempts, decoding and output writing are `tracing` spans.
+//! Without an endpoint no subscriber is installed and the spans cost next to nothing;
+//! with one they go to an OpenTelemetry collector over gRPC, batched in the background.
+
+use opentelemetry::KeyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::TracerProvider;
+use std::sync::OnceLock;
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Layer as _, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+
+static PROVIDER: OnceLock<TracerProvider> = OnceLock::new();
+
+#[derive(clap::Args)]
+pub struct OtelArgs {
+    /// Export tracing spans to this OTLP gRPC collector, e.g. http://localhost:4317
+    #[arg(long, global = true, value_name = "URL")]
+    otel_endpoint: Option<String>,
+
+    /// Service name of the exported spans
+    #[arg(long, global = true, value_name = "NAME", default_value = "tktts")]
+    otel_service_name: String,
+}
+
+/// Starts exporting spans if an endpoint is given; needs the tokio runtime.
+pub fn init(args: &OtelArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(endpoint) = &args.otel_endpoint else {
+        return Ok(());
+    };
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|e| format!("Invalid OTLP endpoint {}: {}", endpoint, e))?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            args.otel_service_name.clone(),
+        )]))
+        .build();
+    let tracer = provider.tracer("tktts");
+    // Only tktts's own spans; those of hyper and the other dependencies would swamp them
+    let targets = Targets::new().with_target("tktts", Level::INFO);
+    tracing_subscriber::registry()
+        .with(
+            tracing_opentelemetry::layer()
+                .with_tracer(tracer)
+                .with_filter(targets),
+        )
+        .try_init()?;
+    let _ = PROVIDER.set(provider);
+    Ok(())
+}
+
+/// Sends the spans still buffered; call before the process exits.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        crate::term::error(format!("Failed to export traces: {}", e));
+    }
+}
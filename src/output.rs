@@ -0,0 +1,475 @@
+//! `--output` targets: a local file, an HTTP(S) URL written with PUT, or an S3 object.
+//!
+//! S3 uploads are signed with AWS Signature Version 4 using credentials from the AWS
+//! chain in its usual order: the `AWS_*` environment variables, the shared credentials
+//! file, a web identity token, the ECS container endpoint and the EC2 instance role
+//! (IMDSv2). SSO profiles are not read. Uploads carry the media type of the audio as
+//! written, by its bytes.
+
+use crate::config::UploadConfig;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// The ECS agent's credential endpoint, for `AWS_CONTAINER_CREDENTIALS_RELATIVE_URI`.
+const CONTAINER_ENDPOINT: &str = "http://169.254.170.2";
+
+/// The EC2 instance metadata service.
+const METADATA_ENDPOINT: &str = "http://169.254.169.254";
+
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encodes an S3 object key the way SigV4 canonical URIs expect, keeping `/`.
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Reads `[profile]` from `~/.aws/credentials` (or `$AWS_SHARED_CREDENTIALS_FILE`).
+fn credentials_from_file(profile: &str) -> Option<AwsCredentials> {
+    let path = match env::var_os("AWS_SHARED_CREDENTIALS_FILE") {
+        Some(path) => path.into(),
+        None => dirs::home_dir()?.join(".aws").join("credentials"),
+    };
+    let text = fs::read_to_string(path).ok()?;
+
+    let (mut in_profile, mut access_key_id, mut secret_access_key, mut session_token) =
+        (false, None, None, None);
+    for line in text.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_profile = section.trim() == profile;
+        } else if in_profile && let Some((key, value)) = line.split_once('=') {
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "aws_access_key_id" => access_key_id = value,
+                "aws_secret_access_key" => secret_access_key = value,
+                "aws_session_token" => session_token = value,
+                _ => {}
+            }
+        }
+    }
+
+    Some(AwsCredentials {
+        access_key_id: access_key_id?,
+        secret_access_key: secret_access_key?,
+        session_token,
+    })
+}
+
+/// Role credentials as the container and instance metadata endpoints return them.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: Option<String>,
+}
+
+impl From<RoleCredentials> for AwsCredentials {
+    fn from(role: RoleCredentials) -> Self {
+        AwsCredentials {
+            access_key_id: role.access_key_id,
+            secret_access_key: role.secret_access_key,
+            session_token: role.token,
+        }
+    }
+}
+
+/// A client for the local credential endpoints, which answer at once or not at all and
+/// must not go through a proxy.
+fn metadata_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .no_proxy()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .unwrap_or_default()
+}
+
+/// The text between `<tag>` and `</tag>` in an STS answer.
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..end].trim().to_string())
+}
+
+/// Exchanges `AWS_WEB_IDENTITY_TOKEN_FILE` for role credentials with STS, as on EKS.
+async fn credentials_from_web_identity()
+-> Result<Option<AwsCredentials>, Box<dyn std::error::Error + Send + Sync>> {
+    let (Ok(token_file), Ok(role_arn)) = (
+        env::var("AWS_WEB_IDENTITY_TOKEN_FILE"),
+        env::var("AWS_ROLE_ARN"),
+    ) else {
+        return Ok(None);
+    };
+    let token = fs::read_to_string(&token_file)
+        .map_err(|e| format!("Failed to read web identity token {}: {}", token_file, e))?;
+    let endpoint = match env::var("AWS_REGION").or_else(|_| env::var("AWS_DEFAULT_REGION")) {
+        Ok(region) => format!("https://sts.{}.amazonaws.com/", region),
+        Err(_) => "https://sts.amazonaws.com/".to_string(),
+    };
+    let session_name = env::var("AWS_ROLE_SESSION_NAME").unwrap_or_else(|_| "tktts".to_string());
+    let response = crate::http::client()
+        .get(endpoint)
+        .query(&[
+            ("Action", "AssumeRoleWithWebIdentity"),
+            ("Version", "2011-06-15"),
+            ("RoleArn", &role_arn),
+            ("RoleSessionName", &session_name),
+            ("WebIdentityToken", token.trim()),
+        ])
+        .send()
+        .await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(format!(
+            "STS refused the web identity token ({}): {}",
+            status,
+            body.trim()
+        )
+        .into());
+    }
+    let (Some(access_key_id), Some(secret_access_key)) = (
+        xml_value(&body, "AccessKeyId"),
+        xml_value(&body, "SecretAccessKey"),
+    ) else {
+        return Err("STS answered without credentials".into());
+    };
+    Ok(Some(AwsCredentials {
+        access_key_id,
+        secret_access_key,
+        session_token: xml_value(&body, "SessionToken"),
+    }))
+}
+
+/// Reads the task role from the ECS container endpoint, when the agent set one up.
+async fn credentials_from_container()
+-> Result<Option<AwsCredentials>, Box<dyn std::error::Error + Send + Sync>> {
+    let url = match (
+        env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI"),
+        env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI"),
+    ) {
+        (Ok(path), _) => format!("{}{}", CONTAINER_ENDPOINT, path),
+        (_, Ok(url)) => url,
+        _ => return Ok(None),
+    };
+    let token = match env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE") {
+        Ok(path) => Some(fs::read_to_string(path)?.trim().to_string()),
+        Err(_) => env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN").ok(),
+    };
+    let mut request = metadata_client().get(&url);
+    if let Some(token) = token {
+        request = request.header("Authorization", token);
+    }
+    let role: RoleCredentials = request
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| format!("Failed to get container credentials from {}: {}", url, e))?
+        .json()
+        .await?;
+    Ok(Some(role.into()))
+}
+
+/// Reads the instance role from the EC2 metadata service with an IMDSv2 session token.
+/// Anywhere but on EC2 the service does not answer, which is not an error.
+async fn credentials_from_instance()
+-> Result<Option<AwsCredentials>, Box<dyn std::error::Error + Send + Sync>> {
+    if env::var("AWS_EC2_METADATA_DISABLED").is_ok_and(|value| value.eq_ignore_ascii_case("true")) {
+        return Ok(None);
+    }
+    let client = metadata_client();
+    let Ok(response) = client
+        .put(format!("{}/latest/api/token", METADATA_ENDPOINT))
+        .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+        .send()
+        .await
+    else {
+        return Ok(None);
+    };
+    let token = response.error_for_status()?.text().await?;
+    let roles_url = format!(
+        "{}/latest/meta-data/iam/security-credentials/",
+        METADATA_ENDPOINT
+    );
+    let get = |url: String| {
+        client
+            .get(url)
+            .header("X-aws-ec2-metadata-token", &token)
+            .send()
+    };
+    let roles = get(roles_url.clone()).await?;
+    // An instance without a role has no credentials to give
+    if roles.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let roles = roles.error_for_status()?.text().await?;
+    let Some(role) = roles.lines().map(str::trim).find(|role| !role.is_empty()) else {
+        return Ok(None);
+    };
+    let role: RoleCredentials = get(format!("{}{}", roles_url, role))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(Some(role.into()))
+}
+
+async fn aws_credentials() -> Result<AwsCredentials, Box<dyn std::error::Error + Send + Sync>> {
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        env::var("AWS_ACCESS_KEY_ID"),
+        env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Ok(AwsCredentials {
+            access_key_id,
+            secret_access_key,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+        });
+    }
+
+    let profile = env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string());
+    if let Some(credentials) = credentials_from_file(&profile) {
+        return Ok(credentials);
+    }
+    if let Some(credentials) = credentials_from_web_identity().await? {
+        return Ok(credentials);
+    }
+    if let Some(credentials) = credentials_from_container().await? {
+        return Ok(credentials);
+    }
+    if let Some(credentials) = credentials_from_instance().await? {
+        return Ok(credentials);
+    }
+    Err(format!(
+        "No AWS credentials found. Set AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY, add a [{}] profile to ~/.aws/credentials or run with a web identity, container or instance role (SSO profiles are not supported)",
+        profile
+    )
+    .into())
+}
+
+async fn put_s3(
+    location: &str,
+    body: &[u8],
+    content_type: &str,
+    config: &UploadConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (bucket, key) = location
+        .split_once('/')
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or("S3 output must look like s3://bucket/key.mp3")?;
+    let credentials = aws_credentials().await?;
+    let region = env::var("AWS_REGION")
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .ok()
+        .or_else(|| config.s3_region.clone())
+        .unwrap_or_else(|| "us-east-1".to_string());
+
+    // Custom endpoints (MinIO, R2, ...) use path-style addressing
+    let (host, canonical_uri, scheme) = match &config.s3_endpoint {
+        Some(endpoint) => {
+            let endpoint = url::Url::parse(endpoint)?;
+            let host = match endpoint.port() {
+                Some(port) => format!("{}:{}", endpoint.host_str().unwrap_or_default(), port),
+                None => endpoint.host_str().unwrap_or_default().to_string(),
+            };
+            let uri = format!("/{}/{}", encode_key(bucket), encode_key(key));
+            (host, uri, endpoint.scheme().to_string())
+        }
+        None => (
+            format!("{}.s3.{}.amazonaws.com", bucket, region),
+            format!("/{}", encode_key(key)),
+            "https".to_string(),
+        ),
+    };
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex(&Sha256::digest(body));
+
+    let mut headers = vec![
+        ("content-type", content_type.to_string()),
+        ("host", host.clone()),
+        ("x-amz-content-sha256", payload_hash.clone()),
+        ("x-amz-date", amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "PUT\n{}\n\n{}\n{}\n{}",
+        canonical_uri, canonical_headers, signed_headers, payload_hash
+    );
+
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let signing_key = ["s3", "aws4_request"].iter().fold(
+        hmac_sha256(
+            &hmac_sha256(
+                format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+                &date,
+            ),
+            &region,
+        ),
+        |key, part| hmac_sha256(&key, part),
+    );
+    let signature = hex(&hmac_sha256(&signing_key, &string_to_sign));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    );
+
+    let mut request = crate::http::client()
+        .put(format!("{}://{}{}", scheme, host, canonical_uri))
+        .header("Authorization", authorization)
+        .body(body.to_vec());
+    for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+        request = request.header(name, value);
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("S3 upload failed ({}): {}", status, body.trim()).into());
+    }
+    Ok(())
+}
+
+async fn put_http(
+    url: &str,
+    body: &[u8],
+    content_type: &str,
+    config: &UploadConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut request = crate::http::client()
+        .put(url)
+        .header("Content-Type", content_type)
+        .body(body.to_vec());
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(format!("Upload to {} failed ({})", url, response.status()).into());
+    }
+    Ok(())
+}
+
+/// Whether `target` is uploaded rather than written to stdout or a local file.
+pub fn is_remote(target: &str) -> bool {
+    target.starts_with("s3://") || target.starts_with("http://") || target.starts_with("https://")
+}
+
+async fn put(
+    target: &str,
+    body: &[u8],
+    content_type: &str,
+    config: &UploadConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(location) = target.strip_prefix("s3://") {
+        put_s3(location, body, content_type, config).await?;
+        crate::term::status(crate::term::Style::Green, "Uploaded", target);
+    } else if target.starts_with("http://") || target.starts_with("https://") {
+        put_http(target, body, content_type, config).await?;
+        crate::term::status(crate::term::Style::Green, "Uploaded", target);
+    } else {
+        fs::write(target, body)?;
+    }
+    Ok(())
+}
+
+/// Writes `audio` to `target`, which is `-` (stdout), a URL, an `s3://` location or a path.
+#[tracing::instrument(name = "output", skip_all, fields(target = target, bytes = audio.len()))]
+pub async fn write(
+    target: &str,
+    audio: &[u8],
+    config: &UploadConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if target == "-" {
+        io::stdout().write_all(audio)?;
+        return Ok(());
+    }
+    let content_type = crate::sniff::sniff(audio).map_or("audio/mpeg", |format| format.mime_type());
+    put(target, audio, content_type, config).await
+}
+
+/// Writes the `--sidecar` metadata next to `target` as `<target>.json`.
+pub async fn write_sidecar(
+    target: &str,
+    metadata: &serde_json::Value,
+    config: &UploadConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if target == "-" {
+        return Ok(());
+    }
+    let json = serde_json::to_vec_pretty(metadata)?;
+    put(
+        &format!("{}.json", target),
+        &json,
+        "application/json",
+        config,
+    )
+    .await
+}
+
+/// Writes `<target>.sha256` for `audio` written to `target`, as a second upload for
+/// remote targets.
+pub async fn write_checksum(
+    target: &str,
+    audio: &[u8],
+    config: &UploadConfig,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if target == "-" {
+        return Ok(());
+    }
+    let contents = crate::checksum::contents(&crate::checksum::sha256_hex(audio), target);
+    put(
+        &format!("{}.sha256", target),
+        contents.as_bytes(),
+        "text/plain",
+        config,
+    )
+    .await
+}
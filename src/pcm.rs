@@ -0,0 +1,329 @@
+//! Decoded audio for modes that edit the sound itself rather than joining MP3 frames:
+//! MP3 and WAV decoding, resampling, time stretching and WAV, AIFF or MP3 output. Audio is
+//! mono throughout; only the stereo WAV output has two channels.
+//!
+//! This work is CPU-bound, so async code runs it through [`offload`]: on the blocking
+//! thread pool, one job per core, while the network requests go on on the runtime.
+
+use crate::TktsError;
+use std::sync::LazyLock;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tokio::sync::Semaphore;
+
+/// Permits for audio jobs in flight, one per core.
+static WORKERS: LazyLock<Semaphore> = LazyLock::new(|| {
+    Semaphore::new(std::thread::available_parallelism().map_or(4, std::num::NonZero::get))
+});
+
+/// Mono samples in -1.0..=1.0.
+pub struct Pcm {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl Pcm {
+    pub fn duration_secs(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+}
+
+/// Sample rate of the API's audio.
+pub const API_SAMPLE_RATE: u32 = 24_000;
+
+/// Decodes an MP3 stream, mixing any channels down to mono.
+pub fn decode(mp3: &[u8]) -> Result<Pcm, TktsError> {
+    decode_as(mp3, "mp3")
+}
+
+/// Decodes an MP3 or WAV file, guessing the format from `extension` first.
+pub fn decode_as(audio: &[u8], extension: &str) -> Result<Pcm, TktsError> {
+    decoded(audio, extension).map_err(TktsError::Decode)
+}
+
+fn decoded(audio: &[u8], extension: &str) -> Result<Pcm, Box<dyn std::error::Error + Send + Sync>> {
+    let source = MediaSourceStream::new(
+        Box::new(std::io::Cursor::new(audio.to_vec())),
+        Default::default(),
+    );
+    let mut hint = Hint::new();
+    hint.with_extension(extension);
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?
+        .format;
+    let track = format.default_track().ok_or("No audio track")?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut pcm = Pcm {
+        sample_rate: track.codec_params.sample_rate.unwrap_or(API_SAMPLE_RATE),
+        samples: Vec::new(),
+    };
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged frame only loses its own samples
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        pcm.sample_rate = spec.rate;
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        pcm.samples.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+    Ok(pcm)
+}
+
+/// Runs `work` on the blocking thread pool once a core is free, so decoding and encoding
+/// neither holds up the runtime's network tasks nor runs more jobs than there are cores.
+pub async fn offload<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    // The semaphore is never closed
+    let _permit = WORKERS.acquire().await.unwrap();
+    tokio::task::spawn_blocking(work)
+        .await
+        .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// Decodes all of `chunks` side by side through [`offload`], in order.
+pub async fn decode_all(chunks: &[Vec<u8>]) -> Result<Vec<Pcm>, TktsError> {
+    futures_util::future::try_join_all(chunks.iter().map(|chunk| {
+        let chunk = chunk.clone();
+        offload(move || decode(&chunk))
+    }))
+    .await
+}
+
+/// Converts to `sample_rate` by linear interpolation.
+pub fn resample(pcm: Pcm, sample_rate: u32) -> Pcm {
+    if pcm.sample_rate == sample_rate || pcm.samples.is_empty() {
+        return Pcm { sample_rate, ..pcm };
+    }
+    let step = pcm.sample_rate as f64 / sample_rate as f64;
+    let len = (pcm.samples.len() as f64 / step) as usize;
+    let last = pcm.samples.len() - 1;
+    let samples = (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = (position as usize).min(last);
+            let next = (index + 1).min(last);
+            let fraction = (position - index as f64) as f32;
+            pcm.samples[index] * (1.0 - fraction) + pcm.samples[next] * fraction
+        })
+        .collect();
+    Pcm {
+        sample_rate,
+        samples,
+    }
+}
+
+/// Changes the length of `pcm` by `factor` (below 1 is faster) without changing its
+/// pitch, using overlap-add of windowed segments that are each shifted a little to line
+/// up with the previous one (WSOLA), which keeps voices from sounding rough.
+pub fn stretch(pcm: &Pcm, factor: f64) -> Pcm {
+    let frame = (pcm.sample_rate as usize * 30 / 1000).max(16);
+    let input = &pcm.samples;
+    if (factor - 1.0).abs() < 0.01 || input.len() <= frame * 2 {
+        return Pcm {
+            sample_rate: pcm.sample_rate,
+            samples: input.clone(),
+        };
+    }
+    let hop_out = frame / 2;
+    let hop_in = hop_out as f64 / factor;
+    let tolerance = frame / 4;
+    let window: Vec<f32> = (0..frame)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / frame as f32).cos())
+        .collect();
+
+    let target = (input.len() as f64 * factor) as usize;
+    let mut output = vec![0.0f32; target + frame];
+    let mut weight = vec![0.0f32; target + frame];
+    let last_start = input.len() - frame;
+    let mut previous = 0usize;
+
+    for k in 0.. {
+        let out_start = k * hop_out;
+        if out_start >= target {
+            break;
+        }
+        let nominal = ((k as f64 * hop_in) as usize).min(last_start);
+        let start = if k == 0 {
+            0
+        } else {
+            // The segment most like the natural continuation of the previous one
+            let natural = (previous + hop_out).min(last_start);
+            let reference = &input[natural..natural + hop_out];
+            let low = nominal.saturating_sub(tolerance);
+            let high = (nominal + tolerance).min(last_start);
+            (low..=high)
+                .max_by(|&a, &b| {
+                    let score = |start: usize| -> f32 {
+                        reference
+                            .iter()
+                            .zip(&input[start..start + hop_out])
+                            .map(|(x, y)| x * y)
+                            .sum()
+                    };
+                    score(a).total_cmp(&score(b))
+                })
+                .unwrap_or(nominal)
+        };
+        for i in 0..frame {
+            output[out_start + i] += input[start + i] * window[i];
+            weight[out_start + i] += window[i];
+        }
+        previous = start;
+    }
+
+    output.truncate(target);
+    for (sample, weight) in output.iter_mut().zip(&weight) {
+        if *weight > 1e-3 {
+            *sample /= weight;
+        }
+    }
+    Pcm {
+        sample_rate: pcm.sample_rate,
+        samples: output,
+    }
+}
+
+/// `pcm` spoken `speed` times as fast and `pitch` times as high, with its samples scaled
+/// by `gain`.
+pub fn voiced(mut pcm: Pcm, speed: f64, pitch: f64, gain: f32) -> Pcm {
+    if (pitch - 1.0).abs() > 0.001 {
+        // Played faster the voice is both higher and shorter; the stretch below restores the length
+        let sample_rate = pcm.sample_rate;
+        pcm = resample(pcm, (f64::from(sample_rate) / pitch).round() as u32);
+        pcm.sample_rate = sample_rate;
+    }
+    pcm = stretch(&pcm, pitch / speed);
+    if (gain - 1.0).abs() > 0.001 {
+        for sample in &mut pcm.samples {
+            *sample = (*sample * gain).clamp(-1.0, 1.0);
+        }
+    }
+    pcm
+}
+
+/// A 16-bit mono WAV file.
+pub fn wav(pcm: &Pcm) -> Vec<u8> {
+    interleaved_wav(pcm.sample_rate, 1, &pcm.samples)
+}
+
+/// A 16-bit stereo WAV file; the shorter channel is padded with silence.
+pub fn stereo_wav(sample_rate: u32, left: &[f32], right: &[f32]) -> Vec<u8> {
+    let frames = left.len().max(right.len());
+    let samples: Vec<f32> = (0..frames)
+        .flat_map(|i| {
+            [
+                left.get(i).copied().unwrap_or(0.0),
+                right.get(i).copied().unwrap_or(0.0),
+            ]
+        })
+        .collect();
+    interleaved_wav(sample_rate, 2, &samples)
+}
+
+fn interleaved_wav(sample_rate: u32, channels: u16, samples: &[f32]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let block_align = channels * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+/// A 16-bit mono AIFF file, the format macOS `say` writes.
+pub fn aiff(pcm: &Pcm) -> Vec<u8> {
+    let data_len = pcm.samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(54 + data_len as usize);
+    bytes.extend_from_slice(b"FORM");
+    bytes.extend_from_slice(&(46 + data_len).to_be_bytes());
+    bytes.extend_from_slice(b"AIFFCOMM");
+    bytes.extend_from_slice(&18u32.to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&(pcm.samples.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&16u16.to_be_bytes());
+    // The sample rate as an 80-bit extended float: exponent, then a normalized mantissa
+    let rate = pcm.sample_rate.max(1);
+    let shift = 31 - rate.leading_zeros();
+    bytes.extend_from_slice(&(16383 + shift as u16).to_be_bytes());
+    bytes.extend_from_slice(&((rate as u64) << (63 - shift)).to_be_bytes());
+    bytes.extend_from_slice(b"SSND");
+    bytes.extend_from_slice(&(8 + data_len).to_be_bytes());
+    bytes.extend_from_slice(&[0; 8]); // offset and block size
+    for sample in &pcm.samples {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&sample.to_be_bytes());
+    }
+    bytes
+}
+
+/// A mono MP3 stream without a VBR header frame, so it can be joined to the API's audio
+/// like another chunk.
+pub fn mp3(pcm: &Pcm) -> Result<Vec<u8>, TktsError> {
+    encoded(pcm).map_err(TktsError::Decode)
+}
+
+fn encoded(pcm: &Pcm) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, MonoPcm, Quality};
+
+    let mut builder = Builder::new().ok_or("Failed to set up the MP3 encoder")?;
+    builder.set_num_channels(1)?;
+    builder.set_sample_rate(pcm.sample_rate)?;
+    builder.set_brate(Bitrate::Kbps64)?;
+    builder.set_quality(Quality::Good)?;
+    builder.set_to_write_vbr_tag(false)?;
+    let mut encoder = builder.build()?;
+
+    let samples: Vec<i16> = pcm
+        .samples
+        .iter()
+        .map(|sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect();
+    let mut mp3 = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(samples.len()));
+    encoder.encode_to_vec(MonoPcm(&samples), &mut mp3)?;
+    mp3.reserve(7200);
+    encoder.flush_to_vec::<FlushNoGap>(&mut mp3)?;
+    Ok(mp3)
+}
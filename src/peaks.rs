@@ -0,0 +1,93 @@
+//! `--peaks FILE`: the waveform of the final audio as JSON in the format of BBC's
+//! audiowaveform, so web players such as peaks.js draw it without analyzing the audio.
+//!
+//! Every `--peaks-zoom` samples become one pixel, a pair of the lowest and highest
+//! sample, scaled to `--peaks-bits` signed integers. The audio is mixed down to mono.
+
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static ARGS: OnceLock<PeaksArgs> = OnceLock::new();
+
+#[derive(clap::Args, Clone)]
+pub struct PeaksArgs {
+    /// Write the waveform of the audio as audiowaveform JSON to FILE
+    #[arg(id = "peaks", long = "peaks", value_name = "FILE", conflicts_with_all = ["url_only", "files", "split_output", "translate_to", "listen_fifo"])]
+    path: Option<PathBuf>,
+
+    /// Samples per pixel of the --peaks waveform
+    #[arg(long, value_name = "N", default_value_t = 256, value_parser = clap::value_parser!(u32).range(1..), requires = "peaks")]
+    peaks_zoom: u32,
+
+    /// Resolution of the --peaks values: 8 (-128 to 127) or 16 bits (-32768 to 32767)
+    #[arg(
+        long,
+        value_name = "BITS",
+        default_value = "8",
+        value_parser = clap::builder::TypedValueParser::map(
+            clap::builder::PossibleValuesParser::new(["8", "16"]),
+            |bits| bits.parse::<u8>().unwrap()
+        ),
+        requires = "peaks"
+    )]
+    peaks_bits: u8,
+}
+
+pub fn init(args: PeaksArgs) {
+    if args.path.is_some() {
+        let _ = ARGS.set(args);
+    }
+}
+
+pub fn enabled() -> bool {
+    ARGS.get().is_some()
+}
+
+/// The lowest and highest sample of each stretch of `size` samples.
+pub fn min_max(samples: &[f32], size: usize) -> Vec<(f32, f32)> {
+    samples
+        .chunks(size.max(1))
+        .map(|stretch| {
+            stretch.iter().fold((0.0f32, 0.0f32), |(low, high), &s| {
+                (low.min(s), high.max(s))
+            })
+        })
+        .collect()
+}
+
+/// Writes the `--peaks` file for `audio`, if one was asked for.
+pub fn write(audio: &[u8]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(args) = ARGS.get() else {
+        return Ok(());
+    };
+    let path = args.path.as_ref().unwrap();
+    let pcm = crate::sniff::decode(audio)?;
+    let scale = match args.peaks_bits {
+        8 => i8::MAX as f32,
+        _ => i16::MAX as f32,
+    };
+    let pixels = min_max(&pcm.samples, args.peaks_zoom as usize);
+    let data: Vec<i32> = pixels
+        .iter()
+        .flat_map(|&(low, high)| [low, high])
+        .map(|sample| (sample.clamp(-1.0, 1.0) * scale).round() as i32)
+        .collect();
+    let waveform = json!({
+        "version": 2,
+        "channels": 1,
+        "sample_rate": pcm.sample_rate,
+        "samples_per_pixel": args.peaks_zoom,
+        "bits": args.peaks_bits,
+        "length": pixels.len(),
+        "data": data,
+    });
+    std::fs::write(path, serde_json::to_vec(&waveform)?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    crate::term::status(
+        crate::term::Style::Green,
+        "Wrote",
+        format!("waveform {}", path.display()),
+    );
+    Ok(())
+}
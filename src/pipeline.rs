@@ -0,0 +1,192 @@
+//! Streaming pipeline for large inputs: chunk → synthesize → decode → write.
+//!
+//! Text is read and split incrementally, at most `jobs` chunks are in flight, and audio is
+//! written in order as soon as each chunk is ready. Memory therefore grows with the
+//! concurrency, not with the size of the document; only the audio of chunks the cache
+//! could not keep is held, for their repeats.
+
+use crate::term::{self, Style};
+use futures_util::future::Shared;
+use futures_util::{FutureExt, StreamExt};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+
+/// Stdin is split whenever this much text has accumulated, at the next line break.
+const READ_BLOCK: usize = 64 * 1024;
+
+pub enum Source {
+    Text(String),
+    Stdin,
+}
+
+/// Where the audio of a chunk came from.
+enum Origin {
+    Cached,
+    Requested,
+    /// An earlier copy of the same chunk in this run
+    Repeated,
+}
+
+/// Splits the source into chunks on a blocking thread, sending them as they are found.
+///
+/// Text filters see stdin one block at a time. The voice for the run is chosen from the
+/// language of the first block, and sent before any chunk.
+fn produce_chunks(
+    source: Source,
+    speaker: &str,
+    voice: oneshot::Sender<String>,
+    chunks: mpsc::Sender<String>,
+) -> io::Result<()> {
+    let mut budget = crate::limits::Budget::default();
+    let mut voice = Some(voice);
+    let mut send_all = |text: &str| -> io::Result<bool> {
+        let text = crate::vars::render(text, &Default::default()).map_err(io::Error::other)?;
+        if let Some(voice) = voice.take() {
+            let _ = voice.send(crate::lang::select(&text, speaker));
+        }
+        let sent = crate::split_text_within(&text, crate::BYTE_LIMIT, &mut budget)
+            .map_err(io::Error::other)?
+            .into_iter()
+            .all(|chunk| chunks.blocking_send(chunk).is_ok());
+        Ok(sent && !budget.exhausted())
+    };
+
+    match source {
+        Source::Text(text) => {
+            send_all(&text)?;
+        }
+        Source::Stdin => {
+            let mut stdin = io::stdin().lock();
+            // UTF-16 cannot be read line by line as bytes, so it is decoded whole
+            if matches!(stdin.fill_buf()?, [0xFF, 0xFE, ..] | [0xFE, 0xFF, ..]) {
+                let mut bytes = Vec::new();
+                stdin.read_to_end(&mut bytes)?;
+                send_all(&crate::console::decode(&bytes)?)?;
+                return Ok(());
+            }
+            let mut buffer = Vec::new();
+            loop {
+                let eof = stdin.read_until(b'\n', &mut buffer)? == 0;
+                if eof || buffer.len() >= READ_BLOCK {
+                    // Stop at the end of input, or when the consumer gave up
+                    if !send_all(&crate::console::decode(&buffer)?)? || eof {
+                        break;
+                    }
+                    buffer.clear();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Synthesizes `source` and writes the audio to `sink`, keeping at most `jobs` requests
+/// in flight. A failed chunk stops the run; audio before it has already been written.
+///
+/// Returns the number of chunks and audio bytes written.
+pub async fn run(
+    source: Source,
+    speaker: &str,
+    session_id: &str,
+    api_root_url: &str,
+    jobs: usize,
+    sink: &mut dyn Write,
+) -> Result<(usize, usize), Box<dyn std::error::Error + Send + Sync>> {
+    let started = Instant::now();
+    let jobs = jobs.max(1);
+    let (sender, receiver) = mpsc::channel(jobs);
+    let (voice_sender, voice) = oneshot::channel();
+    let requested = speaker.to_string();
+    let producer = tokio::task::spawn_blocking(move || {
+        produce_chunks(source, &requested, voice_sender, sender)
+    });
+    // Without any text read there is no voice chosen, nor anything to read with it
+    let speaker = &voice.await.unwrap_or_else(|_| speaker.to_string());
+
+    let chunks = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+    // Repeated chunks (choruses, templated lines) wait for the first copy's audio rather
+    // than being requested again. Only audio the cache could not keep is held for the run.
+    let mut firsts: HashMap<String, Shared<oneshot::Receiver<Arc<Vec<u8>>>>> = HashMap::new();
+    let audio = chunks
+        .enumerate()
+        .map(move |(index, chunk)| {
+            let voice = crate::chunk_voice(&chunk, index, speaker);
+            let (first, sender) = match &voice {
+                Ok(voice) => match firsts.entry(crate::cache::key(&chunk, voice)) {
+                    Entry::Occupied(entry) => (Some(entry.get().clone()), None),
+                    Entry::Vacant(entry) => {
+                        let (sender, receiver) = oneshot::channel();
+                        entry.insert(receiver.shared());
+                        (None, Some(sender))
+                    }
+                },
+                Err(_) => (None, None),
+            };
+            async move {
+                let voice = match voice {
+                    Ok(voice) => voice,
+                    Err(e) => return (index, Origin::Requested, Err(e)),
+                };
+                // A first copy that was cached sends nothing; the repeat reads the cache
+                if let Some(first) = first
+                    && let Ok(audio) = first.await
+                {
+                    term::status(Style::Green, "Reusing", format!("chunk {}", index + 1));
+                    return (index, Origin::Repeated, Ok(audio.to_vec()));
+                }
+                if let Some(audio) = crate::cache::get(&chunk, &voice) {
+                    term::status(Style::Green, "Cached", format!("chunk {}", index + 1));
+                    return (index, Origin::Cached, Ok(audio));
+                }
+                term::status(Style::Cyan, "Requesting", format!("chunk {}", index + 1));
+                let result = crate::fetch_chunk(&chunk, &voice, session_id, api_root_url).await;
+                if let (Ok(audio), Some(sender)) = (&result, sender)
+                    && !crate::cache::contains(&chunk, &voice)
+                {
+                    let _ = sender.send(Arc::new(audio.clone()));
+                }
+                (index, Origin::Requested, result)
+            }
+        })
+        .buffered(jobs);
+    let mut audio = std::pin::pin!(audio);
+
+    let (mut hits, mut misses, mut repeats, mut bytes) = (0, 0, 0, 0);
+    while let Some((index, origin, result)) = audio.next().await {
+        match origin {
+            Origin::Cached => hits += 1,
+            Origin::Requested => misses += 1,
+            Origin::Repeated => repeats += 1,
+        }
+        let data = match result {
+            Ok(data) => data,
+            Err(e) => {
+                crate::cache::record(hits, misses);
+                return Err(crate::ChunkFailed {
+                    number: index + 1,
+                    source: e,
+                }
+                .into());
+            }
+        };
+        sink.write_all(&data)?;
+        sink.flush()?;
+        bytes += data.len();
+    }
+    crate::cache::record(hits, misses);
+
+    producer.await??;
+    let chunks = (hits + misses + repeats) as usize;
+    if chunks == 0 {
+        return Err("No text provided via arguments or stdin".into());
+    }
+    term::summary(chunks, hits as usize, bytes, started.elapsed());
+    crate::script::on_complete(chunks, bytes, speaker);
+    Ok((chunks, bytes))
+}
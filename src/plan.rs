@@ -0,0 +1,152 @@
+//! `tktts plan` and `tktts join`: record how a text is chunked, then assemble the final
+//! audio from cached chunks without touching the network.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize)]
+pub struct Plan {
+    /// [`tktts::NORMALIZATION_VERSION`] the chunks were made with; 0 for plans written
+    /// before it was recorded
+    #[serde(default)]
+    pub normalization: u32,
+    pub voice: String,
+    pub chunks: Vec<PlannedChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlannedChunk {
+    pub text: String,
+    /// The voice code the chunk is synthesized with, as a synthesis would pick it; for
+    /// plans written before it was recorded, the plan's voice
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub voice: String,
+    pub cache_key: String,
+}
+
+impl PlannedChunk {
+    fn voice<'a>(&'a self, plan: &'a Plan) -> &'a str {
+        match self.voice.is_empty() {
+            true => crate::config::resolve_voice(&plan.voice),
+            false => &self.voice,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct PlanArgs {
+    /// Text to plan (read from stdin when omitted)
+    text: Vec<String>,
+
+    /// TikTok speaker voice
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+
+    /// Where to write the plan (default: stdout)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+pub struct JoinArgs {
+    /// Plan file written by `tktts plan`
+    #[arg(long, value_name = "FILE")]
+    plan: PathBuf,
+
+    /// Output file, URL or s3:// location (default: stdout)
+    ///
+    /// S3 credentials come from the AWS chain: AWS_* variables, ~/.aws/credentials, web
+    /// identity, container or instance roles. SSO profiles are not read.
+    #[arg(short, long, value_name = "TARGET", default_value = "-")]
+    output: String,
+}
+
+pub fn plan(args: PlanArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = if args.text.is_empty() {
+        crate::console::read_stdin()?.trim().to_string()
+    } else {
+        args.text.join(" ")
+    };
+    if text.is_empty() {
+        return Err("No text provided via arguments or stdin".into());
+    }
+
+    // Voices are chosen as a synthesis of the same text chooses them, so the cache keys
+    // are those its chunks are cached under
+    let chunks = crate::split_text(&text, crate::BYTE_LIMIT)?;
+    let speaker = crate::catalog::pick(&args.speaker)?;
+    let speaker = crate::lang::select(&chunks.join(" "), &speaker);
+    let chunks = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let voice = crate::chunk_voice(&chunk, index, &speaker)?;
+            Ok(PlannedChunk {
+                cache_key: crate::cache::key(&chunk, &voice),
+                text: chunk,
+                voice,
+            })
+        })
+        .collect::<Result<_, Box<dyn std::error::Error + Send + Sync>>>()?;
+    let plan = Plan {
+        normalization: tktts::NORMALIZATION_VERSION,
+        voice: crate::config::resolve_voice(&speaker).to_string(),
+        chunks,
+    };
+
+    let json = serde_json::to_string_pretty(&plan)?;
+    match args.output {
+        Some(path) => fs::write(path, json + "\n")?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+pub async fn join(args: JoinArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let plan: Plan = serde_json::from_str(&fs::read_to_string(&args.plan)?)
+        .map_err(|e| format!("Invalid plan {}: {}", args.plan.display(), e))?;
+    if plan.normalization != tktts::NORMALIZATION_VERSION {
+        return Err(format!(
+            "{} was made with text normalization version {}, but this tktts uses version {}; run `tktts plan` again",
+            args.plan.display(),
+            plan.normalization,
+            tktts::NORMALIZATION_VERSION
+        )
+        .into());
+    }
+
+    let mut audio = Vec::new();
+    let mut missing = Vec::new();
+    for (index, chunk) in plan.chunks.iter().enumerate() {
+        // A changed voice profile sends different text for the same chunk
+        let voice = chunk.voice(&plan);
+        if crate::cache::key(&chunk.text, voice) != chunk.cache_key {
+            return Err(format!(
+                "Chunk {} of {} no longer has the planned cache key; the voice's text profile has changed since, so run `tktts plan` again",
+                index + 1,
+                args.plan.display()
+            )
+            .into());
+        }
+        match crate::cache::get(&chunk.text, voice) {
+            Some(data) => audio.extend(data),
+            None => missing.push(index + 1),
+        }
+    }
+
+    if !missing.is_empty() {
+        let list: Vec<String> = missing.iter().map(|i| i.to_string()).collect();
+        return Err(format!(
+            "{} of {} chunks are not cached (chunks {}); synthesize the same text with voice {} first",
+            missing.len(),
+            plan.chunks.len(),
+            list.join(", "),
+            plan.voice
+        )
+        .into());
+    }
+
+    let config = crate::config::get();
+    crate::output::write(&args.output, &audio, &config.upload).await
+}
@@ -0,0 +1,164 @@
+//! Audio output for the CLI and the long-running modes: raw bytes on stdout (for piping
+//! into another program), playback through a local player, casting to a speaker, or MPD.
+
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// Players tried in order when `--player` is not given. Each reads audio from stdin.
+#[cfg(not(windows))]
+const DEFAULT_PLAYERS: &[&str] = &[
+    "mpv --no-terminal --really-quiet -",
+    "ffplay -nodisp -autoexit -loglevel quiet -",
+];
+
+/// On Windows both go through WASAPI, mpv by flag and ffplay through SDL's
+/// `SDL_AUDIODRIVER`, instead of the older APIs they may pick on some systems.
+#[cfg(windows)]
+const DEFAULT_PLAYERS: &[&str] = &[
+    "mpv --no-terminal --really-quiet --ao=wasapi -",
+    "ffplay -nodisp -autoexit -loglevel quiet -",
+];
+
+#[derive(clap::Args, Clone)]
+pub struct PlaybackArgs {
+    /// Play the audio through a local player instead of writing it to stdout
+    #[arg(short, long, conflicts_with_all = ["cast", "to_mpd"])]
+    pub play: bool,
+
+    /// Player command that reads audio from stdin (default: mpv or ffplay)
+    #[arg(long, value_name = "COMMAND")]
+    pub player: Option<String>,
+
+    /// Cast the audio to the Chromecast or DLNA renderer with this name
+    #[arg(long, value_name = "DEVICE", conflicts_with = "to_mpd")]
+    pub cast: Option<String>,
+
+    /// Send the audio to the MPD server at HOST:PORT and play it
+    #[arg(long, value_name = "HOST:PORT")]
+    pub to_mpd: Option<String>,
+
+    /// MPD music directory to save files into (default: stream them over HTTP instead)
+    #[arg(long, value_name = "DIR", requires = "to_mpd")]
+    pub mpd_music_dir: Option<PathBuf>,
+
+    /// Password for the MPD server
+    #[arg(long, requires = "to_mpd")]
+    pub mpd_password: Option<String>,
+}
+
+impl PlaybackArgs {
+    /// Whether [`output`](Self::output) would just write to stdout.
+    pub fn writes_stdout(&self) -> bool {
+        !self.play && self.cast.is_none() && self.to_mpd.is_none()
+    }
+
+    /// Plays, casts, sends to MPD or writes `audio` to stdout, depending on the flags.
+    #[tracing::instrument(name = "output", skip_all, fields(bytes = audio.len()))]
+    pub async fn output(
+        &self,
+        audio: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(address) = &self.to_mpd {
+            crate::mpd::play(
+                audio,
+                address,
+                self.mpd_music_dir.as_deref(),
+                self.mpd_password.as_deref(),
+            )
+            .await
+        } else if let Some(device) = &self.cast {
+            crate::cast::cast(audio, device).await
+        } else if self.play {
+            play(audio, self.player.as_deref()).await
+        } else {
+            // Flush after every clip so a downstream player starts without waiting
+            let mut stdout = io::stdout();
+            stdout.write_all(audio)?;
+            stdout.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    // Windows finds `mpv` as `mpv.exe`, trying each extension in PATHEXT
+    let extensions: Vec<String> = match env::var("PATHEXT") {
+        Ok(extensions) if cfg!(windows) => std::iter::once(String::new())
+            .chain(extensions.split(';').map(str::to_string))
+            .collect(),
+        _ => vec![String::new()],
+    };
+    env::var_os("PATH")
+        .map(|paths| {
+            env::split_paths(&paths).any(|dir| {
+                extensions.iter().any(|extension| {
+                    Path::new(&dir)
+                        .join(format!("{}{}", program, extension))
+                        .is_file()
+                })
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Splits a player command line at whitespace; the program may be in double quotes, as
+/// Windows paths such as `"C:\Program Files\mpv\mpv.exe"` often need.
+fn split_command(command_line: &str) -> (&str, std::str::SplitWhitespace<'_>) {
+    let command_line = command_line.trim_start();
+    if let Some(quoted) = command_line.strip_prefix('"')
+        && let Some((program, rest)) = quoted.split_once('"')
+    {
+        return (program, rest.split_whitespace());
+    }
+    let (program, rest) = command_line
+        .split_once(char::is_whitespace)
+        .unwrap_or((command_line, ""));
+    (program, rest.split_whitespace())
+}
+
+/// Pipes `audio` into `player` (or the first available default player) and waits for it to finish.
+pub async fn play(
+    audio: &[u8],
+    player: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let command_line = match player {
+        Some(player) => player,
+        None => DEFAULT_PLAYERS
+            .iter()
+            .copied()
+            .find(|candidate| on_path(split_command(candidate).0))
+            .ok_or("No audio player found. Install mpv or ffplay, or pass --player.")?,
+    };
+
+    let (program, args) = split_command(command_line);
+    if program.is_empty() {
+        return Err("Empty --player command".into());
+    }
+    let mut command = Command::new(program);
+    if cfg!(windows) && env::var_os("SDL_AUDIODRIVER").is_none() {
+        command.env("SDL_AUDIODRIVER", "wasapi");
+    }
+    let mut child = command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        // Stopping playback by dropping this future also stops the player
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to start player '{}': {}", program, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(audio).await?;
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(format!("Player '{}' exited with {}", program, status).into());
+    }
+
+    Ok(())
+}
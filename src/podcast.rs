@@ -0,0 +1,130 @@
+//! `--podcast-out`: a podcast RSS feed of the files written by batch and feed modes, with
+//! enclosures under `--base-url`, so the output directory can be served and subscribed to.
+
+use crate::cast::xml_escape;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::PathBuf;
+use url::Url;
+
+#[derive(clap::Args)]
+pub struct PodcastArgs {
+    /// Also write a podcast RSS feed of the written files
+    #[arg(long, value_name = "FILE", requires = "base_url")]
+    pub podcast_out: Option<PathBuf>,
+
+    /// URL the output directory is served from, for the podcast's enclosures
+    #[arg(long, value_name = "URL", requires = "podcast_out")]
+    pub base_url: Option<String>,
+
+    /// Title of the podcast (default: the source feed's title, or "tktts")
+    #[arg(long, value_name = "TITLE", requires = "podcast_out")]
+    pub podcast_title: Option<String>,
+}
+
+impl PodcastArgs {
+    /// The parsed `--base-url`, checked before any request is spent.
+    pub fn base_url(&self) -> Result<Option<Url>, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(base_url) = &self.base_url else {
+            return Ok(None);
+        };
+        // Without the trailing slash, joining would replace the last path segment
+        let base_url = match base_url.ends_with('/') {
+            true => base_url.clone(),
+            false => format!("{}/", base_url),
+        };
+        Url::parse(&base_url)
+            .map(Some)
+            .map_err(|e| format!("Invalid --base-url {}: {}", base_url, e).into())
+    }
+}
+
+/// One audio file in the podcast.
+pub struct Episode {
+    pub title: String,
+    /// Stable ID, so podcast apps recognize episodes they have seen
+    pub guid: String,
+    /// Path relative to the output directory
+    pub file: String,
+    pub bytes: usize,
+    pub duration_secs: f64,
+    pub date: DateTime<Utc>,
+}
+
+impl Episode {
+    /// An episode for `audio`, written to `file`, taking size and length from the audio.
+    pub fn new(
+        title: String,
+        guid: String,
+        file: String,
+        audio: &[u8],
+        date: DateTime<Utc>,
+    ) -> Self {
+        Episode {
+            title,
+            guid,
+            file,
+            bytes: audio.len(),
+            duration_secs: crate::mp3::info(audio).duration_secs,
+            date,
+        }
+    }
+}
+
+fn duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Writes the podcast feed, newest episode first.
+pub fn write(
+    args: &PodcastArgs,
+    default_title: &str,
+    episodes: &mut [Episode],
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (Some(path), Some(base_url)) = (&args.podcast_out, args.base_url()?) else {
+        return Ok(());
+    };
+    let title = xml_escape(args.podcast_title.as_deref().unwrap_or(default_title));
+    episodes.sort_by_key(|episode| std::cmp::Reverse(episode.date));
+
+    let mut items = String::new();
+    for episode in episodes.iter() {
+        let url = base_url
+            .join(&episode.file)
+            .map_err(|e| format!("Invalid file name {}: {}", episode.file, e))?;
+        items.push_str(&format!(
+            r#"    <item>
+      <title>{}</title>
+      <guid isPermaLink="false">{}</guid>
+      <pubDate>{}</pubDate>
+      <enclosure url="{}" length="{}" type="audio/mpeg"/>
+      <itunes:duration>{}</itunes:duration>
+    </item>
+"#,
+            xml_escape(&episode.title),
+            xml_escape(&episode.guid),
+            episode.date.to_rfc2822(),
+            xml_escape(url.as_str()),
+            episode.bytes,
+            duration(episode.duration_secs),
+        ));
+    }
+
+    let feed = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+  <channel>
+    <title>{title}</title>
+    <link>{link}</link>
+    <description>{title}</description>
+    <itunes:explicit>false</itunes:explicit>
+{items}  </channel>
+</rss>
+"#,
+        link = xml_escape(base_url.as_str()),
+    );
+    fs::write(path, feed).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    crate::term::status(crate::term::Style::Green, "Wrote", path.display());
+    Ok(())
+}
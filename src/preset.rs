@@ -0,0 +1,178 @@
+//! Named endpoint presets bundling API hosts, `aid`, app name and User-Agent.
+//!
+//! Built-in presets can be selected per run with `--preset` (or `preset = "..."` in the
+//! config file). `[presets.<name>]` config sections override single fields of a built-in
+//! preset or define new ones.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+pub struct Preset {
+    /// API hosts, tried in order until one answers
+    pub base_urls: Vec<String>,
+    pub aid: String,
+    pub app_name: String,
+    pub user_agent: String,
+    pub request_style: RequestStyle,
+}
+
+/// Where the TTS parameters go in the POST request.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestStyle {
+    /// In the query string, with an empty body
+    Query,
+    /// In a form-encoded body
+    Form,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PresetConfig {
+    pub base_urls: Option<Vec<String>>,
+    pub aid: Option<String>,
+    pub app_name: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_style: Option<RequestStyle>,
+}
+
+pub type PresetsConfig = BTreeMap<String, PresetConfig>;
+
+/// `(name, hosts, aid, app name, request style)`; all built-ins use the crate's `USER_AGENT`.
+const BUILTIN: [(&str, &[&str], &str, &str, RequestStyle); 3] = [
+    (
+        "us",
+        &[
+            "https://api16-normal-useast5.us.tiktokv.com",
+            "https://api16-normal-c-useast1a.tiktokv.com",
+        ],
+        "1233",
+        "musical_ly",
+        RequestStyle::Query,
+    ),
+    (
+        "eu",
+        &[
+            "https://api16-normal-no1a.tiktokv.eu",
+            "https://api16-normal-c-useast2a.tiktokv.com",
+        ],
+        "1233",
+        "musical_ly",
+        RequestStyle::Query,
+    ),
+    (
+        "musically",
+        &[
+            "https://api.musical.ly",
+            "https://api16-core-c-useast1a.musical.ly",
+        ],
+        "1233",
+        "musical_ly",
+        RequestStyle::Form,
+    ),
+];
+
+static ACTIVE: OnceLock<Option<Preset>> = OnceLock::new();
+static REQUEST_STYLE: OnceLock<RequestStyle> = OnceLock::new();
+
+/// Names of the built-in presets and those defined in the config file.
+pub fn names() -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN.iter().map(|(name, ..)| name.to_string()).collect();
+    for name in crate::config::get().presets.keys() {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
+}
+
+fn resolve(name: &str) -> Result<Preset, Box<dyn std::error::Error + Send + Sync>> {
+    let overrides = crate::config::get().presets.get(name);
+    let builtin = BUILTIN.iter().find(|(builtin, ..)| *builtin == name);
+    if builtin.is_none() && overrides.is_none() {
+        return Err(format!(
+            "Unknown preset '{}' (available: {})",
+            name,
+            names().join(", ")
+        )
+        .into());
+    }
+
+    let mut preset = match builtin {
+        Some((_, base_urls, aid, app_name, request_style)) => Preset {
+            base_urls: base_urls.iter().map(|url| url.to_string()).collect(),
+            aid: aid.to_string(),
+            app_name: app_name.to_string(),
+            user_agent: crate::USER_AGENT.to_string(),
+            request_style: *request_style,
+        },
+        None => default(),
+    };
+    if let Some(overrides) = overrides {
+        if let Some(base_urls) = &overrides.base_urls {
+            preset.base_urls = base_urls.clone();
+        }
+        if let Some(aid) = &overrides.aid {
+            preset.aid = aid.clone();
+        }
+        if let Some(app_name) = &overrides.app_name {
+            preset.app_name = app_name.clone();
+        }
+        if let Some(user_agent) = &overrides.user_agent {
+            preset.user_agent = user_agent.clone();
+        }
+        if let Some(request_style) = overrides.request_style {
+            preset.request_style = request_style;
+        }
+    }
+    if preset.base_urls.is_empty() {
+        return Err(format!("Preset '{}' has no base_urls", name).into());
+    }
+    Ok(preset)
+}
+
+/// Parameters used without a preset; the host then comes from `TIKTOK_API_BASEURL`.
+fn default() -> Preset {
+    Preset {
+        base_urls: Vec::new(),
+        aid: "1233".to_string(),
+        app_name: "musical_ly".to_string(),
+        user_agent: crate::USER_AGENT.to_string(),
+        request_style: RequestStyle::Query,
+    }
+}
+
+/// Selects the preset for this run: `name`, else the config's `preset`, else none.
+/// `request_style` overrides the preset's request style.
+pub fn select(
+    name: Option<&str>,
+    request_style: Option<RequestStyle>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = name.or(crate::config::get().preset.as_deref());
+    let preset = name.map(resolve).transpose()?;
+    let _ = ACTIVE.set(preset);
+    if let Some(request_style) = request_style {
+        let _ = REQUEST_STYLE.set(request_style);
+    }
+    Ok(())
+}
+
+/// The selected preset, if any.
+pub fn selected() -> Option<&'static Preset> {
+    ACTIVE.get().and_then(Option::as_ref)
+}
+
+/// The selected preset, or the default parameters.
+pub fn active() -> &'static Preset {
+    static DEFAULT: OnceLock<Preset> = OnceLock::new();
+    selected().unwrap_or_else(|| DEFAULT.get_or_init(default))
+}
+
+/// How to send TTS parameters: `--request-style`, else the active preset's style.
+pub fn request_style() -> RequestStyle {
+    REQUEST_STYLE
+        .get()
+        .copied()
+        .unwrap_or(active().request_style)
+}
@@ -0,0 +1,348 @@
+//! The server's worker pool: a fixed number of chunk requests run at once, and waiting
+//! chunks are handed the next free worker by priority, oldest first within a priority.
+//!
+//! Priorities apply per chunk, so a bot message queued behind a long audiobook job only
+//! waits for the chunks already being requested, not for the whole book. Between clients
+//! of the same priority, workers go round-robin, and each client may be capped to a number
+//! of chunks in flight, so one large document cannot starve everyone else.
+
+use serde::Deserialize;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Background work such as whole documents
+    Low,
+    #[default]
+    Normal,
+    /// Interactive requests such as bot messages
+    High,
+}
+
+impl Priority {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            _ => Err(format!(
+                "Unknown priority '{}'; expected low, normal or high",
+                name
+            )),
+        }
+    }
+}
+
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    wake: oneshot::Sender<Worker>,
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Max-heap: higher priority first, then the earlier arrival
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+/// One client's chunks, running and waiting.
+struct Client {
+    running: usize,
+    cap: usize,
+    waiting: BinaryHeap<Waiter>,
+    /// When the client was last handed a worker, for round-robin
+    last_served: u64,
+}
+
+impl Client {
+    fn can_start(&self) -> bool {
+        self.running < self.cap
+    }
+}
+
+struct Inner {
+    free: usize,
+    clients: HashMap<String, Client>,
+    /// Counts arrivals and hand-outs alike, so both orders come from one clock
+    clock: u64,
+}
+
+impl Inner {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// The client to hand the next worker: the most urgent waiting chunk of any client
+    /// below its cap, going to the client served longest ago on a tie, and then to the
+    /// older chunk.
+    fn next_client(&self) -> Option<String> {
+        self.clients
+            .iter()
+            .filter(|(_, client)| client.can_start())
+            .filter_map(|(name, client)| {
+                let head = client.waiting.peek()?;
+                let rank = (
+                    head.priority,
+                    Reverse(client.last_served),
+                    Reverse(head.seq),
+                );
+                Some((rank, name))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, name)| name.clone())
+    }
+}
+
+pub struct Queue {
+    inner: Mutex<Inner>,
+}
+
+/// A busy worker, freed for the next waiting chunk when dropped.
+pub struct Worker {
+    queue: Option<Arc<Queue>>,
+    client: String,
+}
+
+impl Queue {
+    pub fn new(workers: usize) -> Arc<Self> {
+        Arc::new(Queue {
+            inner: Mutex::new(Inner {
+                free: workers.max(1),
+                clients: HashMap::new(),
+                clock: 0,
+            }),
+        })
+    }
+
+    /// Waits for a free worker for one chunk of `client`, which may have at most `cap`
+    /// chunks in flight. The cap of the latest request applies to the client's older
+    /// chunks too; those still go first.
+    pub async fn acquire(self: &Arc<Self>, client: &str, cap: usize, priority: Priority) -> Worker {
+        let receiver = {
+            let mut inner = self.inner.lock().unwrap();
+            let seq = inner.tick();
+            let free = inner.free;
+            let state = inner
+                .clients
+                .entry(client.to_string())
+                .or_insert_with(|| Client {
+                    running: 0,
+                    cap,
+                    waiting: BinaryHeap::new(),
+                    last_served: 0,
+                });
+            state.cap = cap.max(1);
+            // Only without older chunks of its own, which a raised cap may let start now
+            if free > 0 && state.can_start() && state.waiting.is_empty() {
+                state.running += 1;
+                state.last_served = seq;
+                inner.free -= 1;
+                return Worker {
+                    queue: Some(self.clone()),
+                    client: client.to_string(),
+                };
+            }
+            let (wake, receiver) = oneshot::channel();
+            state.waiting.push(Waiter {
+                priority,
+                seq,
+                wake,
+            });
+            self.dispatch(&mut inner);
+            receiver
+        };
+        // The queue is never dropped while a waiter is pending, as each worker holds it
+        receiver.await.expect("worker queue dropped")
+    }
+
+    /// Hands the free workers to the waiting chunks that may start.
+    fn dispatch(self: &Arc<Self>, inner: &mut Inner) {
+        while inner.free > 0 {
+            let Some(name) = inner.next_client() else {
+                break;
+            };
+            let served = inner.tick();
+            let state = inner.clients.get_mut(&name).expect("client just found");
+            let waiter = state.waiting.pop().expect("client has a waiting chunk");
+            state.running += 1;
+            state.last_served = served;
+            inner.free -= 1;
+            let worker = Worker {
+                queue: Some(self.clone()),
+                client: name,
+            };
+            // That request was cancelled (client gone); try the next one
+            if let Err(mut worker) = waiter.wake.send(worker) {
+                worker.queue = None;
+                inner.free += 1;
+                if let Some(state) = inner.clients.get_mut(&worker.client) {
+                    state.running -= 1;
+                }
+            }
+        }
+    }
+
+    fn release(self: &Arc<Self>, client: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(state) = inner.clients.get_mut(client) {
+            state.running -= 1;
+        }
+        inner.free += 1;
+        self.dispatch(&mut inner);
+        // Forget idle clients, so the map does not grow with every address ever seen
+        inner
+            .clients
+            .retain(|_, state| state.running > 0 || !state.waiting.is_empty());
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.release(&self.client);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Starts a task that takes a worker for `client`, notes `label` and frees it again.
+    fn take(
+        queue: &Arc<Queue>,
+        served: &Arc<Mutex<Vec<&'static str>>>,
+        client: &'static str,
+        cap: usize,
+        priority: Priority,
+        label: &'static str,
+    ) -> tokio::task::JoinHandle<()> {
+        let (queue, served) = (queue.clone(), served.clone());
+        tokio::spawn(async move {
+            let _worker = queue.acquire(client, cap, priority).await;
+            served.lock().unwrap().push(label);
+        })
+    }
+
+    /// Lets the spawned tasks run until they wait.
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn higher_priorities_go_first_then_older_chunks() {
+        let queue = Queue::new(1);
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let busy = queue.acquire("x", 8, Priority::Normal).await;
+        take(&queue, &served, "a", 8, Priority::Low, "low");
+        settle().await;
+        take(&queue, &served, "b", 8, Priority::Normal, "normal 1");
+        settle().await;
+        take(&queue, &served, "c", 8, Priority::Normal, "normal 2");
+        settle().await;
+        take(&queue, &served, "d", 8, Priority::High, "high");
+        settle().await;
+
+        drop(busy);
+        settle().await;
+        assert_eq!(
+            *served.lock().unwrap(),
+            ["high", "normal 1", "normal 2", "low"]
+        );
+    }
+
+    #[tokio::test]
+    async fn clients_of_one_priority_take_turns() {
+        let queue = Queue::new(1);
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let busy = queue.acquire("a", 8, Priority::Normal).await;
+        for label in ["a1", "a2", "a3"] {
+            take(&queue, &served, "a", 8, Priority::Normal, label);
+        }
+        settle().await;
+        take(&queue, &served, "b", 8, Priority::Normal, "b1");
+        take(&queue, &served, "b", 8, Priority::Normal, "b2");
+        settle().await;
+
+        // b was never served, so it goes first although its chunks came last
+        drop(busy);
+        settle().await;
+        assert_eq!(*served.lock().unwrap(), ["b1", "a1", "b2", "a2", "a3"]);
+    }
+
+    #[tokio::test]
+    async fn cancelled_waiters_pass_the_worker_on() {
+        let queue = Queue::new(1);
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let busy = queue.acquire("x", 8, Priority::Normal).await;
+        let gone = take(&queue, &served, "a", 8, Priority::High, "gone");
+        settle().await;
+        take(&queue, &served, "b", 8, Priority::Normal, "b");
+        settle().await;
+        gone.abort();
+        settle().await;
+
+        drop(busy);
+        settle().await;
+        assert_eq!(*served.lock().unwrap(), ["b"]);
+        let inner = queue.inner.lock().unwrap();
+        assert_eq!(inner.free, 1);
+    }
+
+    #[tokio::test]
+    async fn a_raised_cap_starts_older_chunks_first() {
+        let queue = Queue::new(2);
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let _first = queue.acquire("a", 1, Priority::Normal).await;
+        take(&queue, &served, "a", 1, Priority::Normal, "older");
+        settle().await;
+        assert!(served.lock().unwrap().is_empty());
+
+        let newer = take(&queue, &served, "a", 2, Priority::Normal, "newer");
+        settle().await;
+        assert_eq!(*served.lock().unwrap(), ["older", "newer"]);
+        tokio::time::timeout(Duration::from_secs(1), newer)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_clients_are_forgotten() {
+        let queue = Queue::new(2);
+        let first = queue.acquire("a", 8, Priority::Normal).await;
+        let second = queue.acquire("b", 8, Priority::Normal).await;
+        assert_eq!(queue.inner.lock().unwrap().clients.len(), 2);
+        drop(first);
+        assert_eq!(queue.inner.lock().unwrap().clients.len(), 1);
+        drop(second);
+        let inner = queue.inner.lock().unwrap();
+        assert!(inner.clients.is_empty());
+        assert_eq!(inner.free, 2);
+    }
+}
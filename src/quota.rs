@@ -0,0 +1,194 @@
+//! Local request accounting per session ID, shown by `tktts quota` and capped by
+//! `--daily-limit`, since heavy use gets sessions banned.
+//!
+//! Counts live in `usage.json` in the local data directory, keyed by a hash of the
+//! session ID so the secret itself is never written, then by local date. Every update
+//! holds an advisory lock on `usage.json.lock`, so runs at the same time all count.
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Days of history kept in the usage file.
+const HISTORY_DAYS: i64 = 30;
+
+static DAILY_LIMIT: OnceLock<Option<u64>> = OnceLock::new();
+
+/// Session hash → date → requests.
+type Usage = BTreeMap<String, BTreeMap<String, u64>>;
+
+#[derive(clap::Args)]
+pub struct QuotaArgs {
+    /// Days of history to show
+    #[arg(long, default_value_t = 7, value_name = "N")]
+    days: usize,
+}
+
+pub fn init(daily_limit: Option<u64>) {
+    let _ = DAILY_LIMIT.set(daily_limit);
+}
+
+/// The `--daily-limit`, if any.
+pub fn daily_limit() -> Option<u64> {
+    *DAILY_LIMIT.get_or_init(|| None)
+}
+
+fn usage_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("tktts").join("usage.json"))
+}
+
+fn session_key(session_id: &str) -> String {
+    let digest = Sha256::digest(session_id.as_bytes());
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn load() -> Usage {
+    usage_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Locks the usage file against every other update, in this process or another, until
+/// the returned file is dropped.
+fn lock() -> Result<File, Box<dyn std::error::Error + Send + Sync>> {
+    let path = usage_path().ok_or("Could not determine the data directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("json.lock"))?;
+    file.lock()?;
+    Ok(file)
+}
+
+fn save(usage: &Usage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = usage_path().ok_or("Could not determine the data directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // A temporary file of its own, so runs saving at the same time never rename each
+    // other's half-written file into place
+    let temp = path.with_extension(format!("{:016x}.tmp", fastrand::u64(..)));
+    fs::write(&temp, serde_json::to_vec_pretty(usage)?)?;
+    fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+/// Today's requests of every session, read from the usage file once for several lookups.
+pub struct Today(BTreeMap<String, u64>);
+
+impl Today {
+    pub fn load() -> Self {
+        let today = today();
+        let usage = load()
+            .into_iter()
+            .filter_map(|(session, mut days)| Some((session, days.remove(&today)?)))
+            .collect();
+        Today(usage)
+    }
+
+    /// Requests made today with `session_id`.
+    pub fn used(&self, session_id: &str) -> u64 {
+        self.0.get(&session_key(session_id)).copied().unwrap_or(0)
+    }
+}
+
+/// Fails when `requests` more would go over `--daily-limit`, before a job starts.
+/// `session_ids` may be a comma-separated pool, which shares the work.
+pub fn check(
+    session_ids: &str,
+    requests: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(limit) = daily_limit() else {
+        return Ok(());
+    };
+    let pool: Vec<&str> = crate::sessions::pool(session_ids).collect();
+    let today = Today::load();
+    let left: u64 = pool
+        .iter()
+        .map(|session_id| limit.saturating_sub(today.used(session_id)))
+        .sum();
+    if requests as u64 > left {
+        let sessions = if pool.len() == 1 {
+            ""
+        } else {
+            " across the sessions"
+        };
+        return Err(format!(
+            "Job needs {} requests, but only {} of the daily limit of {} are left{}",
+            requests, left, limit, sessions
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Counts one request about to be sent with `session_id`, refusing it when the daily
+/// limit is reached.
+pub async fn count(session_id: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let session_id = session_id.to_string();
+    tokio::task::spawn_blocking(move || count_blocking(&session_id)).await?
+}
+
+fn count_blocking(session_id: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Counting without the lock is better than failing the request over it
+    let _lock = lock()
+        .inspect_err(|e| progress!("Could not lock the usage file: {}", e))
+        .ok();
+    let mut usage = load();
+    let today = today();
+    let days = usage.entry(session_key(session_id)).or_default();
+    let used = days.entry(today.clone()).or_default();
+    if let Some(limit) = daily_limit()
+        && *used >= limit
+    {
+        return Err(format!("Daily limit of {} requests reached for this session", limit).into());
+    }
+    *used += 1;
+
+    let oldest = (chrono::Local::now() - chrono::Duration::days(HISTORY_DAYS))
+        .format("%Y-%m-%d")
+        .to_string();
+    for days in usage.values_mut() {
+        days.retain(|date, _| *date >= oldest);
+    }
+    usage.retain(|_, days| !days.is_empty());
+    // Losing a count is better than failing the request over it
+    if let Err(e) = save(&usage) {
+        progress!("Could not record request usage: {}", e);
+    }
+    Ok(())
+}
+
+pub fn run(args: QuotaArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_ids, _) = crate::load_credentials()?;
+    let limit = daily_limit();
+    let usage = load();
+    let today = today();
+
+    for session_id in crate::sessions::pool(&session_ids) {
+        let key = session_key(session_id);
+        let days = usage.get(&key).cloned().unwrap_or_default();
+        println!("Session {}", key);
+        let used = days.get(&today).copied().unwrap_or(0);
+        match limit {
+            Some(limit) => println!("  today       {} / {}", used, limit),
+            None => println!("  today       {}", used),
+        }
+        let earlier = days.iter().rev().filter(|(date, _)| **date != today);
+        for (date, requests) in earlier.take(args.days.saturating_sub(1)) {
+            println!("  {}  {}", date, requests);
+        }
+    }
+    Ok(())
+}
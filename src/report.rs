@@ -0,0 +1,347 @@
+//! `--report FILE`: a page about the run for checking large narration jobs, with every
+//! chunk's text, voice, duration, tries and waveform, and the totals.
+//!
+//! Each synthesis of the run (one per file with `--batch`) is a section of the report.
+//! Tries are counted below the retry layer, so a chunk that needed two retries shows 3.
+//! A `.md` path gives Markdown with text waveforms; anything else HTML with SVG ones.
+
+use crate::cast::xml_escape;
+use crate::term::{self, Style};
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tktts::layers::Layer;
+use tktts::transport::{Request, ResponseFuture, Transport};
+
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+static JOBS: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+
+/// Points of each chunk's waveform thumbnail.
+const PEAKS: usize = 48;
+
+/// Bars of the Markdown waveforms, from quiet to loud.
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+tokio::task_local! {
+    static TRIES: Cell<usize>;
+}
+
+#[derive(Clone)]
+pub enum Status {
+    Requested,
+    Cached,
+    /// The same text and voice as an earlier chunk, whose audio is reused
+    Repeat,
+    Failed(String),
+}
+
+pub struct Row {
+    number: usize,
+    text: String,
+    voice: String,
+    status: Status,
+    /// Requests sent for the chunk, 0 when none were needed
+    tries: usize,
+    elapsed: Duration,
+    bytes: usize,
+    duration_secs: f64,
+    peaks: Vec<f32>,
+}
+
+impl Row {
+    /// A chunk of the report, measuring `audio` if there is any.
+    pub fn new(
+        number: usize,
+        text: &str,
+        voice: &str,
+        status: Status,
+        tries: usize,
+        elapsed: Duration,
+        audio: &[u8],
+    ) -> Self {
+        let (duration_secs, peaks) = match crate::pcm::decode(audio) {
+            Ok(pcm) if !audio.is_empty() => (pcm.duration_secs(), peaks(&pcm.samples)),
+            _ => (crate::mp3::info(audio).duration_secs, Vec::new()),
+        };
+        Row {
+            number,
+            text: crate::markdown::unmarked(text).to_string(),
+            voice: voice.to_string(),
+            status,
+            tries,
+            elapsed,
+            bytes: audio.len(),
+            duration_secs,
+            peaks,
+        }
+    }
+
+    /// Chunk `number`, a repeat of this one, which fails with it.
+    pub fn repeat(&self, number: usize) -> Self {
+        Row {
+            number,
+            text: self.text.clone(),
+            voice: self.voice.clone(),
+            status: match &self.status {
+                Status::Failed(e) => Status::Failed(e.clone()),
+                _ => Status::Repeat,
+            },
+            tries: 0,
+            elapsed: Duration::ZERO,
+            bytes: self.bytes,
+            duration_secs: self.duration_secs,
+            peaks: self.peaks.clone(),
+        }
+    }
+}
+
+struct Job {
+    rows: Vec<Row>,
+    elapsed: Duration,
+}
+
+pub fn init(path: Option<PathBuf>) {
+    if let Some(path) = path {
+        let _ = PATH.set(path);
+    }
+}
+
+pub fn enabled() -> bool {
+    PATH.get().is_some()
+}
+
+/// Adds a synthesis to the report, its rows in any order.
+pub fn job(mut rows: Vec<Row>, elapsed: Duration) {
+    rows.sort_by_key(|row| row.number);
+    JOBS.lock().unwrap().push(Job { rows, elapsed });
+}
+
+/// Runs `future`, counting the requests it sends through the [`Tries`] layer.
+pub async fn counting<F: Future>(future: F) -> (F::Output, usize) {
+    TRIES
+        .scope(Cell::new(0), async {
+            let output = future.await;
+            (output, TRIES.with(Cell::get))
+        })
+        .await
+}
+
+/// Counts each request sent for the chunk of [`counting`].
+pub struct Tries;
+
+struct Counted {
+    inner: Arc<dyn Transport>,
+}
+
+impl Layer for Tries {
+    fn layer(&self, inner: Arc<dyn Transport>) -> Arc<dyn Transport> {
+        Arc::new(Counted { inner })
+    }
+}
+
+impl Transport for Counted {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        let _ = TRIES.try_with(|tries| tries.set(tries.get() + 1));
+        self.inner.send(request)
+    }
+}
+
+/// The loudest sample of each of [`PEAKS`] stretches of `samples`.
+fn peaks(samples: &[f32]) -> Vec<f32> {
+    crate::peaks::min_max(samples, samples.len().div_ceil(PEAKS))
+        .into_iter()
+        .map(|(low, high)| high.max(-low))
+        .collect()
+}
+
+fn sparkline(peaks: &[f32]) -> String {
+    peaks
+        .iter()
+        .map(|peak| BARS[((peak.clamp(0.0, 1.0) * 7.0).round()) as usize])
+        .collect()
+}
+
+fn svg(peaks: &[f32]) -> String {
+    let mut bars = String::new();
+    for (i, peak) in peaks.iter().enumerate() {
+        let height = (peak.clamp(0.0, 1.0) * 24.0).max(1.0);
+        let _ = write!(
+            bars,
+            r#"<rect x="{}" y="{:.1}" width="2" height="{:.1}"/>"#,
+            i * 3,
+            12.0 - height / 2.0,
+            height
+        );
+    }
+    format!(
+        r#"<svg width="{}" height="24" viewBox="0 0 {} 24">{}</svg>"#,
+        PEAKS * 3,
+        PEAKS * 3,
+        bars
+    )
+}
+
+fn status_label(status: &Status) -> String {
+    match status {
+        Status::Requested => "requested".to_string(),
+        Status::Cached => "cached".to_string(),
+        Status::Repeat => "repeat".to_string(),
+        Status::Failed(e) => format!("failed: {}", term::redact(e)),
+    }
+}
+
+struct Totals {
+    chunks: usize,
+    requested: usize,
+    cached: usize,
+    failed: usize,
+    retries: usize,
+    bytes: usize,
+    duration_secs: f64,
+}
+
+fn totals(jobs: &[Job]) -> Totals {
+    let rows = jobs.iter().flat_map(|job| &job.rows);
+    let mut totals = Totals {
+        chunks: 0,
+        requested: 0,
+        cached: 0,
+        failed: 0,
+        retries: 0,
+        bytes: 0,
+        duration_secs: 0.0,
+    };
+    for row in rows {
+        totals.chunks += 1;
+        match row.status {
+            Status::Requested => totals.requested += 1,
+            Status::Cached => totals.cached += 1,
+            Status::Failed(_) => totals.failed += 1,
+            Status::Repeat => {}
+        }
+        totals.retries += row.tries.saturating_sub(1);
+        totals.bytes += row.bytes;
+        totals.duration_secs += row.duration_secs;
+    }
+    totals
+}
+
+fn summary(totals: &Totals, elapsed: Duration) -> Vec<(&'static str, String)> {
+    let outputs = crate::hooks::paths();
+    let mut summary = vec![
+        ("Chunks", totals.chunks.to_string()),
+        ("Requested", totals.requested.to_string()),
+        ("Cached", totals.cached.to_string()),
+        ("Failed", totals.failed.to_string()),
+        ("Retries", totals.retries.to_string()),
+        ("Audio", format!("{:.1} s", totals.duration_secs)),
+        ("Size", crate::cache::format_size(totals.bytes as u64)),
+        ("Run time", format!("{:.1} s", elapsed.as_secs_f64())),
+    ];
+    if !outputs.is_empty() {
+        summary.push(("Outputs", outputs.join(", ")));
+    }
+    summary
+}
+
+fn markdown(jobs: &[Job], elapsed: Duration) -> String {
+    let mut page = String::from("# tktts report\n\n| | |\n|---|---|\n");
+    for (name, value) in summary(&totals(jobs), elapsed) {
+        let _ = writeln!(page, "| {} | {} |", name, value);
+    }
+    for (i, job) in jobs.iter().enumerate() {
+        let _ = write!(
+            page,
+            "\n## Synthesis {} ({:.1} s)\n\n| # | Text | Voice | Status | Tries | Time | Audio | Waveform |\n|---|---|---|---|---|---|---|---|\n",
+            i + 1,
+            job.elapsed.as_secs_f64()
+        );
+        for row in &job.rows {
+            let text = row.text.replace('|', "\\|").replace('\n', " ");
+            let _ = writeln!(
+                page,
+                "| {} | {} | {} | {} | {} | {} ms | {:.2} s | {} |",
+                row.number,
+                text,
+                row.voice,
+                status_label(&row.status).replace('|', "\\|"),
+                row.tries,
+                row.elapsed.as_millis(),
+                row.duration_secs,
+                sparkline(&row.peaks)
+            );
+        }
+    }
+    page
+}
+
+fn html(jobs: &[Job], elapsed: Duration) -> String {
+    let mut page = String::from(concat!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>tktts report</title>",
+        "<style>body{font:14px sans-serif;margin:2em}table{border-collapse:collapse}",
+        "td,th{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:middle}",
+        "tr.failed{background:#fdd}svg{fill:#36c}</style></head><body>\n<h1>tktts report</h1>\n<table>\n"
+    ));
+    for (name, value) in summary(&totals(jobs), elapsed) {
+        let _ = writeln!(
+            page,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            name,
+            xml_escape(&value)
+        );
+    }
+    page.push_str("</table>\n");
+    for (i, job) in jobs.iter().enumerate() {
+        let _ = write!(
+            page,
+            "<h2>Synthesis {} ({:.1} s)</h2>\n<table>\n<tr><th>#</th><th>Text</th><th>Voice</th><th>Status</th><th>Tries</th><th>Time</th><th>Audio</th><th>Waveform</th></tr>\n",
+            i + 1,
+            job.elapsed.as_secs_f64()
+        );
+        for row in &job.rows {
+            let class = match row.status {
+                Status::Failed(_) => " class=\"failed\"",
+                _ => "",
+            };
+            let _ = writeln!(
+                page,
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{} ms</td><td>{:.2} s</td><td>{}</td></tr>",
+                class,
+                row.number,
+                xml_escape(&row.text),
+                xml_escape(&row.voice),
+                xml_escape(&status_label(&row.status)),
+                row.tries,
+                row.elapsed.as_millis(),
+                row.duration_secs,
+                svg(&row.peaks)
+            );
+        }
+        page.push_str("</table>\n");
+    }
+    page.push_str("</body></html>\n");
+    page
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("md"))
+}
+
+/// Writes the report of the run so far, if one was asked for.
+pub fn write(elapsed: Duration) -> std::io::Result<()> {
+    let Some(path) = PATH.get() else {
+        return Ok(());
+    };
+    let jobs = JOBS.lock().unwrap();
+    let page = match is_markdown(path) {
+        true => markdown(&jobs, elapsed),
+        false => html(&jobs, elapsed),
+    };
+    std::fs::write(path, page)?;
+    term::status(Style::Green, "Reported", path.display());
+    Ok(())
+}
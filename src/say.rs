@@ -0,0 +1,133 @@
+//! `tktts say`: a stand-in for the macOS `say` command.
+//!
+//! Takes `say`'s own flags (`-v voice`, `-o file`, `-f file`, `-r rate`), so scripts that
+//! call `say` work unchanged. Invoked through a link named `say` or `tktts-say`, the
+//! binary behaves as `tktts say` without the subcommand. Like `say`, it plays the speech
+//! unless `-o` is given, and writes AIFF to an output name without an extension.
+
+use std::path::{Path, PathBuf};
+use tktts::voices;
+
+/// The rate `say` takes as normal, in words per minute; other rates stretch the audio.
+const NORMAL_RATE: f64 = 175.0;
+
+#[derive(clap::Args)]
+pub struct SayArgs {
+    /// Text to speak; read from --input-file or stdin when not given
+    text: Vec<String>,
+
+    /// Voice code, alias or display name; `?` lists the voices
+    #[arg(short, long)]
+    voice: Option<String>,
+
+    /// Write the audio to this file instead of playing it (AIFF without an extension)
+    #[arg(short, long = "output-file", value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Read the text from this file, `-` for stdin
+    #[arg(short = 'f', long = "input-file", value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Speech rate in words per minute
+    #[arg(short, long, value_name = "WPM")]
+    rate: Option<f64>,
+
+    // `say` options without an equivalent, accepted so calls using them still work
+    #[arg(short, long, hide = true)]
+    interactive: bool,
+    #[arg(long, hide = true)]
+    progress: bool,
+    #[arg(long, hide = true)]
+    file_format: Option<String>,
+    #[arg(long, hide = true)]
+    data_format: Option<String>,
+    #[arg(long, hide = true)]
+    channels: Option<String>,
+    #[arg(long, hide = true)]
+    bit_rate: Option<String>,
+    #[arg(long, hide = true)]
+    quality: Option<String>,
+}
+
+/// The voice code for a `-v` value: a code or alias, else a catalog display name, with or
+/// without its parenthesized part.
+pub fn voice(name: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let code = crate::config::resolve_voice(name);
+    if voices::find(code).is_some() {
+        return Ok(code.to_string());
+    }
+    // "Jessie" picks the first of "Jessie (US Female 1)", "Jessie (US Female 2)", ...
+    let named = |voice: &&voices::Voice| {
+        let short = voice.name.split(" (").next().unwrap_or(voice.name);
+        voice.name.eq_ignore_ascii_case(name) || short.eq_ignore_ascii_case(name)
+    };
+    match voices::all().find(named) {
+        Some(voice) => Ok(voice.code.to_string()),
+        None => crate::catalog::check(name).map(|()| code.to_string()),
+    }
+}
+
+/// Prints the voices the way `say -v ?` does: name, locale and a sample sentence.
+fn list() {
+    for voice in voices::all() {
+        println!(
+            "{:<22} {:<8} # Hello, my name is {}.",
+            voice.code,
+            voice.language.replace('-', "_"),
+            voice.name
+        );
+    }
+}
+
+fn text(args: &SayArgs) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if !args.text.is_empty() {
+        return Ok(args.text.join(" "));
+    }
+    match &args.input {
+        Some(path) if path != Path::new("-") => Ok(std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?),
+        _ => Ok(crate::console::read_stdin()?),
+    }
+}
+
+pub async fn run(args: SayArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if args.voice.as_deref() == Some("?") {
+        list();
+        return Ok(());
+    }
+    let speaker = match &args.voice {
+        Some(name) => voice(name)?,
+        None => crate::config::get()
+            .speaker
+            .clone()
+            .unwrap_or_else(|| "en_us_002".to_string()),
+    };
+    let text = text(&args)?;
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let mut audio = crate::synthesize(&text, &speaker, &session_id, &api_root_url).await?;
+    if let Some(rate) = args.rate {
+        if rate <= 0.0 {
+            return Err("The rate must be above 0 words per minute".into());
+        }
+        audio = crate::pcm::offload(move || {
+            let pcm = crate::pcm::decode(&audio)?;
+            crate::pcm::mp3(&crate::pcm::stretch(&pcm, NORMAL_RATE / rate))
+        })
+        .await?;
+    }
+
+    let Some(output) = &args.output else {
+        return crate::playback::play(&audio, None).await;
+    };
+    let mut target = output.to_string_lossy().into_owned();
+    if output.extension().is_none() {
+        target.push_str(".aiff");
+    }
+    let (target, audio) = crate::pcm::offload(move || crate::sniff::fit(&target, audio)).await?;
+    std::fs::write(&target, &audio).map_err(|e| format!("Failed to write {}: {}", target, e))?;
+    Ok(())
+}
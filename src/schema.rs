@@ -0,0 +1,403 @@
+//! The response shapes of the TTS endpoint.
+//!
+//! TikTok has changed what `/media/api/text/speech/invoke/` answers more than once without
+//! notice, and mirrors wrap the audio in shapes of their own. [`parse`] recognizes every
+//! known variant, so switching between them keeps working, and describes anything else
+//! by its shape rather than failing on one missing field.
+//!
+//! The audio of a successful response is most of its body. An [`AudioDecoder`] is fed
+//! the body as it streams in and decodes the audio into a writer on the way, so neither
+//! the body nor its base64 is ever held whole; only the small rest of the body is kept,
+//! to be parsed when it holds no audio after all.
+
+use crate::TktsError;
+use base64::{Engine as _, engine::general_purpose};
+use serde_json::Value;
+use std::io::Write;
+
+/// A known response variant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schema {
+    /// `{"data": {"v_str": ...}, "status_code": 0}`, the app endpoint's own
+    Nested,
+    /// `{"data": "...", "success": true}`, the audio directly under `data` (web mirrors)
+    Flat,
+    /// `{"v_data": "..."}` or `{"audio": "..."}`, the audio at the top level (proxies)
+    TopLevel,
+    /// `{"status_code": 1, "status_msg": ...}` without any audio: only the error shape,
+    /// which all API versions share
+    Error,
+}
+
+impl Schema {
+    pub fn name(self) -> &'static str {
+        match self {
+            Schema::Nested => "nested",
+            Schema::Flat => "flat",
+            Schema::TopLevel => "top-level",
+            Schema::Error => "error",
+        }
+    }
+
+    /// Where this variant keeps the audio.
+    pub fn location(self) -> &'static str {
+        match self {
+            Schema::Nested => "data.v_str",
+            Schema::Flat => "data",
+            Schema::TopLevel => "v_data or audio",
+            Schema::Error => "nowhere (error only)",
+        }
+    }
+}
+
+pub enum Outcome {
+    /// The base64 audio
+    Audio(String),
+    /// The API declined the request, for `reason` as far as its answer tells
+    Rejected { message: String, reason: Reason },
+}
+
+/// Why the API declined a request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reason {
+    /// No session ID reached the API (status 5)
+    MissingSession,
+    /// The session ID is invalid or expired, or belongs to another region than the host;
+    /// the API answers all three alike (status 1)
+    Session,
+    /// The answer names the region: the session belongs to another one than the host
+    Region,
+    /// The voice does not exist or the host does not offer it (status 4)
+    Voice,
+    /// The text is too long for one request (status 2)
+    TextTooLong,
+    Other,
+}
+
+impl Reason {
+    /// Whether another session ID could succeed where this one failed.
+    pub fn is_session(self) -> bool {
+        matches!(self, Reason::MissingSession | Reason::Session)
+    }
+}
+
+pub struct Parsed {
+    pub schema: Schema,
+    pub outcome: Outcome,
+}
+
+/// Status code of the app endpoint for an invalid or expired session.
+const SESSION_STATUS: i64 = 1;
+
+/// Status code of the app endpoint for a request without a session.
+const MISSING_SESSION_STATUS: i64 = 5;
+
+/// Status code of the app endpoint for text over its length limit.
+const TEXT_TOO_LONG_STATUS: i64 = 2;
+
+/// Status code of the app endpoint for an unknown voice.
+const VOICE_STATUS: i64 = 4;
+
+/// The answer old and new endpoints give for an invalid session.
+const SESSION_MESSAGE: &str = "Couldn't load speech. Try again.";
+
+fn text(json: &Value, key: &str) -> Option<String> {
+    json.get(key)
+        .and_then(Value::as_str)
+        .filter(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+/// The error a response reports, if any.
+fn rejection(json: &Value) -> Option<Outcome> {
+    let message = text(json, "status_msg")
+        .or_else(|| text(json, "message").filter(|message| message != "success"))
+        .or_else(|| text(json, "error"));
+    let code = json.get("status_code").and_then(Value::as_i64);
+    let says_session = ["status_msg", "message"]
+        .iter()
+        .any(|key| json.get(*key).and_then(Value::as_str) == Some(SESSION_MESSAGE));
+    let failed = code.is_some_and(|code| code != 0)
+        || json.get("success") == Some(&Value::Bool(false))
+        || says_session;
+    if !failed {
+        return None;
+    }
+    let lowercase = message.as_deref().unwrap_or_default().to_lowercase();
+    let mentions = |words: &[&str]| words.iter().any(|word| lowercase.contains(word));
+    let reason = if mentions(&["region", "country"]) {
+        Reason::Region
+    } else if code == Some(MISSING_SESSION_STATUS) {
+        Reason::MissingSession
+    } else if says_session || code == Some(SESSION_STATUS) {
+        Reason::Session
+    } else if code == Some(VOICE_STATUS) || mentions(&["speaker", "voice"]) {
+        Reason::Voice
+    } else if code == Some(TEXT_TOO_LONG_STATUS) || mentions(&["too long"]) {
+        Reason::TextTooLong
+    } else {
+        Reason::Other
+    };
+    let message = match (message, code) {
+        (Some(message), Some(code)) => format!("{} (status {})", message, code),
+        (Some(message), None) => message,
+        (None, Some(code)) => format!("status {}", code),
+        (None, None) => "request failed".to_string(),
+    };
+    Some(Outcome::Rejected { message, reason })
+}
+
+/// The structure of `json` with its values left out, like
+/// `{data: {s_key: string, v_str: string(28012)}, status_code: number}`.
+pub fn shape(json: &Value) -> String {
+    match json {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        Value::Number(_) => "number".to_string(),
+        Value::String(text) if text.len() > 64 => format!("string({})", text.len()),
+        Value::String(_) => "string".to_string(),
+        Value::Array(items) => match items.first() {
+            Some(first) => format!("[{}; {}]", shape(first), items.len()),
+            None => "[]".to_string(),
+        },
+        Value::Object(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, shape(value)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+/// Recognizes the variant of a response `body` and takes out its audio or error.
+/// Bodies that match no known variant are described by their shape in the error.
+pub fn parse(body: &str) -> Result<Parsed, String> {
+    let json: Value = serde_json::from_str(body).map_err(|_| {
+        let start: String = body.trim_start().chars().take(40).collect();
+        format!("Response is not JSON (starts with {:?})", start)
+    })?;
+    if !json.is_object() {
+        return Err(format!("Unrecognized response shape {}", shape(&json)));
+    }
+
+    let data = json.get("data").unwrap_or(&Value::Null);
+    let found = if let Some(v_str) = text(data, "v_str") {
+        Some((Schema::Nested, v_str))
+    } else if let Some(audio) = data.as_str().filter(|audio| !audio.is_empty()) {
+        Some((Schema::Flat, audio.to_string()))
+    } else {
+        text(&json, "v_data")
+            .or_else(|| text(&json, "audio"))
+            .map(|audio| (Schema::TopLevel, audio))
+    };
+    match (found, rejection(&json)) {
+        (Some((schema, audio)), _) => Ok(Parsed {
+            schema,
+            outcome: Outcome::Audio(audio),
+        }),
+        (None, Some(rejected)) => Ok(Parsed {
+            schema: match data {
+                Value::Object(_) => Schema::Nested,
+                Value::String(_) => Schema::Flat,
+                _ => Schema::Error,
+            },
+            outcome: rejected,
+        }),
+        (None, None) => Err(format!("Unrecognized response shape {}", shape(&json))),
+    }
+}
+
+/// A JSON container the [`AudioDecoder`] is inside of, with the key of the value being
+/// read for objects.
+enum Container {
+    Object(Vec<u8>),
+    Array,
+}
+
+/// Where an [`AudioDecoder`] is within the body.
+#[derive(Clone, Copy, PartialEq)]
+enum Scan {
+    /// Between tokens
+    Outside,
+    /// In a key, or in a string value that holds no audio
+    Text { key: bool, escape: bool },
+    /// In the audio string
+    Audio { escape: bool },
+    /// Past the audio: the rest of the body is not needed
+    Done,
+}
+
+/// What an [`AudioDecoder`] found in the body.
+pub enum Decoded<W> {
+    /// The audio, decoded into the writer
+    Audio(W),
+    /// No audio: the body, to be [`parse`]d for its error
+    Other(String),
+}
+
+/// Decodes the audio of a response body into a writer while the body streams in.
+///
+/// The audio is the first non-empty string in one of the places [`parse`] looks for it.
+/// Its base64 is decoded a piece at a time as [`feed`](Self::feed) is given it, so what
+/// is held at once is the piece and the writer's output; the rest of the body is kept
+/// apart in case no audio turns up.
+pub struct AudioDecoder<W> {
+    sink: W,
+    scan: Scan,
+    containers: Vec<Container>,
+    /// The body but for the audio
+    rest: Vec<u8>,
+    /// Base64 of the audio not yet decoded, fewer than four characters between feeds
+    pending: Vec<u8>,
+    found: bool,
+}
+
+impl<W: Write> AudioDecoder<W> {
+    pub fn new(sink: W) -> Self {
+        AudioDecoder {
+            sink,
+            scan: Scan::Outside,
+            containers: Vec::new(),
+            rest: Vec::new(),
+            pending: Vec::new(),
+            found: false,
+        }
+    }
+
+    /// Whether a string value that starts here is where the audio is kept.
+    fn at_audio(&self) -> bool {
+        let key = |container: &Container, name: &[u8]| matches!(container, Container::Object(key) if key == name);
+        match self.containers.as_slice() {
+            [top] => [&b"data"[..], b"v_data", b"audio"]
+                .iter()
+                .any(|name| key(top, name)),
+            [top, data] => key(top, b"data") && key(data, b"v_str"),
+            _ => false,
+        }
+    }
+
+    /// Takes the next piece of the body.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), TktsError> {
+        for &byte in bytes {
+            match self.scan {
+                Scan::Done => return self.flush(),
+                Scan::Audio { escape: true } => {
+                    // Base64 has no characters to escape but the `\/` some encoders write
+                    if byte != b'/' {
+                        return Err(TktsError::Decode(
+                            "escaped character in the base64 audio".into(),
+                        ));
+                    }
+                    self.pending.push(byte);
+                    self.scan = Scan::Audio { escape: false };
+                }
+                Scan::Audio { escape: false } => match byte {
+                    b'\\' => self.scan = Scan::Audio { escape: true },
+                    b'"' if self.found || !self.pending.is_empty() => {
+                        self.flush()?;
+                        self.finish_audio()?;
+                        self.scan = Scan::Done;
+                    }
+                    // An empty string holds no audio, and the search goes on
+                    b'"' => {
+                        self.rest.push(byte);
+                        self.scan = Scan::Outside;
+                    }
+                    _ => self.pending.push(byte),
+                },
+                Scan::Text { key, escape } => {
+                    self.rest.push(byte);
+                    if escape {
+                        self.scan = Scan::Text { key, escape: false };
+                    } else if byte == b'\\' {
+                        self.scan = Scan::Text { key, escape: true };
+                    } else if byte == b'"' {
+                        self.scan = Scan::Outside;
+                    } else if key && let Some(Container::Object(name)) = self.containers.last_mut()
+                    {
+                        name.push(byte);
+                    }
+                }
+                Scan::Outside => {
+                    self.rest.push(byte);
+                    match byte {
+                        b'{' => self.containers.push(Container::Object(Vec::new())),
+                        b'[' => self.containers.push(Container::Array),
+                        b'}' | b']' => {
+                            self.containers.pop();
+                        }
+                        b',' => {
+                            if let Some(Container::Object(name)) = self.containers.last_mut() {
+                                name.clear();
+                            }
+                        }
+                        b'"' => {
+                            let key = matches!(self.containers.last(), Some(Container::Object(_)))
+                                && !self.after_colon();
+                            self.scan = if !key && self.at_audio() {
+                                // The quotes stay in the rest, which is parsed only
+                                // when the audio is empty
+                                Scan::Audio { escape: false }
+                            } else {
+                                Scan::Text { key, escape: false }
+                            };
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        self.flush()
+    }
+
+    /// Whether the last token of the rest is the colon after a key.
+    fn after_colon(&self) -> bool {
+        self.rest[..self.rest.len() - 1]
+            .iter()
+            .rev()
+            .find(|byte| !byte.is_ascii_whitespace())
+            == Some(&b':')
+    }
+
+    /// Decodes the whole groups of four characters taken so far.
+    fn flush(&mut self) -> Result<(), TktsError> {
+        let whole = self.pending.len() / 4 * 4;
+        if whole == 0 {
+            return Ok(());
+        }
+        let audio = general_purpose::STANDARD
+            .decode(&self.pending[..whole])
+            .map_err(|e| TktsError::Decode(e.into()))?;
+        self.sink.write_all(&audio)?;
+        self.pending.drain(..whole);
+        self.found = true;
+        Ok(())
+    }
+
+    /// Decodes what is left at the end of the audio, which must be nothing.
+    fn finish_audio(&mut self) -> Result<(), TktsError> {
+        if !self.pending.is_empty() {
+            let audio = general_purpose::STANDARD
+                .decode(&self.pending)
+                .map_err(|e| TktsError::Decode(e.into()))?;
+            self.sink.write_all(&audio)?;
+            self.pending.clear();
+        }
+        self.found = true;
+        Ok(())
+    }
+
+    /// Ends the body: the writer with the audio, or the body to parse if it had none.
+    pub fn finish(self) -> Result<Decoded<W>, TktsError> {
+        match self.scan {
+            Scan::Done => Ok(Decoded::Audio(self.sink)),
+            Scan::Audio { .. } => Err(TktsError::Decode(
+                "the response ended inside the audio".into(),
+            )),
+            _ => Ok(Decoded::Other(String::from_utf8(self.rest).unwrap_or_else(
+                |e| String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            ))),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Rhai scripting hooks, loaded from the script paths under `[scripting]` in the config.
+//!
+//! A script defines any of these functions; each hook runs through the scripts in order:
+//!
+//! - `on_text(text)` returns the rewritten input text, after the text filters.
+//! - `on_chunk(chunk, index)` returns the rewritten chunk; longer results are split again.
+//! - `on_voice_select(chunk, index, voice)` returns the voice for that chunk.
+//! - `on_complete(info)` is told `#{ chunks, bytes, voice }` once the audio is ready.
+
+use rhai::{AST, Dynamic, Engine, Scope};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Operations a single hook call may run, so a runaway loop cannot hang requests.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+static SCRIPTS: OnceLock<Scripts> = OnceLock::new();
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptingConfig {
+    /// Rhai scripts, relative to the config file
+    pub scripts: Vec<PathBuf>,
+}
+
+struct Scripts {
+    engine: Engine,
+    asts: Vec<(PathBuf, AST)>,
+}
+
+impl Scripts {
+    /// Calls the hook `name` of every script defining it, building each call's arguments
+    /// from the previous script's result.
+    fn chain<T: Clone + Send + Sync + 'static>(
+        &self,
+        name: &str,
+        mut value: T,
+        args: impl Fn(&T) -> Vec<Dynamic>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        for (path, ast) in &self.asts {
+            let arity = args(&value).len();
+            if !ast
+                .iter_functions()
+                .any(|function| function.name == name && function.params.len() == arity)
+            {
+                continue;
+            }
+            value = self
+                .engine
+                .call_fn::<T>(&mut Scope::new(), ast, name, args(&value))
+                .map_err(|e| format!("{} in {}: {}", name, path.display(), e))?;
+        }
+        Ok(value)
+    }
+}
+
+/// Compiles the configured scripts once for the whole process.
+pub fn init(config: &ScriptingConfig) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if config.scripts.is_empty() {
+        return Ok(());
+    }
+    let config_path = crate::config::path().unwrap_or_default();
+    let base = config_path.parent().unwrap_or(Path::new(""));
+
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let asts = config
+        .scripts
+        .iter()
+        .map(|path| {
+            let path = base.join(path);
+            match engine.compile_file(path.clone()) {
+                Ok(ast) => Ok((path, ast)),
+                Err(e) => Err(format!("Failed to load script {}: {}", path.display(), e)),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let _ = SCRIPTS.set(Scripts { engine, asts });
+    Ok(())
+}
+
+pub fn on_text(text: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    match SCRIPTS.get() {
+        Some(scripts) => scripts.chain("on_text", text, |text| vec![text.into()]),
+        None => Ok(text),
+    }
+}
+
+pub fn on_chunk(
+    chunk: String,
+    index: usize,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    match SCRIPTS.get() {
+        Some(scripts) => scripts.chain("on_chunk", chunk, |chunk| {
+            vec![chunk.into(), (index as i64).into()]
+        }),
+        None => Ok(chunk),
+    }
+}
+
+pub fn on_voice_select(
+    chunk: &str,
+    index: usize,
+    voice: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    match SCRIPTS.get() {
+        Some(scripts) => scripts.chain("on_voice_select", voice.to_string(), |voice| {
+            vec![chunk.into(), (index as i64).into(), voice.into()]
+        }),
+        None => Ok(voice.to_string()),
+    }
+}
+
+/// Runs `on_complete`; its errors are only reported, since the audio is already made.
+pub fn on_complete(chunks: usize, bytes: usize, voice: &str) {
+    let Some(scripts) = SCRIPTS.get() else {
+        return;
+    };
+    let mut info = rhai::Map::new();
+    info.insert("chunks".into(), (chunks as i64).into());
+    info.insert("bytes".into(), (bytes as i64).into());
+    info.insert("voice".into(), voice.into());
+    if let Err(e) = scripts.chain("on_complete", Dynamic::UNIT, |_| vec![info.clone().into()]) {
+        progress!("Script error: {}", e);
+    }
+}
@@ -0,0 +1,255 @@
+//! `tktts self-update`: replaces the running binary with the latest GitHub release.
+//!
+//! A release carries one asset per target, named `tktts-<target>` (`.exe` on Windows) or
+//! `tktts-<target>.tar.gz`, a `SHA256SUMS` file listing them and `SHA256SUMS.minisig`,
+//! its minisign signature. The download must match its listed checksum, and the list
+//! must carry a valid signature by a known public key: built in by release builds
+//! through `TKTTS_RELEASE_PUBLIC_KEY`, or set as `[update] public_key`. Without a key
+//! the update is refused, unless `--no-verify` accepts an unsigned list.
+
+use crate::term::{self, Style};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+/// The target triple this binary was built for.
+const TARGET: &str = env!("TKTTS_TARGET");
+
+/// Key release builds are signed with, when given at build time.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("TKTTS_RELEASE_PUBLIC_KEY");
+
+pub const DEFAULT_REPO: &str = "espeon/tktts";
+
+/// The `[update]` config section.
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// GitHub repository releases come from, as OWNER/NAME
+    pub repo: Option<String>,
+    /// minisign public key (the `RW...` line) release checksums are signed with
+    pub public_key: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct SelfUpdateArgs {
+    /// Only report whether a newer release exists
+    #[arg(long)]
+    check: bool,
+
+    /// Install this release tag (e.g. v0.4.0) instead of the latest, also to downgrade
+    #[arg(long, value_name = "TAG")]
+    version: Option<String>,
+
+    /// Reinstall even when the release is not newer than this binary
+    #[arg(long)]
+    force: bool,
+
+    /// Install even though no release public key is known (builds without one, such as
+    /// `cargo install`), checking only the checksums the release itself lists
+    #[arg(long)]
+    no_verify: bool,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// The numeric parts of a version like `v0.4.1`, for comparing.
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+pub async fn get(url: &str) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+    let response = crate::http::client()
+        .get(url)
+        .header("User-Agent", format!("tktts/{}", env!("CARGO_PKG_VERSION")))
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", url, e))?;
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        reqwest::StatusCode::NOT_FOUND => Err(format!("Not found: {}", url).into()),
+        status => Err(format!("{} answered {}", url, status).into()),
+    }
+}
+
+async fn download(asset: &Asset) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = get(&asset.browser_download_url).await?.bytes().await?;
+    Ok(bytes.to_vec())
+}
+
+/// The checksum `sums` (a `SHA256SUMS` file) lists for `name`.
+fn listed_checksum<'a>(sums: &'a str, name: &str) -> Option<&'a str> {
+    sums.lines().find_map(|line| {
+        let (hash, file) = line.split_once(char::is_whitespace)?;
+        // `sha256sum` marks binary mode with a leading `*`
+        (file.trim().trim_start_matches('*') == name).then_some(hash)
+    })
+}
+
+/// Checks the minisign `signature` of `sums`. Without a known public key that fails,
+/// unless `no_verify` accepts the list unchecked.
+fn verify_signature(
+    sums: &[u8],
+    signature: Option<&str>,
+    no_verify: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = &crate::config::get().update;
+    let Some(key) = config.public_key.as_deref().or(RELEASE_PUBLIC_KEY) else {
+        if !no_verify {
+            return Err("No release public key is known, so the release cannot be \
+                        verified. Set [update] public_key to the minisign key it is signed \
+                        with, or pass --no-verify to trust its checksums alone"
+                .into());
+        }
+        term::status(
+            Style::Yellow,
+            "Unverified",
+            "--no-verify: the release's signature is not checked, only its checksums",
+        );
+        return Ok(());
+    };
+    let signature = signature.ok_or("The release has no SHA256SUMS.minisig signature")?;
+    verify_minisign(sums, signature, key, "SHA256SUMS")
+}
+
+/// Checks that `signature`, the contents of a `.minisig` file, signs `data` with `key`.
+pub fn verify_minisign(
+    data: &[u8],
+    signature: &str,
+    key: &str,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let key = minisign_verify::PublicKey::from_base64(key.trim())
+        .map_err(|e| format!("Invalid public key: {}", e))?;
+    let signature = minisign_verify::Signature::decode(signature)
+        .map_err(|e| format!("Invalid {}.minisig: {}", name, e))?;
+    key.verify(data, &signature, false)
+        .map_err(|e| format!("{} signature does not verify: {}", name, e))?;
+    Ok(())
+}
+
+/// The binary in a downloaded asset, unpacking a `.tar.gz`.
+fn unpack(name: &str, data: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    if !name.ends_with(".tar.gz") {
+        return Ok(data);
+    }
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(data.as_slice()));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        if matches!(file_name, Some("tktts" | "tktts.exe")) && entry.header().entry_type().is_file()
+        {
+            let mut binary = Vec::new();
+            entry.read_to_end(&mut binary)?;
+            return Ok(binary);
+        }
+    }
+    Err(format!("{} does not contain a tktts binary", name).into())
+}
+
+pub async fn run(args: SelfUpdateArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let repo = crate::config::get()
+        .update
+        .repo
+        .as_deref()
+        .unwrap_or(DEFAULT_REPO);
+    let url = match &args.version {
+        Some(tag) => format!(
+            "https://api.github.com/repos/{}/releases/tags/{}",
+            repo, tag
+        ),
+        None => format!("https://api.github.com/repos/{}/releases/latest", repo),
+    };
+    let release: Release = get(&url).await?.json().await?;
+    let current = env!("CARGO_PKG_VERSION");
+
+    let newer = version_parts(&release.tag_name) > version_parts(current);
+    if args.check {
+        match newer {
+            true => println!("{} is available (running {})", release.tag_name, current),
+            false => println!("Up to date ({}, latest {})", current, release.tag_name),
+        }
+        return Ok(());
+    }
+    if !newer && !args.force && args.version.is_none() {
+        term::status(
+            Style::Green,
+            "Up to date",
+            format!("{} (latest release {})", current, release.tag_name),
+        );
+        return Ok(());
+    }
+
+    let binary_name = format!("tktts-{}{}", TARGET, std::env::consts::EXE_SUFFIX);
+    let archive_name = format!("tktts-{}.tar.gz", TARGET);
+    let asset = release
+        .asset(&binary_name)
+        .or_else(|| release.asset(&archive_name))
+        .ok_or_else(|| format!("{} has no build for {}", release.tag_name, TARGET))?;
+    let sums_asset = release
+        .asset("SHA256SUMS")
+        .ok_or_else(|| format!("{} publishes no SHA256SUMS", release.tag_name))?;
+
+    let sums = download(sums_asset).await?;
+    let signature = match release.asset("SHA256SUMS.minisig") {
+        Some(asset) => Some(String::from_utf8(download(asset).await?)?),
+        None => None,
+    };
+    verify_signature(&sums, signature.as_deref(), args.no_verify)?;
+    let sums = String::from_utf8(sums)?;
+    let expected = listed_checksum(&sums, &asset.name)
+        .ok_or_else(|| format!("SHA256SUMS does not list {}", asset.name))?;
+
+    term::status(
+        Style::Cyan,
+        "Downloading",
+        format!("{} {}", asset.name, release.tag_name),
+    );
+    let data = download(asset).await?;
+    let actual = crate::checksum::sha256_hex(&data);
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            asset.name, expected, actual
+        )
+        .into());
+    }
+    let binary = unpack(&asset.name, data)?;
+
+    // Written next to the binary, so the swap is a rename on the same file system
+    let exe = std::env::current_exe()?;
+    let mut temp = exe.as_os_str().to_owned();
+    temp.push(".new");
+    std::fs::write(&temp, &binary)
+        .map_err(|e| format!("Failed to write next to {}: {}", exe.display(), e))?;
+    let replaced = self_replace::self_replace(&temp);
+    let _ = std::fs::remove_file(&temp);
+    replaced.map_err(|e| format!("Failed to replace {}: {}", exe.display(), e))?;
+
+    term::status(
+        Style::Green,
+        "Updated",
+        format!("{} from {} to {}", exe.display(), current, release.tag_name),
+    );
+    Ok(())
+}
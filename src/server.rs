@@ -0,0 +1,440 @@
+//! HTTP server mode.
+//!
+//! Implements the Home Assistant `tts_get_url` contract: `POST /api/tts_get_url` with a
+//! message synthesizes it into the media directory and answers with a URL under
+//! `/api/tts_proxy/`, from which the generated files are served.
+//!
+//! `GET /api/voices` lists the voice catalog and `GET /api/tts?text=..&voice=..` streams
+//! audio chunk by chunk; with `--web-ui` a small page at `/` ties the two together.
+//!
+//! Chunk requests of all clients share `--workers` workers. Requests may carry a
+//! `priority` of `low`, `normal` (default) or `high`, so interactive messages get the
+//! next free worker ahead of long background jobs. A client is its `X-Api-Key` (one of
+//! `[server.api_keys]` in the config) or else its address; clients take turns, and each
+//! has at most its key's `concurrency` (or `--client-concurrency`) chunks in flight.
+//!
+//! With `--grpc-listen` the same service is also offered over gRPC (see [`grpc`]).
+
+mod grpc;
+
+use crate::queue::{Priority, Queue};
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio_util::task::AbortOnDropHandle;
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:5002")]
+    listen: String,
+
+    /// Directory where generated audio files are stored and served from
+    #[arg(long)]
+    media_dir: Option<PathBuf>,
+
+    /// Base URL used in returned media URLs (default: derived from the Host header)
+    #[arg(long)]
+    public_url: Option<String>,
+
+    /// Voice used when a request does not pick one
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+
+    /// Serve a small web UI at `/` for use from a browser
+    #[arg(long)]
+    web_ui: bool,
+
+    /// Chunk requests sent at once, shared by all clients
+    #[arg(long, default_value_t = 4)]
+    workers: usize,
+
+    /// Chunks one client may have in flight, unless its API key sets its own (default: --workers)
+    #[arg(long, value_name = "N")]
+    client_concurrency: Option<usize>,
+
+    /// Also serve the gRPC API of proto/tktts.proto on this address
+    #[arg(long, value_name = "ADDR")]
+    grpc_listen: Option<SocketAddr>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Keys clients send as `X-Api-Key`, e.g. `[server.api_keys.bot]`
+    pub api_keys: BTreeMap<String, ApiKeyConfig>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Chunks this key may have in flight, instead of `--client-concurrency`
+    pub concurrency: Option<usize>,
+}
+
+struct ServerState {
+    session_id: String,
+    api_root_url: String,
+    media_dir: PathBuf,
+    public_url: Option<String>,
+    speaker: String,
+    queue: Arc<Queue>,
+    client_concurrency: usize,
+}
+
+/// Who a request is from, for the worker queue.
+struct Client {
+    name: String,
+    concurrency: usize,
+}
+
+type ChunkResult = Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>;
+
+/// One request's text, split and checked once, with the voice of each chunk.
+struct Job {
+    chunks: Vec<(String, String)>,
+    speaker: String,
+    /// Chunks already in the cache when the job started
+    cached: usize,
+    started: Instant,
+}
+
+impl ServerState {
+    /// Identifies the client by its API key, or by its address when it sends none.
+    fn client(&self, headers: &HeaderMap, addr: SocketAddr) -> Result<Client, ApiError> {
+        let Some(key) = headers.get("x-api-key") else {
+            return Ok(Client {
+                name: addr.ip().to_string(),
+                concurrency: self.client_concurrency,
+            });
+        };
+        let unknown = || (StatusCode::UNAUTHORIZED, "Unknown API key".to_string());
+        let key = key.to_str().map_err(|_| unknown())?;
+        let config = crate::config::get()
+            .server
+            .api_keys
+            .get(key)
+            .ok_or_else(unknown)?;
+        Ok(Client {
+            name: format!("key:{}", key),
+            concurrency: config.concurrency.unwrap_or(self.client_concurrency),
+        })
+    }
+
+    /// Splits `text` and runs the checks of a whole job once: the voice for its
+    /// language, each chunk's voice, and the `--estimate` and `--daily-limit` preflight.
+    fn prepare(
+        &self,
+        text: &str,
+        voice: &str,
+    ) -> Result<Job, Box<dyn std::error::Error + Send + Sync>> {
+        let started = Instant::now();
+        let speaker = crate::lang::select(text, voice);
+        let chunks = crate::split_text(text, crate::BYTE_LIMIT)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let voice = crate::chunk_voice(&chunk, index, &speaker)?;
+                Ok((chunk, voice))
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error + Send + Sync>>>()?;
+        let voiced: Vec<(&str, &str)> = chunks
+            .iter()
+            .map(|(chunk, voice)| (chunk.as_str(), voice.as_str()))
+            .collect();
+        let cached = voiced
+            .iter()
+            .filter(|(chunk, voice)| crate::cache::contains(chunk, voice))
+            .count();
+        crate::estimate::check(&voiced, chunks.len() - cached)?;
+        crate::quota::check(&self.session_id, chunks.len() - cached)?;
+        Ok(Job {
+            chunks,
+            speaker,
+            cached,
+            started,
+        })
+    }
+
+    /// Synthesizes one chunk: from the cache, or as soon as the queue hands it a worker.
+    async fn synthesize_chunk(
+        &self,
+        client: &Client,
+        chunk: &str,
+        voice: &str,
+        priority: Priority,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(audio) = crate::cache::get(chunk, voice) {
+            return Ok(audio);
+        }
+        let _worker = self
+            .queue
+            .acquire(&client.name, client.concurrency, priority)
+            .await;
+        crate::fetch_chunk(chunk, voice, &self.session_id, &self.api_root_url).await
+    }
+
+    /// Reports a finished job once: its summary line and the `on_complete` hook.
+    fn finish(&self, job: &Job, bytes: usize) {
+        let chunks = job.chunks.len();
+        crate::cache::record(job.cached as u64, (chunks - job.cached) as u64);
+        crate::term::summary(chunks, job.cached, bytes, job.started.elapsed());
+        crate::script::on_complete(chunks, bytes, &job.speaker);
+    }
+
+    /// Starts all chunks of `job` at once, each aborted when its handle is dropped, such
+    /// as when the client of a stream goes away.
+    fn spawn_chunks(
+        self: &Arc<Self>,
+        client: &Arc<Client>,
+        job: &Job,
+        priority: Priority,
+    ) -> VecDeque<AbortOnDropHandle<ChunkResult>> {
+        job.chunks
+            .iter()
+            .map(|(chunk, voice)| {
+                let (state, client) = (self.clone(), client.clone());
+                let (chunk, voice) = (chunk.clone(), voice.clone());
+                AbortOnDropHandle::new(tokio::spawn(async move {
+                    state
+                        .synthesize_chunk(&client, &chunk, &voice, priority)
+                        .await
+                }))
+            })
+            .collect()
+    }
+}
+
+type ApiError = (StatusCode, String);
+
+/// Generated files are named after the cache key of the text for the resolved voice, so
+/// repeated requests reuse them, while a remapped alias or a normalization change makes
+/// a new file.
+fn media_file_name(voice: &str, text: &str) -> String {
+    let key = crate::cache::key(text, crate::config::resolve_voice(voice));
+    format!("{}.mp3", &key[..32])
+}
+
+async fn tts_get_url(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    // Home Assistant sends "message"; "text" is accepted for hand-written clients
+    let text = body["message"]
+        .as_str()
+        .or_else(|| body["text"].as_str())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .ok_or((StatusCode::BAD_REQUEST, "Missing message".to_string()))?;
+    let voice = body["options"]["voice"]
+        .as_str()
+        .or_else(|| body["voice"].as_str())
+        .unwrap_or(&state.speaker);
+    let priority = match body["priority"].as_str() {
+        Some(name) => Priority::parse(name).map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+        None => Priority::default(),
+    };
+    let client = state.client(&headers, addr)?;
+
+    let file_name = media_file_name(voice, text);
+    let file_path = state.media_dir.join(&file_name);
+    if !file_path.exists() {
+        let job = state
+            .prepare(text, voice)
+            .map_err(|e| (StatusCode::BAD_GATEWAY, crate::term::redact(e)))?;
+        let requests = job
+            .chunks
+            .iter()
+            .map(|(chunk, voice)| state.synthesize_chunk(&client, chunk, voice, priority));
+        let audio = futures_util::future::try_join_all(requests)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, crate::term::redact(e)))?
+            .concat();
+        state.finish(&job, audio.len());
+        // Written aside and renamed, so a request for the same text meanwhile never
+        // serves a partial file, and two of them never write into the same one
+        let temp = file_path.with_extension(format!("{:016x}.tmp", fastrand::u64(..)));
+        let written = match tokio::fs::write(&temp, &audio).await {
+            Ok(()) => tokio::fs::rename(&temp, &file_path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, crate::term::redact(e)));
+        }
+    }
+
+    let path = format!("/api/tts_proxy/{}", file_name);
+    let base_url = match &state.public_url {
+        Some(public_url) => public_url.trim_end_matches('/').to_string(),
+        None => {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .ok_or((StatusCode::BAD_REQUEST, "Missing Host header".to_string()))?;
+            format!("http://{}", host)
+        }
+    };
+
+    Ok(Json(
+        json!({ "url": format!("{}{}", base_url, path), "path": path }),
+    ))
+}
+
+async fn tts_proxy(
+    State(state): State<Arc<ServerState>>,
+    Path(file_name): Path<String>,
+) -> Result<Response, ApiError> {
+    // Only serve names this server generated; this also rules out path traversal
+    let valid = file_name
+        .strip_suffix(".mp3")
+        .is_some_and(|stem| !stem.is_empty() && stem.chars().all(|c| c.is_ascii_hexdigit()));
+    if !valid {
+        return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+    }
+
+    let audio = tokio::fs::read(state.media_dir.join(&file_name))
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, "Not found".to_string()))?;
+
+    Ok(([(header::CONTENT_TYPE, "audio/mpeg")], audio).into_response())
+}
+
+async fn list_voices() -> Json<Value> {
+    let voices: Vec<Value> = crate::voices::all()
+        .map(|voice| {
+            json!({
+                "code": voice.code,
+                "name": voice.name,
+                "language": voice.language,
+                "tags": voice.tags,
+            })
+        })
+        .collect();
+    Json(Value::Array(voices))
+}
+
+/// Streams the audio of each chunk as soon as it and all chunks before it are ready.
+async fn tts_stream(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let text = params
+        .get("text")
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+        .ok_or((StatusCode::BAD_REQUEST, "Missing text".to_string()))?;
+    let voice = params.get("voice").unwrap_or(&state.speaker).clone();
+    let priority = match params.get("priority") {
+        Some(name) => Priority::parse(name).map_err(|e| (StatusCode::BAD_REQUEST, e))?,
+        None => Priority::default(),
+    };
+    let client = Arc::new(state.client(&headers, addr)?);
+    let job = state
+        .prepare(text, &voice)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, crate::term::redact(e)))?;
+
+    // All chunks are queued at once, but yielded in order; a client that disconnects
+    // drops the body and with it the chunks not yet sent
+    let requests = state.spawn_chunks(&client, &job, priority);
+    let body = futures_util::stream::unfold(
+        (requests, state, job, 0),
+        |(mut requests, state, job, bytes)| async move {
+            let Some(request) = requests.pop_front() else {
+                state.finish(&job, bytes);
+                return None;
+            };
+            let audio = match request.await {
+                Ok(Ok(audio)) => Ok(audio),
+                Ok(Err(e)) => Err(io::Error::other(crate::term::redact(e))),
+                Err(e) => Err(io::Error::other(e)),
+            };
+            let bytes = bytes + audio.as_ref().map_or(0, Vec::len);
+            Some((audio, (requests, state, job, bytes)))
+        },
+    );
+
+    Ok((
+        [(header::CONTENT_TYPE, "audio/mpeg")],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+async fn web_ui() -> Html<&'static str> {
+    Html(include_str!("web/index.html"))
+}
+
+pub async fn run(args: ServeArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let media_dir = args
+        .media_dir
+        .unwrap_or_else(|| std::env::temp_dir().join("tktts"));
+    tokio::fs::create_dir_all(&media_dir).await?;
+
+    let state = Arc::new(ServerState {
+        session_id,
+        api_root_url,
+        media_dir,
+        public_url: args.public_url,
+        speaker: args.speaker,
+        queue: Queue::new(args.workers),
+        client_concurrency: args.client_concurrency.unwrap_or(args.workers),
+    });
+
+    let mut app = Router::new()
+        .route("/api/tts_get_url", post(tts_get_url))
+        .route("/api/tts_proxy/:file", get(tts_proxy))
+        .route("/api/voices", get(list_voices))
+        .route("/api/tts", get(tts_stream));
+    if args.web_ui {
+        app = app.route("/", get(web_ui));
+    }
+    let grpc_state = state.clone();
+    let app = app.with_state(state);
+
+    let listener = TcpListener::bind(&args.listen).await?;
+    progress!("Listening on http://{}", listener.local_addr()?);
+    let http = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    );
+    let Some(grpc_addr) = args.grpc_listen else {
+        http.await?;
+        return Ok(());
+    };
+
+    let grpc_listener = TcpListener::bind(grpc_addr).await?;
+    progress!("Listening for gRPC on {}", grpc_listener.local_addr()?);
+    let incoming = tonic::transport::server::TcpIncoming::from_listener(grpc_listener, true, None)?;
+    let grpc = tonic::transport::Server::builder()
+        .add_service(grpc::TtsServer::new(grpc::Service(grpc_state)))
+        .serve_with_incoming(incoming);
+    tokio::try_join!(
+        async {
+            http.await
+                .map_err(Box::<dyn std::error::Error + Send + Sync>::from)
+        },
+        async {
+            grpc.await
+                .map_err(Box::<dyn std::error::Error + Send + Sync>::from)
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,153 @@
+//! The gRPC service of `--grpc-listen`, defined in `proto/tktts.proto`.
+//!
+//! It shares the REST server's state, so gRPC and HTTP clients take turns on the same
+//! workers and an API key has the same concurrency over either.
+
+use super::ServerState;
+use crate::queue::Priority;
+use futures_util::Stream;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+mod proto {
+    tonic::include_proto!("tktts.v1");
+}
+
+pub use proto::tts_server::TtsServer;
+
+pub struct Service(pub Arc<ServerState>);
+
+struct Job {
+    client: super::Client,
+    text: String,
+    voice: String,
+    priority: Priority,
+}
+
+impl Service {
+    /// The job of `request` (the status is boxed, as `Status` is large).
+    fn job(&self, request: Request<proto::SynthesizeRequest>) -> Result<Job, Box<Status>> {
+        let addr = request
+            .remote_addr()
+            .unwrap_or(SocketAddr::from(([0, 0, 0, 0], 0)));
+        let headers = request.metadata().clone().into_headers();
+        let client = self
+            .0
+            .client(&headers, addr)
+            .map_err(|(_, message)| Status::unauthenticated(message))
+            .map_err(Box::new)?;
+        let body = request.into_inner();
+        let text = body.text.trim();
+        if text.is_empty() {
+            return Err(Box::new(Status::invalid_argument("Missing text")));
+        }
+        let voice = match body.voice.as_str() {
+            "" => &self.0.speaker,
+            voice => voice,
+        };
+        let priority = match body.priority() {
+            proto::Priority::Low => Priority::Low,
+            proto::Priority::Normal => Priority::Normal,
+            proto::Priority::High => Priority::High,
+        };
+        Ok(Job {
+            client,
+            text: text.to_string(),
+            voice: crate::config::resolve_voice(voice).to_string(),
+            priority,
+        })
+    }
+}
+
+impl Job {
+    /// The chunks of the job, split and checked once.
+    fn prepare(&self, state: &ServerState) -> Result<super::Job, Box<Status>> {
+        state
+            .prepare(&self.text, &self.voice)
+            .map_err(|e| Box::new(Status::unavailable(crate::term::redact(e))))
+    }
+}
+
+#[tonic::async_trait]
+impl proto::tts_server::Tts for Service {
+    async fn synthesize(
+        &self,
+        request: Request<proto::SynthesizeRequest>,
+    ) -> Result<Response<proto::SynthesizeResponse>, Status> {
+        let job = self.job(request).map_err(|e| *e)?;
+        let prepared = job.prepare(&self.0).map_err(|e| *e)?;
+        let requests = prepared.chunks.iter().map(|(chunk, voice)| {
+            self.0
+                .synthesize_chunk(&job.client, chunk, voice, job.priority)
+        });
+        let audio = futures_util::future::try_join_all(requests)
+            .await
+            .map_err(|e| Status::unavailable(crate::term::redact(e)))?
+            .concat();
+        self.0.finish(&prepared, audio.len());
+        Ok(Response::new(proto::SynthesizeResponse {
+            audio,
+            voice: job.voice,
+            chunks: prepared.chunks.len() as u32,
+        }))
+    }
+
+    type SynthesizeStreamStream =
+        Pin<Box<dyn Stream<Item = Result<proto::AudioChunk, Status>> + Send>>;
+
+    /// Queues all chunks at once, but sends them in order. Chunks not yet sent are
+    /// dropped with the stream when the client goes away.
+    async fn synthesize_stream(
+        &self,
+        request: Request<proto::SynthesizeRequest>,
+    ) -> Result<Response<Self::SynthesizeStreamStream>, Status> {
+        let job = self.job(request).map_err(|e| *e)?;
+        let prepared = job.prepare(&self.0).map_err(|e| *e)?;
+        let requests = self
+            .0
+            .spawn_chunks(&Arc::new(job.client), &prepared, job.priority);
+        let stream = futures_util::stream::unfold(
+            (requests, self.0.clone(), prepared, 0),
+            |(mut requests, state, job, bytes)| async move {
+                let count = job.chunks.len();
+                let index = count - requests.len();
+                let Some(request) = requests.pop_front() else {
+                    state.finish(&job, bytes);
+                    return None;
+                };
+                let chunk = match request.await {
+                    Ok(Ok(audio)) => Ok(proto::AudioChunk {
+                        index: index as u32,
+                        count: count as u32,
+                        text: job.chunks[index].0.clone(),
+                        audio,
+                    }),
+                    Ok(Err(e)) => Err(Status::unavailable(crate::term::redact(e))),
+                    Err(e) => Err(Status::internal(crate::term::redact(e))),
+                };
+                let bytes = bytes + chunk.as_ref().map_or(0, |chunk| chunk.audio.len());
+                Some((chunk, (requests, state, job, bytes)))
+            },
+        );
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn list_voices(
+        &self,
+        request: Request<proto::ListVoicesRequest>,
+    ) -> Result<Response<proto::ListVoicesResponse>, Status> {
+        let language = request.into_inner().language.to_lowercase();
+        let voices = crate::voices::all()
+            .filter(|voice| voice.language.to_lowercase().starts_with(&language))
+            .map(|voice| proto::Voice {
+                code: voice.code.to_string(),
+                name: voice.name.to_string(),
+                language: voice.language.to_string(),
+                tags: voice.tags.iter().map(|tag| tag.to_string()).collect(),
+            })
+            .collect();
+        Ok(Response::new(proto::ListVoicesResponse { voices }))
+    }
+}
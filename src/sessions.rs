@@ -0,0 +1,178 @@
+//! Scheduling requests across a pool of session IDs.
+//!
+//! `TIKTOK_SESSIONID` may list several comma-separated IDs. Each request goes to the
+//! session with the most headroom: the share of its `--daily-limit` left today (or,
+//! without a limit, the fewest requests so far), discounted by its recent failure rate.
+//! A session the API rejects is marked down and the request moves on to the next one.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Outcomes remembered per session for its failure rate.
+const RECENT: usize = 20;
+
+static HEALTH: Mutex<Option<HashMap<String, Health>>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Health {
+    /// Whether each of the recent requests succeeded, newest last
+    recent: VecDeque<bool>,
+    in_flight: u64,
+}
+
+impl Health {
+    /// Share of recent requests that succeeded, starting from an even guess.
+    fn success_rate(&self) -> f64 {
+        let successes = self.recent.iter().filter(|&&ok| ok).count();
+        (successes as f64 + 1.0) / (self.recent.len() as f64 + 2.0)
+    }
+}
+
+fn with_health<T>(session_id: &str, f: impl FnOnce(&mut Health) -> T) -> T {
+    let mut health = HEALTH.lock().unwrap();
+    let health = health.get_or_insert_with(HashMap::new);
+    f(health.entry(session_id.to_string()).or_default())
+}
+
+/// The session IDs of a comma-separated pool.
+pub fn pool(session_ids: &str) -> impl Iterator<Item = &str> {
+    session_ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+}
+
+/// The sessions of the pool, best first.
+pub fn ranked(session_ids: &str) -> Vec<&str> {
+    let today = crate::quota::Today::load();
+    rank(
+        session_ids,
+        |session_id| today.used(session_id),
+        crate::quota::daily_limit(),
+    )
+}
+
+/// The sessions of the pool by their headroom under `limit` after the requests `used`
+/// today and those in flight, discounted by their success rate.
+fn rank(session_ids: &str, used: impl Fn(&str) -> u64, limit: Option<u64>) -> Vec<&str> {
+    let mut scored: Vec<(f64, &str)> = pool(session_ids)
+        .map(|session_id| {
+            let used = used(session_id);
+            let (in_flight, success_rate) = with_health(session_id, |health| {
+                (health.in_flight, health.success_rate())
+            });
+            let headroom = match limit {
+                Some(limit) => limit.saturating_sub(used + in_flight) as f64 / limit.max(1) as f64,
+                None => 1.0 / (1 + used + in_flight) as f64,
+            };
+            (headroom * success_rate, session_id)
+        })
+        .collect();
+    // Stable, so ties keep the configured order
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+        .into_iter()
+        .map(|(_, session_id)| session_id)
+        .collect()
+}
+
+/// A request in flight on a session, counted until it is dropped.
+pub struct Request<'a> {
+    session_id: &'a str,
+}
+
+pub fn start(session_id: &str) -> Request<'_> {
+    with_health(session_id, |health| health.in_flight += 1);
+    Request { session_id }
+}
+
+impl Request<'_> {
+    /// Records whether the session was accepted, for its failure rate.
+    pub fn finish(self, ok: bool) {
+        with_health(self.session_id, |health| {
+            health.recent.push_back(ok);
+            if health.recent.len() > RECENT {
+                health.recent.pop_front();
+            }
+        });
+    }
+}
+
+impl Drop for Request<'_> {
+    fn drop(&mut self) {
+        with_health(self.session_id, |health| health.in_flight -= 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn used(counts: &[(&str, u64)]) -> impl Fn(&str) -> u64 {
+        let counts: HashMap<String, u64> = counts
+            .iter()
+            .map(|&(session_id, used)| (session_id.to_string(), used))
+            .collect();
+        move |session_id| counts.get(session_id).copied().unwrap_or(0)
+    }
+
+    fn fail(session_id: &str, times: usize) {
+        for _ in 0..times {
+            start(session_id).finish(false);
+        }
+    }
+
+    #[test]
+    fn with_a_limit_the_most_left_of_it_goes_first() {
+        let used = used(&[("limit-a", 80), ("limit-b", 20), ("limit-c", 100)]);
+        assert_eq!(
+            rank("limit-a, limit-b,limit-c", used, Some(100)),
+            ["limit-b", "limit-a", "limit-c"]
+        );
+    }
+
+    #[test]
+    fn without_a_limit_the_least_used_goes_first() {
+        let used = used(&[("free-a", 5), ("free-b", 0), ("free-c", 5)]);
+        // Ties keep the configured order
+        assert_eq!(
+            rank("free-a,free-b,free-c", used, None),
+            ["free-b", "free-a", "free-c"]
+        );
+    }
+
+    #[test]
+    fn requests_in_flight_count_as_used() {
+        let _request = start("flight-a");
+        assert_eq!(
+            rank("flight-a,flight-b", used(&[]), None),
+            ["flight-b", "flight-a"]
+        );
+    }
+
+    #[test]
+    fn failures_discount_the_headroom() {
+        fail("failing-a", 4);
+        // 1/6 success rate on full headroom scores below 1/2 on half of it
+        assert_eq!(
+            rank("failing-a,failing-b", used(&[("failing-b", 50)]), Some(100)),
+            ["failing-b", "failing-a"]
+        );
+        // and below 1/2 on the share left by one request without a limit
+        assert_eq!(
+            rank("failing-a,failing-b", used(&[("failing-b", 1)]), None),
+            ["failing-b", "failing-a"]
+        );
+    }
+
+    #[test]
+    fn the_success_rate_starts_even_and_forgets_old_requests() {
+        assert_eq!(Health::default().success_rate(), 0.5);
+        fail("forgetful", RECENT);
+        for _ in 0..RECENT {
+            start("forgetful").finish(true);
+        }
+        let rate = with_health("forgetful", |health| health.success_rate());
+        assert_eq!(rate, (RECENT as f64 + 1.0) / (RECENT as f64 + 2.0));
+    }
+}
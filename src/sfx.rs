@@ -0,0 +1,93 @@
+//! `--sfx`: `[sfx:ding.wav]` tags in the text splice a local sound file into the audio.
+//!
+//! Each tag becomes a chunk of its own that is never sent to the API. Its file (MP3
+//! or WAV, relative to the `--sfx` directory) is decoded, mixed down to mono,
+//! resampled to the API's rate and encoded as MP3, so it joins the speech like any
+//! other chunk. Effects are loaded once per run and are not cached on disk, so an
+//! edited file is picked up the next time.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex, OnceLock};
+
+static DIR: OnceLock<PathBuf> = OnceLock::new();
+static EFFECTS: Mutex<Option<HashMap<String, Vec<u8>>>> = Mutex::new(None);
+
+/// A `[sfx:FILE]` tag, capturing the file.
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[sfx:([^\]]+)\]").unwrap());
+
+/// Turns on tags, with their files looked up in `dir`.
+pub fn init(dir: Option<PathBuf>) {
+    if let Some(dir) = dir {
+        let _ = DIR.set(dir);
+    }
+}
+
+pub enum Part<'a> {
+    Text(&'a str),
+    /// A whole tag, which is also the text of its chunk
+    Effect(&'a str),
+}
+
+/// `text` cut around its sound effect tags; all text without `--sfx`.
+pub fn parts(text: &str) -> Vec<Part<'_>> {
+    if DIR.get().is_none() {
+        return vec![Part::Text(text)];
+    }
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for tag in TAG.find_iter(text) {
+        parts.push(Part::Text(&text[start..tag.start()]));
+        parts.push(Part::Effect(tag.as_str()));
+        start = tag.end();
+    }
+    parts.push(Part::Text(&text[start..]));
+    parts
+}
+
+fn load(file: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let path = DIR.get().map(|dir| dir.join(file)).unwrap_or_default();
+    let bytes =
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("wav")
+        .to_lowercase();
+    let pcm = crate::pcm::decode_as(&bytes, &extension)
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+    Ok(crate::pcm::mp3(&crate::pcm::resample(
+        pcm,
+        crate::pcm::API_SAMPLE_RATE,
+    ))?)
+}
+
+/// The audio of `chunk`, when it is a sound effect tag.
+pub fn audio(chunk: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+    if DIR.get().is_none() {
+        return Ok(None);
+    }
+    let Some(file) = TAG
+        .captures(chunk)
+        .filter(|captures| captures[0].len() == chunk.len())
+        .map(|captures| captures[1].trim().to_string())
+    else {
+        return Ok(None);
+    };
+    if let Some(audio) = EFFECTS
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|effects| effects.get(&file))
+    {
+        return Ok(Some(audio.clone()));
+    }
+    let audio = load(&file)?;
+    EFFECTS
+        .lock()
+        .unwrap()
+        .get_or_insert_default()
+        .insert(file, audio.clone());
+    Ok(Some(audio))
+}
@@ -0,0 +1,58 @@
+//! `--sidecar` metadata, written as `<file>.json` next to produced audio so that assets
+//! can be traced back to their source text, voice and tool version.
+
+use crate::checksum::sha256_hex;
+use crate::mp3;
+use serde_json::{Value, json};
+
+/// Describes the audio made from `chunks`; `audio_chunks[i]` is the audio of `chunks[i]`.
+pub fn metadata(text: &str, voice: &str, chunks: &[String], audio_chunks: &[Vec<u8>]) -> Value {
+    let voice = crate::config::resolve_voice(voice);
+    let audio = audio_chunks.concat();
+    let info = mp3::info(&audio);
+    let chunks: Vec<Value> = chunks
+        .iter()
+        .zip(audio_chunks)
+        .map(|(chunk, audio)| {
+            json!({
+                "text": crate::markdown::unmarked(chunk),
+                "cache_key": crate::cache::key(chunk, voice),
+                "sha256": sha256_hex(audio),
+                "bytes": audio.len(),
+            })
+        })
+        .collect();
+
+    json!({
+        "text": text,
+        "voice": voice,
+        "backend": "tiktok",
+        "format": "mp3",
+        "duration_secs": (info.duration_secs * 1000.0).round() / 1000.0,
+        "sample_rate": info.sample_rate,
+        "frames": info.frames,
+        "bytes": audio.len(),
+        "sha256": sha256_hex(&audio),
+        "chunks": chunks,
+        "normalization": tktts::NORMALIZATION_VERSION,
+        "tool": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "created": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    })
+}
+
+/// Points the file fields of `metadata` at `audio` as it was written, when that is WAV
+/// or AIFF converted from the chunks' MP3.
+pub fn written_as(mut metadata: Value, audio: &[u8]) -> Value {
+    let format = match crate::sniff::sniff(audio) {
+        Some(crate::sniff::Format::Wav) => "wav",
+        Some(crate::sniff::Format::Aiff) => "aiff",
+        _ => return metadata,
+    };
+    metadata["format"] = json!(format);
+    metadata["bytes"] = json!(audio.len());
+    metadata["sha256"] = json!(sha256_hex(audio));
+    metadata
+}
@@ -0,0 +1,133 @@
+//! Where signature headers are attached, for TikTok endpoints that reject unsigned calls.
+//!
+//! tktts computes no X-Gorgon or X-Argus signature itself: the algorithms are not
+//! published and change with app versions. Signatures come from an external signer, and
+//! what is attached is selected in the config file:
+//!
+//! - `none` (default) sends requests as before.
+//! - `khronos` adds only the timestamp headers (`X-Khronos`, `X-SS-Req-Ticket`) that some
+//!   endpoints check on their own. It signs nothing.
+//! - `command` runs an external signer for every request. It receives
+//!   `{"url": ..., "body": ...}` as JSON on stdin and must print a JSON object of header
+//!   names and values, e.g. `{"X-Gorgon": "...", "X-Khronos": "..."}`. The session
+//!   cookie, which holds the session ID, is only added as `"cookie"` with
+//!   `send_cookie = true`.
+
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    #[default]
+    None,
+    Khronos,
+    Command,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SigningConfig {
+    pub algorithm: Algorithm,
+    /// Signer command line for `algorithm = "command"`, run through `sh -c`
+    pub command: Option<String>,
+    /// Hand the signer the session cookie too, for signatures over it
+    pub send_cookie: bool,
+}
+
+/// Rejects incomplete signing settings up front rather than on the first request.
+pub fn check(config: &SigningConfig) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if config.algorithm == Algorithm::Command && config.command.is_none() {
+        return Err("signing.algorithm = \"command\" needs signing.command to be set".into());
+    }
+    Ok(())
+}
+
+fn khronos_headers() -> Vec<(String, String)> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    vec![
+        ("X-Khronos".to_string(), now.as_secs().to_string()),
+        ("X-SS-Req-Ticket".to_string(), now.as_millis().to_string()),
+    ]
+}
+
+async fn command_headers(
+    command: &str,
+    url: &str,
+    body: &str,
+    cookie: Option<&str>,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start signer '{}': {}", command, e))?;
+
+    let mut request = serde_json::json!({ "url": url, "body": body });
+    if let Some(cookie) = cookie {
+        request["cookie"] = cookie.into();
+    }
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(request.to_string().as_bytes()).await?;
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(format!("Signer '{}' exited with {}", command, output.status).into());
+    }
+    let headers: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Signer '{}' printed invalid JSON: {}", command, e))?;
+
+    Ok(headers
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(value) => (name, value),
+            other => (name, other.to_string()),
+        })
+        .collect())
+}
+
+/// The headers the configured algorithm attaches to a request to `url` with the given
+/// body and cookie; signatures only with `command`, which is given the cookie only with
+/// `send_cookie`.
+pub async fn sign(
+    url: &str,
+    body: &str,
+    cookie: &str,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
+    let config = &crate::config::get().signing;
+    match config.algorithm {
+        Algorithm::None => Ok(Vec::new()),
+        Algorithm::Khronos => Ok(khronos_headers()),
+        Algorithm::Command => {
+            let command = config.command.as_deref().unwrap_or_default();
+            let cookie = config.send_cookie.then_some(cookie);
+            command_headers(command, url, body, cookie).await
+        }
+    }
+}
+
+/// Signs the requests of the TTS client from their URL, body and cookie.
+pub struct ConfiguredSigner;
+
+impl tktts::layers::Signer for ConfiguredSigner {
+    fn sign<'a>(&'a self, request: &'a tktts::transport::Request) -> tktts::layers::Signature<'a> {
+        Box::pin(async move {
+            let cookie = request
+                .headers
+                .iter()
+                .find(|(name, _)| name == "Cookie")
+                .map(|(_, value)| value.as_str())
+                .unwrap_or_default();
+            sign(request.url.as_str(), &request.body, cookie).await
+        })
+    }
+}
@@ -0,0 +1,198 @@
+//! Audio format sniffing.
+//!
+//! Depending on the endpoint, the API answers with MP3 or with AAC (in MP4 or as raw
+//! ADTS). Everything after a request (joining chunks, the cache, durations) works on
+//! MP3, so other chunk audio is converted right away. Output files are checked against
+//! their extension: a `.wav` or `.aiff` file gets that audio, and a name promising a format that
+//! cannot be written here (`.m4a`, `.ogg`, ...) is changed to `.mp3` with a warning.
+
+use crate::pcm;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the conversion warning was shown, so it is not repeated for every chunk.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Mp3,
+    /// MP4 container, usually with AAC audio
+    Mp4,
+    /// Raw AAC frames
+    Adts,
+    Wav,
+    Aiff,
+    Ogg,
+    Flac,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Mp3 => "MP3",
+            Format::Mp4 => "AAC in MP4",
+            Format::Adts => "AAC",
+            Format::Wav => "WAV",
+            Format::Aiff => "AIFF",
+            Format::Ogg => "Ogg",
+            Format::Flac => "FLAC",
+        }
+    }
+
+    /// The media type uploads of this audio are sent with.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Mp3 => "audio/mpeg",
+            Format::Mp4 => "audio/mp4",
+            Format::Adts => "audio/aac",
+            Format::Wav => "audio/wav",
+            Format::Aiff => "audio/aiff",
+            Format::Ogg => "audio/ogg",
+            Format::Flac => "audio/flac",
+        }
+    }
+
+    /// Extension hint for the decoder.
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Mp3 => "mp3",
+            Format::Mp4 => "m4a",
+            Format::Adts => "aac",
+            Format::Wav => "wav",
+            Format::Aiff => "aiff",
+            Format::Ogg => "ogg",
+            Format::Flac => "flac",
+        }
+    }
+
+    fn for_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "mp3" => Some(Format::Mp3),
+            "m4a" | "mp4" => Some(Format::Mp4),
+            "aac" => Some(Format::Adts),
+            "wav" => Some(Format::Wav),
+            "aiff" | "aif" => Some(Format::Aiff),
+            "ogg" | "oga" | "opus" => Some(Format::Ogg),
+            "flac" => Some(Format::Flac),
+            _ => None,
+        }
+    }
+}
+
+/// The format of `audio`, from its first bytes.
+pub fn sniff(audio: &[u8]) -> Option<Format> {
+    match audio {
+        [b'I', b'D', b'3', ..] => Some(Format::Mp3),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(Format::Mp4),
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'A',
+            b'V',
+            b'E',
+            ..,
+        ] => Some(Format::Wav),
+        [
+            b'F',
+            b'O',
+            b'R',
+            b'M',
+            _,
+            _,
+            _,
+            _,
+            b'A',
+            b'I',
+            b'F',
+            b'F',
+            ..,
+        ] => Some(Format::Aiff),
+        [b'O', b'g', b'g', b'S', ..] => Some(Format::Ogg),
+        [b'f', b'L', b'a', b'C', ..] => Some(Format::Flac),
+        // Both start with a frame sync; ADTS has layer bits 00, which MPEG audio never uses
+        [0xff, second, ..] if second & 0xf6 == 0xf0 => Some(Format::Adts),
+        [0xff, second, ..] if second & 0xe0 == 0xe0 => Some(Format::Mp3),
+        _ => None,
+    }
+}
+
+/// Decodes audio in any of the formats [`sniff`] knows, as MP3 when it knows none.
+pub fn decode(audio: &[u8]) -> Result<pcm::Pcm, tktts::TktsError> {
+    pcm::decode_as(audio, sniff(audio).map_or("mp3", Format::extension))
+}
+
+/// Chunk audio from the API as MP3, converting it when it came in another format.
+/// Audio that cannot be recognized is passed on as it is.
+pub fn to_mp3(audio: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let format = match sniff(&audio) {
+        None | Some(Format::Mp3) => return Ok(audio),
+        Some(format) => format,
+    };
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        crate::term::status(
+            crate::term::Style::Yellow,
+            "Converting",
+            format!(
+                "the API answered with {} audio, converting it to MP3",
+                format.name()
+            ),
+        );
+    }
+    let pcm = pcm::decode_as(&audio, format.extension()).map_err(|e| {
+        format!(
+            "Failed to decode {} audio from the API: {}",
+            format.name(),
+            e
+        )
+    })?;
+    Ok(pcm::mp3(&pcm)?)
+}
+
+/// The extension of the last part of `target`, a path, URL or `s3://` location.
+fn extension(target: &str) -> Option<&str> {
+    let name = target.rsplit('/').next()?;
+    name.rsplit_once('.').map(|(_, extension)| extension)
+}
+
+/// Whether MP3 audio can be written to `target` as it is.
+pub fn fits(target: &str) -> bool {
+    extension(target)
+        .and_then(Format::for_extension)
+        .is_none_or(|format| format == Format::Mp3)
+}
+
+/// MP3 `audio` made to match the extension of `target`: converted for `.wav` and `.aiff`, and with
+/// the target renamed to `.mp3` for other audio formats. Other extensions are left alone.
+pub fn fit(
+    target: &str,
+    audio: Vec<u8>,
+) -> Result<(String, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(extension) = extension(target) else {
+        return Ok((target.to_string(), audio));
+    };
+    match Format::for_extension(extension) {
+        None | Some(Format::Mp3) => Ok((target.to_string(), audio)),
+        Some(Format::Wav) => Ok((target.to_string(), pcm::wav(&pcm::decode(&audio)?))),
+        Some(Format::Aiff) => Ok((target.to_string(), pcm::aiff(&pcm::decode(&audio)?))),
+        Some(format) => {
+            let renamed = format!("{}mp3", &target[..target.len() - extension.len()]);
+            crate::term::status(
+                crate::term::Style::Yellow,
+                "Renaming",
+                format!(
+                    "{} to {}, as the audio is MP3 and cannot be written as {}",
+                    target,
+                    renamed,
+                    format.name()
+                ),
+            );
+            Ok((renamed, audio))
+        }
+    }
+}
@@ -0,0 +1,383 @@
+//! `tktts speechd`: an output module for speech-dispatcher, so screen readers like Orca
+//! can speak with TikTok voices.
+//!
+//! speech-dispatcher starts the module (as `sd_tktts`, a link to the binary) and talks to
+//! it over stdin and stdout: settings arrive with `SET`, text with `SPEAK`, and the module
+//! answers with numbered replies and reports `BEGIN`, `END`, `STOPPED` and each SSML
+//! `<mark>` it reaches as events. The text is cut at the marks; each piece is synthesized
+//! while the one before it plays, and the mark is reported when its piece has been played.
+//! `STOP` ends the speech at once, `PAUSE` at the next mark.
+
+use crate::term;
+use regex::Regex;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tktts::voices;
+use tokio::io::AsyncBufReadExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+#[derive(clap::Args)]
+pub struct SpeechdArgs {
+    /// Module configuration file given by speech-dispatcher; not read, as tktts has its own
+    config: Option<PathBuf>,
+
+    /// Player command that reads audio from stdin (default: mpv or ffplay)
+    #[arg(long, value_name = "COMMAND")]
+    player: Option<String>,
+}
+
+/// Voice settings, as sent with `SET`.
+#[derive(Clone)]
+struct Settings {
+    /// -100 to 100, 0 being normal
+    rate: f64,
+    /// -100 to 100, 0 being normal
+    pitch: f64,
+    /// -100 to 100, 100 being the voice at full volume
+    volume: f64,
+    /// MALE1, FEMALE2, CHILD_FEMALE, ...
+    voice_type: String,
+    language: String,
+    /// A voice code from `LIST VOICES`, which takes precedence over the other two
+    synthesis_voice: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            rate: 0.0,
+            pitch: 0.0,
+            volume: 100.0,
+            voice_type: String::new(),
+            language: String::new(),
+            synthesis_voice: String::new(),
+        }
+    }
+}
+
+impl Settings {
+    fn set(&mut self, key: &str, value: &str) {
+        let number = || value.parse::<f64>().map(|n| n.clamp(-100.0, 100.0));
+        match key {
+            "rate" => self.rate = number().unwrap_or(self.rate),
+            "pitch" => self.pitch = number().unwrap_or(self.pitch),
+            "volume" => self.volume = number().unwrap_or(self.volume),
+            "voice" | "voice_type" => self.voice_type = value.to_string(),
+            "language" => self.language = value.to_string(),
+            "synthesis_voice" => self.synthesis_voice = value.to_string(),
+            // Punctuation, spelling and capital settings have no equivalent
+            _ => {}
+        }
+    }
+
+    /// The voice code these settings pick.
+    fn speaker(&self) -> String {
+        if voices::find(&self.synthesis_voice).is_some() {
+            return self.synthesis_voice.clone();
+        }
+        let gender = match self.voice_type.trim_start_matches("CHILD_") {
+            kind if kind.starts_with("FEMALE") => Some("female"),
+            kind if kind.starts_with("MALE") => Some("male"),
+            _ => None,
+        };
+        let language = self.language.replace('_', "-");
+        let voice = match gender {
+            Some(gender) if !language.is_empty() => {
+                crate::lang::gendered_voice(&language, Some(gender))
+            }
+            _ => None,
+        };
+        voice
+            .or_else(|| {
+                if language.contains('-') {
+                    crate::lang::gendered_voice(&language, None)
+                } else {
+                    crate::lang::voice_for(&language.to_lowercase())
+                }
+            })
+            .or_else(|| crate::config::get().speaker.clone())
+            .unwrap_or_else(|| "en_us_002".to_string())
+    }
+}
+
+/// Text to speak up to an index mark, and the mark's name (none for the last piece).
+struct Piece {
+    text: String,
+    mark: Option<String>,
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Cuts an SSML message at its `<mark>` elements, leaving the other markup out.
+fn pieces(message: &str) -> Vec<Piece> {
+    let mark = Regex::new(r#"<mark\s+name\s*=\s*["']([^"']*)["']\s*/?>"#).unwrap();
+    let tag = Regex::new(r"<[^>]*>").unwrap();
+    let text = |part: &str| unescape(&tag.replace_all(part, " "));
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for captures in mark.captures_iter(message) {
+        let whole = captures.get(0).unwrap();
+        pieces.push(Piece {
+            text: text(&message[start..whole.start()]),
+            mark: Some(captures[1].to_string()),
+        });
+        start = whole.end();
+    }
+    pieces.push(Piece {
+        text: text(&message[start..]),
+        mark: None,
+    });
+    pieces
+}
+
+/// Writes a reply or event to speech-dispatcher.
+fn send(reply: &str) {
+    let mut stdout = std::io::stdout().lock();
+    // A failed write means speech-dispatcher is gone, and stdin ends next
+    let _ = writeln!(stdout, "{}", reply);
+    let _ = stdout.flush();
+}
+
+/// Speaks `pieces`, synthesizing each while the one before it plays.
+async fn speak(
+    pieces: Vec<Piece>,
+    settings: Settings,
+    credentials: Arc<(String, String)>,
+    player: Option<String>,
+    control: Arc<Control>,
+) {
+    if !control.event("701 BEGIN") {
+        return;
+    }
+    let speaker = settings.speaker();
+    let (sender, mut receiver) = mpsc::channel(1);
+    let producer = {
+        let texts: Vec<String> = pieces.iter().map(|piece| piece.text.clone()).collect();
+        tokio::spawn(async move {
+            for text in texts {
+                let audio = if text.trim().is_empty() {
+                    Ok(None)
+                } else {
+                    crate::synthesize(&text, &speaker, &credentials.0, &credentials.1)
+                        .await
+                        .map(Some)
+                };
+                if sender.send(audio).await.is_err() {
+                    break;
+                }
+            }
+        })
+    };
+    // Dropping the task on STOP drops the player with it, and this ends the producer
+    let _producer = AbortOnDrop(producer);
+
+    let speed = 2f64.powf(settings.rate / 100.0);
+    let pitch = 2f64.powf(settings.pitch / 200.0);
+    let gain = ((settings.volume + 100.0) / 200.0) as f32;
+    for piece in pieces {
+        let Some(audio) = receiver.recv().await else {
+            break;
+        };
+        let played = match audio {
+            Ok(Some(audio)) => match crate::pcm::decode(&audio) {
+                Ok(pcm) => {
+                    let wav = crate::pcm::wav(&crate::pcm::voiced(pcm, speed, pitch, gain));
+                    crate::playback::play(&wav, player.as_deref()).await
+                }
+                Err(e) => Err(e.into()),
+            },
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = played {
+            term::error(format!("speech failed: {}", e));
+            break;
+        }
+        if let Some(mark) = piece.mark {
+            if !control.event(&format!("700-{}\n700 INDEX MARK", mark)) {
+                return;
+            }
+            if control.pause.load(Ordering::SeqCst) {
+                control.finish("704 PAUSED");
+                return;
+            }
+        }
+    }
+    control.finish("702 END");
+}
+
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Shared between a speech and the commands controlling it.
+#[derive(Default)]
+struct Control {
+    pause: AtomicBool,
+    /// Whether the speech has ended (END, PAUSED or STOPPED has been sent); after that no
+    /// more events may be sent for it
+    over: Mutex<bool>,
+}
+
+impl Control {
+    /// Sends an event of the speech unless it is over.
+    fn event(&self, reply: &str) -> bool {
+        let over = self.over.lock().unwrap();
+        if !*over {
+            send(reply);
+        }
+        !*over
+    }
+
+    /// Sends the final event of the speech unless it is over already.
+    fn finish(&self, reply: &str) -> bool {
+        let mut over = self.over.lock().unwrap();
+        let finished = !*over;
+        if finished {
+            send(reply);
+            *over = true;
+        }
+        finished
+    }
+}
+
+/// The speech in progress.
+struct Job {
+    task: JoinHandle<()>,
+    control: Arc<Control>,
+}
+
+impl Job {
+    fn stop(self) {
+        self.control.finish("703 STOPPED");
+        self.task.abort();
+    }
+}
+
+type Input = tokio::io::Lines<tokio::io::BufReader<tokio::io::Stdin>>;
+
+/// The data lines that follow SPEAK, SET, AUDIO and LOGLEVEL, up to a line with one dot.
+async fn block(lines: &mut Input) -> std::io::Result<Vec<String>> {
+    let mut block = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        if line == "." {
+            break;
+        }
+        // Lines starting with a dot arrive with the dot doubled
+        block.push(match line.strip_prefix('.') {
+            Some(rest) => rest.to_string(),
+            None => line,
+        });
+    }
+    Ok(block)
+}
+
+pub async fn run(args: SpeechdArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    let mut settings = Settings::default();
+    let mut credentials = None;
+    let mut job: Option<Job> = None;
+    while let Some(line) = lines.next_line().await? {
+        let command = line.trim_end();
+        match command {
+            "INIT" => match crate::load_credentials() {
+                Ok(loaded) => {
+                    credentials = Some(Arc::new(loaded));
+                    send("299-tktts initialized\n299 OK LOADED SUCCESSFULLY");
+                }
+                Err(e) => send(&format!("399-{}\n399 ERR CANT INIT MODULE", e)),
+            },
+            "AUDIO" | "LOGLEVEL" => {
+                // tktts plays the audio itself and logs to stderr, so these are only acknowledged
+                send("207 OK RECEIVING SETTINGS");
+                block(&mut lines).await?;
+                send(match command {
+                    "AUDIO" => "203 OK AUDIO INITIALIZED",
+                    _ => "203 OK LOG LEVEL SET",
+                });
+            }
+            "SET" => {
+                send("203 OK RECEIVING SETTINGS");
+                for setting in block(&mut lines).await? {
+                    if let Some((key, value)) = setting.split_once('=') {
+                        settings.set(key.trim(), value.trim());
+                    }
+                }
+                send("203 OK SETTINGS RECEIVED");
+            }
+            "SPEAK" | "CHAR" | "KEY" | "SOUND_ICON" => {
+                send("202 OK RECEIVING MESSAGE");
+                let message = block(&mut lines).await?.join("\n");
+                let Some(credentials) = credentials.clone() else {
+                    send("301 ERROR CANT SPEAK");
+                    continue;
+                };
+                send("200 OK SPEAKING");
+                if let Some(job) = job.take() {
+                    job.stop();
+                }
+                let pieces = match command {
+                    "SPEAK" => pieces(&message),
+                    // Characters, key names like "shift_a" and sound icon names are read out
+                    _ => vec![Piece {
+                        text: match message.as_str() {
+                            " " => "space".to_string(),
+                            name => name.replace(['_', '-'], " "),
+                        },
+                        mark: None,
+                    }],
+                };
+                let control = Arc::new(Control::default());
+                let task = tokio::spawn(speak(
+                    pieces,
+                    settings.clone(),
+                    credentials,
+                    args.player.clone(),
+                    control.clone(),
+                ));
+                job = Some(Job { task, control });
+            }
+            "STOP" => {
+                if let Some(job) = job.take() {
+                    job.stop();
+                }
+            }
+            "PAUSE" => {
+                if let Some(job) = &job {
+                    job.control.pause.store(true, Ordering::SeqCst);
+                }
+            }
+            "LIST VOICES" => {
+                let mut reply = String::new();
+                for voice in voices::all() {
+                    reply.push_str(&format!("200-{}\t{}\tnone\n", voice.code, voice.language));
+                }
+                reply.push_str("200 OK VOICE LIST SENT");
+                send(&reply);
+            }
+            _ if command.starts_with("DEBUG") => send("200 OK DEBUGGING"),
+            "QUIT" => {
+                if let Some(job) = job.take() {
+                    job.stop();
+                }
+                send("210 OK QUIT");
+                break;
+            }
+            _ => send("300 ERR UNKNOWN COMMAND"),
+        }
+    }
+    Ok(())
+}
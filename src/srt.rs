@@ -0,0 +1,235 @@
+//! `tktts srt`: a voice track for an SRT subtitle file, aligned to the cue timings.
+//!
+//! Every cue is synthesized, then sped up (without changing pitch) when it is longer
+//! than its cue, and placed at the cue's start over silence. The result is a WAV file as
+//! long as the subtitles, ready to be mixed under the video.
+
+use crate::pcm::{self, Pcm};
+use regex::Regex;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+/// Formatting such as `<i>..</i>` and `{\an8}`, which is not spoken.
+static MARKUP: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>|\{[^}]*\}").unwrap());
+
+#[derive(clap::Args)]
+pub struct SrtArgs {
+    /// Subtitle file to voice
+    input: PathBuf,
+
+    /// WAV file to write
+    #[arg(short, long, value_name = "FILE")]
+    output: PathBuf,
+
+    /// Fastest a cue is sped up to fit; longer ones run into the next cue
+    #[arg(long, value_name = "FACTOR", default_value_t = 1.6)]
+    max_speedup: f64,
+
+    /// Voice used for the cues
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+}
+
+struct Cue {
+    number: usize,
+    start_secs: f64,
+    end_secs: f64,
+    text: String,
+}
+
+/// Parses `HH:MM:SS,mmm` (a `.` before the milliseconds is accepted too). The fraction
+/// is a decimal one, so `,5` is half a second even without its padding zeros.
+fn timestamp(text: &str) -> Option<f64> {
+    let (clock, fraction) = text.trim().split_once([',', '.'])?;
+    let mut parts = clock.split(':').map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some()
+        || fraction.is_empty()
+        || !fraction.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let fraction: f64 = format!("0.{}", fraction).parse().ok()?;
+    Some((hours * 3600 + minutes * 60 + seconds) as f64 + fraction)
+}
+
+fn parse(text: &str) -> Result<Vec<Cue>, Box<dyn std::error::Error + Send + Sync>> {
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut cues = Vec::new();
+
+    for block in text
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|block| !block.is_empty())
+    {
+        let mut lines = block.lines();
+        let mut line = lines.next().unwrap_or_default();
+        // The cue number is optional in practice
+        if !line.contains("-->") {
+            line = lines.next().unwrap_or_default();
+        }
+        let number = cues.len() + 1;
+        let invalid = || format!("Invalid timing in cue {}: '{}'", number, line);
+        let (start, end) = line.split_once("-->").ok_or_else(invalid)?;
+        // Positions may follow the end time, as in "00:00:01,000 --> 00:00:02,000 X1:40"
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (start_secs, end_secs) = match (timestamp(start), timestamp(end)) {
+            (Some(start), Some(end)) => (start, end.max(start)),
+            _ => return Err(invalid().into()),
+        };
+        let text = lines.collect::<Vec<_>>().join(" ");
+        let text = MARKUP.replace_all(&text, "").trim().to_string();
+        cues.push(Cue {
+            number,
+            start_secs,
+            end_secs,
+            text,
+        });
+    }
+    Ok(cues)
+}
+
+pub async fn run(args: SrtArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    if args.max_speedup < 1.0 {
+        return Err("--max-speedup must be at least 1".into());
+    }
+    let text = fs::read_to_string(&args.input)
+        .map_err(|e| format!("Failed to read {}: {}", args.input.display(), e))?;
+    let cues = parse(&text)?;
+
+    // All cues are requested together, so they share the parallelism and the dedup
+    let mut chunks = Vec::new();
+    let mut chunk_cues = Vec::new();
+    for (index, cue) in cues.iter().enumerate() {
+        for chunk in crate::split_text(&cue.text, crate::BYTE_LIMIT)? {
+            chunks.push(chunk);
+            chunk_cues.push(index);
+        }
+    }
+    let audio_chunks =
+        crate::synthesize_chunks(&chunks, &args.speaker, &session_id, &api_root_url).await?;
+
+    let mut voiced: Vec<Vec<Pcm>> = cues.iter().map(|_| Vec::new()).collect();
+    for (pcm, cue) in pcm::decode_all(&audio_chunks)
+        .await?
+        .into_iter()
+        .zip(chunk_cues)
+    {
+        voiced[cue].push(pcm);
+    }
+    let sample_rate = voiced
+        .iter()
+        .flatten()
+        .map(|pcm| pcm.sample_rate)
+        .next()
+        .unwrap_or(pcm::API_SAMPLE_RATE);
+
+    let end_secs = cues.iter().map(|cue| cue.end_secs).fold(0.0, f64::max);
+    let mut track = vec![0.0f32; (end_secs * sample_rate as f64) as usize];
+    let mut stretched = 0;
+    for (cue, parts) in cues.iter().zip(voiced) {
+        let mut speech = Pcm {
+            sample_rate,
+            samples: Vec::new(),
+        };
+        for part in parts {
+            speech
+                .samples
+                .extend(pcm::resample(part, sample_rate).samples);
+        }
+        let window = cue.end_secs - cue.start_secs;
+        let length = speech.duration_secs();
+        if length > window && window > 0.0 {
+            let speedup = length / window;
+            if speedup > args.max_speedup {
+                crate::term::status(
+                    crate::term::Style::Yellow,
+                    "Overrun",
+                    format!(
+                        "cue {} needs {:.1}s for a {:.1}s cue; {:.1}s run over",
+                        cue.number,
+                        length,
+                        window,
+                        length / args.max_speedup - window
+                    ),
+                );
+            }
+            speech = pcm::stretch(&speech, 1.0 / speedup.min(args.max_speedup));
+            stretched += 1;
+        }
+
+        let start = (cue.start_secs * sample_rate as f64) as usize;
+        if track.len() < start + speech.samples.len() {
+            track.resize(start + speech.samples.len(), 0.0);
+        }
+        for (sample, speech) in track[start..].iter_mut().zip(&speech.samples) {
+            *sample += speech;
+        }
+    }
+
+    let track = Pcm {
+        sample_rate,
+        samples: track,
+    };
+    fs::write(&args.output, pcm::wav(&track))
+        .map_err(|e| format!("Failed to write {}: {}", args.output.display(), e))?;
+    crate::term::status(
+        crate::term::Style::Green,
+        "Wrote",
+        format!(
+            "{} ({} cues, {} sped up, {})",
+            args.output.display(),
+            cues.len(),
+            stretched,
+            crate::estimate::format_duration(std::time::Duration::from_secs_f64(
+                track.duration_secs()
+            ))
+        ),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, timestamp};
+
+    #[test]
+    fn timestamps_read_the_fraction_as_decimal() {
+        assert_eq!(timestamp("00:00:01,500"), Some(1.5));
+        assert_eq!(timestamp("00:00:01,5"), Some(1.5));
+        assert_eq!(timestamp("00:00:01.25"), Some(1.25));
+        assert_eq!(timestamp(" 01:02:03,000 "), Some(3723.0));
+        assert_eq!(timestamp("00:00:01"), None);
+        assert_eq!(timestamp("00:01,000"), None);
+        assert_eq!(timestamp("00:00:01,-5"), None);
+    }
+
+    #[test]
+    fn cues_are_parsed_with_or_without_numbers() {
+        let cues = parse(
+            "\u{feff}1\r\n00:00:01,000 --> 00:00:02,5 X1:40 X2:600\r\n<i>Hello</i> {\\an8}there\r\n\r\n\
+             00:00:03,000 --> 00:00:04,000\r\nSecond\r\nline\r\n",
+        )
+        .unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].number, 1);
+        assert_eq!((cues[0].start_secs, cues[0].end_secs), (1.0, 2.5));
+        assert_eq!(cues[0].text, "Hello there");
+        assert_eq!(cues[1].number, 2);
+        assert_eq!((cues[1].start_secs, cues[1].end_secs), (3.0, 4.0));
+        assert_eq!(cues[1].text, "Second line");
+    }
+
+    #[test]
+    fn bad_timings_name_their_cue() {
+        let error = parse("1\n00:00:01,000 --> 00:00:02,000\nOne\n\n2\nsoon --> later\nTwo")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Invalid timing in cue 2: 'soon --> later'"
+        );
+    }
+}
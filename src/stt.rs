@@ -0,0 +1,138 @@
+//! `--verify MODEL`: every fetched chunk is transcribed with a local whisper.cpp model
+//! and compared with the text it was made from, to catch the API's occasional silent or
+//! garbled audio in unattended jobs.
+//!
+//! Chunks whose transcript is less similar than `--verify-threshold` are reported and
+//! kept out of the cache, so running the job again requests them anew.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::process::Command;
+
+/// Sample rate whisper models expect.
+const WHISPER_RATE: u32 = 16_000;
+
+static VERIFY: OnceLock<Verify> = OnceLock::new();
+static FLAGGED: AtomicUsize = AtomicUsize::new(0);
+static CHECKED: AtomicUsize = AtomicUsize::new(0);
+
+struct Verify {
+    model: PathBuf,
+    command: String,
+    threshold: f64,
+}
+
+pub fn init(model: Option<PathBuf>, command: String, threshold: f64) {
+    if let Some(model) = model {
+        let _ = VERIFY.set(Verify {
+            model,
+            command,
+            threshold,
+        });
+    }
+}
+
+/// Lowercase words, without punctuation, for comparing text with a transcript.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// 1 minus the word-level edit distance relative to the longer text.
+fn similarity(expected: &str, transcript: &str) -> f64 {
+    let (a, b) = (words(expected), words(transcript));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, word) in a.iter().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, other) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(word != other);
+            row.push(substitution.min(previous[j + 1] + 1).min(row[j] + 1));
+        }
+        previous = row;
+    }
+    1.0 - previous[b.len()] as f64 / a.len().max(b.len()) as f64
+}
+
+async fn transcribe(
+    verify: &Verify,
+    audio: &[u8],
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let pcm = crate::pcm::resample(crate::pcm::decode(audio)?, WHISPER_RATE);
+    let path = std::env::temp_dir().join(format!(
+        "tktts-verify-{}-{}.wav",
+        std::process::id(),
+        CHECKED.fetch_add(1, Ordering::Relaxed)
+    ));
+    tokio::fs::write(&path, crate::pcm::wav(&pcm)).await?;
+
+    let output = Command::new(&verify.command)
+        .arg("--model")
+        .arg(&verify.model)
+        .arg("--file")
+        .arg(&path)
+        .args(["--language", "auto", "--no-timestamps", "--no-prints"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await;
+    let _ = tokio::fs::remove_file(&path).await;
+    let output = output.map_err(|e| format!("Failed to start {}: {}", verify.command, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "{} exited with {}: {}",
+            verify.command,
+            output.status,
+            stderr.trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Whether the freshly fetched `audio` says `text`; always true without `--verify`.
+pub async fn check(
+    text: &str,
+    audio: &[u8],
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(verify) = VERIFY.get() else {
+        return Ok(true);
+    };
+    let transcript = transcribe(verify, audio).await?;
+    let similarity = similarity(text, &transcript);
+    if similarity >= verify.threshold {
+        return Ok(true);
+    }
+    FLAGGED.fetch_add(1, Ordering::Relaxed);
+    crate::term::status(
+        crate::term::Style::Yellow,
+        "Mismatch",
+        format!(
+            "{:.0}% similar: '{}' was heard as '{}'",
+            similarity * 100.0,
+            text.trim(),
+            transcript
+        ),
+    );
+    Ok(false)
+}
+
+/// Reports how many chunks failed verification during the run.
+pub fn report() {
+    let flagged = FLAGGED.load(Ordering::Relaxed);
+    if flagged > 0 {
+        crate::term::status(
+            crate::term::Style::Yellow,
+            "Flagged",
+            format!(
+                "{} chunk(s) that did not match their text; run again to re-request them",
+                flagged
+            ),
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Your own tags and ratings of voices, kept in the local data directory as
+//! `voice_tags.json` and used by `tktts voices --tag` and `--speaker tag:<tag>`.
+//!
+//! Your tags are matched like the catalog's own (`female`, `narrator`, ...), so a filter
+//! or pick finds a voice by either.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tktts::voices::Voice;
+
+/// The file as read at the first lookup; commands that change it write it back at once.
+static MARKS: OnceLock<Marks> = OnceLock::new();
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct Mark {
+    pub tags: BTreeSet<String>,
+    /// 1 to 5 stars, or none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+}
+
+/// Voice code to its tags and rating.
+type Marks = BTreeMap<String, Mark>;
+
+fn path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("tktts").join("voice_tags.json"))
+}
+
+fn load() -> Marks {
+    path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save(marks: &Marks) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = path().ok_or("Could not determine the data directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, serde_json::to_vec_pretty(marks)?)?;
+    fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+/// Your tags and rating of `code`.
+pub fn mark(code: &str) -> Mark {
+    MARKS
+        .get_or_init(load)
+        .get(code)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Whether `voice` has `tag`, from the catalog or from you; case does not matter.
+pub fn has(voice: &Voice, tag: &str) -> bool {
+    let tag = tag.to_lowercase();
+    voice.tags.contains(&tag.as_str()) || mark(voice.code).tags.contains(&tag)
+}
+
+/// Adds `tags` to `code`, or removes them.
+pub fn tag(
+    code: &str,
+    tags: &[String],
+    remove: bool,
+) -> Result<Mark, Box<dyn std::error::Error + Send + Sync>> {
+    let mut marks = load();
+    let mark = marks.entry(code.to_string()).or_default();
+    for tag in tags {
+        let tag = tag.to_lowercase();
+        if remove {
+            mark.tags.remove(&tag);
+        } else {
+            mark.tags.insert(tag);
+        }
+    }
+    let mark = mark.clone();
+    if mark.tags.is_empty() && mark.rating.is_none() {
+        marks.remove(code);
+    }
+    save(&marks)?;
+    Ok(mark)
+}
+
+/// Rates `code` with 1 to 5 stars, or clears its rating with 0.
+pub fn rate(code: &str, stars: u8) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut marks = load();
+    let mark = marks.entry(code.to_string()).or_default();
+    mark.rating = (stars > 0).then_some(stars);
+    if mark.tags.is_empty() && mark.rating.is_none() {
+        marks.remove(code);
+    }
+    save(&marks)
+}
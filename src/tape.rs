@@ -0,0 +1,174 @@
+//! Record/replay of upstream TTS responses ("cassettes").
+//!
+//! `--record session.tape` appends every API exchange to a JSON-lines file, with the
+//! session cookie and the device identifiers in the URL and body redacted. `--replay session.tape` answers requests from such a file
+//! instead of the network, so whole runs can be reproduced offline. Exchanges are matched
+//! by voice and text; repeated requests are answered in recorded order.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use url::Url;
+
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    voice: String,
+    text: String,
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct RecordedResponse {
+    status: u16,
+    body: String,
+}
+
+enum Mode {
+    Record(Mutex<File>),
+    Replay(Mutex<HashMap<(String, String), VecDeque<RecordedResponse>>>),
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// Starts recording to `path`, truncating it.
+pub fn record_to(path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let file = File::create(path)
+        .map_err(|e| format!("Failed to create tape {}: {}", path.display(), e))?;
+    let _ = MODE.set(Mode::Record(Mutex::new(file)));
+    Ok(())
+}
+
+/// Loads `path` and answers all further requests from it.
+pub fn replay_from(path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read tape {}: {}", path.display(), e))?;
+    let mut responses: HashMap<(String, String), VecDeque<RecordedResponse>> = HashMap::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let interaction: Interaction = serde_json::from_str(line)
+            .map_err(|e| format!("Invalid tape {} line {}: {}", path.display(), number + 1, e))?;
+        responses
+            .entry((interaction.voice, interaction.text))
+            .or_default()
+            .push_back(interaction.response);
+    }
+    let _ = MODE.set(Mode::Replay(Mutex::new(responses)));
+    Ok(())
+}
+
+/// Whether requests are answered from a tape, so no credentials are needed.
+pub fn replaying() -> bool {
+    matches!(MODE.get(), Some(Mode::Replay(_)))
+}
+
+/// Whether exchanges are being written to a tape.
+pub fn recording() -> bool {
+    matches!(MODE.get(), Some(Mode::Record(_)))
+}
+
+/// The recorded `(status, body)` for a request, or `None` when not replaying.
+pub fn replay(
+    voice: &str,
+    text: &str,
+) -> Option<Result<(u16, String), Box<dyn std::error::Error + Send + Sync>>> {
+    let Some(Mode::Replay(responses)) = MODE.get() else {
+        return None;
+    };
+    let mut responses = responses.lock().unwrap();
+    let response = responses
+        .get_mut(&(voice.to_string(), text.to_string()))
+        .and_then(VecDeque::pop_front);
+    Some(match response {
+        Some(response) => Ok((response.status, response.body)),
+        None => Err(format!(
+            "No recorded response for voice {} and text {:?}",
+            voice, text
+        )
+        .into()),
+    })
+}
+
+/// `query` with the values of the device identifiers replaced, like the cookie's.
+fn redact_query(query: &str) -> String {
+    let pairs = url::form_urlencoded::parse(query.as_bytes()).map(|(name, value)| {
+        if crate::auth::DEVICE_PARAMS.contains(&name.as_ref()) {
+            (name, "REDACTED".into())
+        } else {
+            (name, value)
+        }
+    });
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+/// Appends a POST exchange to the tape when recording. The cookie and the device
+/// identifiers are never written.
+pub fn record(
+    voice: &str,
+    text: &str,
+    url: &Url,
+    user_agent: &str,
+    body: &str,
+    (status, response_body): (u16, &str),
+) {
+    let Some(Mode::Record(file)) = MODE.get() else {
+        return;
+    };
+    let mut url = url.clone();
+    if let Some(query) = url.query() {
+        url.set_query(Some(&redact_query(query)));
+    }
+    let interaction = Interaction {
+        voice: voice.to_string(),
+        text: text.to_string(),
+        request: RecordedRequest {
+            method: "POST".to_string(),
+            url: url.to_string(),
+            headers: HashMap::from([
+                ("User-Agent".to_string(), user_agent.to_string()),
+                ("Cookie".to_string(), "sessionid=REDACTED".to_string()),
+            ]),
+            body: redact_query(body),
+        },
+        response: RecordedResponse {
+            status,
+            body: response_body.to_string(),
+        },
+    };
+    let Ok(line) = serde_json::to_string(&interaction) else {
+        return;
+    };
+    let mut file = file.lock().unwrap();
+    if let Err(e) = writeln!(file, "{}", line) {
+        progress!("Failed to write to tape: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact_query;
+
+    #[test]
+    fn device_identifiers_are_redacted() {
+        assert_eq!(
+            redact_query("text_speaker=en_us_002&device_id=123&iid=456&openudid=ab&aid=1233"),
+            "text_speaker=en_us_002&device_id=REDACTED&iid=REDACTED&openudid=REDACTED&aid=1233"
+        );
+        assert_eq!(redact_query(""), "");
+    }
+}
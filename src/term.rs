@@ -0,0 +1,216 @@
+//! Styled stderr output: aligned status lines, the chunk table, errors and summaries.
+//!
+//! Colors are used only when stderr is a terminal, `NO_COLOR` is unset or empty and
+//! `--no-color` was not given; otherwise the same lines are written as plain text.
+//! `-q` silences everything but errors, `-qq` errors too.
+//!
+//! Everything `progress!` writes, status lines and errors pass through [`redact`] on the
+//! way out, so session IDs, session cookies and request URLs, whose queries carry the
+//! text, are never printed whichever module formats the message; `--log-redaction off`
+//! shows them for debugging.
+
+use regex::Regex;
+use std::env;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+static ERRORS: AtomicBool = AtomicBool::new(true);
+static REDACT: AtomicBool = AtomicBool::new(true);
+
+/// Values printed as `[redacted]`, such as the session IDs in use.
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Session cookies and parameters, in headers, bodies and queries.
+static SESSION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(sessionid|session_id|sessionid_ss|sid_tt|sid_guard)=[^;&\s]+").unwrap()
+});
+
+/// The query of a URL, stopping at what typically ends a URL in a message.
+static QUERY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(https?://[^\s?#"'()]+)\?[^\s#"'()]+"#).unwrap());
+
+/// Whether secrets are hidden in messages, `--log-redaction`.
+#[derive(clap::ValueEnum, Clone, Copy, Default, PartialEq)]
+pub enum Redaction {
+    /// Replace session IDs, cookies and URL queries with `[redacted]`
+    #[default]
+    On,
+    /// Print messages as they are, for debugging
+    Off,
+}
+
+/// Width of the right-aligned verb column of status lines.
+const VERB_WIDTH: usize = 12;
+
+/// Characters of chunk text shown in the chunk table.
+const PREVIEW_CHARS: usize = 60;
+
+#[derive(Clone, Copy)]
+pub enum Style {
+    Dim,
+    Red,
+    Green,
+    Yellow,
+    Cyan,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Dim => "2",
+            Style::Red => "1;31",
+            Style::Green => "1;32",
+            Style::Yellow => "1;33",
+            Style::Cyan => "1;36",
+        }
+    }
+}
+
+pub fn init(no_color: bool, quiet: u8, redaction: Redaction) {
+    REDACT.store(redaction == Redaction::On, Ordering::Relaxed);
+    if quiet > 0 {
+        crate::PROGRESS.store(false, Ordering::Relaxed);
+    }
+    ERRORS.store(quiet < 2, Ordering::Relaxed);
+
+    let color = !no_color
+        && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && env::var("TERM").map_or(true, |term| term != "dumb")
+        && std::io::stderr().is_terminal()
+        && crate::console::enable_ansi();
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+pub fn paint(style: Style, text: impl Display) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Adds a value never to print, such as a session ID.
+pub fn secret(value: &str) {
+    if !value.is_empty() {
+        SECRETS.lock().unwrap().push(value.to_string());
+    }
+}
+
+/// Whether `c` can be part of a token such as a session ID.
+fn is_token_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// `message` with each whole-token occurrence of `secret` replaced by `[redacted]`; one
+/// inside a longer word is a coincidence, not the secret.
+fn replace_token(message: &str, secret: &str) -> String {
+    let mut replaced = String::with_capacity(message.len());
+    let mut last = 0;
+    for (start, _) in message.match_indices(secret) {
+        let end = start + secret.len();
+        let before = message[..start].chars().next_back();
+        let after = message[end..].chars().next();
+        if start < last || before.is_some_and(is_token_char) || after.is_some_and(is_token_char) {
+            continue;
+        }
+        replaced.push_str(&message[last..start]);
+        replaced.push_str("[redacted]");
+        last = end;
+    }
+    replaced.push_str(&message[last..]);
+    replaced
+}
+
+/// `message` with the registered secrets, session cookies and URL queries replaced by
+/// `[redacted]`, unless redaction is off.
+pub fn redact(message: impl Display) -> String {
+    let mut message = message.to_string();
+    if !REDACT.load(Ordering::Relaxed) {
+        return message;
+    }
+    for secret in SECRETS.lock().unwrap().iter() {
+        message = replace_token(&message, secret);
+    }
+    let message = SESSION.replace_all(&message, "$1=[redacted]");
+    QUERY.replace_all(&message, "$1?[redacted]").into_owned()
+}
+
+/// Writes `    Requesting chunk 1/3`-style progress, with the verb right-aligned.
+pub fn status(style: Style, verb: &str, message: impl Display) {
+    progress!(
+        "{} {}",
+        paint(style, format!("{:>width$}", verb, width = VERB_WIDTH)),
+        message
+    );
+}
+
+/// Lists the chunks a text was split into, one aligned row each.
+pub fn chunk_table(chunks: &[String]) {
+    let plural = if chunks.len() == 1 { "" } else { "s" };
+    status(
+        Style::Cyan,
+        "Split",
+        format!("into {} chunk{}", chunks.len(), plural),
+    );
+    let number_width = chunks.len().to_string().len();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let mut preview: String = chunk.trim().chars().take(PREVIEW_CHARS).collect();
+        if chunk.trim().chars().count() > PREVIEW_CHARS {
+            preview.push('…');
+        }
+        if chunk == crate::censor::TONE {
+            preview = paint(Style::Dim, "(beep)");
+        }
+        progress!(
+            "{:width$} {} {} {}",
+            "",
+            paint(Style::Dim, format!("#{:<number_width$}", index + 1)),
+            paint(Style::Dim, format!("{:>3} B", chunk.len())),
+            preview,
+            width = VERB_WIDTH
+        );
+    }
+}
+
+/// Reports a fatal error. Unlike status lines these are kept by `-q`.
+pub fn error(error: impl Display) {
+    if ERRORS.load(Ordering::Relaxed) {
+        eprintln!("{} {}", paint(Style::Red, "Error:"), redact(error));
+    }
+}
+
+/// The closing line of a synthesis run.
+pub fn summary(chunks: usize, cached: usize, bytes: usize, elapsed: Duration) {
+    let mut message = format!("{} chunk{}", chunks, if chunks == 1 { "" } else { "s" });
+    if cached > 0 {
+        message.push_str(&format!(" ({} cached)", cached));
+    }
+    message.push_str(&format!(
+        ", {} in {:.2}s",
+        crate::cache::format_size(bytes as u64),
+        elapsed.as_secs_f64()
+    ));
+    status(Style::Green, "Finished", message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::replace_token;
+
+    #[test]
+    fn secrets_are_replaced_as_whole_tokens_only() {
+        assert_eq!(
+            replace_token("sessionid=x; next x", "x"),
+            "sessionid=[redacted]; next [redacted]"
+        );
+        assert_eq!(replace_token("example text", "x"), "example text");
+        assert_eq!(
+            replace_token("abc123,abc1234", "abc123"),
+            "[redacted],abc1234"
+        );
+    }
+}
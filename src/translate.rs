@@ -0,0 +1,130 @@
+//! Machine translation of the input before synthesis, for `--translate-to`.
+//!
+//! The backend is set under `[translation]` in the config file:
+//!
+//! - `deepl` posts to the DeepL API (`url` defaults to the free plan's host).
+//! - `libretranslate` posts to a LibreTranslate server at `url`.
+
+use serde::{Deserialize, Serialize};
+
+/// DeepL API host for free-plan keys; paid keys use `https://api.deepl.com`.
+const DEEPL_URL: &str = "https://api-free.deepl.com";
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Deepl,
+    Libretranslate,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranslationConfig {
+    pub backend: Option<Backend>,
+    /// Base URL of the translation API
+    pub url: Option<String>,
+    /// API key (required by DeepL, optional for LibreTranslate)
+    pub api_key: Option<String>,
+}
+
+async fn deepl(
+    config: &TranslationConfig,
+    text: &str,
+    language: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let api_key = config
+        .api_key
+        .as_deref()
+        .ok_or("translation.backend = \"deepl\" needs translation.api_key to be set")?;
+    let url = config.url.as_deref().unwrap_or(DEEPL_URL);
+    let response: serde_json::Value = crate::http::client()
+        .post(format!("{}/v2/translate", url.trim_end_matches('/')))
+        .header("Authorization", format!("DeepL-Auth-Key {}", api_key))
+        .form(&[("text", text), ("target_lang", &language.to_uppercase())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let translated = response["translations"][0]["text"]
+        .as_str()
+        .ok_or("Missing translations in DeepL response")?;
+    Ok(translated.to_string())
+}
+
+async fn libretranslate(
+    config: &TranslationConfig,
+    text: &str,
+    language: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let url = config
+        .url
+        .as_deref()
+        .ok_or("translation.backend = \"libretranslate\" needs translation.url to be set")?;
+    let mut body = serde_json::json!({
+        "q": text,
+        "source": "auto",
+        "target": language,
+        "format": "text",
+    });
+    if let Some(api_key) = &config.api_key {
+        body["api_key"] = api_key.as_str().into();
+    }
+    let response: serde_json::Value = crate::http::client()
+        .post(format!("{}/translate", url.trim_end_matches('/')))
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let translated = response["translatedText"]
+        .as_str()
+        .ok_or("Missing translatedText in LibreTranslate response")?;
+    Ok(translated.to_string())
+}
+
+/// Translates `text` into `language` (an ISO 639-1 code) with the configured backend.
+pub async fn translate(
+    text: &str,
+    language: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let config = &crate::config::get().translation;
+    let translated = match config.backend {
+        Some(Backend::Deepl) => deepl(config, text, language).await,
+        Some(Backend::Libretranslate) => libretranslate(config, text, language).await,
+        None => return Err("--translate-to needs a [translation] backend in the config".into()),
+    }
+    .map_err(|e| format!("Failed to translate into {}: {}", language, e))?;
+    crate::term::status(
+        crate::term::Style::Cyan,
+        "Translated",
+        format!("into {}", language),
+    );
+    Ok(translated)
+}
+
+/// The voice for text translated into `language`: `speaker` if it already speaks that
+/// language, else the `[language_voices]` or first catalog voice for it.
+pub fn voice(speaker: &str, language: &str) -> String {
+    let code = crate::config::resolve_voice(speaker);
+    let speaks = tktts::voices::find(code)
+        .is_some_and(|voice| voice.language.split('-').next() == Some(language));
+    if speaks {
+        return speaker.to_string();
+    }
+    crate::lang::voice_for(language).unwrap_or_else(|| speaker.to_string())
+}
+
+/// `output` with the language inserted before the extension: `out.mp3` becomes
+/// `out.de.mp3`, so several translations can be written side by side.
+pub fn localized_output(output: &str, language: &str) -> String {
+    let name_start = output.rfind('/').map_or(0, |i| i + 1);
+    match output[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = name_start + dot;
+            format!("{}.{}{}", &output[..dot], language, &output[dot..])
+        }
+        _ => format!("{}.{}", output, language),
+    }
+}
@@ -0,0 +1,164 @@
+//! The network layer of [`Client`](crate::client::Client).
+//!
+//! Every chunk is one POST request whose response body is the API's JSON. A [`Transport`]
+//! sends it; [`ReqwestTransport`] is the default, and others can answer from a test
+//! double or use another HTTP stack. The body is handed on as a [`Body`] that may still
+//! be coming in, so the audio is decoded while it arrives. Policies such as retries are wrapped around a
+//! transport as [`layers`](crate::layers). The wasm32 build does not use this, as it
+//! sends requests with `fetch` from JavaScript.
+
+use crate::TktsError;
+use crate::schema::{AudioDecoder, Decoded};
+use futures_util::{Stream, StreamExt};
+use std::future::Future;
+use std::pin::Pin;
+use url::Url;
+
+/// One TTS request: a POST, with the parameters in the URL or, for form-style mirrors,
+/// in the body.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub url: Url,
+    /// `User-Agent`, `Cookie` and any signature headers
+    pub headers: Vec<(String, String)>,
+    /// Empty when the parameters are in the URL
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: Body,
+}
+
+/// The pieces of a body as they come from the connection.
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<bytes::Bytes, TktsError>> + Send>>;
+
+/// The body of a [`Response`]: whole, as from a test double or a tape, or still
+/// streaming in.
+pub enum Body {
+    Full(String),
+    Streaming(BodyStream),
+}
+
+impl Body {
+    /// Reads the rest of the body into one string.
+    pub async fn text(self) -> Result<String, TktsError> {
+        match self {
+            Body::Full(text) => Ok(text),
+            Body::Streaming(mut stream) => {
+                let mut body = Vec::new();
+                while let Some(piece) = stream.next().await {
+                    body.extend_from_slice(&piece?);
+                }
+                Ok(String::from_utf8(body)
+                    .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()))
+            }
+        }
+    }
+
+    /// Decodes the audio into `sink` piece by piece as the body comes in; see
+    /// [`AudioDecoder`]. A connection lost on the way fails with the stream's error.
+    pub async fn decode_audio<W: std::io::Write>(self, sink: W) -> Result<Decoded<W>, TktsError> {
+        let mut decoder = AudioDecoder::new(sink);
+        match self {
+            Body::Full(text) => decoder.feed(text.as_bytes())?,
+            Body::Streaming(mut stream) => {
+                while let Some(piece) = stream.next().await {
+                    decoder.feed(&piece?)?;
+                }
+            }
+        }
+        decoder.finish()
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        Body::Full(text)
+    }
+}
+
+impl From<&str> for Body {
+    fn from(text: &str) -> Self {
+        Body::Full(text.to_string())
+    }
+}
+
+impl std::fmt::Debug for Body {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Body::Full(text) => f.debug_tuple("Full").field(text).finish(),
+            Body::Streaming(_) => f.write_str("Streaming(..)"),
+        }
+    }
+}
+
+/// What [`Transport::send`] returns.
+pub type ResponseFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Response, TktsError>> + Send + 'a>>;
+
+/// Sends the client's requests.
+pub trait Transport: Send + Sync {
+    /// Sends `request` and resolves to the response. Error statuses are responses too,
+    /// since the API explains its errors in their bodies; only failures to get an answer
+    /// at all are errors: [`TktsError::Network`] when the host cannot be reached, and
+    /// [`TktsError::Transport`] with their own error as the source for other failures.
+    fn send(&self, request: Request) -> ResponseFuture<'_>;
+}
+
+/// A transport shared with other clients, or kept to inspect later.
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        (**self).send(request)
+    }
+}
+
+/// The default [`Transport`], built on reqwest.
+#[derive(Clone, Default)]
+pub struct ReqwestTransport {
+    http: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl From<reqwest::Client> for ReqwestTransport {
+    fn from(http: reqwest::Client) -> Self {
+        ReqwestTransport { http }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let mut builder = self.http.post(request.url);
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+            if !request.body.is_empty() {
+                builder = builder.body(request.body);
+            }
+            let response = builder
+                .send()
+                .await
+                .map_err(|e| TktsError::Network(e.into()))?;
+            let status = response.status().as_u16();
+            // The body is read as it is taken, not before
+            let pieces = futures_util::stream::unfold(response, |mut response| async move {
+                match response.chunk().await {
+                    Ok(Some(piece)) => Some((Ok(piece), response)),
+                    Ok(None) => None,
+                    Err(e) => Some((Err(TktsError::Network(e.into())), response)),
+                }
+            });
+            Ok(Response {
+                status,
+                body: Body::Streaming(Box::pin(pieces)),
+            })
+        })
+    }
+}
@@ -0,0 +1,386 @@
+//! Interactive terminal UI.
+//!
+//! A text entry pane, a searchable voice list with instant preview, a live view of how
+//! the text will be chunked, and a playback/progress pane.
+
+use crate::voices::{self, Voice};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+/// Number of status lines kept in the playback pane.
+const STATUS_HISTORY: usize = 50;
+
+#[derive(clap::Args)]
+pub struct TuiArgs {
+    /// Player command that reads audio from stdin (default: mpv or ffplay)
+    #[arg(long, value_name = "COMMAND")]
+    player: Option<String>,
+}
+
+#[derive(PartialEq)]
+enum Focus {
+    Text,
+    Voices,
+}
+
+/// Messages from background synthesis tasks to the UI thread.
+enum UiEvent {
+    Status(String),
+    Progress { done: usize, total: usize },
+    Finished,
+}
+
+struct App {
+    text: String,
+    chunks: Vec<String>,
+    search: String,
+    voice_list: ListState,
+    focus: Focus,
+    status: Vec<String>,
+    progress: Option<(usize, usize)>,
+    busy: bool,
+    session_id: String,
+    api_root_url: String,
+    player: Option<String>,
+}
+
+impl App {
+    fn filtered_voices(&self) -> Vec<&'static Voice> {
+        let search = self.search.to_lowercase();
+        voices::all()
+            .filter(|voice| {
+                search.is_empty()
+                    || voice.code.contains(&search)
+                    || voice.name.to_lowercase().contains(&search)
+                    || voice.language.to_lowercase().contains(&search)
+                    || voice.tags.iter().any(|tag| tag.contains(&search))
+            })
+            .collect()
+    }
+
+    fn selected_voice(&self) -> Option<&'static Voice> {
+        let voices = self.filtered_voices();
+        self.voice_list
+            .selected()
+            .and_then(|i| voices.get(i).copied())
+    }
+
+    fn push_status(&mut self, line: String) {
+        self.status.push(line);
+        if self.status.len() > STATUS_HISTORY {
+            self.status.remove(0);
+        }
+    }
+
+    fn text_changed(&mut self) {
+        match crate::split_text(&self.text, crate::BYTE_LIMIT) {
+            Ok(chunks) => self.chunks = chunks,
+            Err(e) => {
+                let line = format!("Filter error: {}", e);
+                if self.status.last() != Some(&line) {
+                    self.push_status(line);
+                }
+            }
+        }
+    }
+
+    fn search_changed(&mut self) {
+        let count = self.filtered_voices().len();
+        self.voice_list.select((count > 0).then_some(0));
+    }
+
+    /// Synthesizes `chunks` one after another, reporting progress, then plays the result.
+    fn start_speaking(
+        &mut self,
+        runtime: &Handle,
+        events: &UnboundedSender<UiEvent>,
+        chunks: Vec<String>,
+        voice: &'static Voice,
+    ) {
+        if self.busy || chunks.is_empty() {
+            return;
+        }
+        self.busy = true;
+        self.progress = Some((0, chunks.len()));
+
+        let events = events.clone();
+        let session_id = self.session_id.clone();
+        let api_root_url = self.api_root_url.clone();
+        let player = self.player.clone();
+        runtime.spawn(async move {
+            let total = chunks.len();
+            let mut audio = Vec::new();
+            for (index, chunk) in chunks.iter().enumerate() {
+                let chunk = std::slice::from_ref(chunk);
+                match crate::synthesize_chunks(chunk, voice.code, &session_id, &api_root_url).await
+                {
+                    Ok(data) => audio.extend(data.concat()),
+                    Err(e) => {
+                        let _ = events.send(UiEvent::Status(format!("Error: {}", e)));
+                        let _ = events.send(UiEvent::Finished);
+                        return;
+                    }
+                }
+                let _ = events.send(UiEvent::Progress {
+                    done: index + 1,
+                    total,
+                });
+            }
+
+            let _ = events.send(UiEvent::Status(format!("Playing with {}", voice.code)));
+            if let Err(e) = crate::playback::play(&audio, player.as_deref()).await {
+                let _ = events.send(UiEvent::Status(format!("Playback error: {}", e)));
+            }
+            let _ = events.send(UiEvent::Finished);
+        });
+    }
+
+    /// Handles a key press; returns `false` when the UI should exit.
+    fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        runtime: &Handle,
+        events: &UnboundedSender<UiEvent>,
+    ) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') if ctrl => return false,
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Text => Focus::Voices,
+                    Focus::Voices => Focus::Text,
+                }
+            }
+            KeyCode::F(5) => self.speak_text(runtime, events),
+            KeyCode::Char('s') if ctrl => self.speak_text(runtime, events),
+            _ if self.focus == Focus::Voices => self.handle_voices_key(key, runtime, events),
+            _ => self.handle_text_key(key),
+        }
+        true
+    }
+
+    fn speak_text(&mut self, runtime: &Handle, events: &UnboundedSender<UiEvent>) {
+        match self.selected_voice() {
+            Some(voice) => {
+                let chunks = self.chunks.clone();
+                self.push_status(format!(
+                    "Speaking {} chunk(s) with {}",
+                    chunks.len(),
+                    voice.code
+                ));
+                self.start_speaking(runtime, events, chunks, voice);
+            }
+            None => self.push_status("No voice selected".to_string()),
+        }
+    }
+
+    fn handle_voices_key(
+        &mut self,
+        key: KeyEvent,
+        runtime: &Handle,
+        events: &UnboundedSender<UiEvent>,
+    ) {
+        let count = self.filtered_voices().len();
+        match key.code {
+            KeyCode::Up => {
+                let selected = self.voice_list.selected().unwrap_or(0);
+                self.voice_list.select(Some(selected.saturating_sub(1)));
+            }
+            KeyCode::Down if count > 0 => {
+                let selected = self.voice_list.selected().map_or(0, |i| i + 1);
+                self.voice_list.select(Some(selected.min(count - 1)));
+            }
+            KeyCode::Enter => {
+                if let Some(voice) = self.selected_voice() {
+                    let sample = format!("Hello, this is {}.", voice.name);
+                    self.push_status(format!("Previewing {}", voice.code));
+                    self.start_speaking(runtime, events, vec![sample], voice);
+                }
+            }
+            KeyCode::Backspace => {
+                self.search.pop();
+                self.search_changed();
+            }
+            KeyCode::Char(c) => {
+                self.search.push(c);
+                self.search_changed();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_text_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.text.push('\n'),
+            KeyCode::Backspace => {
+                self.text.pop();
+            }
+            KeyCode::Char(c) => self.text.push(c),
+            _ => return,
+        }
+        self.text_changed();
+    }
+
+    fn handle_event(&mut self, event: UiEvent) {
+        match event {
+            UiEvent::Status(line) => self.push_status(line),
+            UiEvent::Progress { done, total } => self.progress = Some((done, total)),
+            UiEvent::Finished => {
+                self.busy = false;
+                self.progress = None;
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [voices_area, main_area] =
+            Layout::horizontal([Constraint::Length(40), Constraint::Min(20)]).areas(frame.area());
+        let [text_area, chunks_area, playback_area] = Layout::vertical([
+            Constraint::Percentage(40),
+            Constraint::Percentage(35),
+            Constraint::Min(6),
+        ])
+        .areas(main_area);
+
+        let focused = Style::default().fg(Color::Yellow);
+        let border = |focus: Focus| {
+            if self.focus == focus {
+                focused
+            } else {
+                Style::default()
+            }
+        };
+
+        // Voice list with search field in the title
+        let items: Vec<ListItem> = self
+            .filtered_voices()
+            .iter()
+            .map(|voice| ListItem::new(format!("{:<18} {}", voice.code, voice.name)))
+            .collect();
+        let voice_list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!(" Voices  search: {} ", self.search))
+                    .border_style(border(Focus::Voices)),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(voice_list, voices_area, &mut self.voice_list);
+
+        let text = Paragraph::new(self.text.as_str())
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::bordered()
+                    .title(" Text  (F5 / Ctrl+S: speak, Tab: switch pane, Esc: quit) ")
+                    .border_style(border(Focus::Text)),
+            );
+        frame.render_widget(text, text_area);
+
+        let plan: Vec<Line> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| Line::from(format!("{:>3} [{:>3} B] {}", i + 1, chunk.len(), chunk)))
+            .collect();
+        let plan = Paragraph::new(plan)
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title(format!(" Chunk plan ({}) ", self.chunks.len())));
+        frame.render_widget(plan, chunks_area);
+
+        let [gauge_area, status_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(3)]).areas(playback_area);
+        let (done, total) = self.progress.unwrap_or((0, 0));
+        let ratio = if total == 0 {
+            0.0
+        } else {
+            done as f64 / total as f64
+        };
+        let gauge = Gauge::default()
+            .block(Block::bordered().title(" Playback "))
+            .gauge_style(Style::default().fg(Color::Green))
+            .label(if self.busy {
+                format!("{}/{} chunks", done, total)
+            } else {
+                "idle".to_string()
+            })
+            .ratio(ratio);
+        frame.render_widget(gauge, gauge_area);
+
+        let visible = status_area.height.saturating_sub(2) as usize;
+        let start = self.status.len().saturating_sub(visible);
+        let status: Vec<Line> = self.status[start..]
+            .iter()
+            .map(|line| Line::from(line.as_str()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(status).block(Block::bordered().title(" Status ")),
+            status_area,
+        );
+    }
+}
+
+fn ui_loop(
+    terminal: &mut DefaultTerminal,
+    mut app: App,
+    runtime: Handle,
+    events: UnboundedSender<UiEvent>,
+    mut incoming: UnboundedReceiver<UiEvent>,
+) -> std::io::Result<()> {
+    loop {
+        while let Ok(event) = incoming.try_recv() {
+            app.handle_event(event);
+        }
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && !app.handle_key(key, &runtime, &events)
+        {
+            return Ok(());
+        }
+    }
+}
+
+pub async fn run(args: TuiArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+
+    // Chunk and request logging would draw over the UI
+    crate::PROGRESS.store(false, Ordering::Relaxed);
+
+    let mut app = App {
+        text: String::new(),
+        chunks: Vec::new(),
+        search: String::new(),
+        voice_list: ListState::default(),
+        focus: Focus::Text,
+        status: vec!["Type some text, pick a voice, press F5 to speak.".to_string()],
+        progress: None,
+        busy: false,
+        session_id,
+        api_root_url,
+        player: args.player,
+    };
+    let default_voice = voices::all().position(|v| v.code == "en_us_002");
+    app.voice_list.select(default_voice.or(Some(0)));
+
+    let runtime = Handle::current();
+    let (events, incoming) = mpsc::unbounded_channel();
+    tokio::task::spawn_blocking(move || {
+        let mut terminal = ratatui::init();
+        let result = ui_loop(&mut terminal, app, runtime, events, incoming);
+        ratatui::restore();
+        result
+    })
+    .await??;
+
+    Ok(())
+}
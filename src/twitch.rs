@@ -0,0 +1,204 @@
+//! Twitch chat reader mode.
+//!
+//! Connects anonymously to Twitch IRC, filters chat messages and speaks them one at a
+//! time through the playback/output pipeline.
+
+use crate::playback::PlaybackArgs;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+const TWITCH_IRC_ADDR: &str = "irc.chat.twitch.tv:6667";
+/// Anonymous read-only login accepted by Twitch IRC.
+const ANONYMOUS_NICK: &str = "justinfan31415";
+/// Messages waiting to be spoken without `--max-queue`.
+const DEFAULT_MAX_QUEUE: usize = 20;
+
+#[derive(clap::Args)]
+pub struct TwitchArgs {
+    /// Channel whose chat is read aloud
+    #[arg(long)]
+    channel: String,
+
+    /// Voice used for chat messages
+    #[arg(short, long, default_value = "en_us_002")]
+    speaker: String,
+
+    /// Users whose messages are never spoken (repeatable, e.g. bots)
+    #[arg(long = "ignore", value_name = "USER")]
+    ignored_users: Vec<String>,
+
+    /// Only speak messages starting with this prefix (the prefix itself is not spoken)
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Skip messages starting with this prefix, e.g. "!" for other bots' commands (repeatable)
+    #[arg(long = "skip-prefix", value_name = "PREFIX")]
+    skip_prefixes: Vec<String>,
+
+    /// Minimum seconds between two spoken messages from the same user
+    #[arg(long, default_value_t = 0)]
+    user_cooldown: u64,
+
+    /// Messages longer than this many characters are truncated
+    #[arg(long, default_value_t = 200)]
+    max_length: usize,
+
+    /// Prefix each message with "<user> says"
+    #[arg(long)]
+    say_names: bool,
+
+    #[command(flatten)]
+    flood: crate::flood::FloodArgs,
+
+    #[command(flatten)]
+    playback: PlaybackArgs,
+}
+
+struct ChatMessage {
+    user: String,
+    text: String,
+}
+
+/// Parses a `PRIVMSG` line into its sender and text.
+fn parse_privmsg(line: &str) -> Option<ChatMessage> {
+    let rest = line.strip_prefix(':')?;
+    let (prefix, rest) = rest.split_once(' ')?;
+    let rest = rest.strip_prefix("PRIVMSG ")?;
+    let (_channel, text) = rest.split_once(" :")?;
+    let user = prefix.split('!').next()?;
+
+    Some(ChatMessage {
+        user: user.to_lowercase(),
+        text: text.to_string(),
+    })
+}
+
+/// Applies the ignore lists, prefixes and per-user cooldowns to chat messages.
+struct ChatFilter {
+    ignored_users: Vec<String>,
+    prefix: Option<String>,
+    skip_prefixes: Vec<String>,
+    user_cooldown: Duration,
+    max_length: usize,
+    last_spoken: HashMap<String, Instant>,
+}
+
+impl ChatFilter {
+    fn new(args: &TwitchArgs) -> Self {
+        ChatFilter {
+            ignored_users: args
+                .ignored_users
+                .iter()
+                .map(|u| u.to_lowercase())
+                .collect(),
+            prefix: args.prefix.clone(),
+            skip_prefixes: args.skip_prefixes.clone(),
+            user_cooldown: Duration::from_secs(args.user_cooldown),
+            max_length: args.max_length,
+            last_spoken: HashMap::new(),
+        }
+    }
+
+    /// Returns the text to speak, or `None` if the message is filtered out.
+    fn accept(&mut self, message: &ChatMessage) -> Option<String> {
+        if self.ignored_users.contains(&message.user) {
+            return None;
+        }
+
+        let mut text = message.text.trim();
+        if let Some(prefix) = &self.prefix {
+            text = text.strip_prefix(prefix.as_str())?.trim();
+        }
+        if text.is_empty()
+            || self
+                .skip_prefixes
+                .iter()
+                .any(|p| text.starts_with(p.as_str()))
+        {
+            return None;
+        }
+
+        let now = Instant::now();
+        if let Some(last) = self.last_spoken.get(&message.user)
+            && now.duration_since(*last) < self.user_cooldown
+        {
+            return None;
+        }
+        self.last_spoken.insert(message.user.clone(), now);
+
+        Some(text.chars().take(self.max_length).collect())
+    }
+}
+
+pub async fn run(args: TwitchArgs) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (session_id, api_root_url) = crate::load_credentials()?;
+    let channel = args.channel.trim_start_matches('#').to_lowercase();
+
+    let stream = TcpStream::connect(TWITCH_IRC_ADDR).await?;
+    let (reader, mut writer) = stream.into_split();
+    writer
+        .write_all(format!("NICK {}\r\nJOIN #{}\r\n", ANONYMOUS_NICK, channel).as_bytes())
+        .await?;
+    progress!("Reading chat from #{}", channel);
+
+    // Speak messages sequentially so they never overlap
+    let queue = Arc::new(crate::flood::Queue::new(
+        &args.flood,
+        Some(DEFAULT_MAX_QUEUE),
+    ));
+    let speaker = args.speaker.clone();
+    let playback = args.playback.clone();
+    let speaker_task = tokio::spawn({
+        let queue = queue.clone();
+        async move {
+            while let Some(message) = queue.next().await {
+                let voice = message.voice.as_deref().unwrap_or(&speaker);
+                let result =
+                    match crate::synthesize(&message.text, voice, &session_id, &api_root_url).await
+                    {
+                        Ok(audio) => playback.output(&audio).await,
+                        Err(e) => Err(e),
+                    };
+                queue.spoken();
+                if let Err(e) = result {
+                    crate::term::error(format!("Failed to speak message: {}", e));
+                }
+            }
+        }
+    });
+
+    let mut filter = ChatFilter::new(&args);
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if let Some(server) = line.strip_prefix("PING ") {
+            writer
+                .write_all(format!("PONG {}\r\n", server).as_bytes())
+                .await?;
+            continue;
+        }
+
+        let Some(message) = parse_privmsg(&line) else {
+            continue;
+        };
+        let Some(text) = filter.accept(&message) else {
+            continue;
+        };
+
+        progress!("{}: {}", message.user, text);
+        let source = format!("{}: {}", message.user, text);
+        let text = if args.say_names {
+            format!("{} says {}", message.user, text)
+        } else {
+            text
+        };
+        queue.push(&source, crate::flood::Message { text, voice: None });
+    }
+
+    queue.close();
+    speaker_task.await?;
+
+    Err("Twitch closed the connection".into())
+}
@@ -0,0 +1,50 @@
+//! `{{name}}` placeholders in the input text, filled from `--var name=value`.
+
+use regex::{Captures, Regex};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+static VARS: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+/// Parses a `--var` value of the form `name=value`.
+pub fn parse(assignment: &str) -> Result<(String, String), String> {
+    match assignment.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err(format!("expected name=value, got '{}'", assignment)),
+    }
+}
+
+pub fn init(vars: Vec<(String, String)>) {
+    let _ = VARS.set(vars.into_iter().collect());
+}
+
+/// `text` with its placeholders filled from `extra` (such as a batch row's fields), then
+/// the `--var` values. Without any variables the text is left as is; otherwise an
+/// unknown placeholder is an error, so a typo does not end up spoken.
+pub fn render(
+    text: &str,
+    extra: &BTreeMap<String, String>,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let vars = VARS.get_or_init(BTreeMap::new);
+    if vars.is_empty() && extra.is_empty() {
+        return Ok(text.to_string());
+    }
+    let placeholder = Regex::new(r"\{\{\s*([\w.-]+)\s*\}\}").unwrap();
+    let mut unknown = None;
+    let rendered = placeholder.replace_all(text, |captures: &Captures| {
+        let name = &captures[1];
+        match extra.get(name).or_else(|| vars.get(name)) {
+            Some(value) => value.clone(),
+            None => {
+                unknown.get_or_insert_with(|| name.to_string());
+                captures[0].to_string()
+            }
+        }
+    });
+    if let Some(name) = unknown {
+        return Err(format!("No value for {{{{{}}}}}; pass --var {}=...", name, name).into());
+    }
+    Ok(rendered.into_owned())
+}
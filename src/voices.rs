@@ -0,0 +1,478 @@
+//! Embedded catalog of known TikTok TTS voices, which programs can extend at runtime
+//! with voices discovered since the release.
+
+use std::sync::OnceLock;
+
+/// Voices added with [`extend`].
+static ADDED: OnceLock<Vec<Voice>> = OnceLock::new();
+
+pub struct Voice {
+    /// Voice code sent as `text_speaker`
+    pub code: &'static str,
+    /// Display name as shown in the TikTok app
+    pub name: &'static str,
+    /// BCP 47 language tag
+    pub language: &'static str,
+    pub tags: &'static [&'static str],
+}
+
+macro_rules! voices {
+    ($(($code:literal, $name:literal, $language:literal, [$($tag:literal),*])),* $(,)?) => {
+        &[$(Voice { code: $code, name: $name, language: $language, tags: &[$($tag),*] }),*]
+    };
+}
+
+pub const VOICES: &[Voice] = voices![
+    // English (US)
+    ("en_us_001", "Jessie (US Female 1)", "en-US", ["female"]),
+    ("en_us_002", "Jessie (US Female 2)", "en-US", ["female"]),
+    ("en_us_006", "Joey (US Male 1)", "en-US", ["male"]),
+    ("en_us_007", "Professor (US Male 2)", "en-US", ["male"]),
+    ("en_us_009", "Scientist (US Male 3)", "en-US", ["male"]),
+    ("en_us_010", "Confidence (US Male 4)", "en-US", ["male"]),
+    (
+        "en_male_narration",
+        "Story Teller",
+        "en-US",
+        ["male", "narrator"]
+    ),
+    ("en_male_funny", "Wacky", "en-US", ["male", "funny"]),
+    (
+        "en_female_emotional",
+        "Peaceful",
+        "en-US",
+        ["female", "calm"]
+    ),
+    ("en_female_samc", "Empathetic", "en-US", ["female", "calm"]),
+    ("en_male_cody", "Serious", "en-US", ["male"]),
+    ("en_female_betty", "Bae", "en-US", ["female"]),
+    ("en_male_jarvis", "Alfred", "en-US", ["male", "character"]),
+    ("en_male_ashmagic", "ashmagic", "en-US", ["male"]),
+    ("en_male_olantekkers", "olantekkers", "en-US", ["male"]),
+    ("en_female_shenna", "Debutante", "en-US", ["female"]),
+    ("en_female_pansino", "Varsity", "en-US", ["female"]),
+    ("en_male_trevor", "Marty", "en-US", ["male"]),
+    (
+        "en_female_grandma",
+        "Grandma",
+        "en-US",
+        ["female", "character"]
+    ),
+    (
+        "en_female_richgirl",
+        "Beauty Guru",
+        "en-US",
+        ["female", "character"]
+    ),
+    (
+        "en_male_grinch",
+        "Trickster",
+        "en-US",
+        ["male", "character"]
+    ),
+    ("en_male_wizard", "Magician", "en-US", ["male", "character"]),
+    (
+        "en_male_ghosthost",
+        "Ghost Host",
+        "en-US",
+        ["male", "character"]
+    ),
+    (
+        "en_female_madam_leota",
+        "Madame Leota",
+        "en-US",
+        ["female", "character"]
+    ),
+    ("en_male_pirate", "Pirate", "en-US", ["male", "character"]),
+    (
+        "en_male_deadpool",
+        "Mr. GoodGuy",
+        "en-US",
+        ["male", "character"]
+    ),
+    ("en_male_santa", "Santa", "en-US", ["male", "character"]),
+    (
+        "en_male_santa_narration",
+        "Author",
+        "en-US",
+        ["male", "narrator"]
+    ),
+    (
+        "en_male_santa_effect",
+        "Santa (with effect)",
+        "en-US",
+        ["male", "character"]
+    ),
+    (
+        "en_male_ukneighbor",
+        "Lord Cringe",
+        "en-US",
+        ["male", "character"]
+    ),
+    (
+        "en_male_ukbutler",
+        "Mr. Meticulous",
+        "en-US",
+        ["male", "character"]
+    ),
+    // Disney and Star Wars characters
+    (
+        "en_us_ghostface",
+        "Ghost Face",
+        "en-US",
+        ["male", "character"]
+    ),
+    (
+        "en_us_chewbacca",
+        "Chewbacca",
+        "en-US",
+        ["male", "character"]
+    ),
+    ("en_us_c3po", "C3PO", "en-US", ["male", "character"]),
+    ("en_us_stitch", "Stitch", "en-US", ["male", "character"]),
+    (
+        "en_us_stormtrooper",
+        "Stormtrooper",
+        "en-US",
+        ["male", "character"]
+    ),
+    ("en_us_rocket", "Rocket", "en-US", ["male", "character"]),
+    // English (UK and Australia)
+    (
+        "en_uk_001",
+        "Narrator (UK Male 1)",
+        "en-GB",
+        ["male", "narrator"]
+    ),
+    ("en_uk_003", "UK Male 2", "en-GB", ["male"]),
+    ("en_au_001", "Metro (AU Female)", "en-AU", ["female"]),
+    ("en_au_002", "Smooth (AU Male)", "en-AU", ["male"]),
+    // Singing
+    (
+        "en_female_f08_salut_damour",
+        "Cottagecore",
+        "en-US",
+        ["female", "singing"]
+    ),
+    ("en_male_m03_lobby", "Jingle", "en-US", ["male", "singing"]),
+    (
+        "en_male_m03_sunshine_soon",
+        "Toon Beat",
+        "en-US",
+        ["male", "singing"]
+    ),
+    (
+        "en_male_m03_classy",
+        "Classic Electric",
+        "en-US",
+        ["male", "singing"]
+    ),
+    (
+        "en_female_f08_warmy_breeze",
+        "Open Mic",
+        "en-US",
+        ["female", "singing"]
+    ),
+    (
+        "en_female_f08_twinkle",
+        "Pop Lullaby",
+        "en-US",
+        ["female", "singing"]
+    ),
+    (
+        "en_female_ht_f08_glorious",
+        "Euphoric",
+        "en-US",
+        ["female", "singing"]
+    ),
+    (
+        "en_female_ht_f08_wonderful_world",
+        "Melodrama",
+        "en-US",
+        ["female", "singing"]
+    ),
+    (
+        "en_female_ht_f08_halloween",
+        "Opera",
+        "en-US",
+        ["female", "singing"]
+    ),
+    (
+        "en_female_ht_f08_newyear",
+        "NYE 2023",
+        "en-US",
+        ["female", "singing"]
+    ),
+    (
+        "en_male_sing_funny_it_goes_up",
+        "Hypetrain",
+        "en-US",
+        ["male", "singing"]
+    ),
+    (
+        "en_male_sing_deep_jingle",
+        "Caroler",
+        "en-US",
+        ["male", "singing"]
+    ),
+    (
+        "en_male_m2_xhxs_m03_silly",
+        "Quirky Time",
+        "en-US",
+        ["male", "singing"]
+    ),
+    (
+        "en_male_m2_xhxs_m03_christmas",
+        "Cozy",
+        "en-US",
+        ["male", "singing"]
+    ),
+    // French
+    ("fr_001", "French Male 1", "fr-FR", ["male"]),
+    ("fr_002", "French Male 2", "fr-FR", ["male"]),
+    // German
+    ("de_001", "German Female", "de-DE", ["female"]),
+    ("de_002", "German Male", "de-DE", ["male"]),
+    // Spanish
+    ("es_002", "Spanish Male", "es-ES", ["male"]),
+    ("es_mx_002", "Spanish MX Male", "es-MX", ["male"]),
+    ("es_male_m3", "Julio", "es-MX", ["male"]),
+    ("es_female_f6", "Alejandra", "es-MX", ["female"]),
+    ("es_female_fp1", "Mariana", "es-MX", ["female"]),
+    (
+        "es_mx_female_supermom",
+        "Super Mamá",
+        "es-MX",
+        ["female", "character"]
+    ),
+    // Portuguese
+    ("br_003", "Portuguese BR Female 2", "pt-BR", ["female"]),
+    ("br_004", "Portuguese BR Female 3", "pt-BR", ["female"]),
+    ("br_005", "Portuguese BR Male", "pt-BR", ["male"]),
+    ("bp_female_ivete", "Ivete Sangalo", "pt-BR", ["female"]),
+    ("bp_female_ludmilla", "Ludmilla", "pt-BR", ["female"]),
+    ("pt_female_lhays", "Lhays Macedo", "pt-BR", ["female"]),
+    ("pt_female_laizza", "Laizza", "pt-BR", ["female"]),
+    ("pt_male_bueno", "Galvão Bueno", "pt-BR", ["male"]),
+    // Italian
+    ("it_male_m18", "Italian Male", "it-IT", ["male"]),
+    // Indonesian
+    ("id_001", "Indonesian Female", "id-ID", ["female"]),
+    // Japanese
+    ("jp_001", "Japanese Female 1", "ja-JP", ["female"]),
+    ("jp_003", "Japanese Female 2", "ja-JP", ["female"]),
+    ("jp_005", "Japanese Female 3", "ja-JP", ["female"]),
+    ("jp_006", "Japanese Male", "ja-JP", ["male"]),
+    ("jp_female_fujicochan", "りーさ", "ja-JP", ["female"]),
+    ("jp_female_hasegawariona", "世羅鈴", "ja-JP", ["female"]),
+    (
+        "jp_male_keiichinakano",
+        "Morio's Kitchen",
+        "ja-JP",
+        ["male"]
+    ),
+    ("jp_female_oomaeaika", "夏絵ココ", "ja-JP", ["female"]),
+    ("jp_male_yujinchigusa", "低音ボイス", "ja-JP", ["male"]),
+    ("jp_female_shirou", "四郎", "ja-JP", ["female"]),
+    ("jp_male_tamawakazuki", "玉川寿紀", "ja-JP", ["male"]),
+    ("jp_female_kaorishoji", "庄司果織", "ja-JP", ["female"]),
+    ("jp_female_yagishaki", "八木沙季", "ja-JP", ["female"]),
+    ("jp_male_hikakin", "ヒカキン", "ja-JP", ["male"]),
+    ("jp_female_rei", "丸山礼", "ja-JP", ["female"]),
+    ("jp_male_shuichiro", "修一朗", "ja-JP", ["male"]),
+    ("jp_male_matsudake", "マツダ家の日常", "ja-JP", ["male"]),
+    (
+        "jp_female_machikoriiita",
+        "まちこりーた",
+        "ja-JP",
+        ["female"]
+    ),
+    ("jp_male_matsuo", "モジャオ", "ja-JP", ["male"]),
+    ("jp_male_osada", "モリスケ", "ja-JP", ["male"]),
+    // Korean
+    ("kr_002", "Korean Male 1", "ko-KR", ["male"]),
+    ("kr_003", "Korean Female", "ko-KR", ["female"]),
+    ("kr_004", "Korean Male 2", "ko-KR", ["male"]),
+    // Chinese
+    (
+        "zh_male_zhubajie_clone2",
+        "Zhu Bajie",
+        "zh-CN",
+        ["male", "character"]
+    ),
+    (
+        "zh_male_sunwukong_clone2",
+        "Sun Wukong",
+        "zh-CN",
+        ["male", "character"]
+    ),
+];
+
+/// Adds `voices` to the catalog, leaving out codes it already has. Only the first call
+/// has an effect, so it belongs at startup, before the catalog is read.
+pub fn extend(voices: Vec<Voice>) {
+    let added = voices
+        .into_iter()
+        .filter(|voice| !VOICES.iter().any(|known| known.code == voice.code))
+        .collect();
+    let _ = ADDED.set(added);
+}
+
+/// The whole catalog: the embedded [`VOICES`], then those added with [`extend`].
+pub fn all() -> impl Iterator<Item = &'static Voice> + Clone {
+    VOICES.iter().chain(ADDED.get().into_iter().flatten())
+}
+
+/// English name of a BCP 47 tag's primary language, for languages in the catalog.
+pub fn language_name(tag: &str) -> Option<&'static str> {
+    let name = match tag.split('-').next()? {
+        "de" => "German",
+        "en" => "English",
+        "es" => "Spanish",
+        "fr" => "French",
+        "id" => "Indonesian",
+        "it" => "Italian",
+        "ja" => "Japanese",
+        "ko" => "Korean",
+        "pt" => "Portuguese",
+        "zh" => "Chinese",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The text [`Profile`](crate::Profile) for a voice: singing voices get numbers spelled
+/// out, German voices keep their umlauts. Unknown voices get the default.
+pub fn profile(code: &str) -> crate::Profile {
+    let mut profile = crate::Profile::default();
+    if let Some(voice) = find(code) {
+        profile.replace_umlauts = !voice.language.starts_with("de");
+        profile.spell_numbers = voice.tags.contains(&"singing");
+    }
+    profile
+}
+
+/// Rough speaking rate of a voice in characters per second, for duration estimates.
+/// Scripts without spaces pack more speech into each character; singing is slower.
+pub fn speaking_rate(code: &str) -> f64 {
+    let Some(voice) = find(code) else {
+        return 15.0;
+    };
+    let rate = match voice.language.split('-').next() {
+        Some("ja") => 7.0,
+        Some("ko") => 6.0,
+        Some("zh") => 5.0,
+        _ => 15.0,
+    };
+    if voice.tags.contains(&"singing") {
+        rate * 0.6
+    } else {
+        rate
+    }
+}
+
+/// The catalog entry for `code`.
+pub fn find(code: &str) -> Option<&'static Voice> {
+    all().find(|voice| voice.code == code)
+}
+
+/// Catalog codes close enough to `code` to be what a typo meant, closest first.
+pub fn suggestions(code: &str) -> Vec<&'static str> {
+    let code = code.to_lowercase();
+    let limit = (code.chars().count() / 3).max(2);
+    let mut close: Vec<(usize, &'static str)> = all()
+        .map(|voice| (edit_distance(&code, voice.code), voice.code))
+        .filter(|&(distance, _)| distance <= limit)
+        .collect();
+    close.sort_by_key(|&(distance, _)| distance);
+    close.into_iter().take(3).map(|(_, code)| code).collect()
+}
+
+/// Levenshtein distance between two short words.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// How well one query term matches one word: 3 exact, 2 prefix, 1 within a typo or two.
+fn term_score(term: &str, word: &str) -> u32 {
+    let typos = match term.chars().count() {
+        // Numbered codes like `en_us_001` and `en_us_002` are one typo apart
+        _ if term.contains(|c: char| c.is_ascii_digit()) => 0,
+        0..=3 => 0,
+        4..=6 => 1,
+        _ => 2,
+    };
+    if word == term {
+        3
+    } else if word.starts_with(term) {
+        2
+    } else if typos > 0 && edit_distance(term, word) <= typos {
+        1
+    } else {
+        0
+    }
+}
+
+impl Voice {
+    /// Lowercase words a search can match: the code and its parts, the display name's
+    /// words, the language tag, its parts and language name, and the tags.
+    fn search_words(&self) -> Vec<String> {
+        let mut words = vec![self.code.to_string(), self.language.to_lowercase()];
+        words.extend(self.code.split('_').map(str::to_string));
+        words.extend(
+            self.name
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase),
+        );
+        words.extend(self.language.split('-').map(str::to_lowercase));
+        words.extend(language_name(self.language).map(str::to_lowercase));
+        words.extend(self.tags.iter().map(|tag| tag.to_string()));
+        words
+    }
+}
+
+/// Fuzzy search over codes, display names, languages and tags, best matches first.
+///
+/// Each whitespace separated term of `query` is matched against whole words, by prefix
+/// or with a small typo allowance, so `"french male"` finds the French male voices
+/// without also matching every `female` one. Only the voices matching the most terms are
+/// returned, ordered by how closely they match.
+pub fn search(query: &str) -> Vec<&'static Voice> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut scored: Vec<(usize, u32, &'static Voice)> = all()
+        .map(|voice| {
+            let words = voice.search_words();
+            let scores: Vec<u32> = terms
+                .iter()
+                .map(|term| {
+                    let best = words.iter().map(|word| term_score(term, word)).max();
+                    best.unwrap_or(0)
+                })
+                .collect();
+            let matched = scores.iter().filter(|&&score| score > 0).count();
+            (matched, scores.iter().sum(), voice)
+        })
+        .filter(|&(matched, _, _)| matched > 0)
+        .collect();
+    let best = scored.iter().map(|&(matched, _, _)| matched).max();
+    scored.retain(|&(matched, _, _)| Some(matched) == best);
+    // Stable, so equally good matches keep catalog order
+    scored.sort_by_key(|&(_, score, _)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(_, _, voice)| voice).collect()
+}
@@ -0,0 +1,98 @@
+//! JavaScript bindings for `wasm32-unknown-unknown` builds.
+//!
+//! Exports the splitter, sanitizer, URL builder and voice catalog under camelCase names,
+//! plus `requestChunk`, which performs one TTS request with the global `fetch` and so
+//! works in pages, workers and extension service workers alike.
+
+use js_sys::{Array, Object, Promise, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestCredentials, RequestInit, Response};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(request: &Request) -> Promise;
+}
+
+/// Splits text into request-sized chunks; see `tktts::split_text` for the guarantees.
+#[wasm_bindgen(js_name = splitText)]
+pub fn split_text(text: &str, byte_limit: usize) -> Result<Array, JsError> {
+    if byte_limit < crate::MIN_BYTE_LIMIT {
+        return Err(JsError::new(&format!(
+            "byteLimit must be at least {}",
+            crate::MIN_BYTE_LIMIT
+        )));
+    }
+    Ok(crate::split_text(text, byte_limit)
+        .into_iter()
+        .map(JsValue::from)
+        .collect())
+}
+
+#[wasm_bindgen(js_name = sanitizeText)]
+pub fn sanitize_text(text: &str) -> String {
+    crate::sanitize_text(text)
+}
+
+#[wasm_bindgen(js_name = ttsUrl)]
+pub fn tts_url(root_url: &str, text: &str, speaker: &str, aid: &str) -> Result<String, JsError> {
+    Ok(crate::tts_url(root_url, text, speaker, aid)?.to_string())
+}
+
+/// The voice catalog as an array of `{code, name, language, tags}` objects.
+#[wasm_bindgen]
+pub fn voices() -> Result<Array, JsValue> {
+    let voices = Array::new();
+    for voice in crate::voices::VOICES {
+        let object = Object::new();
+        Reflect::set(&object, &"code".into(), &voice.code.into())?;
+        Reflect::set(&object, &"name".into(), &voice.name.into())?;
+        Reflect::set(&object, &"language".into(), &voice.language.into())?;
+        let tags: Array = voice.tags.iter().map(|tag| JsValue::from(*tag)).collect();
+        Reflect::set(&object, &"tags".into(), &tags)?;
+        voices.push(&object);
+    }
+    Ok(voices)
+}
+
+/// Requests one chunk and resolves to the base64 MP3 data (`data.v_str`).
+///
+/// Browsers do not allow setting the `Cookie` header, so the request is sent with
+/// `credentials: "include"` and relies on the user's TikTok session cookie.
+#[wasm_bindgen(js_name = requestChunk)]
+pub async fn request_chunk(
+    root_url: String,
+    text: String,
+    speaker: String,
+) -> Result<String, JsValue> {
+    let url = crate::tts_url(&root_url, &text, &speaker, "1233")
+        .map_err(|e| JsError::new(&e.to_string()))?;
+
+    let init = RequestInit::new();
+    init.set_method("POST");
+    init.set_credentials(RequestCredentials::Include);
+    let request = Request::new_with_str_and_init(url.as_str(), &init)?;
+
+    let response: Response = JsFuture::from(fetch_with_request(&request))
+        .await?
+        .dyn_into()?;
+    if !response.ok() {
+        return Err(JsError::new(&format!("TTS request failed ({})", response.status())).into());
+    }
+    let json = JsFuture::from(response.json()?).await?;
+
+    let data = Reflect::get(&json, &"data".into())?;
+    let v_str = if data.is_object() {
+        Reflect::get(&data, &"v_str".into())?.as_string()
+    } else {
+        None
+    };
+    v_str.ok_or_else(|| {
+        let message = Reflect::get(&json, &"message".into())
+            .ok()
+            .and_then(|message| message.as_string())
+            .unwrap_or_else(|| "Missing v_str in response".to_string());
+        JsError::new(&message).into()
+    })
+}
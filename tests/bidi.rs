@@ -0,0 +1,88 @@
+use tktts::{Profile, Split, sanitize_text, sanitize_text_with, split_text, split_text_with};
+
+#[test]
+fn arabic_is_split_at_arabic_commas() {
+    let text = "ذهبنا إلى السوق، ثم عدنا إلى البيت.";
+    assert_eq!(
+        split_text(text, 40),
+        vec!["ذهبنا إلى السوق،", " ثم عدنا إلى البيت."]
+    );
+}
+
+#[test]
+fn arabic_questions_end_sentences() {
+    let text = "هل أنت بخير؟ نعم، شكرا.";
+    assert_eq!(
+        split_text_with(text, 30, Split::Sentence),
+        vec!["هل أنت بخير؟", " نعم، شكرا."]
+    );
+}
+
+#[test]
+fn embedded_digits_stay_together() {
+    let text = "دفعنا 1,500 دينار في 2024-05-01 الساعة 12:30 بنسبة 3.5 بالمئة";
+    for limit in 12..text.len() {
+        let chunks = split_text(text, limit);
+        for number in ["1,500", "2024-05-01", "12:30", "3.5"] {
+            assert!(
+                chunks.iter().any(|chunk| chunk.contains(number)),
+                "{} was split at limit {}: {:?}",
+                number,
+                limit,
+                chunks
+            );
+        }
+    }
+}
+
+#[test]
+fn arabic_indic_digits_stay_together() {
+    let text = "السعر ١٬٥٠٠، والخصم ٢٥٪.";
+    let chunks = split_text(text, 24);
+    assert!(
+        chunks.iter().any(|chunk| chunk.contains("١٬٥٠٠")),
+        "{:?}",
+        chunks
+    );
+}
+
+#[test]
+fn latin_names_in_hebrew_are_kept_in_reading_order() {
+    let text = "פגשתי את John Smith, ואז הלכנו הביתה.";
+    let chunks = split_text(text, 30);
+    assert_eq!(chunks, vec!["פגשתי את John Smith,", " ואז הלכנו הביתה."]);
+}
+
+#[test]
+fn directional_formatting_is_dropped() {
+    let text = "\u{202b}مرحبا John\u{202c} \u{200f}123\u{200e}";
+    assert_eq!(split_text(text, 300), vec!["مرحبا John 123"]);
+    assert_eq!(sanitize_text(text), "مرحبا John 123");
+    assert_eq!(sanitize_text("\u{2067}שלום\u{2069}"), "שלום");
+}
+
+#[test]
+fn harakat_stay_with_their_letter() {
+    // A single vocalized word over the limit, so it has to be cut somewhere
+    let word = "سَلَامٌ".repeat(6);
+    for chunk in split_text(&word, 16) {
+        let first = chunk.chars().next().unwrap();
+        assert!(
+            !('\u{64b}'..='\u{65f}').contains(&first),
+            "chunk starts with a mark: {:?}",
+            chunk
+        );
+    }
+}
+
+#[test]
+fn spelled_numbers_leave_arabic_indic_digits_alone() {
+    let profile = Profile {
+        spell_numbers: true,
+        ..Profile::default()
+    };
+    assert_eq!(
+        sanitize_text_with("٣ تفاحات و 3 apples", &profile),
+        "٣ تفاحات و three apples"
+    );
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "blocking")]
+
+use std::time::Duration;
+use tktts::blocking::{CancellationToken, Client, TktsError};
+
+#[test]
+fn blocking_calls_can_be_cancelled_from_another_thread() {
+    // An API host that accepts connections and never answers
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = Client::new(
+        "session",
+        &format!("http://{}", listener.local_addr().unwrap()),
+    );
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        canceller.cancel();
+    });
+
+    let error = client
+        .synthesize_with_cancel("Hello.", "en_us_002", &token)
+        .err()
+        .unwrap();
+    assert!(matches!(error, TktsError::Cancelled));
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut parts = client.synthesize_stream_with_cancel("Hello.", "en_us_002", token);
+    assert!(matches!(
+        parts.next().unwrap().err().unwrap(),
+        TktsError::Cancelled
+    ));
+    assert!(parts.next().is_none());
+}
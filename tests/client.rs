@@ -0,0 +1,264 @@
+use std::time::{Duration, Instant};
+use tktts::TktsError;
+use tktts::client::{CancellationToken, Client, Format, SynthesisRequest};
+use tktts::transport::{Request, Response, ResponseFuture, Transport};
+
+/// An API host that accepts connections and never answers.
+fn silent_host() -> (std::net::TcpListener, String) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    (listener, url)
+}
+
+#[tokio::test]
+async fn cancelling_stops_requests_in_flight() {
+    let (_listener, url) = silent_host();
+    let client = Client::new("session", &url);
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        canceller.cancel();
+    });
+
+    let started = Instant::now();
+    let error = client
+        .synthesize_with_cancel("Hello. This is never answered.", "en_us_002", &token)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, TktsError::Cancelled));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn cancelled_streams_end_with_the_error() {
+    let (_listener, url) = silent_host();
+    let client = Client::new("session", &url);
+    let token = CancellationToken::new();
+    let mut parts = client.synthesize_stream_with_cancel("Hello.", "en_us_002", token.clone());
+    token.cancel();
+
+    let error = parts.recv().await.unwrap().err().unwrap();
+    assert!(matches!(error, TktsError::Cancelled));
+    assert!(parts.recv().await.is_none());
+}
+
+/// An API host that answers every request with the audio "ABC", recording the texts.
+fn answering_host() -> (String, std::sync::mpsc::Receiver<String>) {
+    use std::io::{BufRead, BufReader, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (texts, received) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request_line = String::new();
+            let mut reader = BufReader::new(&stream);
+            reader.read_line(&mut request_line).unwrap();
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                header.clear();
+            }
+            let target = request_line.split(' ').nth(1).unwrap();
+            let url = url::Url::parse(&format!("http://host{}", target)).unwrap();
+            let text = url
+                .query_pairs()
+                .find(|(key, _)| key == "req_text")
+                .unwrap()
+                .1;
+            texts.send(text.into_owned()).unwrap();
+            let body = r#"{"data": {"v_str": "QUJD"}, "message": "success", "status_code": 0}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, received)
+}
+
+#[tokio::test]
+async fn requests_report_each_chunk() {
+    let (url, texts) = answering_host();
+    let client = Client::new("session", &url);
+    let request = SynthesisRequest::new()
+        .text("Salt & pepper. Fish & chips.")
+        .byte_limit(16)
+        .concurrency(1);
+    let result = client.execute(&request).await.unwrap();
+    assert_eq!(result.format, Format::Mp3);
+    assert_eq!(result.audio, b"ABCABC");
+    let chunks: Vec<&str> = result
+        .chunks
+        .iter()
+        .map(|chunk| chunk.text.as_str())
+        .collect();
+    assert_eq!(chunks, ["Salt & pepper.", " Fish & chips."]);
+    assert!(result.chunks.iter().all(|chunk| chunk.audio_bytes == 3));
+    assert_eq!(texts.recv().unwrap(), "Salt and pepper.");
+
+    let raw = request.text("Salt & pepper.").normalize(false);
+    client.execute(&raw).await.unwrap();
+    assert_eq!(texts.iter().nth(1).unwrap(), "Salt & pepper.");
+}
+
+#[tokio::test]
+async fn streamed_chunks_arrive_in_order() {
+    use futures_util::StreamExt;
+    let (url, _texts) = answering_host();
+    let client = Client::new("session", &url).byte_limit(16).concurrency(4);
+    let chunks: Vec<_> = client
+        .synthesize_stream("One two three. Four five six. Seven.", "en_us_002")
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    let indices: Vec<usize> = chunks.iter().map(|chunk| chunk.index).collect();
+    assert_eq!(indices, [0, 1, 2]);
+    assert_eq!(chunks[2].text, " Seven.");
+    assert!(chunks.iter().all(|chunk| chunk.bytes == b"ABC"));
+}
+
+/// Answers from memory, recording the URLs.
+struct Double {
+    urls: std::sync::Mutex<Vec<String>>,
+}
+
+impl Transport for Double {
+    fn send(&self, request: Request) -> ResponseFuture<'_> {
+        self.urls.lock().unwrap().push(request.url.to_string());
+        assert!(
+            request
+                .headers
+                .contains(&("Cookie".to_string(), "sessionid=session".to_string()))
+        );
+        Box::pin(async {
+            Ok(Response {
+                status: 200,
+                body: r#"{"data": {"v_str": "QUJD"}, "message": "success", "status_code": 0}"#
+                    .into(),
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn custom_transports_carry_the_requests() {
+    let double = std::sync::Arc::new(Double {
+        urls: Default::default(),
+    });
+    let client = Client::new("session", "https://tts.invalid").transport(double.clone());
+    assert_eq!(
+        client.synthesize("Hello.", "en_us_002").await.unwrap(),
+        b"ABC"
+    );
+    let urls = double.urls.lock().unwrap();
+    assert_eq!(urls.len(), 1);
+    assert!(
+        urls[0].starts_with(
+            "https://tts.invalid/media/api/text/speech/invoke/?text_speaker=en_us_002"
+        )
+    );
+}
+
+/// Answers with a server error until `failures` requests have been made.
+struct Flaky {
+    failures: usize,
+    requests: std::sync::atomic::AtomicUsize,
+}
+
+impl Transport for Flaky {
+    fn send(&self, _request: Request) -> ResponseFuture<'_> {
+        let n = self
+            .requests
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let failures = self.failures;
+        Box::pin(async move {
+            Ok(if n < failures {
+                Response {
+                    status: 503,
+                    body: "".into(),
+                }
+            } else {
+                Response {
+                    status: 200,
+                    body: r#"{"data": {"v_str": "QUJD"}, "message": "success", "status_code": 0}"#
+                        .into(),
+                }
+            })
+        })
+    }
+}
+
+#[tokio::test]
+async fn layers_retry_and_break_the_circuit() {
+    use tktts::layers::{CircuitBreaker, CircuitOpen, Retry};
+    let flaky = std::sync::Arc::new(Flaky {
+        failures: 2,
+        requests: Default::default(),
+    });
+    let client = Client::new("session", "https://tts.invalid")
+        .transport(flaky.clone())
+        .layer(Retry::new(2).delay(Duration::from_millis(1)));
+    assert_eq!(
+        client.synthesize("Hello.", "en_us_002").await.unwrap(),
+        b"ABC"
+    );
+    assert_eq!(flaky.requests.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    let flaky = std::sync::Arc::new(Flaky {
+        failures: usize::MAX,
+        requests: Default::default(),
+    });
+    let client = Client::new("session", "https://tts.invalid")
+        .transport(flaky.clone())
+        .layer(CircuitBreaker::new(2, Duration::from_secs(60)));
+    for _ in 0..2 {
+        let error = client
+            .synthesize("Hello.", "en_us_002")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, TktsError::Upstream { status: 503, .. }));
+    }
+    let error = client
+        .synthesize("Hello.", "en_us_002")
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, TktsError::Transport(source) if source.is::<CircuitOpen>()));
+    assert_eq!(flaky.requests.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+/// Fails every request with an error of its own.
+struct Refusing;
+
+#[derive(Debug)]
+struct Refused;
+
+impl std::fmt::Display for Refused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("refused")
+    }
+}
+
+impl std::error::Error for Refused {}
+
+impl Transport for Refusing {
+    fn send(&self, _request: Request) -> ResponseFuture<'_> {
+        Box::pin(async { Err(TktsError::Transport(Box::new(Refused))) })
+    }
+}
+
+#[tokio::test]
+async fn custom_transport_errors_can_be_matched() {
+    let client = Client::new("session", "https://tts.invalid").transport(Refusing);
+    let error = client
+        .synthesize("Hello.", "en_us_002")
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, TktsError::Transport(source) if source.is::<Refused>()));
+}
@@ -0,0 +1,115 @@
+// Each test file uses only some of these
+#![allow(dead_code)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+/// An API host that answers every request with the audio "ABC", recording the voices.
+pub fn answering_host() -> (String, mpsc::Receiver<String>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sent, voices) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                if let Some((name, value)) = header.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    length = value.trim().parse().unwrap();
+                }
+                header.clear();
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let target = request_line.split(' ').nth(1).unwrap();
+            let url = url::Url::parse(&format!("http://host{}", target)).unwrap();
+            let voice = url
+                .query_pairs()
+                .find(|(key, _)| key == "text_speaker")
+                .unwrap()
+                .1;
+            sent.send(voice.into_owned()).unwrap();
+            let body = r#"{"data": {"v_str": "QUJD"}, "message": "success", "status_code": 0}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, voices)
+}
+
+/// A directory of its own for the config, cache and data of one test's runs.
+pub fn home(name: &str) -> PathBuf {
+    static RUN: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "tktts-{}-{}-{}",
+        name,
+        std::process::id(),
+        RUN.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs tktts with `args` and `input` on stdin in `home` against the API host `url`,
+/// failing on errors.
+pub fn tktts(home: &Path, url: &str, args: &[&str], input: &str) -> Output {
+    tktts_with_env(home, url, args, input, &[])
+}
+
+/// [`tktts`] with the variables `env` set, and none of the `AWS_*` variables of the
+/// environment the tests run in.
+pub fn tktts_with_env(
+    home: &Path,
+    url: &str,
+    args: &[&str],
+    input: &str,
+    env: &[(&str, &str)],
+) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_tktts"));
+    for (name, _) in std::env::vars().filter(|(name, _)| name.starts_with("AWS_")) {
+        command.env_remove(name);
+    }
+    let mut child = command
+        .args(args)
+        .current_dir(home)
+        .env("TKTTS_CONFIG", home.join("config.toml"))
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("TIKTOK_SESSIONID", "session")
+        .env("TIKTOK_API_BASEURL", url)
+        .env("AWS_SHARED_CREDENTIALS_FILE", home.join("aws-credentials"))
+        .env("AWS_EC2_METADATA_DISABLED", "true")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "tktts {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
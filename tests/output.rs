@@ -0,0 +1,81 @@
+mod common;
+
+use common::{answering_host, home, tktts_with_env};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::mpsc;
+
+/// An S3 stand-in that also serves container credentials at `/credentials`, sending the
+/// `Authorization` header of each upload.
+fn bucket_host() -> (String, mpsc::Receiver<String>) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (sent, authorizations) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let (mut length, mut authorization) = (0, String::new());
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                if let Some((name, value)) = header.split_once(':') {
+                    match name.to_ascii_lowercase().as_str() {
+                        "content-length" => length = value.trim().parse().unwrap(),
+                        "authorization" => authorization = value.trim().to_string(),
+                        _ => {}
+                    }
+                }
+                header.clear();
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let body = if request_line.starts_with("GET /credentials") {
+                assert_eq!(authorization, "container-token");
+                r#"{"AccessKeyId": "AKIDCONTAINER", "SecretAccessKey": "secret", "Token": "token"}"#
+            } else {
+                sent.send(authorization).unwrap();
+                ""
+            };
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, authorizations)
+}
+
+#[test]
+fn uploads_sign_with_container_credentials() {
+    let home = home("output");
+    let (bucket, authorizations) = bucket_host();
+    std::fs::write(
+        home.join("config.toml"),
+        format!("[upload]\ns3_endpoint = \"{}\"\n", bucket),
+    )
+    .unwrap();
+    let (url, _voices) = answering_host();
+
+    let credentials = format!("{}/credentials", bucket);
+    tktts_with_env(
+        &home,
+        &url,
+        &["-o", "s3://bucket/hello.mp3", "Hello."],
+        "",
+        &[
+            ("AWS_CONTAINER_CREDENTIALS_FULL_URI", &credentials),
+            ("AWS_CONTAINER_AUTHORIZATION_TOKEN", "container-token"),
+        ],
+    );
+    let authorization = authorizations.recv().unwrap();
+    assert!(
+        authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDCONTAINER/"),
+        "{}",
+        authorization
+    );
+
+    let _ = std::fs::remove_dir_all(&home);
+}
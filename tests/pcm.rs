@@ -0,0 +1,46 @@
+use tktts::pcm::{self, API_SAMPLE_RATE, Pcm};
+
+fn tone(seconds: f64) -> Vec<u8> {
+    let samples = (0..(seconds * f64::from(API_SAMPLE_RATE)) as usize)
+        .map(|i| (i as f32 * 0.05).sin() * 0.5)
+        .collect();
+    pcm::mp3(&Pcm {
+        sample_rate: API_SAMPLE_RATE,
+        samples,
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn decode_all_keeps_the_order_of_the_chunks() {
+    let lengths = [0.5, 0.1, 1.0, 0.3];
+    let chunks: Vec<Vec<u8>> = lengths.iter().map(|&seconds| tone(seconds)).collect();
+
+    let decoded = pcm::decode_all(&chunks).await.unwrap();
+    assert_eq!(decoded.len(), lengths.len());
+    for (pcm, seconds) in decoded.iter().zip(lengths) {
+        // The encoder pads the ends with up to a frame or two of silence
+        assert!(
+            (pcm.duration_secs() - seconds).abs() < 0.15,
+            "{}",
+            pcm.duration_secs()
+        );
+    }
+}
+
+#[tokio::test]
+async fn decode_all_fails_on_a_broken_chunk() {
+    let chunks = vec![tone(0.2), b"not audio".to_vec()];
+    assert!(pcm::decode_all(&chunks).await.is_err());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn offload_runs_beside_a_single_threaded_runtime() {
+    let handles: Vec<_> = (0..16u64)
+        .map(|i| tokio::spawn(pcm::offload(move || (0..=i).sum::<u64>())))
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        let i = i as u64;
+        assert_eq!(handle.await.unwrap(), i * (i + 1) / 2);
+    }
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{answering_host, home, tktts};
+
+/// A sentence that fills a chunk of its own.
+const CHORUS: &str = "We sing the chorus once again, louder than before, with every voice in the \
+    hall joining in, and the band keeps playing the same four bars over and over until the \
+    lights come up, the doors swing open, the cold air rushes in from the street and the last of \
+    us finally stumble home.";
+
+#[test]
+fn repeated_chunks_are_requested_once_without_the_cache() {
+    let home = home("pipeline");
+    let (url, voices) = answering_host();
+    let text = [CHORUS; 3].join(" ");
+
+    tktts(&home, &url, &["--no-cache", "-o", "out.mp3", &text], "");
+    assert_eq!(std::fs::read(home.join("out.mp3")).unwrap(), b"ABCABCABC");
+    // The second and third chunk both start with the space between the sentences
+    assert_eq!(voices.try_iter().count(), 2);
+
+    let _ = std::fs::remove_dir_all(&home);
+}
+
+#[test]
+fn piped_text_is_read_by_a_voice_for_its_language() {
+    let home = home("pipeline");
+    std::fs::write(
+        home.join("config.toml"),
+        "[language_voices]\nde = \"de_002\"\n",
+    )
+    .unwrap();
+    let (url, voices) = answering_host();
+    let text = "Der schnelle braune Fuchs springt über den faulen Hund, und die Katze schläft \
+        den ganzen Tag auf dem warmen Fensterbrett in der Sonne.";
+
+    let args = ["--speaker", "en_us_002", "-o", "out.mp3"];
+    let stderr = String::from_utf8(tktts(&home, &url, &args, text).stderr).unwrap();
+    assert_eq!(voices.recv().unwrap(), "en_us_002");
+    assert!(stderr.contains("--auto-voice"), "no warning: {}", stderr);
+
+    tktts(&home, &url, &[&args[..], &["--auto-voice"]].concat(), text);
+    assert_eq!(voices.recv().unwrap(), "de_002");
+
+    let _ = std::fs::remove_dir_all(&home);
+}
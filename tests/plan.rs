@@ -0,0 +1,45 @@
+mod common;
+
+use common::{answering_host, home, tktts};
+
+#[test]
+fn plans_with_an_alias_join_from_the_warm_cache() {
+    let home = home("plan");
+    std::fs::write(
+        home.join("config.toml"),
+        "[aliases]\nnarrator = \"en_us_006\"\n",
+    )
+    .unwrap();
+    let (url, voices) = answering_host();
+    let text = "Hello there.";
+
+    tktts(
+        &home,
+        &url,
+        &["--speaker", "narrator", "-o", "first.mp3", text],
+        "",
+    );
+    assert_eq!(voices.recv().unwrap(), "en_us_006");
+
+    tktts(
+        &home,
+        &url,
+        &["plan", "--speaker", "narrator", "-o", "plan.json", text],
+        "",
+    );
+    let plan: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(home.join("plan.json")).unwrap()).unwrap();
+    assert_eq!(plan["voice"], "en_us_006");
+    assert_eq!(plan["chunks"][0]["voice"], "en_us_006");
+
+    tktts(
+        &home,
+        "http://unreachable.invalid",
+        &["join", "--plan", "plan.json", "-o", "joined.mp3"],
+        "",
+    );
+    assert_eq!(std::fs::read(home.join("joined.mp3")).unwrap(), b"ABC");
+    assert!(voices.try_recv().is_err());
+
+    let _ = std::fs::remove_dir_all(&home);
+}
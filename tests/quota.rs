@@ -0,0 +1,27 @@
+mod common;
+
+use common::{answering_host, home, tktts};
+
+#[test]
+fn runs_at_the_same_time_all_count() {
+    let home = home("quota");
+    let (url, voices) = answering_host();
+
+    std::thread::scope(|scope| {
+        for run in 0..8 {
+            let (home, url) = (&home, &url);
+            scope.spawn(move || {
+                let text = format!("Run number {}.", run);
+                let output = format!("{}.mp3", run);
+                tktts(home, url, &["--no-cache", "-o", &output, &text], "");
+            });
+        }
+    });
+    assert_eq!(voices.try_iter().count(), 8);
+
+    let quota = tktts(&home, &url, &["quota", "--days", "1"], "");
+    let quota = String::from_utf8(quota.stdout).unwrap();
+    assert!(quota.contains("today       8"), "{}", quota);
+
+    let _ = std::fs::remove_dir_all(&home);
+}
@@ -0,0 +1,126 @@
+use base64::{Engine as _, engine::general_purpose};
+use tktts::schema::{AudioDecoder, Decoded, Outcome, Reason, Schema, parse};
+
+fn audio(body: &str) -> (Schema, String) {
+    let parsed = parse(body).unwrap();
+    match parsed.outcome {
+        Outcome::Audio(audio) => (parsed.schema, audio),
+        Outcome::Rejected { message, .. } => panic!("rejected: {}", message),
+    }
+}
+
+#[test]
+fn known_variants_yield_their_audio() {
+    let nested =
+        r#"{"data": {"s_key": "k", "v_str": "QUJD"}, "message": "success", "status_code": 0}"#;
+    assert_eq!(audio(nested), (Schema::Nested, "QUJD".to_string()));
+    let flat = r#"{"success": true, "data": "QUJD", "error": null}"#;
+    assert_eq!(audio(flat), (Schema::Flat, "QUJD".to_string()));
+    assert_eq!(
+        audio(r#"{"v_data": "QUJD"}"#),
+        (Schema::TopLevel, "QUJD".to_string())
+    );
+}
+
+#[test]
+fn session_errors_are_told_apart() {
+    let reason = |body: &str| match parse(body).unwrap().outcome {
+        Outcome::Rejected { reason, .. } => reason,
+        Outcome::Audio(_) => panic!("not rejected"),
+    };
+    let body = r#"{"status_code": 1, "status_msg": "Couldn't load speech. Try again.", "data": {"v_str": ""}}"#;
+    assert_eq!(reason(body), Reason::Session);
+    assert!(Reason::Session.is_session());
+    assert_eq!(
+        reason(r#"{"status_code": 5, "status_msg": "No session id found."}"#),
+        Reason::MissingSession
+    );
+    assert_eq!(
+        reason(
+            r#"{"status_code": 1, "status_msg": "This service is not available in your region"}"#
+        ),
+        Reason::Region
+    );
+    assert_eq!(
+        reason(r#"{"status_code": 4, "status_msg": "Invalid speaker"}"#),
+        Reason::Voice
+    );
+    assert!(!Reason::Voice.is_session());
+
+    let body = r#"{"status_code": 2, "status_msg": "Text too long to create speech audio"}"#;
+    let parsed = parse(body).unwrap();
+    assert_eq!(parsed.schema, Schema::Error);
+    let Outcome::Rejected { reason, message } = parsed.outcome else {
+        panic!("not rejected");
+    };
+    assert_eq!(reason, Reason::TextTooLong);
+    assert_eq!(message, "Text too long to create speech audio (status 2)");
+}
+
+#[test]
+fn unknown_shapes_are_described() {
+    let error = parse(r#"{"result": {"audio": "QUJD"}, "code": 0}"#)
+        .err()
+        .unwrap();
+    assert_eq!(
+        error,
+        "Unrecognized response shape {code: number, result: {audio: string}}"
+    );
+    assert!(
+        parse("<html>")
+            .err()
+            .unwrap()
+            .starts_with("Response is not JSON")
+    );
+}
+
+/// Feeds `body` to a decoder `piece` bytes at a time.
+fn decode(body: &str, piece: usize) -> Decoded<Vec<u8>> {
+    let mut decoder = AudioDecoder::new(Vec::new());
+    for piece in body.as_bytes().chunks(piece) {
+        decoder.feed(piece).unwrap();
+    }
+    decoder.finish().ok().unwrap()
+}
+
+#[test]
+fn audio_is_found_in_the_body_like_parse_finds_it() {
+    let bodies = [
+        r#"{"data": {"s_key": "k", "v_str": "QUJD"}, "message": "success", "status_code": 0}"#,
+        r#"{"success": true, "data": "QUJD", "error": null}"#,
+        r#"{"v_data": "QUJD", "status_msg": "ok \u00e9"}"#,
+        r#"{"data": {"v_str": ""}, "audio": "QUJD"}"#,
+        r#"{"extra": {"v_str": "no", "data": "no"}, "data": {"v_str": "QU\/D"}}"#,
+    ];
+    for body in bodies {
+        let expected = general_purpose::STANDARD.decode(audio(body).1).unwrap();
+        // Pieces that split keys, escapes and base64 groups alike
+        for piece in [1, 3, 7, body.len()] {
+            match decode(body, piece) {
+                Decoded::Audio(audio) => assert_eq!(audio, expected, "{}", body),
+                Decoded::Other(_) => panic!("no audio found in {}", body),
+            }
+        }
+    }
+}
+
+#[test]
+fn bodies_without_audio_are_handed_back() {
+    let rejected =
+        r#"{"status_code": 1, "status_msg": "Couldn't \"load\" speech.", "data": {"v_str": ""}}"#;
+    for piece in [1, 5, rejected.len()] {
+        match decode(rejected, piece) {
+            Decoded::Other(body) => assert_eq!(body, rejected),
+            Decoded::Audio(_) => panic!("audio found in a rejection"),
+        }
+    }
+}
+
+#[test]
+fn broken_audio_fails_to_decode() {
+    let mut decoder = AudioDecoder::new(Vec::new());
+    assert!(decoder.feed(br#"{"data": {"v_str": "QU*D"}}"#).is_err());
+    let mut decoder = AudioDecoder::new(Vec::new());
+    decoder.feed(br#"{"data": {"v_str": "QUJD"#).unwrap();
+    assert!(decoder.finish().is_err());
+}
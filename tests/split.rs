@@ -0,0 +1,141 @@
+use proptest::prelude::*;
+use tktts::{Split, split_text, split_text_with};
+
+fn without_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Text mixing words, punctuation, newlines and multi-byte characters.
+fn text() -> impl Strategy<Value = String> {
+    prop::collection::vec(
+        prop_oneof![
+            "[a-zA-Z]{1,12}",
+            "[äöüß€😀中文]{1,6}",
+            "[a-z]{40,120}",
+            Just(" ".to_string()),
+            Just("  \t".to_string()),
+            Just("\n".to_string()),
+            "[.,!?:;—…()<>]",
+        ],
+        0..80,
+    )
+    .prop_map(|parts| parts.concat())
+}
+
+fn split() -> impl Strategy<Value = Split> {
+    prop_oneof![
+        Just(Split::Sentence),
+        Just(Split::Clause),
+        Just(Split::Word),
+        Just(Split::Fixed),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn chunks_fit_the_byte_limit(text in text(), limit in 4usize..400) {
+        for chunk in split_text(&text, limit) {
+            prop_assert!(chunk.len() <= limit, "{} bytes > {}: {:?}", chunk.len(), limit, chunk);
+        }
+    }
+
+    #[test]
+    fn chunks_are_never_blank(text in text(), limit in 4usize..400) {
+        for chunk in split_text(&text, limit) {
+            prop_assert!(!chunk.trim().is_empty());
+        }
+    }
+
+    #[test]
+    fn chunks_round_trip_modulo_whitespace(text in text(), limit in 4usize..400) {
+        let chunks = split_text(&text, limit);
+        prop_assert_eq!(without_whitespace(&chunks.concat()), without_whitespace(&text));
+    }
+
+    #[test]
+    fn arbitrary_unicode_round_trips(text in any::<String>(), limit in 4usize..64) {
+        let chunks = split_text(&text, limit);
+        prop_assert!(chunks.iter().all(|chunk| chunk.len() <= limit));
+        prop_assert_eq!(without_whitespace(&chunks.concat()), without_whitespace(&text));
+    }
+
+    #[test]
+    fn every_strategy_keeps_the_guarantees(text in text(), limit in 4usize..400, split in split()) {
+        let chunks = split_text_with(&text, limit, split);
+        prop_assert!(chunks.iter().all(|chunk| chunk.len() <= limit && !chunk.trim().is_empty()));
+        prop_assert_eq!(without_whitespace(&chunks.concat()), without_whitespace(&text));
+    }
+}
+
+#[test]
+fn short_sentences_are_merged() {
+    assert_eq!(
+        split_text("Hello there. How are you?", 300),
+        vec!["Hello there. How are you?"]
+    );
+}
+
+#[test]
+fn overlong_words_are_split_between_characters() {
+    let word = "ä".repeat(10);
+    let chunks = split_text(&word, 5);
+    assert_eq!(chunks, vec!["ää", "ää", "ää", "ää", "ää"]);
+}
+
+#[test]
+fn overlong_words_are_read_in_pieces() {
+    assert_eq!(
+        split_text(
+            "Read methylenedioxymethamphetaminehydrochloridesupercalifragilistic now.",
+            40
+        ),
+        vec![
+            "Read methylenedioxymethamphe",
+            "taminehydrochloridesuper califragilistic",
+            "now."
+        ]
+    );
+}
+
+#[test]
+fn overlong_words_keep_modifiers_with_their_character() {
+    let word = "👍🏽".repeat(10);
+    for chunk in split_text(&word, 10) {
+        assert_eq!(chunk, "👍🏽");
+    }
+}
+
+#[test]
+fn sentences_are_not_cut_at_commas() {
+    let text = "One. Two, three.";
+    assert_eq!(
+        split_text_with(text, 12, Split::Sentence),
+        vec!["One.", " Two, three."]
+    );
+    assert_eq!(
+        split_text_with(text, 12, Split::Clause),
+        vec!["One. Two,", " three."]
+    );
+}
+
+#[test]
+fn words_ignore_punctuation() {
+    assert_eq!(
+        split_text_with("Hi. This is a test", 10, Split::Word),
+        vec!["Hi. This", "is a test"]
+    );
+}
+
+#[test]
+fn fixed_packs_to_the_limit() {
+    assert_eq!(
+        split_text_with("Hi. This is a test", 10, Split::Fixed),
+        vec!["Hi. This i", "s a test"]
+    );
+}
+
+#[test]
+#[should_panic(expected = "byte_limit must be at least")]
+fn tiny_limits_are_rejected() {
+    split_text("hi", 3);
+}